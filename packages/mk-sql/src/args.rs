@@ -3,7 +3,7 @@
 // conditions defined in the file COPYING, which is part of this source code package.
 
 use crate::constants;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Default)]
@@ -44,6 +44,29 @@ pub struct Args {
     /// Use custom config file
     #[arg(short, long)]
     pub config_file: Option<PathBuf>,
+
+    /// Checks config file and prints found problems with their positions
+    #[arg(long)]
+    pub validate_config: bool,
+
+    /// Discovers instances and prints only section headers and instance config lines,
+    /// no data is queried
+    #[arg(long)]
+    pub skeleton: bool,
+
+    /// Simulated failures for testing: comma separated `fault[@instance][/section]`,
+    /// fault is one of timeout, login or malformed
+    #[arg(long, hide = true)]
+    pub fault_injection: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Prints supported sections, auth types and features as JSON
+    Capabilities,
 }
 
 impl Args {
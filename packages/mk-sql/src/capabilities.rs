@@ -0,0 +1,100 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Self-description of the binary for `mk-sql capabilities`: the bakery and the server
+//! adapt rules to the deployed plugin instead of guessing by version
+
+use crate::config::defines::values;
+use crate::config::section::{
+    get_known_section_names, get_per_database_sections, Section, SectionKind,
+};
+use crate::constants;
+use crate::telemetry::to_json_string;
+
+/// JSON document with the version, the config schema, sections, auth types and features
+pub fn make_capabilities() -> String {
+    let per_database = get_per_database_sections();
+    let sections = get_known_section_names()
+        .into_iter()
+        .map(|name| {
+            let section = Section::new(name);
+            format!(
+                "{{\"name\":{},\"sep\":{},\"kind\":\"{}\",\"per_database\":{}}}",
+                to_json_string(name),
+                to_json_string(&section.sep().to_string()),
+                match section.kind() {
+                    SectionKind::Sync => "sync",
+                    SectionKind::Async => "async",
+                    SectionKind::Disabled => "disabled",
+                },
+                per_database.iter().any(|s| s == name)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    let auth_types = get_auth_types()
+        .iter()
+        .map(|a| to_json_string(a))
+        .collect::<Vec<String>>()
+        .join(",");
+    let features = get_features()
+        .iter()
+        .map(|(name, available)| format!("{}:{available}", to_json_string(name)))
+        .collect::<Vec<String>>()
+        .join(",");
+    format!(
+        "{{\"version\":{},\"config_schema_version\":{},\"platform\":{},\
+         \"sections\":[{sections}],\"auth_types\":[{auth_types}],\
+         \"features\":{{{features}}}}}",
+        to_json_string(constants::VERSION),
+        constants::CONFIG_SCHEMA_VERSION,
+        to_json_string(std::env::consts::OS),
+    )
+}
+
+fn get_features() -> [(&'static str, bool); 3] {
+    [
+        // SSRP is spoken over plain UDP by the own client: `browser_scan` works everywhere
+        ("sql_browser", true),
+        // SSPI of Windows is the only provider of the integrated authentication
+        ("integrated_auth", cfg!(windows)),
+        // odbc-api is a dependency only on Windows
+        ("odbc", cfg!(windows)),
+    ]
+}
+
+fn get_auth_types() -> Vec<&'static str> {
+    #[cfg(windows)]
+    return vec![values::SQL_SERVER, values::WINDOWS, values::INTEGRATED];
+    #[cfg(unix)]
+    return vec![values::SQL_SERVER];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_capabilities() {
+        let c = make_capabilities();
+        assert!(c.starts_with(&format!(
+            "{{\"version\":\"{}\",\"config_schema_version\":{},",
+            constants::VERSION,
+            constants::CONFIG_SCHEMA_VERSION
+        )));
+        assert!(c.contains(
+            "{\"name\":\"instance\",\"sep\":\"|\",\"kind\":\"sync\",\"per_database\":false}"
+        ));
+        assert!(c.contains(
+            "{\"name\":\"tablespaces\",\"sep\":\" \",\"kind\":\"async\",\"per_database\":true}"
+        ));
+        assert!(c.contains("{\"name\":\"jobs\",\"sep\":\"\\t\","));
+        #[cfg(unix)]
+        assert!(c.ends_with(
+            "\"auth_types\":[\"sql_server\"],\
+             \"features\":{\"sql_browser\":true,\"integrated_auth\":false,\"odbc\":false}}"
+        ));
+        assert_eq!(c.matches('{').count(), c.matches('}').count());
+    }
+}
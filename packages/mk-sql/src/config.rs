@@ -3,11 +3,12 @@
 // conditions defined in the file COPYING, which is part of this source code package.
 
 pub mod defines;
+pub mod diagnostics;
 pub mod ms_sql;
 pub mod section;
 pub mod system;
 pub mod yaml;
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use std::path::Path;
 
 /// Contains config to check database(MS SQL)
@@ -17,8 +18,49 @@ pub struct CheckConfig {
 }
 
 impl CheckConfig {
+    /// Problems found by diagnostics are logged, and added to the error if the config is unusable
     pub fn load_file(file: &Path) -> Result<Self> {
-        let data = yaml::load_from_file(file)?;
+        let content = yaml::read_config_file(file)?;
+        let problems = diagnostics::check(&content);
+        for problem in &problems {
+            log::warn!("Config: {problem}");
+        }
+        CheckConfig::from_content(&content).map_err(|e| {
+            if problems.is_empty() {
+                e
+            } else {
+                anyhow!(
+                    "{e}; config problems: {}",
+                    problems
+                        .iter()
+                        .map(|p| p.to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                )
+            }
+        })
+    }
+
+    /// Report for `--validate-config`: one line per problem, Ok if config is usable and clean
+    pub fn validate_file(file: &Path) -> Result<String> {
+        let content = yaml::read_config_file(file)?;
+        let problems = diagnostics::check(&content);
+        let mut report = problems
+            .iter()
+            .map(|p| p.to_string() + "\n")
+            .collect::<String>();
+        let usable = CheckConfig::from_content(&content).map_err(|e| {
+            report += &format!("ERROR: {e}\n");
+        });
+        if problems.is_empty() && usable.is_ok() {
+            Ok(format!("OK: {}\n", file.display()))
+        } else {
+            Err(anyhow!("{report}"))
+        }
+    }
+
+    fn from_content(content: &str) -> Result<Self> {
+        let data = yaml::load_from_str(content)?;
         if data.is_empty() {
             bail!("Not yaml document");
         }
@@ -65,4 +107,12 @@ mod tests {
             .ms_sql()
             .is_some());
     }
+
+    #[test]
+    fn test_validate_file() {
+        assert!(CheckConfig::validate_file(&YML_MINI_FILE)
+            .unwrap()
+            .starts_with("OK: "));
+        assert!(CheckConfig::validate_file(&NOT_YAML_FILE).is_err());
+    }
 }
@@ -8,38 +8,125 @@ pub mod keys {
 
     pub const OPTIONS: &str = "options";
     pub const MAX_CONNECTIONS: &str = "max_connections";
+    pub const MAX_QUERIES: &str = "max_queries";
+    pub const SPREAD_SECONDS: &str = "spread_seconds";
+    pub const HOST_HEAVY_SECTIONS: &str = "host_heavy_sections";
+    pub const SECTION_PARALLELISM: &str = "section_parallelism";
+    pub const BLACKOUT_WINDOWS: &str = "blackout_windows";
+    pub const GENERATORS: &str = "generators";
+
+    pub const TELEMETRY: &str = "telemetry";
+    pub const OTLP_ENDPOINT: &str = "otlp_endpoint";
+
+    pub const LIMITS: &str = "limits";
+    pub const MAX_MEMORY_MB: &str = "max_memory_mb";
+
+    pub const ERROR_SUPPRESSION: &str = "error_suppression";
+    pub const AFTER: &str = "after";
+
+    pub const EMIT: &str = "emit";
+    pub const STATIC_SECTIONS: &str = "static_sections";
+    pub const PER_INSTANCE: &str = "per_instance";
+    pub const NAMES: &str = "names";
+    pub const SECTION_PREFIX: &str = "section_prefix";
+    pub const VALUES_LOCALE: &str = "values_locale";
+
+    pub const SECURITY: &str = "security";
+    pub const QUERY_ALLOWLIST: &str = "query_allowlist";
+    pub const ALLOWED_QUERIES: &str = "allowed_queries";
+    pub const FILE: &str = "file";
+    pub const SHA256: &str = "sha256";
+    pub const ALLOW_WRITE: &str = "allow_write";
 
     pub const AUTHENTICATION: &str = "authentication";
     pub const USERNAME: &str = "username";
     pub const PASSWORD: &str = "password";
+    pub const REMEMBER_PASSWORD: &str = "remember_password";
     pub const TYPE: &str = "type";
     pub const ACCESS_TOKEN: &str = "access_token";
+    pub const KERBEROS: &str = "kerberos";
+    pub const SPN_HOST: &str = "spn_host";
 
     pub const CONNECTION: &str = "connection";
     pub const HOSTNAME: &str = "hostname";
+    pub const HOSTNAME_TEMPLATE: &str = "hostname_template";
     pub const FAIL_OVER_PARTNER: &str = "failoverpartner";
     pub const TLS: &str = "tls";
     pub const PORT: &str = "port";
     pub const SOCKET: &str = "socket";
     pub const TIMEOUT: &str = "timeout";
+    pub const MIN_QUERY_GAP_MS: &str = "min_query_gap_ms";
+    pub const SQL_BROWSER: &str = "sql_browser";
+    pub const ENABLED: &str = "enabled";
+    pub const TIMEOUT_MS: &str = "timeout_ms";
+    pub const CONNECTION_STRING: &str = "connection_string";
+    pub const DATABASE: &str = "database";
     pub const CA: &str = "ca";
     pub const CLIENT_CERTIFICATE: &str = "client_certificate";
     pub const TRUST_SERVER_CERTIFICATE: &str = "trust_server_certificate";
+    pub const LISTENER: &str = "listener";
+    pub const AVAILABILITY_GROUP: &str = "availability_group";
+    pub const CONNECT_OPTIONS: &str = "connect_options";
+    pub const PACKET_SIZE: &str = "packet_size";
+    pub const MARS: &str = "mars";
+    pub const LANGUAGE: &str = "language";
+    pub const READONLY: &str = "readonly";
 
     pub const SECTIONS: &str = "sections";
     pub const CACHE_AGE: &str = "cache_age";
+    pub const SECTION_TIMEOUT: &str = "section_timeout";
+    pub const PROFILE: &str = "profile";
     pub const IS_ASYNC: &str = "is_async";
     pub const DISABLED: &str = "disabled";
     pub const SEP: &str = "sep";
+    pub const SAMPLE_INTERVAL: &str = "sample_interval";
+    pub const MAIN_DB: &str = "main_db";
+    pub const REQUIRED_DATABASES: &str = "required_databases";
+    pub const PER_DATABASE: &str = "per_database";
+    pub const MAX_AGE_DAYS: &str = "max_age_days";
+    pub const INCLUDE_SQL_TEXT: &str = "include_sql_text";
+    pub const COMPUTE_RATES: &str = "compute_rates";
+    pub const PRIORITY: &str = "priority";
+    pub const FILTERS: &str = "filters";
+    pub const COLUMN: &str = "column";
+    pub const EQUALS: &str = "equals";
+    pub const CONTAINS: &str = "contains";
+    pub const MATCHES: &str = "matches";
+    pub const ACTION: &str = "action";
+    pub const MASKING: &str = "masking";
+    pub const BASELINE: &str = "baseline";
+    pub const RECOVERY_MODEL: &str = "recovery_model";
+    pub const AUTO_SHRINK: &str = "auto_shrink";
+    pub const AUTO_CLOSE: &str = "auto_close";
+    pub const PAGE_VERIFY: &str = "page_verify";
+    pub const REPLACEMENT: &str = "replacement";
+    pub const PROCEDURE: &str = "procedure";
+    pub const PARAMS: &str = "params";
+    pub const NAME: &str = "name";
+    pub const VALUE: &str = "value";
 
     pub const PIGGYBACK_HOST: &str = "piggyback_host";
     pub const DISCOVERY: &str = "discovery";
     pub const DETECT: &str = "detect";
     pub const INCLUDE: &str = "include";
+    pub const INCLUDE_FILE: &str = "include_file";
+    pub const CONTAINERS: &str = "containers";
+    pub const IMAGE: &str = "image";
+    pub const LABEL: &str = "label";
     pub const EXCLUDE: &str = "exclude";
+    pub const SCAN: &str = "scan";
+    pub const PORTS: &str = "ports";
+    pub const SERVICES: &str = "services";
 
     pub const MODE: &str = "mode";
 
+    pub const CLUSTER_DEDUP: &str = "cluster_dedup";
+    pub const CLUSTER: &str = "cluster";
+    pub const PASSIVE_NODE_POLICY: &str = "passive_node_policy";
+
+    pub const QUALIFY_WITH_HOST: &str = "qualify_with_host";
+    pub const INSTANCE_PREFIX: &str = "instance_prefix";
+
     pub const INSTANCES: &str = "instances";
 
     pub const SID: &str = "sid";
@@ -66,12 +153,45 @@ pub mod values {
     pub const SOCKET: &str = "socket";
     /// AuthType::Special
     pub const SPECIAL: &str = "special";
+    /// ClusterDedup::Off
+    pub const OFF: &str = "off";
+    /// ClusterDedup::ActiveNode
+    pub const ACTIVE_NODE: &str = "active_node";
+    /// ClusterDedup::Piggyback
+    pub const PIGGYBACK: &str = "piggyback";
+    /// RowFilter action
+    pub const DROP: &str = "drop";
+    /// RowFilter action, NameEncoding::Keep
+    pub const KEEP: &str = "keep";
+    /// NameEncoding::Escape
+    pub const ESCAPE: &str = "escape";
+    /// Profile::Minimal
+    pub const MINIMAL: &str = "minimal";
+    /// Profile::Standard
+    pub const STANDARD: &str = "standard";
+    /// Profile::Full
+    pub const FULL: &str = "full";
+    /// PassiveNodePolicy::Query
+    pub const QUERY: &str = "query";
+    /// PassiveNodePolicy::Skip
+    pub const SKIP: &str = "skip";
+    /// type of procedure parameter
+    pub const INT: &str = "int";
+    pub const FLOAT: &str = "float";
+    pub const BOOL: &str = "bool";
+    pub const STRING: &str = "string";
 }
 
 pub mod defaults {
     use super::values;
     pub const MAX_CONNECTIONS: u32 = 6;
     pub const MAX_QUERIES: u32 = 64;
+    /// start of all instances at once
+    pub const SPREAD_SECONDS: u32 = 0;
+    /// heavy sections of instances sharing a host are run one by one
+    pub const HOST_HEAVY_SECTIONS: u32 = 1;
+    /// sections of an instance are run one by one
+    pub const SECTION_PARALLELISM: u32 = 1;
 
     #[cfg(windows)]
     pub const AUTH_TYPE: &str = values::INTEGRATED;
@@ -81,7 +201,14 @@ pub mod defaults {
     pub const CONNECTION_HOST_NAME: &str = "localhost";
     pub const CONNECTION_PORT: u16 = 1433;
     pub const CONNECTION_TIMEOUT: u64 = 5;
+    /// queries are sent without pause
+    pub const MIN_QUERY_GAP_MS: u64 = 0;
+    pub const SQL_BROWSER_TIMEOUT_MS: u64 = 1000;
     pub const SECTIONS_CACHE_AGE: u32 = 600;
+    /// optional sections of the full profile are heavy, they are refreshed less often
+    pub const FULL_PROFILE_CACHE_AGE: u32 = 1800;
+    /// seconds for a section to finish, 0 - no limit
+    pub const SECTIONS_TIMEOUT: u32 = 0;
     pub const SECTIONS_ALWAYS: &[&str] = &[
         "instance",
         "databases",
@@ -94,8 +221,18 @@ pub mod defaults {
         "connections",
     ];
     pub const SECTIONS_CACHED: &[&str] = &["tablespaces", "datafiles", "backup", "jobs"];
+    /// seconds between two samples of the rate based sections
+    pub const SECTION_SAMPLE_INTERVAL: u32 = 5;
+    /// integrity check of a database older than this is overdue
+    pub const SECTION_MAX_AGE_DAYS: u32 = 7;
+    /// replaces masked values in the result rows
+    pub const MASK_REPLACEMENT: &str = "***";
 
     pub const DISCOVERY_DETECT: bool = true;
+    /// Docker API, Podman provides the compatible one at `/run/podman/podman.sock`
+    pub const CONTAINER_SOCKET: &str = "/var/run/docker.sock";
+    /// substring of the image name of SQL Server containers
+    pub const CONTAINER_IMAGE: &str = "mssql";
 
     pub const TRUST_SERVER_CERTIFICATE: bool = true;
     pub const DEFAULT_SEP: char = ' ';
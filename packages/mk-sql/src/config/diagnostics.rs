@@ -0,0 +1,669 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Config diagnostics: finds unknown keys, values of wrong type and bad section names
+//! and reports them with YAML path, line and column.
+//! yaml_rust2::Yaml has no positions, thus the config is parsed once more into `Node` tree
+
+use super::defines::keys;
+use super::section::get_known_section_names;
+use super::system;
+use super::yaml::to_bool;
+use crate::types::edit_distance;
+use std::fmt;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Problem {
+    Syntax(String),
+    UnknownKey,
+    WrongType(&'static str),
+    InvalidSectionName,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Syntax(info) => write!(f, "syntax error: {info}"),
+            Problem::UnknownKey => write!(f, "unknown key"),
+            Problem::WrongType(expected) => write!(f, "wrong type, expected {expected}"),
+            Problem::InvalidSectionName => write!(f, "invalid section name"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    /// e.g. `mssql.main.sections[2].databses`
+    pub path: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based
+    pub col: usize,
+    pub problem: Problem,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}: {}",
+            if self.path.is_empty() {
+                "<root>"
+            } else {
+                &self.path
+            },
+            self.line,
+            self.col,
+            self.problem
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks config text, returns all found problems, empty if nothing found
+pub fn check(content: &str) -> Vec<Diagnostic> {
+    let mut receiver = EventCollector::default();
+    if let Err(e) = Parser::new_from_str(content).load(&mut receiver, false) {
+        return vec![Diagnostic {
+            path: String::new(),
+            line: e.marker().line(),
+            col: e.marker().col() + 1,
+            problem: Problem::Syntax(e.info().to_string()),
+            suggestion: None,
+        }];
+    }
+    let mut events = receiver.events.into_iter().peekable();
+    let mut diagnostics = Vec::new();
+    // skip stream and document start
+    while let Some((event, _)) = events.peek() {
+        if matches!(event, Event::StreamStart | Event::DocumentStart) {
+            events.next();
+        } else {
+            break;
+        }
+    }
+    if let Some(root) = Node::build(&mut events) {
+        check_node(&root, &Expect::Map(ROOT), "", &mut diagnostics);
+    }
+    diagnostics
+}
+
+#[derive(Default)]
+struct EventCollector {
+    events: Vec<(Event, Marker)>,
+}
+
+impl MarkedEventReceiver for EventCollector {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        self.events.push((ev, mark));
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Null,
+    Bool,
+    Int,
+    Str(String),
+    Map(Vec<(String, Node)>),
+    Seq(Vec<Node>),
+}
+
+#[derive(Debug)]
+struct Node {
+    line: usize,
+    col: usize,
+    value: Value,
+}
+
+impl Node {
+    fn build<I: Iterator<Item = (Event, Marker)>>(
+        events: &mut std::iter::Peekable<I>,
+    ) -> Option<Node> {
+        let (event, mark) = events.next()?;
+        let value = match event {
+            Event::Scalar(s, style, _, _) => scalar_value(&s, style),
+            Event::SequenceStart(..) => {
+                let mut items = Vec::new();
+                while !matches!(events.peek(), Some((Event::SequenceEnd, _)) | None) {
+                    items.extend(Node::build(events));
+                }
+                events.next();
+                Value::Seq(items)
+            }
+            Event::MappingStart(..) => {
+                let mut entries = Vec::new();
+                while !matches!(events.peek(), Some((Event::MappingEnd, _)) | None) {
+                    let key = match events.peek() {
+                        Some((Event::Scalar(k, ..), _)) => k.clone(),
+                        _ => String::new(),
+                    };
+                    let key_node = Node::build(events);
+                    if let (Some(key_node), Some(value)) = (key_node, Node::build(events)) {
+                        // position of the key is more useful than position of the value
+                        entries.push((
+                            key,
+                            Node {
+                                line: key_node.line,
+                                col: key_node.col,
+                                value: value.value,
+                            },
+                        ));
+                    }
+                }
+                events.next();
+                Value::Map(entries)
+            }
+            _ => Value::Null,
+        };
+        Some(Node {
+            line: mark.line(),
+            col: mark.col() + 1,
+            value,
+        })
+    }
+}
+
+fn scalar_value(s: &str, style: TScalarStyle) -> Value {
+    if style != TScalarStyle::Plain {
+        return Value::Str(s.to_string());
+    }
+    match s {
+        "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+        "true" | "True" | "TRUE" | "false" | "False" | "FALSE" => Value::Bool,
+        _ if s.parse::<i64>().is_ok() => Value::Int,
+        _ => Value::Str(s.to_string()),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Expect {
+    Str,
+    Int,
+    Bool,
+    StrList,
+    /// list of strings or integers
+    ScalarList,
+    /// single string or list of strings
+    StrOrList,
+    Map(&'static [Field]),
+    MapList(&'static [Field]),
+    /// mapping of any strings
+    StrMap,
+    /// string, integer or boolean
+    Scalar,
+    Sections,
+}
+
+struct Field(&'static str, Expect);
+
+const ROOT: &[Field] = &[
+    Field(keys::MSSQL, Expect::Map(MSSQL)),
+    Field(system::keys::SYSTEM, Expect::Map(SYSTEM)),
+];
+const SYSTEM: &[Field] = &[Field(system::keys::LOGGING, Expect::Map(LOGGING))];
+const LOGGING: &[Field] = &[
+    Field(system::keys::LEVEL, Expect::Str),
+    Field(system::keys::MAX_SIZE, Expect::Int),
+    Field(system::keys::MAX_COUNT, Expect::Int),
+];
+const MSSQL: &[Field] = &[
+    Field(keys::MAIN, Expect::Map(MAIN)),
+    Field(keys::CONFIGS, Expect::MapList(CONFIG)),
+];
+const CONFIG: &[Field] = &[Field(keys::MAIN, Expect::Map(MAIN))];
+const MAIN: &[Field] = &[
+    Field(keys::OPTIONS, Expect::Map(OPTIONS)),
+    Field(keys::AUTHENTICATION, Expect::Map(AUTHENTICATION)),
+    Field(keys::CONNECTION, Expect::Map(CONNECTION)),
+    Field(keys::SECTIONS, Expect::Sections),
+    Field(keys::CACHE_AGE, Expect::Int),
+    Field(keys::SECTION_TIMEOUT, Expect::Int),
+    Field(keys::PROFILE, Expect::Str),
+    Field(keys::PIGGYBACK_HOST, Expect::Str),
+    Field(keys::PIGGYBACK, Expect::Map(PIGGYBACK)),
+    Field(keys::DISCOVERY, Expect::Map(DISCOVERY)),
+    Field(keys::MODE, Expect::Str),
+    Field(keys::CLUSTER_DEDUP, Expect::Str),
+    Field(keys::CLUSTER, Expect::Map(CLUSTER)),
+    Field(keys::QUALIFY_WITH_HOST, Expect::Bool),
+    Field(keys::INSTANCE_PREFIX, Expect::Str),
+    Field(keys::INSTANCES, Expect::MapList(INSTANCE)),
+    Field(keys::TELEMETRY, Expect::Map(TELEMETRY)),
+    Field(keys::LIMITS, Expect::Map(LIMITS)),
+    Field(keys::ERROR_SUPPRESSION, Expect::Map(ERROR_SUPPRESSION)),
+    Field(keys::EMIT, Expect::Map(EMIT)),
+    Field(keys::STATIC_SECTIONS, Expect::MapList(STATIC_SECTION)),
+    Field(keys::SECURITY, Expect::Map(SECURITY)),
+];
+const CLUSTER: &[Field] = &[Field(keys::PASSIVE_NODE_POLICY, Expect::Str)];
+const OPTIONS: &[Field] = &[
+    Field(keys::MAX_CONNECTIONS, Expect::Int),
+    Field(keys::MAX_QUERIES, Expect::Int),
+    Field(keys::SPREAD_SECONDS, Expect::Int),
+    Field(keys::HOST_HEAVY_SECTIONS, Expect::Int),
+    Field(keys::SECTION_PARALLELISM, Expect::Int),
+    Field(keys::BLACKOUT_WINDOWS, Expect::StrList),
+];
+const TELEMETRY: &[Field] = &[Field(keys::OTLP_ENDPOINT, Expect::Str)];
+const LIMITS: &[Field] = &[Field(keys::MAX_MEMORY_MB, Expect::Int)];
+const ERROR_SUPPRESSION: &[Field] = &[Field(keys::AFTER, Expect::Int)];
+const EMIT: &[Field] = &[
+    Field(keys::SECTION_PREFIX, Expect::Str),
+    Field(keys::NAMES, Expect::Str),
+    Field(keys::VALUES_LOCALE, Expect::Str),
+];
+const STATIC_SECTION: &[Field] = &[
+    Field(keys::NAME, Expect::Str),
+    Field(keys::FILE, Expect::Str),
+    Field(keys::SEP, Expect::Str),
+    Field(keys::PER_INSTANCE, Expect::Bool),
+];
+const SECURITY: &[Field] = &[
+    Field(keys::QUERY_ALLOWLIST, Expect::Bool),
+    Field(keys::ALLOWED_QUERIES, Expect::MapList(ALLOWED_QUERY)),
+];
+const ALLOWED_QUERY: &[Field] = &[
+    Field(keys::FILE, Expect::Str),
+    Field(keys::SHA256, Expect::Str),
+    Field(keys::ALLOW_WRITE, Expect::Bool),
+];
+const AUTHENTICATION: &[Field] = &[
+    Field(keys::USERNAME, Expect::Str),
+    Field(keys::PASSWORD, Expect::StrOrList),
+    Field(keys::REMEMBER_PASSWORD, Expect::Bool),
+    Field(keys::TYPE, Expect::Str),
+    Field(keys::ACCESS_TOKEN, Expect::Str),
+    Field(keys::KERBEROS, Expect::Map(KERBEROS)),
+];
+const KERBEROS: &[Field] = &[Field(keys::SPN_HOST, Expect::Str)];
+const CONNECTION: &[Field] = &[
+    Field(keys::HOSTNAME, Expect::Str),
+    Field(keys::FAIL_OVER_PARTNER, Expect::Str),
+    Field(keys::PORT, Expect::Int),
+    Field(keys::SOCKET, Expect::Str),
+    Field(keys::TLS, Expect::Map(TLS)),
+    Field(keys::TIMEOUT, Expect::Int),
+    Field(keys::MIN_QUERY_GAP_MS, Expect::Int),
+    Field(keys::SQL_BROWSER, Expect::Map(SQL_BROWSER)),
+    Field(keys::CONNECTION_STRING, Expect::Str),
+    Field(keys::DATABASE, Expect::Str),
+    Field(keys::TRUST_SERVER_CERTIFICATE, Expect::Bool),
+    Field(keys::LISTENER, Expect::Map(LISTENER)),
+    Field(keys::CONNECT_OPTIONS, Expect::Map(CONNECT_OPTIONS)),
+];
+const SQL_BROWSER: &[Field] = &[
+    Field(keys::ENABLED, Expect::Bool),
+    Field(keys::PORT, Expect::Int),
+    Field(keys::TIMEOUT_MS, Expect::Int),
+];
+const CONNECT_OPTIONS: &[Field] = &[
+    Field(keys::PACKET_SIZE, Expect::Int),
+    Field(keys::MARS, Expect::Bool),
+    Field(keys::LANGUAGE, Expect::Str),
+    Field(keys::READONLY, Expect::Bool),
+];
+const LISTENER: &[Field] = &[
+    Field(keys::AVAILABILITY_GROUP, Expect::Str),
+    Field(keys::PIGGYBACK, Expect::Bool),
+];
+const TLS: &[Field] = &[
+    Field(keys::CA, Expect::Str),
+    Field(keys::CLIENT_CERTIFICATE, Expect::Str),
+];
+const DISCOVERY: &[Field] = &[
+    Field(keys::DETECT, Expect::Bool),
+    Field(keys::INCLUDE, Expect::StrList),
+    Field(keys::INCLUDE_FILE, Expect::Str),
+    Field(keys::EXCLUDE, Expect::StrList),
+    Field(keys::SCAN, Expect::StrList),
+    Field(keys::PORTS, Expect::ScalarList),
+    Field(keys::SERVICES, Expect::Bool),
+    Field(keys::CONTAINERS, Expect::Map(CONTAINERS)),
+];
+const CONTAINERS: &[Field] = &[
+    Field(keys::SOCKET, Expect::Str),
+    Field(keys::IMAGE, Expect::Str),
+    Field(keys::LABEL, Expect::Str),
+    Field(keys::PIGGYBACK, Expect::Bool),
+];
+const INSTANCE: &[Field] = &[
+    Field(keys::SID, Expect::Str),
+    Field(keys::AUTHENTICATION, Expect::Map(AUTHENTICATION)),
+    Field(keys::CONNECTION, Expect::Map(CONNECTION)),
+    Field(keys::ALIAS, Expect::Str),
+    Field(keys::PIGGYBACK, Expect::Map(PIGGYBACK)),
+    Field(keys::MAIN_DB, Expect::StrMap),
+    Field(keys::REQUIRED_DATABASES, Expect::StrList),
+];
+const PIGGYBACK: &[Field] = &[
+    Field(keys::HOSTNAME, Expect::Str),
+    Field(keys::HOSTNAME_TEMPLATE, Expect::Str),
+    Field(keys::SECTIONS, Expect::Sections),
+    Field(keys::CACHE_AGE, Expect::Int),
+    Field(keys::SECTION_TIMEOUT, Expect::Int),
+    Field(keys::PROFILE, Expect::Str),
+];
+const SECTION: &[Field] = &[
+    Field(keys::IS_ASYNC, Expect::Bool),
+    Field(keys::DISABLED, Expect::Bool),
+    Field(keys::SEP, Expect::Str),
+    Field(keys::CACHE_AGE, Expect::Int),
+    Field(keys::TIMEOUT, Expect::Int),
+    Field(keys::SAMPLE_INTERVAL, Expect::Int),
+    Field(keys::MAIN_DB, Expect::Str),
+    Field(keys::PER_DATABASE, Expect::Bool),
+    Field(keys::MAX_AGE_DAYS, Expect::Int),
+    Field(keys::INCLUDE_SQL_TEXT, Expect::Bool),
+    Field(keys::COMPUTE_RATES, Expect::Bool),
+    Field(keys::PRIORITY, Expect::Int),
+    Field(keys::FILTERS, Expect::MapList(ROW_FILTER)),
+    Field(keys::MASKING, Expect::MapList(MASK_RULE)),
+    Field(keys::BASELINE, Expect::Map(BASELINE)),
+    Field(keys::PROCEDURE, Expect::Str),
+    Field(keys::PARAMS, Expect::MapList(PROCEDURE_PARAM)),
+];
+const PROCEDURE_PARAM: &[Field] = &[
+    Field(keys::NAME, Expect::Str),
+    Field(keys::TYPE, Expect::Str),
+    Field(keys::VALUE, Expect::Scalar),
+];
+const ROW_FILTER: &[Field] = &[
+    Field(keys::COLUMN, Expect::Str),
+    Field(keys::EQUALS, Expect::Str),
+    Field(keys::CONTAINS, Expect::Str),
+    Field(keys::MATCHES, Expect::Str),
+    Field(keys::ACTION, Expect::Str),
+];
+const BASELINE: &[Field] = &[
+    Field(keys::RECOVERY_MODEL, Expect::Str),
+    Field(keys::AUTO_SHRINK, Expect::Bool),
+    Field(keys::AUTO_CLOSE, Expect::Bool),
+    Field(keys::PAGE_VERIFY, Expect::Str),
+];
+const MASK_RULE: &[Field] = &[
+    Field(keys::COLUMN, Expect::Str),
+    Field(keys::EQUALS, Expect::Str),
+    Field(keys::CONTAINS, Expect::Str),
+    Field(keys::MATCHES, Expect::Str),
+    Field(keys::REPLACEMENT, Expect::Str),
+];
+
+fn check_node(node: &Node, expect: &Expect, path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut report = |problem: Problem, suggestion: Option<String>| {
+        diagnostics.push(Diagnostic {
+            path: path.to_string(),
+            line: node.line,
+            col: node.col,
+            problem,
+            suggestion,
+        })
+    };
+    match (expect, &node.value) {
+        // absent value is always allowed and means default
+        (_, Value::Null) => {}
+        (Expect::Str | Expect::StrOrList, Value::Str(_))
+        | (Expect::Int, Value::Int)
+        | (Expect::Bool, Value::Bool)
+        | (Expect::Scalar, Value::Str(_) | Value::Int | Value::Bool) => {}
+        (Expect::Str | Expect::StrOrList, Value::Int | Value::Bool) => report(
+            Problem::WrongType("string"),
+            Some("enclose the value in quotes".to_string()),
+        ),
+        (Expect::Int, Value::Str(_)) => report(
+            Problem::WrongType("integer"),
+            Some("remove quotes around the number".to_string()),
+        ),
+        // yes/no are accepted too, see `Get::get_optional_bool`
+        (Expect::Bool, Value::Str(s)) if to_bool(s).is_ok() => {}
+        (Expect::Bool, Value::Str(_) | Value::Int) => report(
+            Problem::WrongType("boolean"),
+            Some("use yes/no or true/false".to_string()),
+        ),
+        (Expect::StrList | Expect::StrOrList, Value::Seq(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                check_node(item, &Expect::Str, &format!("{path}[{i}]"), diagnostics);
+            }
+        }
+        (Expect::ScalarList, Value::Seq(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                check_node(item, &Expect::Scalar, &format!("{path}[{i}]"), diagnostics);
+            }
+        }
+        (Expect::StrMap, Value::Map(entries)) => {
+            for (key, value) in entries {
+                check_node(value, &Expect::Str, &join(path, key), diagnostics);
+            }
+        }
+        (Expect::Map(fields), Value::Map(entries)) => check_map(fields, entries, path, diagnostics),
+        (Expect::MapList(fields), Value::Seq(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                check_node(
+                    item,
+                    &Expect::Map(fields),
+                    &format!("{path}[{i}]"),
+                    diagnostics,
+                );
+            }
+        }
+        (Expect::Sections, Value::Seq(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                check_section(item, &format!("{path}[{i}]"), diagnostics);
+            }
+        }
+        (expect, _) => report(Problem::WrongType(expect.describe()), None),
+    }
+}
+
+fn check_map(
+    fields: &[Field],
+    entries: &[(String, Node)],
+    path: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for (key, value) in entries {
+        let key_path = join(path, key);
+        match fields.iter().find(|f| f.0 == key) {
+            Some(Field(_, expect)) => check_node(value, expect, &key_path, diagnostics),
+            None => diagnostics.push(Diagnostic {
+                path: key_path,
+                line: value.line,
+                col: value.col,
+                problem: Problem::UnknownKey,
+                suggestion: find_similar(key, fields.iter().map(|f| f.0))
+                    .map(|s| format!("did you mean `{s}`?")),
+            }),
+        }
+    }
+}
+
+/// section is a single key map: `- name:` with optional settings
+fn check_section(node: &Node, path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let entries = match &node.value {
+        Value::Map(entries) => entries,
+        _ => {
+            return check_node(node, &Expect::Map(&[]), path, diagnostics);
+        }
+    };
+    for (name, value) in entries {
+        let name_path = join(path, name);
+        let known = get_known_section_names();
+        if !known.contains(&name.as_str()) {
+            let bad_chars = name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            let similar = find_similar(name, known.iter().copied());
+            if bad_chars || similar.is_some() {
+                diagnostics.push(Diagnostic {
+                    path: name_path.clone(),
+                    line: value.line,
+                    col: value.col,
+                    problem: Problem::InvalidSectionName,
+                    suggestion: similar
+                        .map(|s| format!("did you mean `{s}`?"))
+                        .or_else(|| Some("use only letters, digits, `_` and `-`".to_string())),
+                });
+            }
+        }
+        check_node(value, &Expect::Map(SECTION), &name_path, diagnostics);
+    }
+}
+
+impl Expect {
+    fn describe(&self) -> &'static str {
+        match self {
+            Expect::Str => "string",
+            Expect::Int => "integer",
+            Expect::Bool => "boolean",
+            Expect::StrList => "list of strings",
+            Expect::ScalarList => "list of scalars",
+            Expect::StrOrList => "string or list of strings",
+            Expect::Map(_) | Expect::StrMap => "mapping",
+            Expect::MapList(_) => "list of mappings",
+            Expect::Sections => "list of sections",
+            Expect::Scalar => "scalar",
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// the closest candidate with a couple of typos, case is ignored
+fn find_similar<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    candidates
+        .map(|c| (edit_distance(&name, &c.chars().collect::<Vec<_>>()), c))
+        .filter(|(d, c)| *d <= 2 && *d < c.len() / 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_valid() {
+        const SOURCE: &str = r#"
+---
+system:
+  logging:
+    level: "warn"
+mssql:
+  main:
+    authentication:
+      username: "u"
+      password: ['p', "old"]
+      remember_password: yes
+    connection:
+      port: 1433
+      trust_server_certificate: yes
+    sections:
+      - instance:
+      - jobs:
+          is_async: yes
+      - someOtherSQL:
+    discovery:
+      include: ["a"]
+    instances:
+      - sid: "INST"
+        main_db:
+          jobs: "msdb2"
+"#;
+        assert_eq!(check(SOURCE), vec![]);
+    }
+
+    #[test]
+    fn test_check_problems() {
+        const SOURCE: &str = r#"mssql:
+  main:
+    authentcation:
+      username: "u"
+    connection:
+      port: "1433"
+      hostname: 12
+    sections:
+      - databses:
+      - "bad name":
+      - jobs:
+          is_asinc: yes
+    discovery:
+      include: "a"
+      detect: maybe
+"#;
+        let d = check(SOURCE);
+        assert_eq!(
+            d.iter()
+                .map(|d| (d.path.as_str(), d.line, d.problem.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("mssql.main.authentcation", 3, Problem::UnknownKey),
+                (
+                    "mssql.main.connection.port",
+                    6,
+                    Problem::WrongType("integer")
+                ),
+                (
+                    "mssql.main.connection.hostname",
+                    7,
+                    Problem::WrongType("string")
+                ),
+                (
+                    "mssql.main.sections[0].databses",
+                    9,
+                    Problem::InvalidSectionName
+                ),
+                (
+                    "mssql.main.sections[1].bad name",
+                    10,
+                    Problem::InvalidSectionName
+                ),
+                (
+                    "mssql.main.sections[2].jobs.is_asinc",
+                    12,
+                    Problem::UnknownKey
+                ),
+                (
+                    "mssql.main.discovery.include",
+                    14,
+                    Problem::WrongType("list of strings")
+                ),
+                (
+                    "mssql.main.discovery.detect",
+                    15,
+                    Problem::WrongType("boolean")
+                ),
+            ]
+        );
+        assert_eq!(
+            d[0].to_string(),
+            "mssql.main.authentcation at line 3 column 5: unknown key, did you mean `authentication`?"
+        );
+        assert_eq!(
+            d[3].suggestion.as_deref(),
+            Some("did you mean `databases`?")
+        );
+    }
+
+    #[test]
+    fn test_check_syntax() {
+        let d = check("mssql:\n  main: [\n");
+        assert_eq!(d.len(), 1);
+        assert!(matches!(d[0].problem, Problem::Syntax(_)));
+        assert!(d[0].to_string().starts_with("<root> at line"));
+    }
+}
@@ -5,6 +5,7 @@
 use super::defines::{defaults, keys, values};
 use super::section::{Section, SectionKind, Sections};
 use super::yaml::{Get, Yaml};
+use crate::locale::ValueLocale;
 use crate::platform;
 use crate::platform::registry::get_instances;
 use crate::platform::InstanceInfo;
@@ -13,7 +14,7 @@ use crate::types::{
 };
 use anyhow::{anyhow, bail, Context, Result};
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -26,17 +27,33 @@ pub struct Config {
     sections: Sections,
     discovery: Discovery,
     piggyback_host: Option<String>,
+    piggyback_template: Option<String>,
     mode: Mode,
+    cluster_dedup: ClusterDedup,
+    passive_node_policy: PassiveNodePolicy,
+    qualify_with_host: bool,
+    instance_prefix: Option<String>,
     custom_instances: Vec<CustomInstance>,
     configs: Vec<Config>,
     hash: String,
     options: Options,
+    telemetry: Telemetry,
+    limits: Limits,
+    error_suppression: ErrorSuppression,
+    emit: Emit,
+    static_sections: Vec<StaticSection>,
+    security: Security,
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Options {
     max_connections: MaxConnections,
     max_queries: MaxQueries,
+    spread_seconds: u32,
+    host_heavy_sections: u32,
+    section_parallelism: u32,
+    blackout_windows: Vec<BlackoutWindow>,
+    generators: Vec<String>,
 }
 
 impl Default for Options {
@@ -44,6 +61,11 @@ impl Default for Options {
         Self {
             max_connections: defaults::MAX_CONNECTIONS.into(),
             max_queries: defaults::MAX_QUERIES.into(),
+            spread_seconds: defaults::SPREAD_SECONDS,
+            host_heavy_sections: defaults::HOST_HEAVY_SECTIONS,
+            section_parallelism: defaults::SECTION_PARALLELISM,
+            blackout_windows: vec![],
+            generators: vec![],
         }
     }
 }
@@ -53,6 +75,11 @@ impl Options {
         Self {
             max_connections,
             max_queries: defaults::MAX_QUERIES.into(),
+            spread_seconds: defaults::SPREAD_SECONDS,
+            host_heavy_sections: defaults::HOST_HEAVY_SECTIONS,
+            section_parallelism: defaults::SECTION_PARALLELISM,
+            blackout_windows: vec![],
+            generators: vec![],
         }
     }
 
@@ -64,6 +91,31 @@ impl Options {
         self.max_queries.clone()
     }
 
+    /// start of instances is spread over this period to avoid login storm
+    pub fn spread_seconds(&self) -> u32 {
+        self.spread_seconds
+    }
+
+    /// heavy sections running at once on instances sharing a host, 0 - no limit
+    pub fn host_heavy_sections(&self) -> u32 {
+        self.host_heavy_sections
+    }
+
+    /// sections of one instance running at once, every extra one uses own connection
+    pub fn section_parallelism(&self) -> u32 {
+        self.section_parallelism
+    }
+
+    /// only the instance section is generated during these windows
+    pub fn blackout_windows(&self) -> &[BlackoutWindow] {
+        &self.blackout_windows
+    }
+
+    /// plugins of extra section generators to use, see [`crate::ms_sql::generator::register`]
+    pub fn generators(&self) -> &[String] {
+        &self.generators
+    }
+
     pub fn from_yaml(yaml: &Yaml) -> Result<Option<Self>> {
         let options = yaml.get(keys::OPTIONS);
         if options.is_badvalue() {
@@ -79,6 +131,393 @@ impl Options {
                 })
                 .into(),
             max_queries: defaults::MAX_QUERIES.into(),
+            spread_seconds: options
+                .get_int::<u32>(keys::SPREAD_SECONDS)
+                .unwrap_or(defaults::SPREAD_SECONDS),
+            host_heavy_sections: options
+                .get_int::<u32>(keys::HOST_HEAVY_SECTIONS)
+                .unwrap_or(defaults::HOST_HEAVY_SECTIONS),
+            section_parallelism: options
+                .get_int::<u32>(keys::SECTION_PARALLELISM)
+                .unwrap_or(defaults::SECTION_PARALLELISM)
+                .max(1),
+            blackout_windows: options
+                .get_string_vector(keys::BLACKOUT_WINDOWS, &[])
+                .iter()
+                .map(|w| BlackoutWindow::try_from(w.as_str()))
+                .collect::<Result<Vec<_>>>()?,
+            generators: options.get_string_vector(keys::GENERATORS, &[]),
+        }))
+    }
+}
+
+/// Cron-like window `<days> <HH:MM>-<HH:MM>` in the local time of the SQL Server
+/// days are `*` or list of days and ranges, 0 and 7 are Sunday: `1-5`, `0,6`
+/// window with end before start crosses midnight: `5 22:00-02:00` ends on Saturday
+#[derive(PartialEq, Debug, Clone)]
+pub struct BlackoutWindow {
+    days: [bool; 7],
+    start: u32,
+    end: u32,
+}
+
+impl TryFrom<&str> for BlackoutWindow {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        let (days, time) = value
+            .trim()
+            .split_once(char::is_whitespace)
+            .with_context(|| {
+                format!("Bad blackout window `{value}`, expected `<days> <HH:MM>-<HH:MM>`")
+            })?;
+        let (start, end) = time
+            .trim()
+            .split_once('-')
+            .with_context(|| format!("Bad time range in blackout window `{value}`"))?;
+        Ok(Self {
+            days: parse_days(days)
+                .with_context(|| format!("Bad days in blackout window `{value}`"))?,
+            start: parse_minutes(start)
+                .with_context(|| format!("Bad start in blackout window `{value}`"))?,
+            end: parse_minutes(end)
+                .with_context(|| format!("Bad end in blackout window `{value}`"))?,
+        })
+    }
+}
+
+impl BlackoutWindow {
+    /// `weekday` is 0 for Sunday, `minutes` since midnight
+    pub fn contains(&self, weekday: u32, minutes: u32) -> bool {
+        let today = self.days[(weekday % 7) as usize];
+        if self.start <= self.end {
+            today && minutes >= self.start && minutes < self.end
+        } else {
+            let yesterday = self.days[((weekday + 6) % 7) as usize];
+            (today && minutes >= self.start) || (yesterday && minutes < self.end)
+        }
+    }
+}
+
+fn parse_days(days: &str) -> Option<[bool; 7]> {
+    let mut result = [false; 7];
+    for part in days.split(',') {
+        let (first, last) = match part.trim() {
+            "*" => (0, 6),
+            p => match p.split_once('-') {
+                Some((first, last)) => (first.trim().parse().ok()?, last.trim().parse().ok()?),
+                None => {
+                    let day = p.parse().ok()?;
+                    (day, day)
+                }
+            },
+        };
+        if first > last || last > 7 {
+            return None;
+        }
+        for day in first..=last {
+            result[(day % 7) as usize] = true;
+        }
+    }
+    Some(result)
+}
+
+fn parse_minutes(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours <= 24 && minutes < 60 && hours * 60 + minutes <= 24 * 60).then_some(hours * 60 + minutes)
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Telemetry {
+    otlp_endpoint: Option<String>,
+}
+
+impl Telemetry {
+    /// `http://host:port` of OTLP collector, None if tracing is not required
+    pub fn otlp_endpoint(&self) -> Option<&str> {
+        self.otlp_endpoint.as_deref()
+    }
+
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<Self>> {
+        let telemetry = yaml.get(keys::TELEMETRY);
+        if telemetry.is_badvalue() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            otlp_endpoint: telemetry
+                .get_string(keys::OTLP_ENDPOINT)
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+        }))
+    }
+}
+
+/// Resources the plugin may use
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Limits {
+    max_memory_mb: Option<u32>,
+}
+
+impl Limits {
+    /// budget of every section, its output is truncated if exceeded
+    pub fn max_memory(&self) -> Option<usize> {
+        self.max_memory_mb.map(|mb| mb as usize * 1024 * 1024)
+    }
+
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<Self>> {
+        let limits = yaml.get(keys::LIMITS);
+        if limits.is_badvalue() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            max_memory_mb: limits
+                .get_int::<u32>(keys::MAX_MEMORY_MB)
+                .filter(|mb| *mb > 0),
+        }))
+    }
+}
+
+/// Output of an unreachable instance is reduced if the error repeats
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ErrorSuppression {
+    after: u32,
+}
+
+impl ErrorSuppression {
+    /// count of consecutive identical failures reported in full, None if suppression is off
+    pub fn after(&self) -> Option<u32> {
+        (self.after > 0).then_some(self.after)
+    }
+
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<Self>> {
+        let suppression = yaml.get(keys::ERROR_SUPPRESSION);
+        if suppression.is_badvalue() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            after: suppression.get_int::<u32>(keys::AFTER).unwrap_or_default(),
+        }))
+    }
+}
+
+/// Naming of the output, e.g. to run a new plugin version alongside the old one
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Emit {
+    section_prefix: Option<String>,
+    names: NameEncoding,
+    values_locale: Option<String>,
+}
+
+/// Encoding of names(databases, files, counters) in the data lines
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum NameEncoding {
+    /// UTF-8 as is
+    #[default]
+    Keep,
+    /// non-ASCII as UTF-16 units `_xHHHH_`, the way SQL Server encodes names in XML
+    Escape,
+}
+
+impl TryFrom<&str> for NameEncoding {
+    type Error = anyhow::Error;
+
+    fn try_from(str: &str) -> Result<Self> {
+        match str::to_ascii_lowercase(str).trim() {
+            values::KEEP => Ok(NameEncoding::Keep),
+            values::ESCAPE => Ok(NameEncoding::Escape),
+            _ => Err(anyhow!("unsupported names `{str}`")),
+        }
+    }
+}
+
+impl Emit {
+    /// replaces `mssql` in `<<<mssql_name>>>`
+    pub fn section_prefix(&self) -> Option<&str> {
+        self.section_prefix.as_deref()
+    }
+
+    pub fn names(&self) -> NameEncoding {
+        self.names
+    }
+
+    /// SQL Server language whose number and date formats are converted to invariant ones
+    pub fn values_locale(&self) -> Option<&str> {
+        self.values_locale.as_deref()
+    }
+
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<Self>> {
+        let emit = yaml.get(keys::EMIT);
+        if emit.is_badvalue() {
+            return Ok(None);
+        }
+        let section_prefix = emit
+            .get_string(keys::SECTION_PREFIX)
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty());
+        if let Some(prefix) = &section_prefix {
+            if !prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                anyhow::bail!("Bad section_prefix `{prefix}`: only letters, digits and _ allowed");
+            }
+        }
+        let names = emit
+            .get_string(keys::NAMES)
+            .map(|n| NameEncoding::try_from(n.as_str()))
+            .transpose()?
+            .unwrap_or_default();
+        let values_locale = emit
+            .get_string(keys::VALUES_LOCALE)
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty());
+        if let Some(language) = &values_locale {
+            if ValueLocale::from_language(language).is_none() {
+                anyhow::bail!("unsupported values_locale `{language}`");
+            }
+        }
+        Ok(Some(Self {
+            section_prefix,
+            names,
+            values_locale,
+        }))
+    }
+}
+
+/// Site-specific section, the file produced by other tooling is emitted verbatim
+#[derive(PartialEq, Debug, Clone)]
+pub struct StaticSection {
+    name: String,
+    file: PathBuf,
+    sep: char,
+    per_instance: bool,
+}
+
+impl StaticSection {
+    /// header is `<<<name>>>`, without `mssql` prefix
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    pub fn sep(&self) -> char {
+        self.sep
+    }
+
+    /// emitted into the piggyback data of every instance, otherwise once
+    pub fn per_instance(&self) -> bool {
+        self.per_instance
+    }
+
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
+        let name = yaml
+            .get_string(keys::NAME)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .context("Bad/Missing name in static section")?;
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            anyhow::bail!("Bad static section name `{name}`: only letters, digits and _ allowed");
+        }
+        let file = yaml
+            .get_string(keys::FILE)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .with_context(|| format!("Bad/Missing file in static section `{name}`"))?;
+        Ok(Self {
+            name,
+            file: PathBuf::from(file),
+            sep: yaml
+                .get_string(keys::SEP)
+                .and_then(|s| s.chars().next())
+                .unwrap_or(defaults::DEFAULT_SEP),
+            per_instance: yaml.get_bool(keys::PER_INSTANCE, false),
+        })
+    }
+}
+
+/// Custom sql file permitted in safe mode
+#[derive(PartialEq, Debug, Clone)]
+pub struct AllowedQuery {
+    file: String,
+    sha256: String,
+    allow_write: bool,
+}
+
+impl AllowedQuery {
+    /// file name in the sql directory, e.g. `my_section.sql`
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// lower case hex
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    /// data and schema modifying statements, GRANT and EXEC are permitted
+    pub fn allow_write(&self) -> bool {
+        self.allow_write
+    }
+
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
+        Ok(Self {
+            file: yaml
+                .get_string(keys::FILE)
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .context("Bad/Missing file in allowed query")?,
+            sha256: yaml
+                .get_string(keys::SHA256)
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .context("Bad/Missing sha256 in allowed query")?,
+            allow_write: yaml.get_bool(keys::ALLOW_WRITE, false),
+        })
+    }
+}
+
+/// Safe mode: custom sql files must be explicitly allowed
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Security {
+    query_allowlist: bool,
+    allowed_queries: Vec<AllowedQuery>,
+}
+
+impl Security {
+    pub fn query_allowlist(&self) -> bool {
+        self.query_allowlist
+    }
+
+    pub fn allowed_queries(&self) -> &[AllowedQuery] {
+        &self.allowed_queries
+    }
+
+    pub fn find_allowed_query(&self, file: &str) -> Option<&AllowedQuery> {
+        self.allowed_queries
+            .iter()
+            .find(|q| q.file().eq_ignore_ascii_case(file))
+    }
+
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<Self>> {
+        let security = yaml.get(keys::SECURITY);
+        if security.is_badvalue() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            query_allowlist: security.get_bool(keys::QUERY_ALLOWLIST, false),
+            allowed_queries: security
+                .get_yaml_vector(keys::ALLOWED_QUERIES)
+                .iter()
+                .map(AllowedQuery::from_yaml)
+                .collect::<Result<Vec<AllowedQuery>>>()?,
         }))
     }
 }
@@ -91,11 +530,22 @@ impl Default for Config {
             sections: Sections::default(),
             discovery: Discovery::default(),
             piggyback_host: None,
+            piggyback_template: None,
             mode: Mode::Port,
+            cluster_dedup: ClusterDedup::default(),
+            passive_node_policy: PassiveNodePolicy::default(),
+            qualify_with_host: false,
+            instance_prefix: None,
             custom_instances: vec![],
             configs: vec![],
             hash: String::new(),
             options: Options::default(),
+            telemetry: Telemetry::default(),
+            limits: Limits::default(),
+            error_suppression: ErrorSuppression::default(),
+            emit: Emit::default(),
+            static_sections: vec![],
+            security: Security::default(),
         }
     }
 }
@@ -151,6 +601,17 @@ impl Config {
         let conn =
             Connection::from_yaml(main, Some(&auth))?.unwrap_or_else(|| default.conn().clone());
         let options = Options::from_yaml(main)?.unwrap_or_else(|| default.options().clone());
+        let telemetry = Telemetry::from_yaml(main)?.unwrap_or_else(|| default.telemetry().clone());
+        let limits = Limits::from_yaml(main)?.unwrap_or_else(|| default.limits().clone());
+        let error_suppression = ErrorSuppression::from_yaml(main)?
+            .unwrap_or_else(|| default.error_suppression().clone());
+        let emit = Emit::from_yaml(main)?.unwrap_or_else(|| default.emit().clone());
+        let static_sections = main
+            .get_yaml_vector(keys::STATIC_SECTIONS)
+            .iter()
+            .map(StaticSection::from_yaml)
+            .collect::<Result<Vec<StaticSection>>>()?;
+        let security = Security::from_yaml(main)?.unwrap_or_else(|| default.security().clone());
         let discovery = Discovery::from_yaml(main)?.unwrap_or_else(|| default.discovery().clone());
         let section_info = Sections::from_yaml(main, &default.sections)?;
 
@@ -159,6 +620,9 @@ impl Config {
             .into_iter()
             .map(|v| CustomInstance::from_yaml(&v, &auth, &conn, &section_info))
             .collect::<Result<Vec<CustomInstance>>>()?;
+        let included_instances =
+            get_included_instances(&custom_instances, discovery.included(), &auth, &conn);
+        custom_instances.extend(included_instances);
         if discovery.detect() {
             let registry_instances =
                 get_additional_registry_instances(&custom_instances, &auth, &conn);
@@ -177,7 +641,24 @@ impl Config {
             log::info!("skipping registry instances: the reason detection disabled");
         }
         let mode = Mode::from_yaml(main).unwrap_or_else(|_| default.mode().clone());
+        let cluster_dedup = ClusterDedup::from_yaml(main).unwrap_or_else(|e| {
+            log::warn!("{e}, using default");
+            default.cluster_dedup().clone()
+        });
+        let passive_node_policy = PassiveNodePolicy::from_yaml(main).unwrap_or_else(|e| {
+            log::warn!("{e}, using default");
+            default.passive_node_policy().clone()
+        });
         let piggyback_host = main.get_string(keys::PIGGYBACK_HOST);
+        let piggyback_template = main
+            .get(keys::PIGGYBACK)
+            .get_string(keys::HOSTNAME_TEMPLATE)
+            .filter(|t| !t.trim().is_empty());
+        let qualify_with_host = main.get_bool(keys::QUALIFY_WITH_HOST, default.qualify_with_host);
+        let instance_prefix = main
+            .get_string(keys::INSTANCE_PREFIX)
+            .map(|s| s.trim().to_uppercase())
+            .filter(|s| !s.is_empty());
 
         Ok(Some(Self {
             auth,
@@ -185,11 +666,22 @@ impl Config {
             sections: section_info,
             discovery,
             piggyback_host,
+            piggyback_template,
             mode,
+            cluster_dedup,
+            passive_node_policy,
+            qualify_with_host,
+            instance_prefix,
             custom_instances,
             configs: vec![],
             hash,
             options,
+            telemetry,
+            limits,
+            error_suppression,
+            emit,
+            static_sections,
+            security,
         }))
     }
 
@@ -197,6 +689,30 @@ impl Config {
         &self.options
     }
 
+    pub fn telemetry(&self) -> &Telemetry {
+        &self.telemetry
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn error_suppression(&self) -> &ErrorSuppression {
+        &self.error_suppression
+    }
+
+    pub fn emit(&self) -> &Emit {
+        &self.emit
+    }
+
+    pub fn static_sections(&self) -> &[StaticSection] {
+        &self.static_sections
+    }
+
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+
     pub fn endpoint(&self) -> Endpoint {
         Endpoint::new(&self.auth, &self.conn)
     }
@@ -222,12 +738,31 @@ impl Config {
         self.piggyback_host.as_deref()
     }
 
+    /// piggyback host of instances without explicit one, `{instance}` and `{hostname}` are expanded
+    pub fn piggyback_template(&self) -> Option<&str> {
+        self.piggyback_template.as_deref()
+    }
+
     pub fn discovery(&self) -> &Discovery {
         &self.discovery
     }
     pub fn mode(&self) -> &Mode {
         &self.mode
     }
+    pub fn cluster_dedup(&self) -> &ClusterDedup {
+        &self.cluster_dedup
+    }
+    pub fn passive_node_policy(&self) -> &PassiveNodePolicy {
+        &self.passive_node_policy
+    }
+    /// instance in the output is named `<HOST>_<NAME>` to avoid collisions between configs
+    pub fn qualify_with_host(&self) -> bool {
+        self.qualify_with_host
+    }
+    /// prepended to the name of every instance of the config in the output
+    pub fn instance_prefix(&self) -> Option<&str> {
+        self.instance_prefix.as_deref()
+    }
     pub fn instances(&self) -> &Vec<CustomInstance> {
         &self.custom_instances
     }
@@ -243,6 +778,16 @@ impl Config {
         &self.sections
     }
 
+    /// sections of the piggyback block of the instance, if any, inherit from the config
+    pub fn instance_sections(&self, name: &InstanceName) -> &Sections {
+        self.custom_instances
+            .iter()
+            .find(|i| i.name().normalized() == name.normalized())
+            .and_then(|i| i.piggyback())
+            .map(|p| p.sections())
+            .unwrap_or(&self.sections)
+    }
+
     pub fn is_instance_allowed(&self, name: &impl ToString) -> bool {
         self.discovery
             .is_instance_allowed(&InstanceName::from(name.to_string()))
@@ -271,13 +816,13 @@ fn get_additional_registry_instances(
 
     let names: HashSet<String> = already_found_instances
         .iter()
-        .map(|i| i.name().to_string().to_lowercase().clone())
+        .map(|i| i.name().normalized())
         .collect();
     log::info!("localhost is defined, adding registry instances");
     platform::registry::get_instances()
         .into_iter()
         .filter_map(|i| {
-            if names.contains(&i.name.to_string().to_lowercase()) {
+            if names.contains(&i.name.normalized()) {
                 log::info!(
                     "{} is ignored as already defined in custom instances",
                     i.name
@@ -295,48 +840,111 @@ fn get_additional_registry_instances(
         .collect::<Vec<CustomInstance>>()
 }
 
+/// Included instances with explicit port or hostname become custom instances
+fn get_included_instances(
+    already_found_instances: &[CustomInstance],
+    included: &[IncludedInstance],
+    auth: &Authentication,
+    conn: &Connection,
+) -> Vec<CustomInstance> {
+    let names: HashSet<String> = already_found_instances
+        .iter()
+        .map(|i| i.name().normalized())
+        .collect();
+    included
+        .iter()
+        .filter(|i| i.port.is_some() || i.hostname.is_some())
+        .filter(|i| {
+            let is_defined = names.contains(&i.name.normalized());
+            if is_defined {
+                log::info!(
+                    "{} is ignored as already defined in custom instances",
+                    i.name
+                );
+            }
+            !is_defined
+        })
+        .map(|i| CustomInstance::from_included(i, auth, conn))
+        .collect()
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Authentication {
     username: String,
-    password: Option<String>,
+    /// candidates in order of preference, more than one during password rotation
+    passwords: Vec<String>,
+    remember_password: bool,
     auth_type: AuthType,
     access_token: Option<String>,
+    kerberos: Option<Kerberos>,
 }
 
 impl Default for Authentication {
     fn default() -> Self {
         Self {
             username: "".to_owned(),
-            password: None,
+            passwords: vec![],
+            remember_password: false,
             auth_type: AuthType::default(),
             access_token: None,
+            kerberos: None,
         }
     }
 }
 impl Authentication {
     pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
+        Authentication::from_yaml_inherited(yaml, &Authentication::default())
+    }
+
+    /// Values absent in yaml are taken from `base`:
+    /// - credentials are inherited only if auth type is the same
+    /// - password is inherited only if username is the same
+    pub fn from_yaml_inherited(yaml: &Yaml, base: &Authentication) -> Result<Self> {
         let auth = yaml.get(keys::AUTHENTICATION);
         if auth.is_badvalue() {
             anyhow::bail!("authentication is missing");
         }
 
+        let auth_type = auth
+            .get_string(keys::TYPE)
+            .map(|t| AuthType::try_from(t.as_str()))
+            .transpose()?
+            .unwrap_or_else(|| base.auth_type.clone());
+        let base = if auth_type == base.auth_type {
+            base.clone()
+        } else {
+            Self::default()
+        };
+        let username = auth.get_string(keys::USERNAME);
+        let passwords = match username {
+            Some(ref u) if u != &base.username => get_passwords(auth).unwrap_or_default(),
+            _ => get_passwords(auth).unwrap_or(base.passwords),
+        };
         Ok(Self {
-            username: auth.get_string(keys::USERNAME).unwrap_or_default(),
-            password: auth.get_string(keys::PASSWORD),
-            auth_type: AuthType::try_from(
-                auth.get_string(keys::TYPE)
-                    .as_deref()
-                    .unwrap_or(defaults::AUTH_TYPE),
-            )?,
-            access_token: auth.get_string(keys::ACCESS_TOKEN),
+            username: username.unwrap_or(base.username),
+            passwords,
+            remember_password: auth
+                .get_optional_bool(keys::REMEMBER_PASSWORD)
+                .unwrap_or(base.remember_password),
+            auth_type,
+            access_token: auth.get_string(keys::ACCESS_TOKEN).or(base.access_token),
+            kerberos: Kerberos::from_yaml(auth).or(base.kerberos),
         }
         .ensure())
     }
     pub fn username(&self) -> &str {
         &self.username
     }
+    /// the first candidate
     pub fn password(&self) -> Option<&String> {
-        self.password.as_ref()
+        self.passwords.first()
+    }
+    pub fn passwords(&self) -> &[String] {
+        &self.passwords
+    }
+    /// the working candidate is persisted between runs
+    pub fn remember_password(&self) -> bool {
+        self.remember_password
     }
     pub fn auth_type(&self) -> &AuthType {
         &self.auth_type
@@ -344,6 +952,9 @@ impl Authentication {
     pub fn access_token(&self) -> Option<&String> {
         self.access_token.as_ref()
     }
+    pub fn kerberos(&self) -> Option<&Kerberos> {
+        self.kerberos.as_ref()
+    }
 
     pub fn defined(&self) -> bool {
         self.auth_type() == &AuthType::Integrated || !self.username().is_empty()
@@ -352,13 +963,53 @@ impl Authentication {
     fn ensure(mut self) -> Self {
         if self.auth_type() == &AuthType::Integrated {
             self.username = String::new();
-            self.password = None;
+            self.passwords = vec![];
             self.access_token = None;
         }
         self
     }
 }
 
+/// Kerberos logon of a gateway host, credentials may be delegated to remote servers
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Kerberos {
+    /// FQDN of the SQL Server defining the SPN, used as host name to connect to
+    spn_host: Option<HostName>,
+}
+
+impl Kerberos {
+    pub fn from_yaml(auth: &Yaml) -> Option<Self> {
+        let kerberos = auth.get(keys::KERBEROS);
+        if kerberos.is_badvalue() {
+            return None;
+        }
+        Some(Self {
+            spn_host: kerberos
+                .get_string(keys::SPN_HOST)
+                .map(|h| h.trim().to_string())
+                .filter(|h| !h.is_empty())
+                .map(HostName::from),
+        })
+    }
+
+    pub fn spn_host(&self) -> Option<&HostName> {
+        self.spn_host.as_ref()
+    }
+}
+
+/// `password` is either a string or a list of candidates
+fn get_passwords(auth: &Yaml) -> Option<Vec<String>> {
+    match auth.get(keys::PASSWORD) {
+        Yaml::Array(candidates) => Some(
+            candidates
+                .iter()
+                .filter_map(|c| c.as_str().map(str::to_string))
+                .collect(),
+        ),
+        _ => auth.get_string(keys::PASSWORD).map(|p| vec![p]),
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum AuthType {
     SqlServer,
@@ -404,10 +1055,25 @@ pub struct Connection {
     trust_server_certificate: bool,
     tls: Option<ConnectionTls>,
     timeout: u64,
+    listener: Option<Listener>,
+    min_query_gap_ms: u64,
+    sql_browser: SqlBrowser,
+    connection_string: Option<String>,
+    database: Option<String>,
+    connect_options: ConnectOptions,
 }
 
 impl Connection {
     pub fn from_yaml(yaml: &Yaml, auth: Option<&Authentication>) -> Result<Option<Self>> {
+        Connection::from_yaml_inherited(yaml, auth, &Connection::default())
+    }
+
+    /// Values absent in yaml are taken from `base`
+    pub fn from_yaml_inherited(
+        yaml: &Yaml,
+        auth: Option<&Authentication>,
+        base: &Connection,
+    ) -> Result<Option<Self>> {
         let conn = yaml.get(keys::CONNECTION);
         if conn.is_badvalue() {
             return Ok(None);
@@ -423,24 +1089,48 @@ impl Connection {
                             s
                         }
                     })
-                    .unwrap_or_else(|| defaults::CONNECTION_HOST_NAME.to_string())
+                    .unwrap_or_else(|| base.hostname.to_string())
                     .to_lowercase()
                     .into(),
-                fail_over_partner: conn.get_string(keys::FAIL_OVER_PARTNER),
-                port: Port(conn.get_int::<u16>(keys::PORT).unwrap_or_else(|| {
-                    log::debug!("no port specified, using default");
-                    defaults::CONNECTION_PORT
-                })),
-                socket: conn.get_pathbuf(keys::SOCKET),
+                fail_over_partner: conn
+                    .get_string(keys::FAIL_OVER_PARTNER)
+                    .or_else(|| base.fail_over_partner.clone()),
+                port: conn
+                    .get_int::<u16>(keys::PORT)
+                    .map(Port)
+                    .unwrap_or_else(|| {
+                        log::debug!("no port specified, using {}", base.port);
+                        base.port.clone()
+                    }),
+                socket: conn
+                    .get_pathbuf(keys::SOCKET)
+                    .or_else(|| base.socket.clone()),
                 trust_server_certificate: conn.get_bool(
                     keys::TRUST_SERVER_CERTIFICATE,
-                    defaults::TRUST_SERVER_CERTIFICATE,
+                    base.trust_server_certificate,
                 ),
-                tls: ConnectionTls::from_yaml(conn)?,
+                tls: ConnectionTls::from_yaml(conn)?.or_else(|| base.tls.clone()),
                 timeout: conn.get_int::<u64>(keys::TIMEOUT).unwrap_or_else(|| {
-                    log::debug!("no timeout specified, using default");
-                    defaults::CONNECTION_TIMEOUT
+                    log::debug!("no timeout specified, using {}", base.timeout);
+                    base.timeout
                 }),
+                listener: Listener::from_yaml(conn)?.or_else(|| base.listener.clone()),
+                min_query_gap_ms: conn
+                    .get_int::<u64>(keys::MIN_QUERY_GAP_MS)
+                    .unwrap_or(base.min_query_gap_ms),
+                sql_browser: SqlBrowser::from_yaml(conn, &base.sql_browser),
+                connection_string: conn
+                    .get_string(keys::CONNECTION_STRING)
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .or_else(|| base.connection_string.clone()),
+                database: conn
+                    .get_string(keys::DATABASE)
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .or_else(|| base.database.clone()),
+                connect_options: ConnectOptions::from_yaml(conn)
+                    .unwrap_or_else(|| base.connect_options.clone()),
             }
             .ensure(auth),
         ))
@@ -455,7 +1145,7 @@ impl Connection {
         self.port.clone()
     }
     pub fn sql_browser_port(&self) -> Option<u16> {
-        None
+        self.sql_browser.port()
     }
     pub fn socket(&self) -> Option<&PathBuf> {
         self.socket.as_ref()
@@ -469,6 +1159,35 @@ impl Connection {
     pub fn timeout(&self) -> Duration {
         Duration::from_secs(self.timeout)
     }
+    /// hostname is an AG listener
+    pub fn listener(&self) -> Option<&Listener> {
+        self.listener.as_ref()
+    }
+    /// pause between consecutive queries of a connection
+    pub fn min_query_gap(&self) -> Duration {
+        Duration::from_millis(self.min_query_gap_ms)
+    }
+    /// waiting time for the answer of SQL Browser
+    pub fn browser_timeout(&self) -> Duration {
+        self.sql_browser.timeout()
+    }
+    pub fn sql_browser(&self) -> &SqlBrowser {
+        &self.sql_browser
+    }
+    /// ADO connection string used as is instead of the settings above,
+    /// `{username}` and `{password}` are replaced with credentials of the authentication
+    /// quoted as `{value}`, so the placeholders must not be quoted in the string
+    pub fn connection_string(&self) -> Option<&str> {
+        self.connection_string.as_deref()
+    }
+    /// database to login into when a section doesn't require a specific one,
+    /// by default the login database of the user
+    pub fn database(&self) -> Option<&str> {
+        self.database.as_deref()
+    }
+    pub fn connect_options(&self) -> &ConnectOptions {
+        &self.connect_options
+    }
 
     fn ensure(mut self, auth: Option<&Authentication>) -> Self {
         match auth {
@@ -492,8 +1211,152 @@ impl Default for Connection {
             trust_server_certificate: defaults::TRUST_SERVER_CERTIFICATE,
             tls: None,
             timeout: defaults::CONNECTION_TIMEOUT,
+            listener: None,
+            min_query_gap_ms: defaults::MIN_QUERY_GAP_MS,
+            sql_browser: SqlBrowser::default(),
+            connection_string: None,
+            database: None,
+            connect_options: ConnectOptions::default(),
+        }
+    }
+}
+
+/// SQL Browser of the host, used to find ports of named instances
+#[derive(PartialEq, Debug, Clone)]
+pub struct SqlBrowser {
+    enabled: bool,
+    port: Option<u16>,
+    timeout_ms: u64,
+}
+
+impl Default for SqlBrowser {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            port: None,
+            timeout_ms: defaults::SQL_BROWSER_TIMEOUT_MS,
+        }
+    }
+}
+
+impl SqlBrowser {
+    /// values absent in yaml are taken from `base`
+    pub fn from_yaml(conn: &Yaml, base: &SqlBrowser) -> Self {
+        let browser = conn.get(keys::SQL_BROWSER);
+        if browser.is_badvalue() {
+            return base.clone();
+        }
+        Self {
+            enabled: browser.get_bool(keys::ENABLED, base.enabled),
+            port: browser.get_int::<u16>(keys::PORT).or(base.port),
+            timeout_ms: browser
+                .get_int::<u64>(keys::TIMEOUT_MS)
+                .unwrap_or(base.timeout_ms),
         }
     }
+    /// no probes of SQL Browser if disabled, e.g. the service is stopped in the estate
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    /// None - default port 1434
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// Session settings requested at connect time
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ConnectOptions {
+    packet_size: Option<u32>,
+    mars: bool,
+    language: Option<String>,
+    readonly: bool,
+}
+
+impl ConnectOptions {
+    pub fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        let options = yaml.get(keys::CONNECT_OPTIONS);
+        if options.is_badvalue() {
+            return None;
+        }
+        Some(Self {
+            packet_size: options.get_int::<u32>(keys::PACKET_SIZE),
+            mars: options.get_bool(keys::MARS, false),
+            language: options
+                .get_string(keys::LANGUAGE)
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            readonly: options.get_bool(keys::READONLY, false),
+        })
+    }
+    /// network packet size in bytes
+    pub fn packet_size(&self) -> Option<u32> {
+        self.packet_size
+    }
+    /// multiple active result sets
+    pub fn mars(&self) -> bool {
+        self.mars
+    }
+    /// language of the session, affects date formats and messages
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+    /// read-only application intent, routes to a readable secondary replica
+    pub fn readonly(&self) -> bool {
+        self.readonly
+    }
+    /// options as attributes of an ODBC connection string
+    pub fn to_odbc_attributes(&self) -> String {
+        let mut attributes = String::new();
+        if let Some(packet_size) = self.packet_size {
+            attributes += &format!("Packet Size={packet_size};");
+        }
+        if self.mars {
+            attributes += "MARS_Connection=yes;";
+        }
+        if let Some(language) = &self.language {
+            attributes += &format!("Language={{{language}}};");
+        }
+        if self.readonly {
+            attributes += "ApplicationIntent=ReadOnly;";
+        }
+        attributes
+    }
+}
+
+/// Availability group listener: the connection always reaches the current primary replica
+#[derive(PartialEq, Debug, Clone)]
+pub struct Listener {
+    availability_group: String,
+    piggyback: bool,
+}
+
+impl Listener {
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<Self>> {
+        let listener = yaml.get(keys::LISTENER);
+        if listener.is_badvalue() {
+            return Ok(None);
+        }
+        let availability_group = listener
+            .get_string(keys::AVAILABILITY_GROUP)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .context("Bad/Missing availability_group in listener")?;
+        Ok(Some(Self {
+            availability_group,
+            piggyback: listener.get_bool(keys::PIGGYBACK, false),
+        }))
+    }
+    pub fn availability_group(&self) -> &str {
+        &self.availability_group
+    }
+    /// data is sent to the host of the primary replica
+    pub fn piggyback(&self) -> bool {
+        self.piggyback
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -544,6 +1407,17 @@ impl Endpoint {
             conn: conn.clone(),
         }
     }
+    /// the same endpoint at another address, e.g. a port published by a container
+    pub fn with_address(&self, hostname: HostName, port: Port) -> Self {
+        Self {
+            auth: self.auth.clone(),
+            conn: Connection {
+                hostname,
+                port,
+                ..self.conn.clone()
+            },
+        }
+    }
     pub fn auth(&self) -> &Authentication {
         &self.auth
     }
@@ -570,6 +1444,12 @@ pub struct Discovery {
     detect: bool,
     include: Vec<String>,
     exclude: Vec<String>,
+    scan: Vec<String>,
+    ports: Vec<u16>,
+    services: bool,
+    include_file: Option<PathBuf>,
+    included: Vec<IncludedInstance>,
+    containers: Option<ContainerDiscovery>,
 }
 
 impl Default for Discovery {
@@ -578,6 +1458,12 @@ impl Default for Discovery {
             detect: defaults::DISCOVERY_DETECT,
             include: vec![],
             exclude: vec![],
+            scan: vec![],
+            ports: vec![],
+            services: false,
+            include_file: None,
+            included: vec![],
+            containers: None,
         }
     }
 }
@@ -588,10 +1474,28 @@ impl Discovery {
         if discovery.is_badvalue() {
             return Ok(None);
         }
+        let mut include = discovery.get_string_vector(keys::INCLUDE, &[]);
+        let include_file = discovery.get_pathbuf(keys::INCLUDE_FILE);
+        let included = include_file
+            .as_deref()
+            .map(|path| {
+                load_include_file(path).unwrap_or_else(|e| {
+                    log::error!("Can't use include file {}: {e}", path.display());
+                    vec![]
+                })
+            })
+            .unwrap_or_default();
+        include.extend(included.iter().map(|i| i.name.to_string()));
         Ok(Some(Self {
             detect: discovery.get_bool(keys::DETECT, defaults::DISCOVERY_DETECT),
-            include: discovery.get_string_vector(keys::INCLUDE, &[]),
+            include,
             exclude: discovery.get_string_vector(keys::EXCLUDE, &[]),
+            scan: discovery.get_string_vector(keys::SCAN, &[]),
+            ports: expand_ports(discovery.get(keys::PORTS))?,
+            services: discovery.get_bool(keys::SERVICES, false),
+            include_file,
+            included,
+            containers: ContainerDiscovery::from_yaml(discovery),
         }))
     }
     pub fn detect(&self) -> bool {
@@ -603,42 +1507,289 @@ impl Discovery {
     pub fn exclude(&self) -> &Vec<String> {
         &self.exclude
     }
+    /// hosts and IPv4 CIDR ranges to be probed with SQL Browser
+    pub fn scan(&self) -> &Vec<String> {
+        &self.scan
+    }
+    /// ports of the endpoint host to be probed with TDS pre-login
+    pub fn ports(&self) -> &Vec<u16> {
+        &self.ports
+    }
+    /// Reporting, Integration and Analysis services installed on the host
+    pub fn services(&self) -> bool {
+        self.services
+    }
+    /// file maintained by deployment automation, read on every run
+    pub fn include_file(&self) -> Option<&Path> {
+        self.include_file.as_deref()
+    }
+    /// instances loaded from `include_file`
+    pub fn included(&self) -> &Vec<IncludedInstance> {
+        &self.included
+    }
+    /// SQL Server containers of the local container runtime
+    pub fn containers(&self) -> Option<&ContainerDiscovery> {
+        self.containers.as_ref()
+    }
 
     pub fn is_instance_allowed(&self, name: &InstanceName) -> bool {
+        let is_listed = |list: &Vec<String>| {
+            list.iter()
+                .any(|i| InstanceName::from(i.as_str()).is_same(name))
+        };
         if !self.include.is_empty() {
-            return self.include.contains(&name.to_string());
+            return is_listed(&self.include);
+        }
+
+        if is_listed(&self.exclude) {
+            return false;
         }
 
-        if self.exclude.contains(&name.to_string()) {
-            return false;
+        true
+    }
+}
+
+/// Containers of the local Docker/Podman publishing the SQL Server port
+#[derive(PartialEq, Debug, Clone)]
+pub struct ContainerDiscovery {
+    socket: PathBuf,
+    image: String,
+    label: Option<String>,
+    piggyback: bool,
+}
+
+impl ContainerDiscovery {
+    pub fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        let containers = yaml.get(keys::CONTAINERS);
+        if containers.is_badvalue() {
+            return None;
+        }
+        Some(Self {
+            socket: containers
+                .get_pathbuf(keys::SOCKET)
+                .unwrap_or_else(|| PathBuf::from(defaults::CONTAINER_SOCKET)),
+            image: containers
+                .get_string(keys::IMAGE)
+                .map(|s| s.trim().to_lowercase())
+                .unwrap_or_else(|| defaults::CONTAINER_IMAGE.to_string()),
+            label: containers
+                .get_string(keys::LABEL)
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            piggyback: containers.get_bool(keys::PIGGYBACK, false),
+        })
+    }
+    /// unix socket of the container runtime API
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+    /// substring of the image name, empty matches any image
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+    /// `key` or `key=value`, the container must have the label
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    /// data of the container is sent to the host named after the container
+    pub fn piggyback(&self) -> bool {
+        self.piggyback
+    }
+}
+
+/// Instance listed in the discovery include file
+#[derive(PartialEq, Debug, Clone)]
+pub struct IncludedInstance {
+    name: InstanceName,
+    hostname: Option<HostName>,
+    port: Option<Port>,
+}
+
+impl IncludedInstance {
+    fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        if let Some(name) = yaml.as_str() {
+            return Some(Self {
+                name: InstanceName::from(name.trim()),
+                hostname: None,
+                port: None,
+            });
+        }
+        let name = yaml.get_string(keys::SID)?;
+        Some(Self {
+            name: InstanceName::from(name.trim()),
+            hostname: yaml.get_string(keys::HOSTNAME).map(HostName::from),
+            port: yaml.get_int::<u16>(keys::PORT).map(Port::from),
+        })
+    }
+
+    pub fn name(&self) -> &InstanceName {
+        &self.name
+    }
+    pub fn hostname(&self) -> Option<&HostName> {
+        self.hostname.as_ref()
+    }
+    pub fn port(&self) -> Option<&Port> {
+        self.port.as_ref()
+    }
+}
+
+const MAX_DISCOVERY_PORTS: usize = 1024;
+
+/// `[1433, "14330-14340"]` into the list of ports, duplicates are removed
+fn expand_ports(ports: &Yaml) -> Result<Vec<u16>> {
+    let items = match ports {
+        Yaml::Array(items) => items.clone(),
+        Yaml::BadValue | Yaml::Null => return Ok(vec![]),
+        single => vec![single.clone()],
+    };
+    let mut expanded: Vec<u16> = Vec::new();
+    for item in items {
+        let text = match item {
+            Yaml::Integer(i) => i.to_string(),
+            Yaml::String(s) => s,
+            other => anyhow::bail!("Bad discovery port {other:?}"),
+        };
+        let parse = |p: &str| {
+            p.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|p| *p != 0)
+                .ok_or_else(|| anyhow!("Bad discovery port `{p}`"))
+        };
+        let (first, last) = match text.split_once('-') {
+            Some((first, last)) => (parse(first)?, parse(last)?),
+            None => (parse(&text)?, parse(&text)?),
+        };
+        if first > last {
+            anyhow::bail!("Bad discovery port range `{text}`");
+        }
+        for port in first..=last {
+            if !expanded.contains(&port) {
+                expanded.push(port);
+            }
+        }
+        if expanded.len() > MAX_DISCOVERY_PORTS {
+            anyhow::bail!("Too many discovery ports, {MAX_DISCOVERY_PORTS} is the limit");
+        }
+    }
+    Ok(expanded)
+}
+
+/// Loads instances from YAML or JSON file, the list may be top level or under `instances`
+fn load_include_file(path: &Path) -> Result<Vec<IncludedInstance>> {
+    let content = std::fs::read_to_string(path)?;
+    let docs = YamlLoader::load_from_str(&content)?;
+    let root = docs.first().context("Empty include file")?;
+    let list = match root {
+        Yaml::Array(list) => list.clone(),
+        _ => root.get_yaml_vector(keys::INSTANCES),
+    };
+    let instances = list
+        .iter()
+        .filter_map(|i| {
+            IncludedInstance::from_yaml(i).or_else(|| {
+                log::warn!("Bad entry in include file {}: {:?}", path.display(), i);
+                None
+            })
+        })
+        .collect::<Vec<_>>();
+    log::info!(
+        "Loaded {} instances from include file {}",
+        instances.len(),
+        path.display()
+    );
+    Ok(instances)
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Mode {
+    Port,
+    Socket,
+    Special,
+}
+
+impl Mode {
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
+        Mode::try_from(yaml.get_string(keys::MODE).as_deref().unwrap_or_default())
+    }
+}
+
+impl TryFrom<&str> for Mode {
+    type Error = anyhow::Error;
+
+    fn try_from(str: &str) -> Result<Self> {
+        match str::to_ascii_lowercase(str).as_ref() {
+            values::PORT => Ok(Mode::Port),
+            values::SOCKET => Ok(Mode::Socket),
+            values::SPECIAL => Ok(Mode::Special),
+            _ => Err(anyhow!("unsupported mode")),
+        }
+    }
+}
+
+/// How to avoid duplicated data of the clustered instance monitored from several nodes
+#[derive(PartialEq, Debug, Clone, Default)]
+pub enum ClusterDedup {
+    #[default]
+    Off,
+    /// only the agent on the active node of the cluster reports the instance
+    ActiveNode,
+    /// the instance is reported in the piggyback host named after the cluster
+    Piggyback,
+}
+
+impl ClusterDedup {
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
+        ClusterDedup::try_from(
+            yaml.get_string(keys::CLUSTER_DEDUP)
+                .as_deref()
+                .unwrap_or(values::OFF),
+        )
+    }
+}
+
+impl TryFrom<&str> for ClusterDedup {
+    type Error = anyhow::Error;
+
+    fn try_from(str: &str) -> Result<Self> {
+        match str::to_ascii_lowercase(str).as_ref() {
+            values::OFF => Ok(ClusterDedup::Off),
+            values::ACTIVE_NODE => Ok(ClusterDedup::ActiveNode),
+            values::PIGGYBACK => Ok(ClusterDedup::Piggyback),
+            _ => Err(anyhow!("unsupported cluster_dedup `{str}`")),
         }
-
-        true
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub enum Mode {
-    Port,
-    Socket,
-    Special,
+/// What to do with the clustered instance whose SQL resource is owned by another node
+#[derive(PartialEq, Debug, Clone, Default)]
+pub enum PassiveNodePolicy {
+    /// instance is queried as usual
+    #[default]
+    Query,
+    /// no queries, only the instance section reports the owner node
+    Skip,
 }
 
-impl Mode {
+impl PassiveNodePolicy {
     pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
-        Mode::try_from(yaml.get_string(keys::MODE).as_deref().unwrap_or_default())
+        PassiveNodePolicy::try_from(
+            yaml.get(keys::CLUSTER)
+                .get_string(keys::PASSIVE_NODE_POLICY)
+                .as_deref()
+                .unwrap_or(values::QUERY),
+        )
     }
 }
 
-impl TryFrom<&str> for Mode {
+impl TryFrom<&str> for PassiveNodePolicy {
     type Error = anyhow::Error;
 
     fn try_from(str: &str) -> Result<Self> {
         match str::to_ascii_lowercase(str).as_ref() {
-            values::PORT => Ok(Mode::Port),
-            values::SOCKET => Ok(Mode::Socket),
-            values::SPECIAL => Ok(Mode::Special),
-            _ => Err(anyhow!("unsupported mode")),
+            values::QUERY => Ok(PassiveNodePolicy::Query),
+            values::SKIP => Ok(PassiveNodePolicy::Skip),
+            _ => Err(anyhow!("unsupported passive_node_policy `{str}`")),
         }
     }
 }
@@ -652,6 +1803,10 @@ pub struct CustomInstance {
     alias: Option<InstanceAlias>,
     piggyback: Option<Piggyback>,
     tcp: bool,
+    /// section name -> database to use instead of the section's default
+    main_dbs: HashMap<String, String>,
+    /// reported as missing in the databases section if absent
+    required_databases: Vec<String>,
 }
 
 impl CustomInstance {
@@ -668,6 +1823,7 @@ impl CustomInstance {
         let name = InstanceName::from(
             yaml.get_string(keys::SID)
                 .context("Bad/Missing sid in instance")?
+                .trim()
                 .to_uppercase(),
         );
         let (auth, conn) = CustomInstance::ensure_auth_and_conn(yaml, main_auth, main_conn, &name)?;
@@ -679,9 +1835,36 @@ impl CustomInstance {
             alias: yaml.get_string(keys::ALIAS).map(InstanceAlias::from),
             piggyback: Piggyback::from_yaml(yaml, sections)?,
             tcp,
+            main_dbs: CustomInstance::get_main_dbs(yaml),
+            required_databases: yaml
+                .get_string_vector(keys::REQUIRED_DATABASES, &[])
+                .into_iter()
+                .map(|db| db.trim().to_string())
+                .filter(|db| !db.is_empty())
+                .collect(),
         })
     }
 
+    /// main_db:
+    ///   jobs: "msdb_renamed"
+    ///   mirroring: "monitoring"
+    fn get_main_dbs(yaml: &Yaml) -> HashMap<String, String> {
+        yaml.get(keys::MAIN_DB)
+            .as_hash()
+            .map(|h| {
+                h.iter()
+                    .filter_map(|(section, db)| {
+                        Some((
+                            section.as_str()?.trim().to_string(),
+                            db.as_str()?.trim().to_string(),
+                        ))
+                    })
+                    .filter(|(section, db)| !section.is_empty() && !db.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     pub fn from_registry(
         name: &InstanceName,
         main_auth: &Authentication,
@@ -700,11 +1883,39 @@ impl CustomInstance {
             alias: None,
             piggyback: None,
             tcp: port.is_some(),
+            main_dbs: HashMap::new(),
+            required_databases: vec![],
+        }
+    }
+
+    pub fn from_included(
+        included: &IncludedInstance,
+        main_auth: &Authentication,
+        main_conn: &Connection,
+    ) -> Self {
+        let conn = Connection {
+            hostname: included
+                .hostname
+                .clone()
+                .unwrap_or_else(|| main_conn.hostname()),
+            port: included.port.clone().unwrap_or_else(|| main_conn.port()),
+            ..main_conn.clone()
+        };
+        Self {
+            name: included.name.clone(),
+            auth: main_auth.clone(),
+            conn,
+            alias: None,
+            piggyback: None,
+            tcp: true,
+            main_dbs: HashMap::new(),
+            required_databases: vec![],
         }
     }
 
     /// Make auth and conn for custom instance using yaml
     /// - fallback on main_auth and main_conn if not defined in yaml
+    /// - values absent in yaml are inherited from main_auth and main_conn
     /// - correct connection hostname if needed
     fn ensure_auth_and_conn(
         yaml: &Yaml,
@@ -712,8 +1923,13 @@ impl CustomInstance {
         main_conn: &Connection,
         sid: &InstanceName,
     ) -> Result<(Authentication, Connection)> {
-        let auth = Authentication::from_yaml(yaml).unwrap_or(main_auth.clone());
-        let conn = Connection::from_yaml(yaml, Some(&auth))?.unwrap_or(main_conn.clone());
+        let auth = if yaml.get(keys::AUTHENTICATION).is_badvalue() {
+            main_auth.clone()
+        } else {
+            Authentication::from_yaml_inherited(yaml, main_auth)?
+        };
+        let conn = Connection::from_yaml_inherited(yaml, Some(&auth), main_conn)?
+            .unwrap_or_else(|| main_conn.clone().ensure(Some(&auth)));
 
         let instance_host = calc_real_host(&auth, &conn);
         let main_host = calc_real_host(main_auth, main_conn);
@@ -764,6 +1980,12 @@ impl CustomInstance {
     pub fn piggyback(&self) -> Option<&Piggyback> {
         self.piggyback.as_ref()
     }
+    pub fn main_dbs(&self) -> &HashMap<String, String> {
+        &self.main_dbs
+    }
+    pub fn required_databases(&self) -> &[String] {
+        &self.required_databases
+    }
     pub fn calc_real_host(&self) -> HostName {
         calc_real_host(&self.auth, &self.conn)
     }
@@ -782,7 +2004,9 @@ pub fn is_local_host(auth: &Authentication, _conn: &Connection) -> bool {
 }
 
 pub fn is_use_tcp(name: &InstanceName, auth: &Authentication, conn: &Connection) -> bool {
-    if is_local_host(auth, conn) {
+    if conn.connection_string().is_some() {
+        true
+    } else if is_local_host(auth, conn) {
         get_registry_instance_info(name)
             .map(|i| i.is_tcp())
             .unwrap_or(true)
@@ -837,11 +2061,41 @@ mssql:
   main: # mandatory, to be used if no specific config
     options:
       max_connections: 5
+      spread_seconds: 0 # optional(default: 0), start of instances is spread over this period
+      host_heavy_sections: 1 # optional(default: 1), counters sections at once per host, 0 - no limit
+      section_parallelism: 1 # optional(default: 1), sections of an instance at once, each uses own connection
+      blackout_windows: # optional, only instance section is generated, SQL Server local time
+        - "1-5 01:00-04:30" # days(cron-like, 0 and 7 are Sunday) and time range
+      generators: [] # optional, plugins of extra section generators registered by the application
+    telemetry: # optional
+      otlp_endpoint: "http://localhost:4318" # optional, spans of the run are sent here
+    limits: # optional
+      max_memory_mb: 0 # optional(default: 0 - no limit), per section, its output is truncated if exceeded
+    error_suppression: # optional
+      after: 0 # optional(default: 0 - off), after so many identical failures only the state line is sent
+    emit: # optional
+      section_prefix: "mssql" # optional(default: "mssql"), sections are named <<<PREFIX_name>>>
+      names: keep # optional(default: keep), escape: non-ASCII characters of names as _xHHHH_
+      values_locale: "Deutsch" # optional(default: connection language), numbers and dates are converted to invariant formats
+    static_sections: # optional, files produced by other tooling, emitted verbatim
+      - name: "site_inventory" # mandatory, section is named <<<site_inventory>>>
+        file: "/var/lib/site/inventory.txt" # mandatory
+        sep: "|" # optional(default: " ")
+        per_instance: no # optional(default: no), emitted into the piggyback data of every instance
+    security: # optional
+      query_allowlist: no # optional(default: no), only listed custom sql files are executed
+      allowed_queries: # optional
+        - file: "my_section.sql" # mandatory, file name in the sql directory
+          sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad" # mandatory
+          allow_write: no # optional(default: no), permits DML, DDL, GRANT and EXEC
     authentication: # mandatory
       username: "foo" # mandatory
-      password: "bar" # optional
+      password: "bar" # optional, list of candidates is allowed: ["new", "old"]
+      remember_password: no # optional(default: no), persist the working candidate between runs
       type: "sql_server" # optional, default: "integrated", values: sql_server, windows, token and integrated (current windows user) 
       access_token: "baz" # optional
+      kerberos: # optional, Windows only, type "integrated": credentials may be delegated to remote servers
+        spn_host: "sql1.corp.example.com" # optional(default: hostname), FQDN of the SPN MSSQLSvc/<spn_host>:<port>
     connection: # optional
       hostname: "localhost" # optional(default: "localhost")
       failoverpartner: "localhost2" # optional
@@ -852,10 +2106,24 @@ mssql:
         ca: 'C:\path\to\file' # mandatory
         client_certificate: 'C:\path\to\file' # mandatory
       timeout: 5 # optional(default: 5)
+      min_query_gap_ms: 0 # optional(default: 0), pause between consecutive queries
+      sql_browser: # optional
+        enabled: yes # optional(default: yes), no - SQL Browser is never asked
+        port: 1434 # optional(default: 1434)
+        timeout_ms: 1000 # optional(default: 1000), waiting time for SQL Browser answer
+      connection_string: "" # optional, ADO string used as is, {username} and {password} are substituted
+      database: "" # optional, login database for discovery and instance sections, e.g. low-privilege one
+      connect_options: # optional
+        packet_size: 8192 # optional, ODBC only
+        mars: no # optional(default: no), ODBC only
+        language: "us_english" # optional
+        readonly: no # optional(default: no), ODBC only
     sections: # optional
     - instance:  # special section
     - databases:
     - counters:
+        compute_rates: no # optional(default: no), per second rates of `/sec` counters
+        priority: 10 # optional(default: 0), higher is executed and emitted earlier
     - blocked_sessions:
     - transactionlogs:
     - clusters:
@@ -870,24 +2138,54 @@ mssql:
         is_async: yes
     - jobs:
         is_async: yes
+        cache_age: 1200 # optional, overrides cache_age of the config and of the instance
+        timeout: 60 # optional, overrides section_timeout of the config and of the instance
     - someOtherSQL:
         is_async: yes
         disabled: yes
+        per_database: yes # optional(default: no), custom sections only, runs in every database
+        procedure: "dbo.check_jobs" # optional, custom sections only, executed instead of someOtherSQL.sql
+        params: # optional, bound to the procedure, never concatenated
+          - name: "days"
+            type: int # optional, int, float, bool or string, deduced from the value if absent
+            value: 7
     cache_age: 600 # optional(default:600)
+    section_timeout: 0 # optional(default: 0 - no limit), seconds for a section to finish
+    profile: "standard" # optional, "minimal", "standard" or "full" set of sections, sections above take precedence
     piggyback_host: "my_pb_host"
+    piggyback: # optional
+      hostname_template: "sql-{instance}-{hostname}" # optional, used if piggyback_host and instance piggyback are absent
+    qualify_with_host: no # optional(default: no), instance is reported as <HOST>_<NAME>
+    instance_prefix: "" # optional, not inherited, prepended to the instance name in the output
     discovery: # optional
       detect: true # optional(default:yes)
       include: ["foo", "bar", "INST2"] # optional prio 2; use instance even if excluded
       exclude: ["baz"] # optional, prio 3
+      scan: ["sql1.example.com", "10.1.2.0/30"] # optional, hosts or CIDR to probe with SQL Browser
+      ports: [1433, "14330-14340"] # optional, ports of the hostname probed with TDS pre-login, for disabled SQL Browser
+      services: no # optional(default: no), Windows only, inventory of SSRS/SSIS/SSAS services of the host
+      include_file: "c:/deploy/sql_instances.json" # optional, instances(and ports) in YAML/JSON, read every run
+      containers: # optional, Linux only, containers publishing port 1433
+        socket: "/var/run/docker.sock" # optional(default), Podman: "/run/podman/podman.sock"
+        image: "mssql" # optional(default), substring of the image name
+        label: "monitoring=yes" # optional, `key` or `key=value`
+        piggyback: no # optional(default: no), data is sent to the host named after the container
     mode: "socket" # optional(default:"port") - "socket", "port" or "special"
+    cluster: # optional
+      passive_node_policy: "skip" # optional(default:"query") - "query" or "skip", Windows FCI only
     instances: # optional
       - sid: "INST1" # mandatory
         authentication: # optional, same as above
         connection: # optional,  same as above
         alias: "someApplicationName" # optional
+        main_db: # optional, section -> database to use instead of default
+          jobs: "msdb"
+        required_databases: ["Sales", "HR"] # optional, absent ones are reported as MISSING in databases section
         piggyback: # optional
           hostname: "myPiggybackHost" # mandatory
           sections: # optional, same as above
+          cache_age: 300 # optional(default: cache_age of the config)
+          section_timeout: 30 # optional(default: section_timeout of the config)
       - sid: "INST2"
         authentication:
           username: "u"
@@ -959,12 +2257,32 @@ connection:
     ca: 'C:\path\to\file_ca'
     client_certificate: 'C:\path\to\file_client'
   timeout: 341
+  min_query_gap_ms: 250
+  sql_browser:
+    timeout_ms: 300
+  connection_string: "Server=tcp:alice,9999;User Id={username};Password={password};Packet Size=8192"
+  database: " monitoring "
+  listener:
+    availability_group: "AG1"
+    piggyback: yes
+  connect_options:
+    packet_size: 8192
+    mars: yes
+    language: "Deutsch"
+    readonly: yes
 "#;
         pub const DISCOVERY_FULL: &str = r#"
 discovery:
   detect: false
   include: ["a", "b" ]
   exclude: ["c", "d" ]
+  scan: ["host", "10.0.0.0/30" ]
+  ports: [1433, "14330-14332", 1433]
+  services: yes
+  containers:
+    socket: "/run/podman/podman.sock"
+    label: "monitoring=yes"
+    piggyback: yes
 "#;
         pub const PIGGYBACK_HOST: &str = "piggyback_host: zuzu";
 
@@ -998,6 +2316,10 @@ piggyback:
   hostname: "piggy"
   sections:
   cache_age: 123
+main_db:
+  jobs: "msdb2"
+  mirroring: " "
+required_databases: ["Sales", " ", " HR "]
 "#;
         pub const PIGGYBACK_NO_HOSTNAME: &str = r#"
 piggyback:
@@ -1023,11 +2345,22 @@ piggyback:
                 sections: Sections::default(),
                 discovery: Discovery::default(),
                 piggyback_host: None,
+                piggyback_template: None,
                 mode: Mode::Port,
+                cluster_dedup: ClusterDedup::Off,
+                passive_node_policy: PassiveNodePolicy::Query,
+                qualify_with_host: false,
+                instance_prefix: None,
                 custom_instances: vec![],
                 configs: vec![],
                 hash: String::new(),
                 options: Options::default(),
+                telemetry: Telemetry::default(),
+                limits: Limits::default(),
+                error_suppression: ErrorSuppression::default(),
+                emit: Emit::default(),
+                static_sections: vec![],
+                security: Security::default(),
             }
         );
     }
@@ -1037,6 +2370,215 @@ piggyback:
         let s = Options::default();
         assert_eq!(s.max_connections(), MAX_CONNECTIONS.into());
         assert_eq!(s.max_queries(), MAX_QUERIES.into());
+        assert_eq!(s.spread_seconds(), 0);
+        assert_eq!(s.host_heavy_sections(), 1);
+        assert_eq!(s.section_parallelism(), 1);
+    }
+
+    #[test]
+    fn test_options_from_yaml() {
+        const SOURCE: &str = r#"
+options:
+  max_connections: 7
+  spread_seconds: 30
+  host_heavy_sections: 0
+  section_parallelism: 3
+"#;
+        let s = Options::from_yaml(&create_yaml(SOURCE)).unwrap().unwrap();
+        assert_eq!(s.max_connections(), 7.into());
+        assert_eq!(s.spread_seconds(), 30);
+        assert_eq!(s.host_heavy_sections(), 0);
+        assert_eq!(s.section_parallelism(), 3);
+        assert!(s.blackout_windows().is_empty());
+        assert!(s.generators().is_empty());
+        let s = Options::from_yaml(&create_yaml("options:\n  generators: [\"acme\"]\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(s.generators(), ["acme"]);
+        let s = Options::from_yaml(&create_yaml(
+            "options:\n  blackout_windows: [\"1-5 01:00-04:30\", \"* 23:00-00:30\"]\n",
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(s.blackout_windows().len(), 2);
+        assert!(Options::from_yaml(&create_yaml(
+            "options:\n  blackout_windows: [\"1-5 01:00\"]\n"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_blackout_window() {
+        let w = BlackoutWindow::try_from("1-5 01:00-04:30").unwrap();
+        assert!(w.contains(1, 60));
+        assert!(w.contains(5, 269));
+        assert!(!w.contains(5, 270));
+        assert!(!w.contains(6, 120));
+        assert!(!w.contains(0, 120));
+        let w = BlackoutWindow::try_from("5 22:00-02:00").unwrap();
+        assert!(w.contains(5, 23 * 60));
+        assert!(w.contains(6, 60));
+        assert!(!w.contains(5, 60));
+        assert!(!w.contains(6, 23 * 60));
+        let w = BlackoutWindow::try_from("0,7 00:00-24:00").unwrap();
+        assert!(w.contains(0, 0));
+        assert!(!w.contains(1, 0));
+        assert!(BlackoutWindow::try_from("* 00:00-24:00")
+            .unwrap()
+            .contains(3, 1439));
+        for bad in [
+            "",
+            "1-5",
+            "8 01:00-02:00",
+            "5-1 01:00-02:00",
+            "* 25:00-26:00",
+            "* 1:60-2:00",
+        ] {
+            assert!(BlackoutWindow::try_from(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_security_from_yaml() {
+        const SOURCE: &str = r#"
+security:
+  query_allowlist: yes
+  allowed_queries:
+    - file: "a.sql"
+      sha256: " ABCD "
+    - file: "b.sql"
+      sha256: "ef"
+      allow_write: yes
+"#;
+        let s = Security::from_yaml(&create_yaml(SOURCE)).unwrap().unwrap();
+        assert!(s.query_allowlist());
+        assert_eq!(s.allowed_queries().len(), 2);
+        let a = s.find_allowed_query("A.sql").unwrap();
+        assert_eq!(a.sha256(), "abcd");
+        assert!(!a.allow_write());
+        assert!(s.find_allowed_query("b.sql").unwrap().allow_write());
+        assert!(s.find_allowed_query("c.sql").is_none());
+        const BAD: &str = r#"
+security:
+  allowed_queries:
+    - file: "a.sql"
+"#;
+        assert!(Security::from_yaml(&create_yaml(BAD)).is_err());
+        assert!(!Security::default().query_allowlist());
+    }
+
+    #[test]
+    fn test_limits_from_yaml() {
+        let l = Limits::from_yaml(&create_yaml("limits:\n  max_memory_mb: 64\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(l.max_memory(), Some(64 * 1024 * 1024));
+        let l = Limits::from_yaml(&create_yaml("limits:\n  max_memory_mb: 0\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(l.max_memory(), None);
+        assert!(
+            Limits::from_yaml(&create_yaml("options:\n  max_connections: 1\n"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_sql_browser_from_yaml() {
+        let base = SqlBrowser::default();
+        let b = SqlBrowser::from_yaml(&create_yaml("browser_timeout_ms: 300\n"), &base);
+        assert_eq!(b, base);
+        let b = SqlBrowser::from_yaml(
+            &create_yaml("sql_browser:\n  enabled: no\n  port: 2434\n  timeout_ms: 50\n"),
+            &base,
+        );
+        assert!(!b.enabled());
+        assert_eq!(b.port(), Some(2434));
+        assert_eq!(b.timeout(), Duration::from_millis(50));
+        let inherited = SqlBrowser::from_yaml(&create_yaml("sql_browser:\n  timeout_ms: 70\n"), &b);
+        assert!(!inherited.enabled());
+        assert_eq!(inherited.port(), Some(2434));
+        assert_eq!(inherited.timeout(), Duration::from_millis(70));
+    }
+
+    #[test]
+    fn test_static_section_from_yaml() {
+        let s = StaticSection::from_yaml(&create_yaml(
+            "name: site_inventory\nfile: /tmp/x.txt\nsep: '|'\nper_instance: yes\n",
+        ))
+        .unwrap();
+        assert_eq!(s.name(), "site_inventory");
+        assert_eq!(s.file(), Path::new("/tmp/x.txt"));
+        assert_eq!(s.sep(), '|');
+        assert!(s.per_instance());
+        let s = StaticSection::from_yaml(&create_yaml("name: a\nfile: b\n")).unwrap();
+        assert_eq!(s.sep(), ' ');
+        assert!(!s.per_instance());
+        assert!(StaticSection::from_yaml(&create_yaml("name: a\n")).is_err());
+        assert!(StaticSection::from_yaml(&create_yaml("name: 'a>b'\nfile: b\n")).is_err());
+    }
+
+    #[test]
+    fn test_emit_from_yaml() {
+        let e = Emit::from_yaml(&create_yaml("emit:\n  section_prefix: mssql_v2\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(e.section_prefix(), Some("mssql_v2"));
+        let e = Emit::from_yaml(&create_yaml("emit:\n  section_prefix: ''\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(e.section_prefix(), None);
+        assert!(Emit::from_yaml(&create_yaml("emit:\n  section_prefix: 'a>b'\n")).is_err());
+        let e = Emit::from_yaml(&create_yaml("emit:\n  names: Escape\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(e.names(), NameEncoding::Escape);
+        assert_eq!(e.section_prefix(), None);
+        assert!(Emit::from_yaml(&create_yaml("emit:\n  names: latin\n")).is_err());
+        let e = Emit::from_yaml(&create_yaml("emit:\n  values_locale: Français\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(e.values_locale(), Some("Français"));
+        assert!(Emit::from_yaml(&create_yaml("emit:\n  values_locale: Klingon\n")).is_err());
+        assert!(
+            Emit::from_yaml(&create_yaml("limits:\n  max_memory_mb: 1\n"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_error_suppression_from_yaml() {
+        let s = ErrorSuppression::from_yaml(&create_yaml("error_suppression:\n  after: 3\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(s.after(), Some(3));
+        let s = ErrorSuppression::from_yaml(&create_yaml("error_suppression:\n  after: 0\n"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(s.after(), None);
+        assert!(
+            ErrorSuppression::from_yaml(&create_yaml("limits:\n  max_memory_mb: 1\n"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_telemetry_from_yaml() {
+        const SOURCE: &str = r#"
+telemetry:
+  otlp_endpoint: " http://collector:4318 "
+"#;
+        let t = Telemetry::from_yaml(&create_yaml(SOURCE)).unwrap().unwrap();
+        assert_eq!(t.otlp_endpoint(), Some("http://collector:4318"));
+        assert!(
+            Telemetry::from_yaml(&create_yaml("options:\n  max_connections: 1\n"))
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(Telemetry::default().otlp_endpoint(), None);
     }
 
     #[test]
@@ -1072,6 +2614,58 @@ piggyback:
         assert_eq!(a.password(), Some(&"bar".to_owned()));
         assert_eq!(a.auth_type(), &AuthType::SqlServer);
         assert_eq!(a.access_token(), Some(&"baz".to_owned()));
+        assert_eq!(a.kerberos(), None);
+    }
+
+    #[test]
+    fn test_authentication_kerberos() {
+        let a = Authentication::from_yaml(&create_yaml(
+            r#"
+authentication:
+  username: "foo"
+  kerberos:
+    spn_host: " sql1.corp.local "
+"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            a.kerberos().and_then(|k| k.spn_host()),
+            Some(&HostName::from("sql1.corp.local".to_string()))
+        );
+        let a = Authentication::from_yaml(&create_yaml(
+            r#"
+authentication:
+  username: "foo"
+  kerberos: {}
+"#,
+        ))
+        .unwrap();
+        assert_eq!(a.kerberos(), Some(&Kerberos::default()));
+    }
+
+    #[test]
+    fn test_authentication_password_candidates() {
+        let a = Authentication::from_yaml(&create_yaml(
+            r#"
+authentication:
+  username: "foo"
+  password: ["new", "old"]
+  remember_password: yes
+  type: "sql_server"
+"#,
+        ))
+        .unwrap();
+        assert_eq!(a.password(), Some(&"new".to_owned()));
+        assert_eq!(a.passwords(), &["new".to_owned(), "old".to_owned()]);
+        assert!(a.remember_password());
+        let b = Authentication::from_yaml_inherited(
+            &create_yaml("authentication:\n  type: sql_server\n"),
+            &a,
+        )
+        .unwrap();
+        assert_eq!(b.passwords(), a.passwords());
+        assert!(b.remember_password());
+        assert!(!Authentication::default().remember_password());
     }
 
     #[test]
@@ -1126,12 +2720,32 @@ authentication:
         assert_eq!(c.socket(), Some(&PathBuf::from(r"C:\path\to\file_socket")));
         assert!(!c.trust_server_certificate());
         assert_eq!(c.timeout(), Duration::from_secs(341));
+        assert_eq!(c.min_query_gap(), Duration::from_millis(250));
+        assert_eq!(c.browser_timeout(), Duration::from_millis(300));
+        assert_eq!(
+            c.connection_string(),
+            Some("Server=tcp:alice,9999;User Id={username};Password={password};Packet Size=8192")
+        );
+        assert_eq!(c.database(), Some("monitoring"));
         let tls = c.tls().unwrap();
         assert_eq!(tls.ca(), PathBuf::from(r"C:\path\to\file_ca"));
         assert_eq!(
             tls.client_certificate(),
             &r"C:\path\to\file_client".to_owned().into()
         );
+        let listener = c.listener().unwrap();
+        assert_eq!(listener.availability_group(), "AG1");
+        assert!(listener.piggyback());
+        let options = c.connect_options();
+        assert_eq!(options.packet_size(), Some(8192));
+        assert!(options.mars());
+        assert_eq!(options.language(), Some("Deutsch"));
+        assert!(options.readonly());
+        assert_eq!(
+            options.to_odbc_attributes(),
+            "Packet Size=8192;MARS_Connection=yes;Language={Deutsch};ApplicationIntent=ReadOnly;"
+        );
+        assert_eq!(ConnectOptions::default().to_odbc_attributes(), "");
     }
 
     #[cfg(windows)]
@@ -1215,6 +2829,19 @@ connection:
         assert!(!discovery.detect());
         assert_eq!(discovery.include(), &vec!["a".to_string(), "b".to_string()]);
         assert_eq!(discovery.exclude(), &vec!["c".to_string(), "d".to_string()]);
+        assert_eq!(
+            discovery.scan(),
+            &vec!["host".to_string(), "10.0.0.0/30".to_string()]
+        );
+        assert_eq!(discovery.ports(), &vec![1433, 14330, 14331, 14332]);
+        assert!(discovery.services());
+        assert!(!Discovery::default().services());
+        let containers = discovery.containers().unwrap();
+        assert_eq!(containers.socket(), Path::new("/run/podman/podman.sock"));
+        assert_eq!(containers.image(), "mssql");
+        assert_eq!(containers.label(), Some("monitoring=yes"));
+        assert!(containers.piggyback());
+        assert_eq!(Discovery::default().containers(), None);
     }
 
     #[test]
@@ -1223,6 +2850,67 @@ connection:
         assert_eq!(ph.as_deref(), Some("zuzu"));
     }
 
+    #[test]
+    fn test_discovery_include_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join("instances.json");
+        std::fs::write(
+            &json,
+            r#"{"instances": ["INST1", {"sid": "INST2", "port": 1435}, {"sid": "INST3", "hostname": "sql3"}, {"port": 1}]}"#,
+        )
+        .unwrap();
+        let yaml = dir.path().join("instances.yml");
+        std::fs::write(&yaml, "- INST4\n- sid: INST5\n  port: 1436\n").unwrap();
+
+        let make = |file: &Path| {
+            let source = format!(
+                r#"
+mssql:
+  main:
+    authentication:
+      username: "u"
+    discovery:
+      detect: false
+      include: ["a"]
+      include_file: "{}"
+    instances:
+      - sid: "INST3"
+"#,
+                file.display().to_string().replace('\\', "/")
+            );
+            Config::from_string(&source).unwrap().unwrap()
+        };
+
+        let c = make(&json);
+        assert_eq!(c.discovery().include_file(), Some(json.as_path()));
+        assert_eq!(c.discovery().included().len(), 3);
+        assert_eq!(
+            c.discovery().include(),
+            &vec!["a", "INST1", "INST2", "INST3"]
+        );
+        let included = c
+            .instances()
+            .iter()
+            .map(|i| (i.name().to_string(), i.conn().port(), i.is_tcp()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            included,
+            vec![
+                ("INST3".to_string(), defaults::CONNECTION_PORT.into(), true),
+                ("INST2".to_string(), Port(1435), true),
+            ]
+        );
+
+        let c = make(&yaml);
+        assert_eq!(c.discovery().include(), &vec!["a", "INST4", "INST5"]);
+        assert_eq!(c.instances().len(), 2);
+        assert_eq!(c.instances()[1].conn().port(), Port(1436));
+
+        let c = make(&dir.path().join("absent.json"));
+        assert_eq!(c.discovery().include(), &vec!["a"]);
+        assert!(c.discovery().included().is_empty());
+    }
+
     #[test]
     fn test_discovery_from_yaml_default() {
         let discovery = Discovery::from_yaml(&create_discovery_yaml_default())
@@ -1231,6 +2919,21 @@ connection:
         assert!(discovery.detect());
         assert!(discovery.include().is_empty());
         assert!(discovery.exclude().is_empty());
+        assert!(discovery.scan().is_empty());
+        assert!(discovery.ports().is_empty());
+    }
+
+    #[test]
+    fn test_discovery_bad_ports() {
+        let ports = |text: &str| {
+            Discovery::from_yaml(&create_yaml(&format!("discovery:\n  ports: {text}\n")))
+                .map(|d| d.unwrap().ports().clone())
+        };
+        assert_eq!(ports("1433").unwrap(), vec![1433]);
+        assert!(ports("[\"14340-14330\"]").is_err());
+        assert!(ports("[0]").is_err());
+        assert!(ports("[\"a-b\"]").is_err());
+        assert!(ports("[\"1-2000\"]").is_err());
     }
 
     fn create_discovery_yaml_default() -> Yaml {
@@ -1259,6 +2962,141 @@ discovery:
         );
     }
 
+    #[test]
+    fn test_cluster_dedup_from_yaml() {
+        assert_eq!(
+            ClusterDedup::from_yaml(&create_yaml("no_cluster_dedup: x")).unwrap(),
+            ClusterDedup::Off
+        );
+        assert_eq!(
+            ClusterDedup::from_yaml(&create_yaml("cluster_dedup: Active_Node")).unwrap(),
+            ClusterDedup::ActiveNode
+        );
+        assert_eq!(
+            ClusterDedup::from_yaml(&create_yaml("cluster_dedup: piggyback")).unwrap(),
+            ClusterDedup::Piggyback
+        );
+        assert!(ClusterDedup::from_yaml(&create_yaml("cluster_dedup: zu")).is_err());
+    }
+
+    #[test]
+    fn test_passive_node_policy_from_yaml() {
+        assert_eq!(
+            PassiveNodePolicy::from_yaml(&create_yaml("no_cluster: x")).unwrap(),
+            PassiveNodePolicy::Query
+        );
+        assert_eq!(
+            PassiveNodePolicy::from_yaml(&create_yaml("cluster:\n  passive_node_policy: Skip"))
+                .unwrap(),
+            PassiveNodePolicy::Skip
+        );
+        assert!(
+            PassiveNodePolicy::from_yaml(&create_yaml("cluster:\n  passive_node_policy: zu"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_instance_naming_from_yaml() {
+        const SOURCE: &str = r#"
+mssql:
+  main:
+    authentication:
+      username: "u"
+    qualify_with_host: yes
+    instance_prefix: " prod_ "
+  configs:
+    - main:
+        authentication:
+          username: "v"
+    - main:
+        authentication:
+          username: "w"
+        qualify_with_host: no
+        instance_prefix: "Test_"
+"#;
+        let c = Config::from_string(SOURCE).unwrap().unwrap();
+        assert!(c.qualify_with_host());
+        assert_eq!(c.instance_prefix(), Some("PROD_"));
+        assert!(c.configs()[0].qualify_with_host());
+        assert_eq!(c.configs()[0].instance_prefix(), None);
+        assert!(!c.configs()[1].qualify_with_host());
+        assert_eq!(c.configs()[1].instance_prefix(), Some("TEST_"));
+    }
+
+    #[test]
+    fn test_section_options_inheritance() {
+        const SOURCE: &str = r#"
+mssql:
+  main:
+    authentication:
+      username: "u"
+    sections:
+    - jobs:
+        timeout: 20
+    - backup:
+        is_async: yes
+    cache_age: 900
+    section_timeout: 40
+    instances:
+    - sid: "INST1"
+      piggyback:
+        hostname: "piggy"
+        cache_age: 300
+        sections:
+        - jobs:
+            cache_age: 60
+        - backup:
+            is_async: yes
+    - sid: "INST2"
+  configs:
+    - main:
+        authentication:
+          username: "v"
+        section_timeout: 10
+"#;
+        let c = Config::from_string(SOURCE).unwrap().unwrap();
+        let options = |sections: &Sections| {
+            sections
+                .sections()
+                .iter()
+                .map(|s| {
+                    let o = sections.options(s);
+                    (
+                        s.name().to_string(),
+                        o.cache_age(),
+                        o.timeout().map(|t| t.as_secs()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            options(c.sections()),
+            [
+                ("jobs".to_string(), 900, Some(20)),
+                ("backup".to_string(), 900, Some(40))
+            ]
+        );
+        assert_eq!(
+            options(c.instance_sections(&"inst1".to_string().into())),
+            [
+                ("jobs".to_string(), 60, Some(40)),
+                ("backup".to_string(), 300, Some(40))
+            ]
+        );
+        assert_eq!(
+            c.instance_sections(&"INST2".to_string().into()),
+            c.sections()
+        );
+        let config = &c.configs()[0];
+        assert_eq!(config.sections().cache_age(), 900);
+        assert_eq!(config.sections().timeout(), 10);
+        assert_eq!(
+            Sections::default().options(&Section::new("jobs")).timeout(),
+            None
+        );
+    }
+
     fn as_names(sections: Vec<&Section>) -> Vec<&str> {
         sections.iter().map(|s| s.name()).collect()
     }
@@ -1337,6 +3175,11 @@ discovery:
         assert_eq!(instance.alias(), &Some("a1".to_string().into()));
         assert_eq!(instance.piggyback().unwrap().hostname(), "piggy");
         assert_eq!(instance.piggyback().unwrap().sections().cache_age(), 123);
+        assert_eq!(
+            instance.main_dbs(),
+            &HashMap::from([("jobs".to_string(), "msdb2".to_string())])
+        );
+        assert_eq!(instance.required_databases(), ["Sales", "HR"]);
     }
 
     fn expected_count_in_registry() -> usize {
@@ -1421,6 +3264,58 @@ connection:
         assert_eq!(instance.calc_real_host(), "localhost".to_string().into());
     }
 
+    #[test]
+    fn test_custom_instance_partial_override() {
+        let main_auth = Authentication {
+            username: "u".to_string(),
+            passwords: vec!["p".to_string()],
+            remember_password: false,
+            auth_type: AuthType::SqlServer,
+            access_token: None,
+            kerberos: None,
+        };
+        let main_conn = Connection {
+            hostname: "mh".to_string().into(),
+            port: Port(1500),
+            timeout: 7,
+            ..Default::default()
+        };
+        let make_instance = |source: &str| {
+            CustomInstance::from_yaml(
+                &create_yaml(source),
+                &main_auth,
+                &main_conn,
+                &Sections::default(),
+            )
+            .unwrap()
+        };
+
+        let instance = make_instance("sid: INST1\nauthentication:\n  password: p2\n");
+        assert_eq!(instance.auth().username(), "u");
+        assert_eq!(instance.auth().password().unwrap(), "p2");
+        assert_eq!(instance.conn(), &main_conn);
+
+        let instance = make_instance("sid: INST1\nauthentication:\n  username: u2\n");
+        assert_eq!(instance.auth().username(), "u2");
+        assert!(instance.auth().password().is_none());
+
+        let instance = make_instance("sid: INST1\nconnection:\n  port: 5555\n");
+        assert_eq!(instance.auth(), &main_auth);
+        assert_eq!(instance.conn().hostname(), "mh".to_string().into());
+        assert_eq!(instance.conn().port(), Port(5555));
+        assert_eq!(instance.conn().timeout(), Duration::from_secs(7));
+        assert_eq!(
+            instance.endpoint(),
+            Endpoint::new(
+                &main_auth,
+                &Connection {
+                    port: Port(5555),
+                    ..main_conn.clone()
+                }
+            )
+        );
+    }
+
     /// remove some custom instances
     fn clean_config_from_custom_instances(c: &mut Config) {
         c.custom_instances
@@ -1431,7 +3326,13 @@ connection:
     fn test_config() {
         let mut c = Config::from_string(data::TEST_CONFIG).unwrap().unwrap();
         clean_config_from_custom_instances(&mut c);
-        assert_eq!(c.options(), &Options::new(5.into()));
+        assert_eq!(c.options().max_connections(), 5.into());
+        assert_eq!(c.options().max_queries(), MAX_QUERIES.into());
+        assert_eq!(c.options().spread_seconds(), 0);
+        assert_eq!(
+            c.options().blackout_windows(),
+            &[BlackoutWindow::try_from("1-5 01:00-04:30").unwrap()]
+        );
         assert_eq!(c.instances().len(), 2 + expected_count_in_registry());
         assert!(c.instances()[0].piggyback().is_some());
         assert_eq!(
@@ -1442,7 +3343,7 @@ connection:
         let inst2 = &c.instances()[1];
 
         assert_eq!(inst2.name().to_string(), "INST2");
-        assert_eq!(inst2.auth().password, Some("p".to_string()));
+        assert_eq!(inst2.auth().passwords, vec!["p".to_string()]);
         assert_eq!(&inst2.auth().username, "u");
         assert_eq!(inst2.auth().auth_type, AuthType::SqlServer);
         assert_eq!(inst2.conn().hostname, HostName::from("local".to_string()));
@@ -1467,6 +3368,9 @@ connection:
             &PathBuf::from(r"C:\path\to\file")
         );
         assert_eq!(c.piggyback_host(), Some("my_pb_host"));
+        assert_eq!(c.piggyback_template(), Some("sql-{instance}-{hostname}"));
+        assert!(!c.qualify_with_host());
+        assert_eq!(c.instance_prefix(), None);
         assert_eq!(c.conn().tls().unwrap().ca(), Path::new(r"C:\path\to\file"));
         assert_eq!(
             c.conn().tls().unwrap().client_certificate(),
@@ -1506,6 +3410,10 @@ connection:
         assert!(!c.is_instance_allowed(&"weird"));
         assert!(c.is_instance_allowed(&"a"));
         assert!(c.is_instance_allowed(&"b"));
+        assert!(c.is_instance_allowed(&"B "));
+        let c = make_detect_config(&[], &["İnst"]);
+        assert!(!c.is_instance_allowed(&"INST"));
+        assert!(c.is_instance_allowed(&"INST1"));
     }
 
     #[cfg(windows)]
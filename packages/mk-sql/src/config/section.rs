@@ -4,9 +4,9 @@
 
 use std::collections::HashSet;
 
-use super::defines::{defaults, keys};
+use super::defines::{defaults, keys, values};
 use super::yaml::{Get, Yaml};
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 pub mod names {
     pub const INSTANCE: &str = "instance";
@@ -25,10 +25,51 @@ pub mod names {
     pub const JOBS: &str = "jobs";
     pub const MIRRORING: &str = "mirroring";
     pub const AVAILABILITY_GROUPS: &str = "availability_groups";
+
+    // optional sections, must be requested in config explicitly
+    pub const PERF_KEY_METRICS: &str = "perf_key_metrics";
+    pub const WAITS_DELTA: &str = "waits_delta";
+    pub const DB_CONNECTIONS: &str = "db_connections";
+    pub const TEMPDB_CONTENTION: &str = "tempdb_contention";
+    pub const DB_OPTION_DRIFT: &str = "db_option_drift";
+    /// host labels of Checkmk, header is `<<<labels:sep(0)>>>`
+    pub const LABELS: &str = "labels";
+    pub const TRACE_FLAGS: &str = "traceflags";
+    pub const ENCRYPTION: &str = "encryption";
+    pub const SCHEDULERS: &str = "schedulers";
+    pub const SESSIONS: &str = "sessions";
+    pub const CHECKDB: &str = "checkdb";
+    pub const BACKUP_URL: &str = "backup_url";
+    pub const PERMISSIONS: &str = "permissions";
+    pub const FILEGROUPS: &str = "filegroups";
+    pub const CONSTRAINTS: &str = "constraints";
+    pub const TOP_TABLES: &str = "top_tables";
+    pub const FILESTREAM: &str = "filestream";
+    pub const UPTIME: &str = "uptime";
+    pub const VERSION_STORE: &str = "version_store";
+    pub const MEMORY_FEATURES: &str = "memory_features";
+    pub const DISTRIBUTED_TRANSACTIONS: &str = "distributed_transactions";
+    pub const COLLATION: &str = "collation";
+    pub const AGENT_HEALTH: &str = "agent_health";
+    pub const CERTS: &str = "certs";
+    pub const QUERY_STORE: &str = "query_store";
+    pub const LOGINS: &str = "logins";
+    pub const AG_CONFIG: &str = "ag_config";
+    pub const HOST_SETTINGS: &str = "host_settings";
+    pub const VOLUMES: &str = "volumes";
+    pub const DEFAULT_TRACE: &str = "default_trace";
+    pub const ORPHANED_FILES: &str = "orphaned_files";
+    pub const MAINTENANCE: &str = "maintenance";
+    pub const AUDITS: &str = "audits";
+    pub const AZURE_RESOURCES: &str = "azure_resources";
+
+    // generated always, not configurable
+    pub const SECTION_STATUS: &str = "section_status";
+    pub const PERMISSION_HINTS: &str = "permission_hints";
 }
 
 /// TODO(sk): convert into HashSet
-const PIPE_SEP_SECTIONS: [&str; 8] = [
+const PIPE_SEP_SECTIONS: [&str; 19] = [
     names::INSTANCE,
     names::COUNTERS,
     names::BLOCKED_SESSIONS,
@@ -37,11 +78,54 @@ const PIPE_SEP_SECTIONS: [&str; 8] = [
     names::DATAFILES,
     names::DATABASES,
     names::CLUSTERS,
+    names::PERF_KEY_METRICS,
+    names::WAITS_DELTA,
+    names::DB_CONNECTIONS,
+    names::TEMPDB_CONTENTION,
+    names::DB_OPTION_DRIFT,
+    names::PERMISSIONS,
+    names::FILEGROUPS,
+    names::CONSTRAINTS,
+    names::TOP_TABLES,
+    names::CHECKDB,
+    names::BACKUP_URL,
 ];
 
+/// sections generated always, they are neither known nor configurable, separated by '|'
+const GENERATED_SECTIONS: [&str; 2] = [names::SECTION_STATUS, names::PERMISSION_HINTS];
+
 const SPACE_SEP_SECTIONS: [&str; 2] = [names::TABLE_SPACES, names::CONNECTIONS];
 
-const QUERY_BASED_SECTIONS: [&str; 3] = [names::JOBS, names::MIRRORING, names::AVAILABILITY_GROUPS];
+/// sections of Checkmk itself, they have neither prefix nor field separator
+const AGENT_SECTIONS: [&str; 1] = [names::LABELS];
+
+const QUERY_BASED_SECTIONS: [&str; 25] = [
+    names::JOBS,
+    names::MIRRORING,
+    names::AVAILABILITY_GROUPS,
+    names::TRACE_FLAGS,
+    names::ENCRYPTION,
+    names::SCHEDULERS,
+    names::SESSIONS,
+    names::FILESTREAM,
+    names::UPTIME,
+    names::VERSION_STORE,
+    names::MEMORY_FEATURES,
+    names::DISTRIBUTED_TRANSACTIONS,
+    names::COLLATION,
+    names::AGENT_HEALTH,
+    names::CERTS,
+    names::QUERY_STORE,
+    names::LOGINS,
+    names::AG_CONFIG,
+    names::HOST_SETTINGS,
+    names::VOLUMES,
+    names::DEFAULT_TRACE,
+    names::ORPHANED_FILES,
+    names::MAINTENANCE,
+    names::AUDITS,
+    names::AZURE_RESOURCES,
+];
 const PREDEFINED_SECTIONS: [&str; 13] = [
     names::INSTANCE,
     names::DATABASES,
@@ -65,15 +149,47 @@ const ASYNC_SECTIONS: [&str; 4] = [
     names::JOBS,
 ];
 
-const PER_DATABASE_SECTIONS: [&str; 5] = [
+/// optional sections which are async if requested
+const OPTIONAL_ASYNC_SECTIONS: [&str; 13] = [
+    names::PERMISSIONS,
+    names::FILEGROUPS,
+    names::CONSTRAINTS,
+    names::TOP_TABLES,
+    names::CHECKDB,
+    names::FILESTREAM,
+    names::BACKUP_URL,
+    names::LOGINS,
+    names::AG_CONFIG,
+    names::DEFAULT_TRACE,
+    names::ORPHANED_FILES,
+    names::MAINTENANCE,
+    names::DB_OPTION_DRIFT,
+];
+
+const PER_DATABASE_SECTIONS: [&str; 9] = [
     names::DATABASES,
     names::TRANSACTION_LOG,
     names::TABLE_SPACES,
     names::DATAFILES,
     names::CLUSTERS,
+    names::PERMISSIONS,
+    names::FILEGROUPS,
+    names::CONSTRAINTS,
+    names::TOP_TABLES,
 ];
 
 const FIRST_LINE_SECTIONS: [&str; 2] = [names::MIRRORING, names::JOBS];
+
+/// sections loading the host, e.g. reading `sys.dm_os_performance_counters`
+const HOST_HEAVY_SECTIONS: [&str; 2] = [names::COUNTERS, names::PERF_KEY_METRICS];
+
+/// sections waiting `sample_interval` between samples of the server
+const SAMPLING_SECTIONS: [&str; 4] = [
+    names::PERF_KEY_METRICS,
+    names::WAITS_DELTA,
+    names::DB_CONNECTIONS,
+    names::TEMPDB_CONTENTION,
+];
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum SectionKind {
     Sync,
@@ -86,20 +202,47 @@ pub struct SectionBuilder {
     sep: char,
     is_async: bool,
     is_disabled: bool,
+    cache_age: Option<u32>,
+    timeout: Option<u32>,
     sql: Option<String>,
+    sample_interval: u32,
+    main_db: Option<String>,
+    per_database: bool,
+    max_age_days: u32,
+    include_sql_text: bool,
+    compute_rates: bool,
+    priority: i32,
+    filters: Vec<RowFilter>,
+    masking: Vec<MaskRule>,
+    baseline: DatabaseBaseline,
+    procedure: Option<Procedure>,
 }
 
 impl SectionBuilder {
     pub fn new<S: Into<String>>(name: S) -> Self {
         let name = name.into();
         let sep = get_default_separator(&name);
-        let is_async = ASYNC_SECTIONS.contains(&name.as_str());
+        let is_async = ASYNC_SECTIONS.contains(&name.as_str())
+            || OPTIONAL_ASYNC_SECTIONS.contains(&name.as_str());
         Self {
             name,
             sep,
             is_async,
             is_disabled: false,
+            cache_age: None,
+            timeout: None,
             sql: None,
+            sample_interval: defaults::SECTION_SAMPLE_INTERVAL,
+            main_db: None,
+            per_database: false,
+            max_age_days: defaults::SECTION_MAX_AGE_DAYS,
+            include_sql_text: false,
+            compute_rates: false,
+            priority: 0,
+            filters: vec![],
+            masking: vec![],
+            baseline: DatabaseBaseline::default(),
+            procedure: None,
         }
     }
     pub fn sep(mut self, sep: Option<char>) -> Self {
@@ -118,11 +261,97 @@ impl SectionBuilder {
         self
     }
 
+    /// overrides `cache_age` of the config and of the instance
+    pub fn cache_age(mut self, cache_age: Option<u32>) -> Self {
+        self.cache_age = cache_age;
+        self
+    }
+
+    /// overrides `section_timeout` of the config and of the instance
+    pub fn timeout(mut self, timeout: Option<u32>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
     pub fn sql<S: Into<String>>(mut self, sql: S) -> Self {
         self.sql = Some(sql.into());
         self
     }
 
+    pub fn sample_interval(mut self, interval: Option<u32>) -> Self {
+        if let Some(interval) = interval {
+            self.sample_interval = interval;
+        }
+        self
+    }
+
+    /// database to connect to instead of the default one of the section
+    pub fn main_db(mut self, main_db: Option<String>) -> Self {
+        self.main_db = main_db
+            .map(|db| db.trim().to_string())
+            .filter(|db| !db.is_empty());
+        self
+    }
+
+    /// query is executed in every database of the instance
+    pub fn per_database(mut self, value: bool) -> Self {
+        self.per_database = value;
+        self
+    }
+
+    pub fn max_age_days(mut self, days: Option<u32>) -> Self {
+        if let Some(days) = days {
+            self.max_age_days = days;
+        }
+        self
+    }
+
+    /// statements of blocked and blocking sessions are reported too
+    pub fn include_sql_text(mut self, value: bool) -> Self {
+        self.include_sql_text = value;
+        self
+    }
+
+    /// rates of cumulative counters are reported too
+    pub fn compute_rates(mut self, value: bool) -> Self {
+        self.compute_rates = value;
+        self
+    }
+
+    /// sections with higher priority are executed and emitted first
+    pub fn priority(mut self, priority: Option<i32>) -> Self {
+        if let Some(priority) = priority {
+            self.priority = priority;
+        }
+        self
+    }
+
+    /// rows of the result are filtered before output
+    pub fn filters(mut self, filters: Vec<RowFilter>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// sensitive values of the result are masked before output
+    pub fn masking(mut self, masking: Vec<MaskRule>) -> Self {
+        self.masking = masking;
+        self
+    }
+
+    /// expected options of databases, db_option_drift section only
+    pub fn baseline(mut self, baseline: Option<DatabaseBaseline>) -> Self {
+        if let Some(baseline) = baseline {
+            self.baseline = baseline;
+        }
+        self
+    }
+
+    /// stored procedure executed instead of the query, custom sections only
+    pub fn procedure(mut self, procedure: Option<Procedure>) -> Self {
+        self.procedure = procedure;
+        self
+    }
+
     pub fn build(self) -> Section {
         Section {
             name: self.name,
@@ -134,7 +363,20 @@ impl SectionBuilder {
             } else {
                 SectionKind::Sync
             },
+            cache_age: self.cache_age,
+            timeout: self.timeout,
             sql: self.sql,
+            sample_interval: self.sample_interval,
+            main_db: self.main_db,
+            per_database: self.per_database,
+            max_age_days: self.max_age_days,
+            include_sql_text: self.include_sql_text,
+            compute_rates: self.compute_rates,
+            priority: self.priority,
+            filters: self.filters,
+            masking: self.masking,
+            baseline: self.baseline,
+            procedure: self.procedure,
         }
     }
 }
@@ -144,7 +386,20 @@ pub struct Section {
     name: String,
     sep: char,
     kind: SectionKind,
+    cache_age: Option<u32>,
+    timeout: Option<u32>,
     sql: Option<String>,
+    sample_interval: u32,
+    main_db: Option<String>,
+    per_database: bool,
+    max_age_days: u32,
+    include_sql_text: bool,
+    compute_rates: bool,
+    priority: i32,
+    filters: Vec<RowFilter>,
+    masking: Vec<MaskRule>,
+    baseline: DatabaseBaseline,
+    procedure: Option<Procedure>,
 }
 
 impl Section {
@@ -164,15 +419,454 @@ impl Section {
         self.kind
     }
 
+    /// explicit value of the section, see [`Sections::options`] for the effective one
+    pub fn cache_age(&self) -> Option<u32> {
+        self.cache_age
+    }
+
+    /// explicit value of the section, see [`Sections::options`] for the effective one
+    pub fn timeout(&self) -> Option<u32> {
+        self.timeout
+    }
+
     pub fn sql(&self) -> Option<&str> {
         self.sql.as_deref()
     }
+
+    /// seconds between two samples, used only by sections calculating rates
+    pub fn sample_interval(&self) -> u32 {
+        self.sample_interval
+    }
+
+    pub fn main_db(&self) -> Option<&str> {
+        self.main_db.as_deref()
+    }
+
+    pub fn per_database(&self) -> bool {
+        self.per_database
+    }
+
+    /// age limit of the tracked activity, used only by checkdb section
+    pub fn max_age_days(&self) -> u32 {
+        self.max_age_days
+    }
+
+    /// statements of the sessions, used only by blocked_sessions section
+    pub fn include_sql_text(&self) -> bool {
+        self.include_sql_text
+    }
+
+    /// per second rates from the previous sample, used only by counters section
+    pub fn compute_rates(&self) -> bool {
+        self.compute_rates
+    }
+
+    /// sections are executed in descending order of priority, config order is kept for equal
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn filters(&self) -> &[RowFilter] {
+        &self.filters
+    }
+
+    pub fn masking(&self) -> &[MaskRule] {
+        &self.masking
+    }
+
+    pub fn baseline(&self) -> &DatabaseBaseline {
+        &self.baseline
+    }
+
+    pub fn procedure(&self) -> Option<&Procedure> {
+        self.procedure.as_ref()
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Condition {
+    Equals(String),
+    Contains(String),
+    /// wildcards `*` and `?`
+    Matches(String),
+}
+
+/// Filter of the result rows
+/// - column: "step_name" # mandatory, name of the column, case insensitive
+///   matches: "backup*" # mandatory, one of equals, contains or matches(wildcards `*` and `?`)
+///   action: keep # optional(default: drop), `keep` drops all rows which don't match
+#[derive(PartialEq, Debug, Clone)]
+pub struct RowFilter {
+    column: String,
+    condition: Condition,
+    keep: bool,
+}
+
+impl RowFilter {
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
+        let column = yaml
+            .get_string(keys::COLUMN)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .context("Bad/Missing column")?;
+        let condition = if let Some(value) = yaml.get_string(keys::EQUALS) {
+            Condition::Equals(value)
+        } else if let Some(value) = yaml.get_string(keys::CONTAINS) {
+            Condition::Contains(value)
+        } else if let Some(value) = yaml.get_string(keys::MATCHES) {
+            Condition::Matches(value)
+        } else {
+            anyhow::bail!("No condition for column `{column}`");
+        };
+        let keep = match yaml
+            .get_string(keys::ACTION)
+            .map(|s| s.to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some(values::DROP) => false,
+            Some(values::KEEP) => true,
+            Some(action) => anyhow::bail!("Unsupported action `{action}`"),
+        };
+        Ok(Self {
+            column,
+            condition,
+            keep,
+        })
+    }
+
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// absent column never matches
+    fn is_match(&self, value: Option<&str>) -> bool {
+        match (value, &self.condition) {
+            (None, _) => false,
+            (Some(value), Condition::Equals(expected)) => value.trim() == expected,
+            (Some(value), Condition::Contains(part)) => value.contains(part.as_str()),
+            (Some(value), Condition::Matches(pattern)) => is_wildcard_match(pattern, value.trim()),
+        }
+    }
+}
+
+/// `headline` contains column names of the `row`
+pub fn is_row_passed(filters: &[RowFilter], headline: &[String], row: &[String]) -> bool {
+    filters.iter().all(|f| {
+        let value = headline
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(f.column()))
+            .and_then(|idx| row.get(idx))
+            .map(String::as_str);
+        f.is_match(value) == f.keep
+    })
+}
+
+/// Expected options of user databases, an absent option is not checked
+/// - baseline:
+///     recovery_model: full # optional, simple, full or bulk_logged
+///     auto_shrink: no # optional(default: no)
+///     auto_close: no # optional(default: no)
+///     page_verify: checksum # optional(default: checksum), none, torn_page_detection or checksum
+#[derive(PartialEq, Debug, Clone)]
+pub struct DatabaseBaseline {
+    recovery_model: Option<String>,
+    auto_shrink: Option<bool>,
+    auto_close: Option<bool>,
+    page_verify: Option<String>,
+}
+
+impl Default for DatabaseBaseline {
+    fn default() -> Self {
+        Self {
+            recovery_model: None,
+            auto_shrink: Some(false),
+            auto_close: Some(false),
+            page_verify: Some("CHECKSUM".to_string()),
+        }
+    }
+}
+
+impl DatabaseBaseline {
+    pub fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        let baseline = yaml.get(keys::BASELINE);
+        if baseline.is_badvalue() {
+            return None;
+        }
+        let default = Self::default();
+        let upper = |key: &str| {
+            baseline
+                .get_string(key)
+                .map(|s| s.trim().to_uppercase())
+                .filter(|s| !s.is_empty())
+        };
+        Some(Self {
+            recovery_model: upper(keys::RECOVERY_MODEL),
+            auto_shrink: baseline
+                .get_optional_bool(keys::AUTO_SHRINK)
+                .or(default.auto_shrink),
+            auto_close: baseline
+                .get_optional_bool(keys::AUTO_CLOSE)
+                .or(default.auto_close),
+            page_verify: upper(keys::PAGE_VERIFY).or(default.page_verify),
+        })
+    }
+
+    /// upper case, e.g. `FULL`
+    pub fn recovery_model(&self) -> Option<&str> {
+        self.recovery_model.as_deref()
+    }
+
+    pub fn auto_shrink(&self) -> Option<bool> {
+        self.auto_shrink
+    }
+
+    pub fn auto_close(&self) -> Option<bool> {
+        self.auto_close
+    }
+
+    /// upper case, e.g. `CHECKSUM`
+    pub fn page_verify(&self) -> Option<&str> {
+        self.page_verify.as_deref()
+    }
+}
+
+/// Masking of sensitive values in the result rows, e.g. host and user names
+/// - column: "login_name" # optional, name of the column, case insensitive, all columns if absent
+///   contains: "corp.local" # mandatory, one of equals, contains or matches(wildcards `*` and `?`)
+///   replacement: "<host>" # optional(default: `***`)
+///
+/// `contains` replaces every occurrence of the part, `equals` and `matches` the whole value
+#[derive(PartialEq, Debug, Clone)]
+pub struct MaskRule {
+    column: Option<String>,
+    condition: Condition,
+    replacement: String,
+}
+
+impl MaskRule {
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
+        let column = yaml
+            .get_string(keys::COLUMN)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        let condition = if let Some(value) = yaml.get_string(keys::EQUALS) {
+            Condition::Equals(value)
+        } else if let Some(value) = yaml.get_string(keys::CONTAINS).filter(|s| !s.is_empty()) {
+            Condition::Contains(value)
+        } else if let Some(value) = yaml.get_string(keys::MATCHES) {
+            Condition::Matches(value)
+        } else {
+            anyhow::bail!("No condition for masking");
+        };
+        Ok(Self {
+            column,
+            condition,
+            replacement: yaml
+                .get_string(keys::REPLACEMENT)
+                .unwrap_or_else(|| defaults::MASK_REPLACEMENT.to_string()),
+        })
+    }
+
+    pub fn column(&self) -> Option<&str> {
+        self.column.as_deref()
+    }
+
+    fn apply(&self, value: &str) -> String {
+        match &self.condition {
+            Condition::Equals(expected) if value.trim() == expected => self.replacement.clone(),
+            Condition::Contains(part) => value.replace(part.as_str(), &self.replacement),
+            Condition::Matches(pattern) if is_wildcard_match(pattern, value.trim()) => {
+                self.replacement.clone()
+            }
+            _ => value.to_string(),
+        }
+    }
+}
+
+/// `headline` contains column names of the `row`, rules are applied in order
+pub fn mask_row(rules: &[MaskRule], headline: &[String], row: Vec<String>) -> Vec<String> {
+    if rules.is_empty() {
+        return row;
+    }
+    row.into_iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            let column = headline.get(idx);
+            rules
+                .iter()
+                .filter(|r| match (r.column(), column) {
+                    (None, _) => true,
+                    (Some(expected), Some(column)) => column.eq_ignore_ascii_case(expected),
+                    (Some(_), None) => false,
+                })
+                .fold(value, |value, r| r.apply(&value))
+        })
+        .collect()
+}
+
+fn is_wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParamValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl ParamValue {
+    /// without `type` the value is taken as is
+    fn from_yaml(value: &Yaml, param_type: Option<&str>) -> Result<Self> {
+        let text = match value {
+            Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+            Yaml::Integer(i) => Some(i.to_string()),
+            Yaml::Boolean(b) => Some(b.to_string()),
+            _ => None,
+        }
+        .context("Bad/Missing value")?;
+        let result = match (param_type, value) {
+            (None, Yaml::Integer(i)) => ParamValue::Int(*i),
+            (None, Yaml::Real(_)) => ParamValue::Float(text.parse()?),
+            (None, Yaml::Boolean(b)) => ParamValue::Bool(*b),
+            (None, _) | (Some(values::STRING), _) => ParamValue::Str(text),
+            (Some(values::INT), _) => ParamValue::Int(text.trim().parse()?),
+            (Some(values::FLOAT), _) => ParamValue::Float(text.trim().parse()?),
+            (Some(values::BOOL), _) => ParamValue::Bool(super::yaml::to_bool(text.trim())?),
+            (Some(t), _) => anyhow::bail!("Unsupported type `{t}`"),
+        };
+        Ok(result)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ProcedureParam {
+    name: String,
+    value: ParamValue,
+}
+
+impl ProcedureParam {
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self> {
+        let name = yaml
+            .get_string(keys::NAME)
+            .map(|s| s.trim().trim_start_matches('@').to_string())
+            .filter(|s| is_identifier(s))
+            .context("Bad/Missing name")?;
+        let param_type = yaml.get_string(keys::TYPE).map(|t| t.to_ascii_lowercase());
+        let value = ParamValue::from_yaml(&yaml[keys::VALUE], param_type.as_deref())
+            .with_context(|| format!("Bad parameter `{name}`"))?;
+        Ok(Self { name, value })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &ParamValue {
+        &self.value
+    }
+}
+
+/// Stored procedure of a custom section, parameters are bound, never concatenated
+/// - my_section:
+///     procedure: "dbo.check_jobs"
+///     params:
+///       - name: "days"
+///         type: int # optional, int, float, bool or string, deduced from the value if absent
+///         value: 7
+#[derive(PartialEq, Debug, Clone)]
+pub struct Procedure {
+    name: String,
+    params: Vec<ProcedureParam>,
+}
+
+impl Procedure {
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<Self>> {
+        let Some(name) = yaml.get_string(keys::PROCEDURE) else {
+            return Ok(None);
+        };
+        let parts = name
+            .split('.')
+            .map(|p| p.trim().trim_start_matches('[').trim_end_matches(']'))
+            .collect::<Vec<_>>();
+        if parts.iter().any(|p| p.is_empty()) || parts.len() > 3 {
+            anyhow::bail!("Bad procedure name `{name}`");
+        }
+        let params = yaml
+            .get_yaml_vector(keys::PARAMS)
+            .iter()
+            .map(ProcedureParam::from_yaml)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(Self {
+            name: parts.join("."),
+            params,
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn params(&self) -> &[ProcedureParam] {
+        &self.params
+    }
+
+    /// `EXEC [dbo].[name] @a = @P1, @b = @P2`, values are bound to `@P<n>` placeholders
+    pub fn statement(&self) -> String {
+        let name = self
+            .name
+            .split('.')
+            .map(|p| format!("[{}]", p.replace(']', "]]")))
+            .collect::<Vec<_>>()
+            .join(".");
+        let params = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, p)| format!("@{} = @P{}", p.name, i + 1))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("EXEC {name} {params}").trim_end().to_string()
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Sections {
     sections: Vec<Section>,
     cache_age: u32,
+    timeout: u32,
 }
 
 impl Default for Sections {
@@ -180,6 +874,85 @@ impl Default for Sections {
         Self {
             sections: get_predefined_sections(),
             cache_age: defaults::SECTIONS_CACHE_AGE,
+            timeout: defaults::SECTIONS_TIMEOUT,
+        }
+    }
+}
+
+/// Effective options of a section, see [`Sections::options`]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SectionOptions {
+    cache_age: u32,
+    sep: char,
+    timeout: u32,
+}
+
+impl SectionOptions {
+    pub fn cache_age(&self) -> u32 {
+        self.cache_age
+    }
+
+    pub fn sep(&self) -> char {
+        self.sep
+    }
+
+    /// `None` - no limit
+    pub fn timeout(&self) -> Option<std::time::Duration> {
+        (self.timeout > 0).then(|| std::time::Duration::from_secs(self.timeout.into()))
+    }
+}
+
+const MINIMAL_PROFILE_SECTIONS: [&str; 5] = [
+    names::INSTANCE,
+    names::DATABASES,
+    names::COUNTERS,
+    names::BLOCKED_SESSIONS,
+    names::BACKUP,
+];
+
+/// Curated set of sections, explicit `sections` take precedence
+#[derive(PartialEq, Debug, Clone)]
+pub enum Profile {
+    /// core health only
+    Minimal,
+    /// the same as default
+    Standard,
+    /// all sections provided by the plugin
+    Full,
+}
+
+impl TryFrom<&str> for Profile {
+    type Error = anyhow::Error;
+
+    fn try_from(str: &str) -> Result<Self> {
+        match str::to_ascii_lowercase(str).trim() {
+            values::MINIMAL => Ok(Profile::Minimal),
+            values::STANDARD => Ok(Profile::Standard),
+            values::FULL => Ok(Profile::Full),
+            _ => Err(anyhow::anyhow!("unsupported profile `{str}`")),
+        }
+    }
+}
+
+impl Profile {
+    pub fn sections(&self) -> Vec<Section> {
+        match self {
+            Profile::Minimal => MINIMAL_PROFILE_SECTIONS
+                .iter()
+                .map(|&s| Section::new(s))
+                .collect(),
+            Profile::Standard => get_predefined_sections(),
+            Profile::Full => get_known_section_names()
+                .into_iter()
+                .map(Section::new)
+                .collect(),
+        }
+    }
+
+    pub fn cache_age(&self) -> u32 {
+        match self {
+            Profile::Minimal | Profile::Standard => defaults::SECTIONS_CACHE_AGE,
+            Profile::Full => defaults::FULL_PROFILE_CACHE_AGE,
         }
     }
 }
@@ -191,6 +964,24 @@ fn get_predefined_sections() -> Vec<Section> {
         .collect()
 }
 
+/// all sections provided by the plugin, other names are custom sections
+pub fn get_known_section_names() -> Vec<&'static str> {
+    PIPE_SEP_SECTIONS
+        .iter()
+        .chain(SPACE_SEP_SECTIONS.iter())
+        .chain(QUERY_BASED_SECTIONS.iter())
+        .copied()
+        .collect()
+}
+
+pub fn is_host_heavy_section(name: &str) -> bool {
+    HOST_HEAVY_SECTIONS.contains(&name)
+}
+
+pub fn is_sampling_section(name: &str) -> bool {
+    SAMPLING_SECTIONS.contains(&name)
+}
+
 pub fn get_per_database_sections() -> Vec<String> {
     PER_DATABASE_SECTIONS
         .iter()
@@ -220,6 +1011,24 @@ impl Section {
     /// - databases:     # name
     ///   is_async: true    # option
     ///   disabled: true # option
+    ///   sep: "|" # option, the default separator of the section otherwise
+    ///   cache_age: 300 # option, async sections only, overrides `cache_age` of config and instance
+    ///   timeout: 30 # option, seconds, overrides `section_timeout` of config and instance
+    ///   sample_interval: 5 # option, rate based sections only
+    ///   main_db: "monitoring" # option, database to run the query in
+    ///   per_database: yes # option, custom sections only, query runs in every database
+    ///   max_age_days: 7 # option, checkdb and backup_url sections only
+    ///   include_sql_text: yes # option, blocked_sessions section only
+    ///   compute_rates: yes # option, counters section only
+    ///   priority: 10 # option, higher is executed earlier, negative is later than default 0
+    ///   filters: # option, query based and custom sections only, see RowFilter
+    ///     - column: "last_outcome"
+    ///       equals: "succeeded"
+    ///   masking: # option, query based and custom sections only, see MaskRule
+    ///     - contains: "corp.local"
+    ///   baseline: # option, db_option_drift section only, see DatabaseBaseline
+    ///     recovery_model: full
+    ///   procedure: "dbo.check" # option, custom sections only, see Procedure
     /// Note: yaml_rust2 represents such entry as a LinkedHashMap
     pub fn from_yaml(entry: &Yaml) -> Result<Self> {
         let mut section = entry
@@ -243,7 +1052,44 @@ impl Section {
     }
     fn from_yaml_entry(name: &str, yaml: &Yaml) -> Self {
         let c = yaml.get_string(keys::SEP).and_then(|s| s.chars().next());
-        let builder = SectionBuilder::new(name).sep(c);
+        let builder = SectionBuilder::new(name)
+            .sep(c)
+            .cache_age(yaml.get_int::<u32>(keys::CACHE_AGE))
+            .timeout(yaml.get_int::<u32>(keys::TIMEOUT))
+            .sample_interval(yaml.get_int::<u32>(keys::SAMPLE_INTERVAL))
+            .main_db(yaml.get_string(keys::MAIN_DB))
+            .per_database(yaml.get_bool(keys::PER_DATABASE, false))
+            .max_age_days(yaml.get_int::<u32>(keys::MAX_AGE_DAYS))
+            .include_sql_text(yaml.get_bool(keys::INCLUDE_SQL_TEXT, false))
+            .compute_rates(yaml.get_bool(keys::COMPUTE_RATES, false))
+            .priority(yaml.get_int::<i32>(keys::PRIORITY))
+            .filters(
+                yaml.get_yaml_vector(keys::FILTERS)
+                    .iter()
+                    .filter_map(|f| {
+                        RowFilter::from_yaml(f)
+                            .map_err(|e| log::error!("Bad filter in section `{name}`: {e}"))
+                            .ok()
+                    })
+                    .collect(),
+            )
+            .masking(
+                yaml.get_yaml_vector(keys::MASKING)
+                    .iter()
+                    .filter_map(|m| {
+                        MaskRule::from_yaml(m)
+                            .map_err(|e| log::error!("Bad masking in section `{name}`: {e}"))
+                            .ok()
+                    })
+                    .collect(),
+            )
+            .baseline(DatabaseBaseline::from_yaml(yaml))
+            .procedure(
+                Procedure::from_yaml(yaml)
+                    .map_err(|e| log::error!("Bad procedure in section `{name}`: {e}"))
+                    .ok()
+                    .flatten(),
+            );
 
         if yaml.get_optional_bool(keys::DISABLED) == Some(true) {
             builder.set_disabled()
@@ -257,15 +1103,30 @@ impl Section {
 }
 
 impl Sections {
+    /// explicit `sections` and `cache_age` override the profile, the profile overrides default
+    /// `default` is the parent level: built-in defaults for a config, the config for an instance
     pub fn from_yaml(yaml: &Yaml, default: &Sections) -> Result<Self> {
+        let profile = yaml.get_string(keys::PROFILE).and_then(|p| {
+            Profile::try_from(p.as_str())
+                .map_err(|e| log::warn!("{e}, ignored"))
+                .ok()
+        });
         let cache_age = yaml.get_int::<u32>(keys::CACHE_AGE).unwrap_or_else(|| {
             log::debug!("Using default cache age");
-            default.cache_age()
+            profile
+                .as_ref()
+                .map(Profile::cache_age)
+                .unwrap_or_else(|| default.cache_age())
         });
         let sections = Sections::get_sections(yaml.get(keys::SECTIONS));
         Ok(Self {
-            sections: sections.unwrap_or(default.sections().clone()),
+            sections: sections
+                .or_else(|| profile.as_ref().map(Profile::sections))
+                .unwrap_or(default.sections().clone()),
             cache_age,
+            timeout: yaml
+                .get_int::<u32>(keys::SECTION_TIMEOUT)
+                .unwrap_or(default.timeout),
         })
     }
 
@@ -288,19 +1149,45 @@ impl Sections {
         self.cache_age
     }
 
+    /// seconds, 0 - no limit
+    pub fn timeout(&self) -> u32 {
+        self.timeout
+    }
+
+    /// The only place where options of a section are resolved, the most specific wins:
+    /// built-in defaults -> config -> instance -> section
+    /// `self` is the most specific level, levels above are merged into it on load
+    pub fn options(&self, section: &Section) -> SectionOptions {
+        SectionOptions {
+            cache_age: section.cache_age().unwrap_or(self.cache_age),
+            sep: section.sep(),
+            timeout: section.timeout().unwrap_or(self.timeout),
+        }
+    }
+
     pub fn select(&self, kinds: &[SectionKind]) -> Vec<&Section> {
-        self.sections()
+        let mut selected = self
+            .sections()
             .iter()
             .filter(|s| kinds.contains(&s.kind()))
-            .collect()
+            .collect::<Vec<_>>();
+        selected.sort_by_key(|s| std::cmp::Reverse(s.priority()));
+        selected
     }
 }
 
+/// header is not prefixed, see [`AGENT_SECTIONS`]
+pub fn is_agent_section(name: &str) -> bool {
+    AGENT_SECTIONS.contains(&name)
+}
+
 fn get_default_separator(name: &str) -> char {
-    if PIPE_SEP_SECTIONS.contains(&name) {
+    if PIPE_SEP_SECTIONS.contains(&name) || GENERATED_SECTIONS.contains(&name) {
         '|'
     } else if SPACE_SEP_SECTIONS.contains(&name) {
         ' '
+    } else if AGENT_SECTIONS.contains(&name) {
+        '\0'
     } else if QUERY_BASED_SECTIONS.contains(&name) {
         '\t'
     } else {
@@ -329,9 +1216,17 @@ sections:
     sep: |
 - ddd:
     is_async: yes
+    main_db: " monitoring "
+    per_database: yes
 - "eee":
     sep: "|ss"
     disabled: yes
+- perf_key_metrics:
+    sample_interval: 2
+- checkdb:
+    max_age_days: 14
+- blocked_sessions:
+    include_sql_text: yes
 "#;
 
     #[test]
@@ -342,14 +1237,54 @@ sections:
                 .iter()
                 .map(|s| (s.name(), s.sep()))
                 .collect::<Vec<(&str, char)>>(),
-            [("aaa", '|'), ("bbb", '|')]
+            [
+                ("aaa", '|'),
+                ("bbb", '|'),
+                ("perf_key_metrics", '|'),
+                ("blocked_sessions", '|')
+            ]
+        );
+        assert_eq!(
+            s.sections()
+                .iter()
+                .map(|s| s.sample_interval())
+                .collect::<Vec<u32>>(),
+            [5, 5, 5, 5, 5, 2, 5, 5]
+        );
+        assert_eq!(
+            s.sections()
+                .iter()
+                .map(|s| s.main_db())
+                .collect::<Vec<Option<&str>>>(),
+            [None, None, None, Some("monitoring"), None, None, None, None]
+        );
+        assert_eq!(
+            s.sections()
+                .iter()
+                .map(|s| s.per_database())
+                .collect::<Vec<bool>>(),
+            [false, false, false, true, false, false, false, false]
+        );
+        assert_eq!(
+            s.sections()
+                .iter()
+                .map(|s| s.max_age_days())
+                .collect::<Vec<u32>>(),
+            [7, 7, 7, 7, 7, 7, 14, 7]
+        );
+        assert_eq!(
+            s.sections()
+                .iter()
+                .map(|s| s.include_sql_text())
+                .collect::<Vec<bool>>(),
+            [false, false, false, false, false, false, false, true]
         );
         assert_eq!(
             s.select(&[SectionKind::Async])
                 .iter()
                 .map(|s| s.name())
                 .collect::<Vec<&str>>(),
-            ["ccc", "ddd"]
+            ["ccc", "ddd", "checkdb"]
         );
         assert_eq!(
             s.select(&[SectionKind::Disabled])
@@ -360,6 +1295,214 @@ sections:
         );
     }
 
+    #[test]
+    fn test_row_filter() {
+        let make = |source: &str| RowFilter::from_yaml(&create_yaml(source));
+        let headline = ["job_name".to_string(), "last_outcome".to_string()];
+        let row = |name: &str, outcome: &str| [name.to_string(), outcome.to_string()];
+
+        let drop_succeeded = make("column: Last_Outcome\nequals: succeeded\n").unwrap();
+        let filters = [drop_succeeded];
+        assert!(!is_row_passed(&filters, &headline, &row("a", "succeeded")));
+        assert!(is_row_passed(&filters, &headline, &row("a", "failed")));
+
+        let keep_backup =
+            make("column: job_name\nmatches: \"Backup*Full?\"\naction: keep\n").unwrap();
+        let filters = [keep_backup];
+        assert!(is_row_passed(
+            &filters,
+            &headline,
+            &row("Backup db Full1", "x")
+        ));
+        assert!(!is_row_passed(
+            &filters,
+            &headline,
+            &row("Backup db Full", "x")
+        ));
+        assert!(!is_row_passed(&filters, &headline, &row("Index", "x")));
+
+        let drop_absent = make("column: absent\ncontains: x\n").unwrap();
+        assert!(is_row_passed(&[drop_absent], &headline, &row("x", "x")));
+
+        assert!(make("equals: x\n").is_err());
+        assert!(make("column: a\n").is_err());
+        assert!(make("column: a\nequals: x\naction: hide\n").is_err());
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(is_wildcard_match("*", ""));
+        assert!(is_wildcard_match("a*c", "abbc"));
+        assert!(is_wildcard_match("a?c", "abc"));
+        assert!(is_wildcard_match("*b*", "abc"));
+        assert!(!is_wildcard_match("a*d", "abc"));
+        assert!(!is_wildcard_match("abc", "ab"));
+    }
+
+    #[test]
+    fn test_mask_row() {
+        let make = |source: &str| MaskRule::from_yaml(&create_yaml(source)).unwrap();
+        let headline = ["login_name".to_string(), "sql_text".to_string()];
+        let row = |login: &str, text: &str| vec![login.to_string(), text.to_string()];
+        let rules = [
+            make("column: Login_Name\nmatches: \"CORP\\\\*\"\n"),
+            make("contains: sql1.corp.local\nreplacement: <host>\n"),
+        ];
+        assert_eq!(
+            mask_row(
+                &rules,
+                &headline,
+                row("CORP\\john", "EXEC AT [sql1.corp.local]")
+            ),
+            row("***", "EXEC AT [<host>]")
+        );
+        assert_eq!(
+            mask_row(&rules, &headline, row("sa", "CORP\\x")),
+            row("sa", "CORP\\x")
+        );
+        assert_eq!(mask_row(&[], &headline, row("a", "b")), row("a", "b"));
+        assert!(MaskRule::from_yaml(&create_yaml("column: x\n")).is_err());
+    }
+
+    #[test]
+    fn test_database_baseline() {
+        assert!(DatabaseBaseline::from_yaml(&create_yaml("sep: '|'\n")).is_none());
+        let b = DatabaseBaseline::from_yaml(&create_yaml(
+            "baseline:\n  recovery_model: full\n  auto_close: yes\n",
+        ))
+        .unwrap();
+        assert_eq!(b.recovery_model(), Some("FULL"));
+        assert_eq!(b.auto_shrink(), Some(false));
+        assert_eq!(b.auto_close(), Some(true));
+        assert_eq!(b.page_verify(), Some("CHECKSUM"));
+        assert_eq!(
+            Section::new(names::DB_OPTION_DRIFT).baseline(),
+            &DatabaseBaseline::default()
+        );
+    }
+
+    #[test]
+    fn test_section_filters_from_yaml() {
+        let s = Sections::from_yaml(
+            &create_yaml(
+                r#"
+sections:
+- jobs:
+    filters:
+      - column: "last_outcome"
+        equals: "succeeded"
+      - column: "bad"
+"#,
+            ),
+            &Sections::default(),
+        )
+        .unwrap();
+        assert_eq!(s.sections()[0].filters().len(), 1);
+        assert_eq!(s.sections()[0].filters()[0].column(), "last_outcome");
+    }
+
+    #[test]
+    fn test_sections_priority() {
+        let s = Sections::from_yaml(
+            &create_yaml(
+                r#"
+sections:
+- jobs:
+    priority: -5
+- instance:
+- backup:
+    priority: 10
+- databases:
+    priority: 10
+"#,
+            ),
+            &Sections::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            s.select(&[SectionKind::Sync, SectionKind::Async])
+                .iter()
+                .map(|s| s.name())
+                .collect::<Vec<&str>>(),
+            ["backup", "databases", "instance", "jobs"]
+        );
+    }
+
+    #[test]
+    fn test_procedure_from_yaml() {
+        let make = |source: &str| Procedure::from_yaml(&create_yaml(source));
+        let p = make(
+            r#"
+procedure: "[dbo].check]x"
+params:
+  - name: "@days"
+    value: 7
+  - name: name
+    type: string
+    value: 12
+  - name: ratio
+    type: float
+    value: "0.5"
+  - name: full
+    value: yes
+"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(p.name(), "dbo.check]x");
+        assert_eq!(
+            p.statement(),
+            "EXEC [dbo].[check]]x] @days = @P1, @name = @P2, @ratio = @P3, @full = @P4"
+        );
+        assert_eq!(
+            p.params()
+                .iter()
+                .map(|p| p.value().clone())
+                .collect::<Vec<_>>(),
+            [
+                ParamValue::Int(7),
+                ParamValue::Str("12".to_string()),
+                ParamValue::Float(0.5),
+                ParamValue::Str("yes".to_string()),
+            ]
+        );
+        assert_eq!(
+            make(
+                "procedure: sp_who
+"
+            )
+            .unwrap()
+            .unwrap()
+            .statement(),
+            "EXEC [sp_who]"
+        );
+        assert!(make("sep: x\n").unwrap().is_none());
+        assert!(make("procedure: a..b\n").is_err());
+        let bad_param = |param: &str| make(&format!("procedure: a\nparams:\n  - {param}\n"));
+        assert!(bad_param("name: \"x; y\"\n    value: 1").is_err());
+        assert!(bad_param("name: x\n    type: int\n    value: z").is_err());
+        assert!(bad_param("name: x\n    type: date\n    value: 1").is_err());
+        assert!(bad_param("name: x").is_err());
+    }
+
+    #[test]
+    fn test_section_compute_rates_from_yaml() {
+        let s = Sections::from_yaml(
+            &create_yaml(
+                r#"
+sections:
+- counters:
+    compute_rates: yes
+- jobs:
+"#,
+            ),
+            &Sections::default(),
+        )
+        .unwrap();
+        assert!(s.sections()[0].compute_rates());
+        assert!(!s.sections()[1].compute_rates());
+    }
+
     #[test]
     fn test_sections_from_yaml_default() {
         let s = Sections::from_yaml(&create_sections_yaml_default(), &Sections::default()).unwrap();
@@ -388,6 +1531,44 @@ sections:
         );
     }
 
+    #[test]
+    fn test_profile() {
+        assert!(Profile::try_from("bad").is_err());
+        assert_eq!(Profile::try_from("Full ").unwrap(), Profile::Full);
+        let from = |source: &str| Sections::from_yaml(&create_yaml(source), &Sections::default());
+        let names = |s: &Sections| {
+            s.sections()
+                .iter()
+                .map(|s| s.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let minimal = from("profile: minimal\n").unwrap();
+        assert_eq!(names(&minimal), MINIMAL_PROFILE_SECTIONS);
+        assert_eq!(minimal.cache_age(), defaults::SECTIONS_CACHE_AGE);
+        assert_eq!(
+            minimal.select(&[SectionKind::Async])[0].name(),
+            names::BACKUP
+        );
+
+        let standard = from("profile: standard\n").unwrap();
+        assert_eq!(names(&standard), names(&Sections::default()));
+
+        let full = from("profile: full\n").unwrap();
+        assert_eq!(full.cache_age(), defaults::FULL_PROFILE_CACHE_AGE);
+        assert!(names(&full).contains(&names::CHECKDB.to_string()));
+        assert!(!names(&full).contains(&names::SECTION_STATUS.to_string()));
+        assert!(!names(&full).contains(&names::PERMISSION_HINTS.to_string()));
+        assert_eq!(full.sections().len(), get_known_section_names().len());
+
+        let overridden = from("profile: full\ncache_age: 100\nsections:\n  - instance:\n").unwrap();
+        assert_eq!(names(&overridden), [names::INSTANCE]);
+        assert_eq!(overridden.cache_age(), 100);
+
+        let bad = from("profile: huge\n").unwrap();
+        assert_eq!(names(&bad), names(&Sections::default()));
+    }
+
     fn create_sections_yaml_default() -> Yaml {
         const SOURCE: &str = r#"
 sections:
@@ -404,7 +1585,91 @@ _nothing: "nothing"
         assert_eq!(get_default_separator("jobs"), '\t');
         assert_eq!(get_default_separator("mirroring"), '\t');
         assert_eq!(get_default_separator("availability_groups"), '\t');
+        assert_eq!(get_default_separator("traceflags"), '\t');
+        assert_eq!(get_default_separator("encryption"), '\t');
+        assert_eq!(get_default_separator("schedulers"), '\t');
+        assert_eq!(get_default_separator("sessions"), '\t');
+        assert_eq!(get_default_separator("filestream"), '\t');
+        assert_eq!(get_default_separator("uptime"), '\t');
+        assert_eq!(get_default_separator("version_store"), '\t');
+        assert_eq!(get_default_separator("memory_features"), '\t');
+        assert_eq!(get_default_separator("distributed_transactions"), '\t');
+        assert_eq!(get_default_separator("collation"), '\t');
+        assert_eq!(get_default_separator("agent_health"), '\t');
+        assert_eq!(get_default_separator("certs"), '\t');
+        assert_eq!(get_default_separator("query_store"), '\t');
+        assert_eq!(get_default_separator("logins"), '\t');
+        assert_eq!(get_default_separator("ag_config"), '\t');
+        assert_eq!(get_default_separator("host_settings"), '\t');
+        assert_eq!(get_default_separator("volumes"), '\t');
+        assert_eq!(get_default_separator("default_trace"), '\t');
+        assert_eq!(get_default_separator("orphaned_files"), '\t');
+        assert_eq!(get_default_separator("maintenance"), '\t');
+        assert_eq!(get_default_separator("audits"), '\t');
+        assert_eq!(get_default_separator("azure_resources"), '\t');
         assert_eq!(get_default_separator("instance"), '|');
+        assert_eq!(get_default_separator("perf_key_metrics"), '|');
+        assert_eq!(get_default_separator("waits_delta"), '|');
+        assert_eq!(get_default_separator("db_connections"), '|');
+        assert_eq!(get_default_separator("tempdb_contention"), '|');
+        assert_eq!(get_default_separator("db_option_drift"), '|');
+        assert_eq!(get_default_separator("labels"), '\0');
+        assert_eq!(get_default_separator("checkdb"), '|');
+        assert_eq!(get_default_separator("backup_url"), '|');
+        assert_eq!(get_default_separator("permissions"), '|');
+        assert_eq!(get_default_separator("filegroups"), '|');
+        assert_eq!(get_default_separator("constraints"), '|');
+        assert_eq!(get_default_separator("top_tables"), '|');
+        assert_eq!(get_default_separator("section_status"), '|');
+        assert_eq!(get_default_separator("permission_hints"), '|');
+        assert!(!get_known_section_names().contains(&names::SECTION_STATUS));
+        assert!(!get_known_section_names().contains(&names::PERMISSION_HINTS));
+    }
+
+    #[test]
+    fn test_host_heavy_sections() {
+        assert!(is_host_heavy_section(names::COUNTERS));
+        assert!(is_host_heavy_section(names::PERF_KEY_METRICS));
+        assert!(!is_host_heavy_section(names::INSTANCE));
+    }
+
+    #[test]
+    fn test_sampling_sections() {
+        assert!(is_sampling_section(names::PERF_KEY_METRICS));
+        assert!(is_sampling_section(names::WAITS_DELTA));
+        assert!(is_sampling_section(names::DB_CONNECTIONS));
+        assert!(is_sampling_section(names::TEMPDB_CONTENTION));
+        assert!(!is_sampling_section(names::COUNTERS));
+    }
+
+    #[test]
+    fn test_optional_async_sections() {
+        assert_eq!(Section::new(names::PERMISSIONS).kind(), SectionKind::Async);
+        assert_eq!(Section::new(names::FILEGROUPS).kind(), SectionKind::Async);
+        assert_eq!(Section::new(names::CONSTRAINTS).kind(), SectionKind::Async);
+        assert_eq!(Section::new(names::TOP_TABLES).kind(), SectionKind::Async);
+        assert_eq!(Section::new(names::CHECKDB).kind(), SectionKind::Async);
+        assert_eq!(Section::new(names::FILESTREAM).kind(), SectionKind::Async);
+        assert_eq!(Section::new(names::BACKUP_URL).kind(), SectionKind::Async);
+        assert_eq!(Section::new(names::LOGINS).kind(), SectionKind::Async);
+        assert_eq!(Section::new(names::AG_CONFIG).kind(), SectionKind::Async);
+        assert_eq!(
+            Section::new(names::DEFAULT_TRACE).kind(),
+            SectionKind::Async
+        );
+        assert_eq!(
+            Section::new(names::ORPHANED_FILES).kind(),
+            SectionKind::Async
+        );
+        assert_eq!(Section::new(names::MAINTENANCE).kind(), SectionKind::Async);
+        assert_eq!(
+            Section::new(names::DB_OPTION_DRIFT).kind(),
+            SectionKind::Async
+        );
+        assert_eq!(
+            Section::new(names::PERF_KEY_METRICS).kind(),
+            SectionKind::Sync
+        );
     }
     #[test]
     fn test_get_no_first_line() {
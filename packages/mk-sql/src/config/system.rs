@@ -8,7 +8,7 @@ use anyhow::Result;
 use std::path::Path;
 use yaml_rust2::YamlLoader;
 
-mod keys {
+pub mod keys {
     pub const SYSTEM: &str = "system";
     pub const LOGGING: &str = "logging";
 
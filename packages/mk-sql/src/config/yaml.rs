@@ -136,8 +136,12 @@ impl Get for Yaml {
 }
 
 pub fn load_from_file(file_name: &Path) -> Result<Vec<Yaml>> {
+    load_from_str(&read_config_file(file_name)?)
+}
+
+pub fn read_config_file(file_name: &Path) -> Result<String> {
     match read_file(file_name) {
-        Ok(content) => load_from_str(&content),
+        Ok(content) => Ok(content),
         Err(e) => anyhow::bail!(
             "Can't read config file: {}, {e} ",
             // Use relatively complicated  method to print name of the file
@@ -148,11 +152,11 @@ pub fn load_from_file(file_name: &Path) -> Result<Vec<Yaml>> {
     }
 }
 
-fn load_from_str(content: &str) -> Result<Vec<Yaml>> {
+pub fn load_from_str(content: &str) -> Result<Vec<Yaml>> {
     Ok(YamlLoader::load_from_str(content)?)
 }
 
-fn to_bool(value: &str) -> Result<bool> {
+pub fn to_bool(value: &str) -> Result<bool> {
     match value.to_lowercase().as_ref() {
         "yes" | "true" => Ok(true),
         "no" | "false" => Ok(false),
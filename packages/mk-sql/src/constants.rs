@@ -3,6 +3,8 @@
 // conditions defined in the file COPYING, which is part of this source code package.
 
 pub const VERSION: &str = "2.4.0b1";
+/// incremented on incompatible changes of the config file
+pub const CONFIG_SCHEMA_VERSION: u32 = 1;
 use crate::types::HostName;
 
 use lazy_static::lazy_static;
@@ -2,17 +2,43 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
+use crate::config::ms_sql::NameEncoding;
 use crate::types::PiggybackHostName;
 
 use super::config::defines::defaults;
 const PREFIX: &str = "mssql";
 
-pub fn header(name: &str, separator: char) -> String {
+/// Name of database, file, counter, etc. as a field of a data line:
+/// whitespace and control characters, e.g. tab or ideographic space, can't break separation
+pub fn name_field(name: &str, encoding: NameEncoding) -> String {
+    let mut field = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_whitespace() || c.is_control() {
+            field.push('_');
+        } else if c.is_ascii() || encoding == NameEncoding::Keep {
+            field.push(c);
+        } else {
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                field.push_str(&format!("_x{unit:04X}_"));
+            }
+        }
+    }
+    field
+}
+
+/// `prefix` is the configured replacement of [`PREFIX`]
+pub fn header(prefix: Option<&str>, name: &str, separator: char) -> String {
+    plain_header(&format!("{}_{name}", prefix.unwrap_or(PREFIX)), separator)
+}
+
+/// header without prefix, e.g. for site-specific static sections
+pub fn plain_header(name: &str, separator: char) -> String {
     if separator == defaults::DEFAULT_SEP {
-        format!("<<<{PREFIX}_{name}>>>\n")
+        format!("<<<{name}>>>\n")
     } else {
         let sep = separator as u8;
-        format!("<<<{PREFIX}_{name}:sep({sep:0>2})>>>\n")
+        format!("<<<{name}:sep({sep:0>2})>>>\n")
     }
 }
 
@@ -29,10 +55,26 @@ mod test {
     use super::*;
     #[test]
     fn test_header() {
-        assert_eq!(header("name", '\n'), "<<<mssql_name:sep(10)>>>\n");
-        assert_eq!(header("name", '\t'), "<<<mssql_name:sep(09)>>>\n");
-        assert_eq!(header("name", '|'), "<<<mssql_name:sep(124)>>>\n");
-        assert_eq!(header("name", ' '), "<<<mssql_name>>>\n");
+        assert_eq!(header(None, "name", '\n'), "<<<mssql_name:sep(10)>>>\n");
+        assert_eq!(header(None, "name", '\t'), "<<<mssql_name:sep(09)>>>\n");
+        assert_eq!(header(None, "name", '|'), "<<<mssql_name:sep(124)>>>\n");
+        assert_eq!(header(None, "name", ' '), "<<<mssql_name>>>\n");
+        assert_eq!(header(Some("v2"), "name", '|'), "<<<v2_name:sep(124)>>>\n");
+        assert_eq!(header(Some("v2"), "name", ' '), "<<<v2_name>>>\n");
+        assert_eq!(plain_header("site", '|'), "<<<site:sep(124)>>>\n");
+    }
+
+    #[test]
+    fn test_name_field() {
+        use NameEncoding::{Escape, Keep};
+        assert_eq!(name_field("my db", Keep), "my_db");
+        assert_eq!(name_field("База\tданных", Keep), "База_данных");
+        assert_eq!(name_field("销售\u{3000}数据", Keep), "销售_数据");
+        assert_eq!(name_field("a\u{a0}b\nc", Keep), "a_b_c");
+        assert_eq!(name_field("Бд 1", Escape), "_x0411__x0434__1");
+        assert_eq!(name_field("销", Escape), "_x9500_");
+        assert_eq!(name_field("😀", Escape), "_xD83D__xDE00_");
+        assert_eq!(name_field("plain_name", Escape), "plain_name");
     }
 
     #[test]
@@ -3,11 +3,15 @@
 // conditions defined in the file COPYING, which is part of this source code package.
 
 pub mod args;
+pub mod capabilities;
 pub mod config;
 pub mod constants;
 pub mod emit;
+pub mod locale;
+pub mod memory;
 pub mod ms_sql;
 pub mod platform;
 pub mod setup;
+pub mod telemetry;
 pub mod types;
 pub mod utils;
@@ -0,0 +1,312 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Conversion of values formatted by the server locale into invariant formats:
+//! numbers use `.` as decimal separator without grouping, dates `YYYY-MM-DD HH:MM:SS`
+//! Only numeric and datetime columns are converted, names and texts are kept as is
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateOrder {
+    Mdy,
+    Dmy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueLocale {
+    decimal_comma: bool,
+    date_order: DateOrder,
+    /// short month names in lower case, the way `sys.syslanguages` lists them
+    months: [&'static str; 12],
+}
+
+const ENGLISH_MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+impl ValueLocale {
+    /// `language` is the name or the alias of SQL Server language
+    pub fn from_language(language: &str) -> Option<Self> {
+        let (decimal_comma, date_order, months) = match language.trim().to_lowercase().as_str() {
+            "us_english" | "english" => (false, DateOrder::Mdy, ENGLISH_MONTHS),
+            "british" | "british english" => (false, DateOrder::Dmy, ENGLISH_MONTHS),
+            "deutsch" | "german" => (
+                true,
+                DateOrder::Dmy,
+                [
+                    "jan", "feb", "mär", "apr", "mai", "jun", "jul", "aug", "sep", "okt", "nov",
+                    "dez",
+                ],
+            ),
+            "français" | "french" => (
+                true,
+                DateOrder::Dmy,
+                [
+                    "janv", "févr", "mars", "avr", "mai", "juin", "juil", "août", "sept", "oct",
+                    "nov", "déc",
+                ],
+            ),
+            "italiano" | "italian" => (
+                true,
+                DateOrder::Dmy,
+                [
+                    "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov",
+                    "dic",
+                ],
+            ),
+            "español" | "spanish" => (
+                true,
+                DateOrder::Dmy,
+                [
+                    "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sep", "oct", "nov",
+                    "dic",
+                ],
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            decimal_comma,
+            date_order,
+            months,
+        })
+    }
+
+    /// invariant form of `value` if it is a number or a date formatted by the locale
+    pub fn normalize(&self, value: &str) -> Option<String> {
+        let value = value.trim();
+        self.normalize_number(value)
+            .or_else(|| self.normalize_date(value))
+    }
+
+    /// `1.234,5` and `1 234,5` for decimal comma locales, `1,234.5` otherwise
+    /// Numbers without decimal comma are left as is for decimal comma locales:
+    /// `1.234` is produced by `cast` on every server
+    fn normalize_number(&self, value: &str) -> Option<String> {
+        let (sign, digits) = match value.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", value),
+        };
+        let (decimal, groups): (char, &[char]) = if self.decimal_comma {
+            (',', &['.', ' ', '\u{a0}', '\u{202f}'])
+        } else {
+            ('.', &[','])
+        };
+        let (integer, fraction) = match digits.split_once(decimal) {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (digits, None),
+        };
+        if fraction.is_some_and(|f| f.is_empty() || !f.chars().all(|c| c.is_ascii_digit())) {
+            return None;
+        }
+        let parts: Vec<&str> = integer.split(|c| groups.contains(&c)).collect();
+        let is_grouped = parts.len() > 1;
+        let is_valid = parts.iter().enumerate().all(|(i, p)| {
+            p.chars().all(|c| c.is_ascii_digit())
+                && if i == 0 {
+                    !p.is_empty() && (!is_grouped || p.len() <= 3)
+                } else {
+                    p.len() == 3
+                }
+        });
+        if !is_valid || (!is_grouped && fraction.is_none()) {
+            return None;
+        }
+        if self.decimal_comma && fraction.is_none() && integer.contains('.') {
+            return None;
+        }
+        let integer = parts.concat();
+        let number = match fraction {
+            Some(fraction) => format!("{sign}{integer}.{fraction}"),
+            None => format!("{sign}{integer}"),
+        };
+        (number != value).then_some(number)
+    }
+
+    /// `15.10.2026 15:04:05`, `10/15/2026 3:04:05 PM` or `Okt 15 2026  3:04PM`(style 0)
+    fn normalize_date(&self, value: &str) -> Option<String> {
+        let (date, time) = match value.find(' ') {
+            Some(pos) if value[..pos].contains(['.', '/', '-']) => {
+                (value[..pos].to_string(), value[pos..].trim())
+            }
+            _ => return self.normalize_style0_date(value),
+        };
+        let parts: Vec<&str> = date.split(['.', '/', '-']).collect();
+        let [first, second, year] = parts.as_slice() else {
+            return None;
+        };
+        let (day, month) = match self.date_order {
+            DateOrder::Dmy => (first, second),
+            DateOrder::Mdy => (second, first),
+        };
+        let date = make_date(year, month.parse().ok()?, day)?;
+        Some(format!("{date} {}", parse_time(time)?))
+    }
+
+    fn normalize_style0_date(&self, value: &str) -> Option<String> {
+        let mut fields = value.split_whitespace();
+        let month = fields.next()?.trim_end_matches('.').to_lowercase();
+        let month = self.months.iter().position(|m| *m == month)? + 1;
+        let day = fields.next()?;
+        let year = fields.next()?;
+        let time = fields.collect::<Vec<&str>>().join(" ");
+        let date = make_date(year, month as u32, day)?;
+        if time.is_empty() {
+            return Some(date);
+        }
+        Some(format!("{date} {}", parse_time(&time)?))
+    }
+}
+
+fn make_date(year: &str, month: u32, day: &str) -> Option<String> {
+    let day: u32 = day.parse().ok()?;
+    if year.len() != 4 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let year: u32 = year.parse().ok()?;
+    Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+/// `15:04:05.123`, `3:04:05 PM` or `3:04PM` into `HH:MM:SS[.fff]`
+fn parse_time(time: &str) -> Option<String> {
+    let upper = time.trim().to_uppercase();
+    let (clock, pm) = match upper
+        .strip_suffix("PM")
+        .map(|t| (t, Some(true)))
+        .or_else(|| upper.strip_suffix("AM").map(|t| (t, Some(false))))
+    {
+        Some((clock, pm)) => (clock.trim(), pm),
+        None => (upper.as_str(), None),
+    };
+    let (clock, fraction) = match clock.split_once(['.', ',']) {
+        Some((clock, fraction)) if fraction.chars().all(|c| c.is_ascii_digit()) => {
+            (clock, Some(fraction))
+        }
+        Some(_) => return None,
+        None => (clock, None),
+    };
+    let mut fields = clock.split(':').map(|f| f.parse::<u32>().ok());
+    let hour = fields.next()??;
+    let minute = fields.next()??;
+    let second = fields.next().unwrap_or(Some(0))?;
+    if fields.next().is_some() || minute > 59 || second > 59 {
+        return None;
+    }
+    let hour = match pm {
+        Some(pm) if (1..=12).contains(&hour) => hour % 12 + if pm { 12 } else { 0 },
+        Some(_) => return None,
+        None if hour < 24 => hour,
+        None => return None,
+    };
+    let time = format!("{hour:02}:{minute:02}:{second:02}");
+    Some(match fraction {
+        Some(fraction) => format!("{time}.{fraction}"),
+        None => time,
+    })
+}
+
+/// value of a numeric column, kept as is if it is not a number formatted by the `locale`
+pub fn to_invariant_number(value: String, locale: Option<&ValueLocale>) -> String {
+    locale
+        .and_then(|l| l.normalize_number(value.trim()))
+        .unwrap_or(value)
+}
+
+/// value of a datetime column, kept as is if it is not a date formatted by the `locale`
+pub fn to_invariant_datetime(value: String, locale: Option<&ValueLocale>) -> String {
+    locale
+        .and_then(|l| l.normalize_date(value.trim()))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_german_values() {
+        let locale = ValueLocale::from_language("Deutsch").unwrap();
+        assert_eq!(locale, ValueLocale::from_language("german").unwrap());
+        let n = |v: &str| locale.normalize(v);
+        assert_eq!(n("3,5").as_deref(), Some("3.5"));
+        assert_eq!(n("-1.234.567,25").as_deref(), Some("-1234567.25"));
+        assert_eq!(n("1.234"), None);
+        assert_eq!(n("1.5"), None);
+        assert_eq!(n("42"), None);
+        assert_eq!(n("15.0.2000.5"), None);
+        assert_eq!(n("1,2,3"), None);
+        assert_eq!(
+            n("15.10.2026 15:04:05").as_deref(),
+            Some("2026-10-15 15:04:05")
+        );
+        assert_eq!(
+            n("01.02.2026 07:08:09,123").as_deref(),
+            Some("2026-02-01 07:08:09.123")
+        );
+        assert_eq!(
+            n("Okt 15 2026  3:04PM").as_deref(),
+            Some("2026-10-15 15:04:00")
+        );
+        assert_eq!(
+            n("Mär  1 2026 12:00AM").as_deref(),
+            Some("2026-03-01 00:00:00")
+        );
+        assert_eq!(n("2026-10-15 15:04:05"), None);
+        assert_eq!(n("Sales DB"), None);
+    }
+
+    #[test]
+    fn test_french_values() {
+        let locale = ValueLocale::from_language(" Français ").unwrap();
+        let n = |v: &str| locale.normalize(v);
+        assert_eq!(n("1 234,56").as_deref(), Some("1234.56"));
+        assert_eq!(n("1\u{202f}234\u{202f}567").as_deref(), Some("1234567"));
+        assert_eq!(n("0,001").as_deref(), Some("0.001"));
+        assert_eq!(
+            n("15/10/2026 15:04:05").as_deref(),
+            Some("2026-10-15 15:04:05")
+        );
+        assert_eq!(
+            n("oct 15 2026  3:04PM").as_deref(),
+            Some("2026-10-15 15:04:00")
+        );
+        assert_eq!(n("févr 3 2026").as_deref(), Some("2026-02-03"));
+        assert_eq!(n("32/10/2026 15:04:05"), None);
+    }
+
+    #[test]
+    fn test_english_values() {
+        let locale = ValueLocale::from_language("us_english").unwrap();
+        let n = |v: &str| locale.normalize(v);
+        assert_eq!(n("1,234.5").as_deref(), Some("1234.5"));
+        assert_eq!(n("1.5"), None);
+        assert_eq!(n("12,34"), None);
+        assert_eq!(
+            n("10/15/2026 3:04:05 PM").as_deref(),
+            Some("2026-10-15 15:04:05")
+        );
+        assert_eq!(
+            n("Oct 15 2026 12:30AM").as_deref(),
+            Some("2026-10-15 00:30:00")
+        );
+        assert!(ValueLocale::from_language("Klingon").is_none());
+    }
+
+    #[test]
+    fn test_to_invariant() {
+        let locale = ValueLocale::from_language("Deutsch").unwrap();
+        let number = |v: &str, l| to_invariant_number(v.to_string(), l);
+        let datetime = |v: &str, l| to_invariant_datetime(v.to_string(), l);
+        assert_eq!(number("3,5", Some(&locale)), "3.5");
+        assert_eq!(number("3,5", None), "3,5");
+        assert_eq!(
+            number("01.02.2024 10:00:00", Some(&locale)),
+            "01.02.2024 10:00:00"
+        );
+        assert_eq!(
+            datetime("01.02.2024 10:00:00", Some(&locale)),
+            "2024-02-01 10:00:00"
+        );
+        assert_eq!(datetime("1,234", Some(&locale)), "1,234");
+        assert_eq!(datetime("01.02.2024 10:00:00", None), "01.02.2024 10:00:00");
+    }
+}
@@ -1,26 +1,88 @@
 // Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
-use mk_sql::setup;
+use mk_sql::config::CheckConfig;
+use mk_sql::setup::{self, Action, Env};
+use std::io::Write;
+use tokio::io::AsyncWriteExt;
 
 #[tokio::main]
 async fn main() {
-    let result = setup::init(std::env::args_os());
-    if let Ok((config, environment)) = result {
-        match config.exec(&environment).await {
-            Ok(output) => {
-                print!("{output}");
-                log::info!("Success");
-                std::process::exit(0);
-            }
-            Err(e) => {
-                display_and_log(e);
-                std::process::exit(1);
-            }
+    match setup::init(std::env::args_os()) {
+        Ok(Action::Check(config, environment)) => check(config, environment).await,
+        Ok(Action::Print { output, success }) => {
+            print!("{output}");
+            let _ = std::io::stdout().flush();
+            std::process::exit(if success { 0 } else { 1 });
+        }
+        Err(e) => {
+            display_and_log(&e);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn check(config: CheckConfig, environment: Env) -> ! {
+    let mut stdout = tokio::io::stdout();
+    // on signal the future is dropped: in-flight queries are cancelled with their connections
+    let outcome = tokio::select! {
+        r = config.exec_to(&environment, &mut stdout) => Some(r),
+        signal = wait_for_stop_signal() => {
+            log::warn!("Stopped by {signal}, output is partial");
+            None
+        }
+    };
+    // data of already processed instances must reach the agent
+    let _ = stdout.flush().await;
+    match outcome {
+        Some(Ok(())) => {
+            log::info!("Success");
+            std::process::exit(0);
+        }
+        Some(Err(e)) => {
+            display_and_log(e);
+            std::process::exit(1);
+        }
+        None => std::process::exit(1),
+    }
+}
+
+/// Returns the name of the signal requesting stop
+/// SIGHUP doesn't stop: the config is read anew on every run, nothing to reload
+#[cfg(unix)]
+async fn wait_for_stop_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+    let (Ok(mut terminate), Ok(mut hangup)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::hangup()),
+    ) else {
+        log::error!("Failed to install signal handlers");
+        let _ = tokio::signal::ctrl_c().await;
+        return "CTRL+C";
+    };
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => return "CTRL+C",
+            _ = terminate.recv() => return "SIGTERM",
+            _ = hangup.recv() => log::info!("SIGHUP ignored, the config is read on every run"),
+        }
+    }
+}
+
+/// Returns the name of the signal requesting stop
+#[cfg(windows)]
+async fn wait_for_stop_signal() -> &'static str {
+    use tokio::signal::windows::ctrl_close;
+    match ctrl_close() {
+        Ok(mut close) => tokio::select! {
+            _ = tokio::signal::ctrl_c() => "CTRL+C",
+            _ = close.recv() => "CTRL+CLOSE",
+        },
+        Err(e) => {
+            log::error!("Failed to install CTRL+CLOSE handler: {e}");
+            let _ = tokio::signal::ctrl_c().await;
+            "CTRL+C"
         }
-    } else {
-        display_and_log(&result.err().unwrap());
-        std::process::exit(1);
     }
 }
 
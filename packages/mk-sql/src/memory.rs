@@ -0,0 +1,135 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Optional memory budget of a section
+
+use std::cell::Cell;
+use std::future::Future;
+
+pub const BUDGET_EXCEEDED: &str = "Memory budget exceeded";
+
+tokio::task_local! {
+    /// budget of the section run within [`track_budget`] and bytes used by it
+    static SECTION_BUDGET: SectionBudget;
+}
+
+struct SectionBudget {
+    budget: usize,
+    used: Cell<usize>,
+}
+
+/// Runs `f` with its own memory budget, None - no budget
+/// Concurrent sections don't share the budget: every one is accounted apart
+pub async fn track_budget<F: Future>(budget: Option<usize>, f: F) -> F::Output {
+    match budget {
+        Some(budget) => {
+            SECTION_BUDGET
+                .scope(
+                    SectionBudget {
+                        budget,
+                        used: Cell::new(0),
+                    },
+                    f,
+                )
+                .await
+        }
+        None => f.await,
+    }
+}
+
+/// Adds `bytes` to the budget of the current section
+/// Returns false if the budget is exceeded, the bytes should be dropped then
+pub fn account(bytes: usize) -> bool {
+    SECTION_BUDGET
+        .try_with(|b| {
+            let used = b.used.get().saturating_add(bytes);
+            if is_over(used, b.budget) {
+                return false;
+            }
+            b.used.set(used);
+            true
+        })
+        .unwrap_or(true)
+}
+
+/// Cuts the text by whole lines to fit into the budget of the current section
+/// Returns the text and whether it has been truncated
+pub fn truncate_to_budget(text: String) -> (String, bool) {
+    match SECTION_BUDGET.try_with(|b| b.budget) {
+        Ok(budget) => {
+            let size = text.len();
+            to_truncated(text, size, budget)
+        }
+        Err(_) => (text, false),
+    }
+}
+
+fn is_over(allocated: usize, budget: usize) -> bool {
+    budget != 0 && allocated > budget
+}
+
+fn to_truncated(mut text: String, allocated: usize, budget: usize) -> (String, bool) {
+    if !is_over(allocated, budget) {
+        return (text, false);
+    }
+    let keep = text.len().saturating_sub(allocated - budget);
+    let keep = (0..=keep)
+        .rev()
+        .find(|i| text.is_char_boundary(*i))
+        .unwrap_or(0);
+    let end = text[..keep].rfind('\n').map(|p| p + 1).unwrap_or(0);
+    text.truncate(end);
+    (text, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_truncated() {
+        let text = || "a|1\nb|2\nc|3\n".to_string();
+        assert_eq!(to_truncated(text(), 1000, 0), (text(), false));
+        assert_eq!(to_truncated(text(), 1000, 1000), (text(), false));
+        assert_eq!(
+            to_truncated(text(), 1003, 1000),
+            ("a|1\nb|2\n".to_string(), true)
+        );
+        assert_eq!(
+            to_truncated(text(), 1005, 1000),
+            ("a|1\n".to_string(), true)
+        );
+        assert_eq!(to_truncated(text(), 2000, 1000), (String::new(), true));
+        assert_eq!(
+            to_truncated("ä|1\nö|2\n".to_string(), 1002, 1000),
+            ("ä|1\n".to_string(), true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_track_budget() {
+        assert!(account(1000));
+        assert_eq!(
+            truncate_to_budget("a|1\n".to_string()),
+            ("a|1\n".to_string(), false)
+        );
+        track_budget(Some(10), async {
+            assert!(account(6));
+            assert!(!account(6));
+            assert!(account(4));
+            assert!(!account(1));
+            assert_eq!(
+                truncate_to_budget("a|1\nb|2\nc|3\n".to_string()),
+                ("a|1\nb|2\n".to_string(), true)
+            );
+        })
+        .await;
+        let (a, b) = futures::join!(
+            track_budget(Some(10), async { account(8) }),
+            track_budget(Some(10), async { account(8) })
+        );
+        assert!(a && b);
+        track_budget(None, async { assert!(account(usize::MAX)) }).await;
+    }
+}
@@ -2,10 +2,18 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
+pub mod browser;
+pub mod cache;
 pub mod client;
+pub mod container;
 pub mod custom;
 pub mod defaults;
+pub mod fault;
+pub mod generator;
 pub mod instance;
+pub mod kerberos;
+pub mod permission;
+pub mod prelogin;
 pub mod query;
 pub mod section;
 pub mod sqls;
@@ -0,0 +1,328 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Network scan for SQL Server instances using SQL Browser (SSRP, UDP 1434)
+
+use super::defaults;
+use crate::emit;
+use crate::utils::prepare_error;
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+pub const SECTION_NAME: &str = "browser_scan";
+const SEP: char = '|';
+
+/// CLNT_UCAST_EX: request for all instances on the host
+const CLNT_UCAST_EX: u8 = 0x03;
+/// CLNT_UCAST_INST: request for the given instance on the host
+const CLNT_UCAST_INST: u8 = 0x04;
+/// SVR_RESP: header byte of the answer
+const SVR_RESP: u8 = 0x05;
+/// no more than 4096 addresses per range, i.e. /20
+const MAX_RANGE_PREFIX: u8 = 20;
+const MAX_PARALLEL_PROBES: usize = 64;
+
+/// `host:port` of SQL Browsers which didn't answer, owned by the discovery: browsers
+/// are not asked again while it lasts
+#[derive(Debug, Default)]
+pub struct SilentBrowsers(Mutex<HashSet<String>>);
+
+impl SilentBrowsers {
+    fn contains(&self, key: &str) -> bool {
+        self.0.lock().is_ok_and(|s| s.contains(key))
+    }
+
+    fn insert(&self, key: String) {
+        if let Ok(mut silent) = self.0.lock() {
+            silent.insert(key);
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct BrowsedInstance {
+    pub host: String,
+    pub server_name: String,
+    pub instance_name: String,
+    pub is_clustered: String,
+    pub version: String,
+    pub tcp_port: String,
+}
+
+impl BrowsedInstance {
+    fn to_entry(&self) -> String {
+        format!(
+            "{}{SEP}{}{SEP}{}{SEP}{}{SEP}{}{SEP}{}\n",
+            self.host,
+            self.server_name,
+            self.instance_name,
+            self.version,
+            self.is_clustered,
+            self.tcp_port
+        )
+    }
+}
+
+/// Converts list of hosts and IPv4 CIDR ranges into list of hosts
+/// Network and broadcast addresses of ranges are skipped
+pub fn expand_targets(targets: &[String]) -> Result<Vec<String>> {
+    let mut hosts: Vec<String> = Vec::new();
+    for target in targets.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        let expanded = match target.split_once('/') {
+            Some((addr, prefix)) => expand_cidr(addr, prefix)?,
+            None => vec![target.to_string()],
+        };
+        for host in expanded {
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+    }
+    Ok(hosts)
+}
+
+fn expand_cidr(addr: &str, prefix: &str) -> Result<Vec<String>> {
+    let addr: Ipv4Addr = addr
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Bad scan address '{}': {}", addr, e))?;
+    let prefix: u8 = prefix
+        .trim()
+        .parse()
+        .ok()
+        .filter(|p| *p <= 32)
+        .ok_or_else(|| anyhow::anyhow!("Bad scan prefix '{}'", prefix))?;
+    if prefix < MAX_RANGE_PREFIX {
+        anyhow::bail!(
+            "Scan range {}/{} is too large, /{} is the limit",
+            addr,
+            prefix,
+            MAX_RANGE_PREFIX
+        );
+    }
+    let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+    let network = u32::from(addr) & mask;
+    let broadcast = network | !mask;
+    let (first, last) = if prefix >= 31 {
+        (network, broadcast)
+    } else {
+        (network + 1, broadcast - 1)
+    };
+    Ok((first..=last)
+        .map(|ip| Ipv4Addr::from(ip).to_string())
+        .collect())
+}
+
+/// Parses SVR_RESP: `0x05 <u16 size> ServerName;S;InstanceName;I;IsClustered;No;Version;V;tcp;1433;;...`
+pub fn parse_response(host: &str, data: &[u8]) -> Vec<BrowsedInstance> {
+    if data.len() < 3 || data[0] != SVR_RESP {
+        return vec![];
+    }
+    let size = u16::from_le_bytes([data[1], data[2]]) as usize;
+    let payload = &data[3..data.len().min(3 + size)];
+    String::from_utf8_lossy(payload)
+        .split(";;")
+        .filter_map(|record| parse_record(host, record))
+        .collect()
+}
+
+fn parse_record(host: &str, record: &str) -> Option<BrowsedInstance> {
+    let fields: Vec<&str> = record.split(';').collect();
+    let value = |key: &str| {
+        fields
+            .chunks(2)
+            .find(|kv| kv[0].eq_ignore_ascii_case(key))
+            .and_then(|kv| kv.get(1))
+            .map(|v| v.to_string())
+            .unwrap_or_default()
+    };
+    let instance_name = value("InstanceName");
+    if instance_name.is_empty() {
+        return None;
+    }
+    Some(BrowsedInstance {
+        host: host.to_string(),
+        server_name: value("ServerName"),
+        instance_name,
+        is_clustered: value("IsClustered"),
+        version: value("Version"),
+        tcp_port: value("tcp"),
+    })
+}
+
+/// Asks SQL Browser on the `host` for all instances
+pub async fn probe(host: &str, port: u16, timeout: Duration) -> Result<Vec<BrowsedInstance>> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect((host, port)).await?;
+    socket.send(&[CLNT_UCAST_EX]).await?;
+    let mut buf = vec![0u8; 65535 + 3];
+    let received = tokio::time::timeout(timeout, socket.recv(&mut buf))
+        .await
+        .map_err(|_| anyhow::anyhow!("No answer from SQL Browser on {}", host))??;
+    Ok(parse_response(host, &buf[..received]))
+}
+
+/// Asks SQL Browser on the `host` for the TCP port of the instance
+pub async fn resolve_port(
+    host: &str,
+    browser_port: Option<u16>,
+    instance: &str,
+    timeout: Duration,
+    silent: &SilentBrowsers,
+) -> Result<u16> {
+    let browser_port = browser_port.unwrap_or(defaults::SQL_BROWSER_PORT);
+    let key = format!("{}:{}", host.to_lowercase(), browser_port);
+    if silent.contains(&key) {
+        anyhow::bail!("SQL Browser on {} didn't answer before", host);
+    }
+    let received = ask_instance(host, browser_port, instance, timeout).await;
+    if received.is_err() {
+        silent.insert(key);
+    }
+    to_instance_port(host, &received?, instance)
+}
+
+async fn ask_instance(
+    host: &str,
+    browser_port: u16,
+    instance: &str,
+    timeout: Duration,
+) -> Result<Vec<u8>> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect((host, browser_port)).await?;
+    socket
+        .send(&[&[CLNT_UCAST_INST], instance.as_bytes()].concat())
+        .await?;
+    let mut buf = vec![0u8; 4096];
+    let received = tokio::time::timeout(timeout, socket.recv(&mut buf))
+        .await
+        .map_err(|_| anyhow::anyhow!("No answer from SQL Browser on {}", host))??;
+    buf.truncate(received);
+    Ok(buf)
+}
+
+fn to_instance_port(host: &str, data: &[u8], instance: &str) -> Result<u16> {
+    parse_response(host, data)
+        .into_iter()
+        .find(|i| i.instance_name.eq_ignore_ascii_case(instance))
+        .and_then(|i| i.tcp_port.parse::<u16>().ok())
+        .ok_or_else(|| anyhow::anyhow!("No TCP port of {} from SQL Browser on {}", instance, host))
+}
+
+/// Probes all targets in parallel, hosts without SQL Browser are ignored
+pub async fn scan(targets: &[String], timeout: Duration) -> Result<Vec<BrowsedInstance>> {
+    let hosts = expand_targets(targets)?;
+    log::info!("Scanning {} hosts for SQL Browser", hosts.len());
+    let found = stream::iter(hosts)
+        .map(|host| async move {
+            match probe(&host, defaults::SQL_BROWSER_PORT, timeout).await {
+                Ok(instances) => instances,
+                Err(e) => {
+                    log::debug!("Scan of {} failed: {}", host, e);
+                    vec![]
+                }
+            }
+        })
+        .buffered(MAX_PARALLEL_PROBES)
+        .collect::<Vec<_>>()
+        .await;
+    Ok(found.into_iter().flatten().collect())
+}
+
+/// Discovery only section, always in the main(no piggyback) output
+pub async fn generate_scan_section(
+    targets: &[String],
+    timeout: Duration,
+    prefix: Option<&str>,
+) -> String {
+    let body = match scan(targets, timeout).await {
+        Ok(instances) => instances.iter().map(|i| i.to_entry()).collect::<String>(),
+        Err(e) => format!("{}\n", prepare_error(&e)),
+    };
+    emit::header(prefix, SECTION_NAME, SEP) + &body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_targets() {
+        let targets = [
+            "sql1".to_string(),
+            " 10.0.0.0/30 ".to_string(),
+            "10.0.0.1".to_string(),
+            "10.0.1.7/32".to_string(),
+            "".to_string(),
+        ];
+        assert_eq!(
+            expand_targets(&targets).unwrap(),
+            vec!["sql1", "10.0.0.1", "10.0.0.2", "10.0.1.7"]
+        );
+        assert_eq!(
+            expand_targets(&["10.0.0.5/31".to_string()]).unwrap(),
+            vec!["10.0.0.4", "10.0.0.5"]
+        );
+        assert!(expand_targets(&["10.0.0.0/8".to_string()]).is_err());
+        assert!(expand_targets(&["10.0.0.0/33".to_string()]).is_err());
+        assert!(expand_targets(&["host/24".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let payload = "ServerName;SRV;InstanceName;MSSQLSERVER;IsClustered;No;Version;16.0.1000.6;tcp;1433;;\
+ServerName;SRV;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;np;\\\\SRV\\pipe\\sql\\query;;";
+        let mut data = vec![SVR_RESP];
+        data.extend((payload.len() as u16).to_le_bytes());
+        data.extend(payload.as_bytes());
+        let instances = parse_response("h", &data);
+        assert_eq!(instances.len(), 2);
+        assert_eq!(
+            instances[0].to_entry(),
+            "h|SRV|MSSQLSERVER|16.0.1000.6|No|1433\n"
+        );
+        assert_eq!(instances[1].tcp_port, "");
+        assert!(parse_response("h", &[0x04, 0, 0]).is_empty());
+        assert!(parse_response("h", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_to_instance_port() {
+        let payload =
+            "ServerName;SRV;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;50123;;";
+        let mut data = vec![SVR_RESP];
+        data.extend((payload.len() as u16).to_le_bytes());
+        data.extend(payload.as_bytes());
+        assert_eq!(to_instance_port("h", &data, "sqlexpress").unwrap(), 50123);
+        assert!(to_instance_port("h", &data, "OTHER").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_port_silent_browser() {
+        let timeout = Duration::from_millis(50);
+        let silent = SilentBrowsers::default();
+        assert!(resolve_port("127.0.0.1", Some(9), "X", timeout, &silent)
+            .await
+            .is_err());
+        let err = resolve_port("127.0.0.1", Some(9), "Y", timeout, &silent)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("didn't answer before"));
+        let err = resolve_port(
+            "127.0.0.1",
+            Some(9),
+            "Y",
+            timeout,
+            &SilentBrowsers::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(!err.to_string().contains("didn't answer before"));
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Cache of section bodies between runs
+//! Time and storage are behind traits to make the cache logic testable and
+//! to allow storages other than files
+
+use crate::utils;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Source of the current UTC time in seconds
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> Result<u64>;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Result<u64> {
+        utils::get_utc_now()
+    }
+}
+
+/// Storage of cache entries by key
+pub trait CacheStore: Send + Sync + fmt::Debug {
+    /// UTC time in seconds of the last write of the entry
+    fn modified(&self, key: &str) -> Result<u64>;
+    fn read(&self, key: &str) -> Result<String>;
+    fn write(&self, key: &str, body: &str) -> Result<()>;
+}
+
+/// Every entry is a file in the `dir`
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl CacheStore for FileStore {
+    fn modified(&self, key: &str) -> Result<u64> {
+        utils::get_modified_utc_time(self.dir.join(key))
+    }
+    fn read(&self, key: &str) -> Result<String> {
+        Ok(std::fs::read_to_string(self.dir.join(key))?)
+    }
+    fn write(&self, key: &str, body: &str) -> Result<()> {
+        Ok(std::fs::write(self.dir.join(key), body)?)
+    }
+}
+
+/// Entries are kept in memory, modification time is taken from `clock`
+#[derive(Debug)]
+pub struct MemoryStore {
+    clock: Arc<dyn Clock>,
+    entries: Mutex<HashMap<String, (u64, String)>>,
+}
+
+impl MemoryStore {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Result<(u64, String)> {
+        self.entries
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{e}"))?
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No cache entry `{key}`"))
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock))
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn modified(&self, key: &str) -> Result<u64> {
+        self.get(key).map(|(modified, _)| modified)
+    }
+    fn read(&self, key: &str) -> Result<String> {
+        self.get(key).map(|(_, body)| body)
+    }
+    fn write(&self, key: &str, body: &str) -> Result<()> {
+        let now = self.clock.now()?;
+        self.entries
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{e}"))?
+            .insert(key.to_string(), (now, body.to_string()));
+        Ok(())
+    }
+}
+
+/// Age of the entry in seconds, an entry from the future(clock skew) is treated as new
+pub fn to_age(now: u64, modified: u64) -> u64 {
+    now.saturating_sub(modified)
+}
+
+/// returns the body with its age in seconds if the entry is not older than `cache_age`
+pub fn read(
+    store: &dyn CacheStore,
+    clock: &dyn Clock,
+    key: &str,
+    cache_age: u64,
+) -> Option<(String, u64)> {
+    let age = match (store.modified(key), clock.now()) {
+        (Ok(modified), Ok(now)) => to_age(now, modified),
+        _ => return None,
+    };
+    if age > cache_age {
+        return None;
+    }
+    log::info!("Cache entry {key} is new enough for {cache_age} cache_age");
+    store
+        .read(key)
+        .map_err(|e| log::error!("{e} reading cache entry {key}"))
+        .ok()
+        .map(|body| (body, age))
+}
+
+pub fn write(store: &dyn CacheStore, key: &str, body: &str) {
+    store
+        .write(key, body)
+        .unwrap_or_else(|e| log::error!("Error {e} writing cache"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Debug, Default)]
+    struct TestClock(AtomicU64);
+
+    impl TestClock {
+        fn set(&self, now: u64) {
+            self.0.store(now, Ordering::Relaxed);
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> Result<u64> {
+            Ok(self.0.load(Ordering::Relaxed))
+        }
+    }
+
+    #[derive(Debug)]
+    struct DeniedStore;
+
+    impl CacheStore for DeniedStore {
+        fn modified(&self, _key: &str) -> Result<u64> {
+            Ok(0)
+        }
+        fn read(&self, _key: &str) -> Result<String> {
+            anyhow::bail!("Permission denied")
+        }
+        fn write(&self, _key: &str, _body: &str) -> Result<()> {
+            anyhow::bail!("Permission denied")
+        }
+    }
+
+    #[test]
+    fn test_read_ttl() {
+        let clock = Arc::new(TestClock::default());
+        clock.set(1000);
+        let store = MemoryStore::new(clock.clone());
+        assert!(read(&store, clock.as_ref(), "a", 100).is_none());
+        write(&store, "a", "body");
+        clock.set(1100);
+        assert_eq!(
+            read(&store, clock.as_ref(), "a", 100),
+            Some(("body".to_string(), 100))
+        );
+        clock.set(1101);
+        assert!(read(&store, clock.as_ref(), "a", 100).is_none());
+    }
+
+    #[test]
+    fn test_read_clock_skew() {
+        let clock = Arc::new(TestClock::default());
+        clock.set(1000);
+        let store = MemoryStore::new(clock.clone());
+        write(&store, "a", "body");
+        clock.set(900);
+        assert_eq!(
+            read(&store, clock.as_ref(), "a", 0),
+            Some(("body".to_string(), 0))
+        );
+    }
+
+    #[test]
+    fn test_read_denied() {
+        let clock = TestClock::default();
+        write(&DeniedStore, "a", "body");
+        assert!(read(&DeniedStore, &clock, "a", 100).is_none());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path().to_owned());
+        write(&store, "a;b.mssql", "body");
+        assert_eq!(
+            read(&store, &SystemClock, "a;b.mssql", 100).map(|(body, _)| body),
+            Some("body".to_string())
+        );
+        assert!(read(&store, &SystemClock, "missing", 100).is_none());
+    }
+}
@@ -2,9 +2,18 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
-use crate::config::{self, ms_sql::AuthType, ms_sql::Endpoint};
+use crate::config::{
+    self,
+    ms_sql::{AuthType, ConnectOptions, Endpoint},
+};
 use crate::types::{CertPath, HostName, Port};
 use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 #[cfg(windows)]
 use crate::types::InstanceName; // only on windows possible to connect by name
@@ -14,7 +23,7 @@ use tiberius::{AuthMethod, Config};
 use tokio::net::TcpStream;
 use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
 
-use super::defaults;
+use super::{defaults, kerberos, permission, query};
 pub type StdClient = tiberius::Client<Compat<TcpStream>>;
 #[derive(Debug)]
 pub struct OdbcClient {
@@ -37,8 +46,90 @@ impl OdbcClient {
 
 #[derive(Debug)]
 pub enum UniClient {
-    Std(StdClient),
-    Odbc(OdbcClient),
+    Std(StdClient, Pacing, Connectivity),
+    Odbc(OdbcClient, Pacing),
+}
+
+/// How the login reached the server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Connectivity {
+    #[default]
+    Direct,
+    /// gateway or AG listener routed the login to `host:port`
+    Redirected(String),
+}
+
+impl Connectivity {
+    pub fn mode(&self) -> &'static str {
+        match self {
+            Connectivity::Direct => "direct",
+            Connectivity::Redirected(_) => "redirect",
+        }
+    }
+
+    pub fn target(&self) -> &str {
+        match self {
+            Connectivity::Direct => "",
+            Connectivity::Redirected(target) => target,
+        }
+    }
+}
+
+impl UniClient {
+    pub fn with_min_query_gap(mut self, min_gap: Duration) -> Self {
+        *self.pacing() = Pacing::new(min_gap);
+        self
+    }
+
+    pub fn pacing(&mut self) -> &mut Pacing {
+        match self {
+            UniClient::Std(_, pacing, _) | UniClient::Odbc(_, pacing) => pacing,
+        }
+    }
+
+    pub fn connectivity(&self) -> Connectivity {
+        match self {
+            UniClient::Std(_, _, connectivity) => connectivity.clone(),
+            UniClient::Odbc(..) => Connectivity::Direct,
+        }
+    }
+}
+
+/// Keeps the minimal gap between the end of a query and the start of the next one
+#[derive(Debug, Default)]
+pub struct Pacing {
+    min_gap: Duration,
+    last_query_end: Option<Instant>,
+}
+
+impl Pacing {
+    pub fn new(min_gap: Duration) -> Self {
+        Self {
+            min_gap,
+            last_query_end: None,
+        }
+    }
+
+    pub fn calc_wait(&self, now: Instant) -> Duration {
+        self.last_query_end
+            .map(|end| {
+                self.min_gap
+                    .saturating_sub(now.saturating_duration_since(end))
+            })
+            .unwrap_or_default()
+    }
+
+    pub async fn wait(&self) {
+        let wait = self.calc_wait(Instant::now());
+        if !wait.is_zero() {
+            log::debug!("Waiting {} ms before the next query", wait.as_millis());
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub fn mark_query_end(&mut self) {
+        self.last_query_end = Some(Instant::now());
+    }
 }
 
 pub struct RemoteConnection<'a> {
@@ -70,6 +161,8 @@ pub struct NamedConnection {
 
 enum ClientConnection<'a> {
     Remote(RemoteConnection<'a>),
+    /// ADO connection string with substituted credentials
+    Raw(String),
     #[cfg(windows)]
     Named(NamedConnection),
     #[cfg(windows)]
@@ -82,6 +175,7 @@ pub struct ClientBuilder<'a> {
     database: Option<String>,
     certificate: Option<CertPath>,
     trust_server_certificate: bool,
+    language: Option<String>,
 }
 
 impl<'a> Default for ClientBuilder<'a> {
@@ -91,6 +185,7 @@ impl<'a> Default for ClientBuilder<'a> {
             database: None,
             certificate: None,
             trust_server_certificate: config::defines::defaults::TRUST_SERVER_CERTIFICATE,
+            language: None,
         }
     }
 }
@@ -164,6 +259,20 @@ impl<'a> ClientBuilder<'a> {
         self
     }
 
+    /// connection string is used as is, only credentials are substituted
+    pub fn logon_with_connection_string(
+        mut self,
+        connection_string: &str,
+        credentials: Option<Credentials<'a>>,
+    ) -> Self {
+        log::info!("Logon with connection string");
+        self.client_connection = Some(ClientConnection::Raw(substitute_credentials(
+            connection_string,
+            credentials,
+        )));
+        self
+    }
+
     pub fn database<S: Into<String>>(mut self, database: Option<S>) -> Self {
         self.database = database.map(|d| d.into());
         self
@@ -179,7 +288,23 @@ impl<'a> ClientBuilder<'a> {
         self
     }
 
+    /// TDS client supports only the language, the rest is available for ODBC
+    pub fn connect_options(mut self, options: &ConnectOptions) -> Self {
+        if options.packet_size().is_some() || options.mars() || options.readonly() {
+            log::warn!("packet_size, mars and readonly are not supported by TDS client, ignored");
+        }
+        self.language = options.language().map(str::to_string);
+        self
+    }
+
     pub fn make_config(&self) -> Result<Config> {
+        if let Some(ClientConnection::Raw(connection_string)) = &self.client_connection {
+            let mut config = Config::from_ado_string(connection_string)?;
+            if let Some(db) = &self.database {
+                config.database(db);
+            }
+            return Ok(config);
+        }
         let mut config = Config::new();
         if let Some(db) = &self.database {
             config.database(db);
@@ -240,60 +365,202 @@ impl<'a> ClientBuilder<'a> {
 
     pub async fn build(self) -> Result<UniClient> {
         let tiberius_config = self.make_config()?;
-        match self.client_connection {
+        let client = match self.client_connection {
             Some(ClientConnection::Remote(_)) => create_remote_client(tiberius_config).await,
+            Some(ClientConnection::Raw(_)) => connect_via_tcp(tiberius_config).await,
             #[cfg(windows)]
             Some(ClientConnection::Named(_)) => create_named_instance_client(tiberius_config).await,
             #[cfg(windows)]
             Some(ClientConnection::Local(_)) => connect_via_tcp(tiberius_config).await,
             _ => anyhow::bail!("No client connection provided"),
+        }?;
+        match self.language {
+            Some(language) => set_language(client, &language).await,
+            None => Ok(client),
         }
     }
 }
 
+async fn set_language(mut client: UniClient, language: &str) -> Result<UniClient> {
+    query::run_custom_query(&mut client, make_set_language_statement(language))
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to set language `{language}`: {e}"))?;
+    Ok(client)
+}
+
+fn make_set_language_statement(language: &str) -> String {
+    format!("SET LANGUAGE [{}]", language.replace(']', "]]"))
+}
+
 pub enum Credentials<'a> {
     SqlServer { user: &'a str, password: &'a str },
     Windows { user: &'a str, password: &'a str },
 }
 
+fn substitute_credentials(connection_string: &str, credentials: Option<Credentials>) -> String {
+    let (user, password) = match credentials {
+        Some(Credentials::SqlServer { user, password })
+        | Some(Credentials::Windows { user, password }) => (user, password),
+        None => ("", ""),
+    };
+    connection_string
+        .replace("{username}", &quote_ado_value(user))
+        .replace("{password}", &quote_ado_value(password))
+}
+
+/// `a;b}` -> `{a;b}}}`: keywords can't be injected with `;`, `=` or braces of the value
+fn quote_ado_value(value: &str) -> String {
+    format!("{{{}}}", value.replace('}', "}}"))
+}
+
 pub const SQL_LOGIN_ERROR_TAG: &str = "[SQL LOGIN ERROR]";
 pub const SQL_TCP_ERROR_TAG: &str = "[SQL TCP ERROR]";
+pub const PASSWORD_CHOICES_FILE: &str = "password_choices";
 
-pub async fn connect_main_endpoint(endpoint: &Endpoint) -> Result<UniClient> {
-    connect_custom_endpoint(endpoint, endpoint.port()).await
+/// `Login failed for user '%.*ls'.`: the only error a next candidate password may fix
+const LOGIN_FAILED: u32 = 18456;
+
+/// Candidate passwords which worked during the run, shared by all logons of the run
+#[derive(Debug, Default)]
+pub struct PasswordChoices {
+    /// `user@host` -> fingerprint of the candidate password which worked last time
+    choices: Mutex<HashMap<String, String>>,
 }
 
-pub async fn connect_custom_endpoint(endpoint: &Endpoint, port: Port) -> Result<UniClient> {
+impl PasswordChoices {
+    /// Loads remembered password candidates, absent file means nothing remembered
+    pub fn load(path: &Path) -> Self {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        let choices = content
+            .lines()
+            .filter_map(|l| l.rsplit_once('\t'))
+            .map(|(key, fingerprint)| (key.to_string(), fingerprint.trim().to_string()))
+            .collect();
+        Self {
+            choices: Mutex::new(choices),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = self.choices.lock().unwrap().iter().fold(
+            String::new(),
+            |mut content, (key, fingerprint)| {
+                let _ = writeln!(content, "{key}\t{fingerprint}");
+                content
+            },
+        );
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.choices.lock().unwrap().get(key).cloned()
+    }
+
+    fn remember(&self, key: String, fingerprint: String) {
+        self.choices.lock().unwrap().insert(key, fingerprint);
+    }
+}
+
+pub async fn connect_main_endpoint(
+    endpoint: &Endpoint,
+    choices: &PasswordChoices,
+) -> Result<UniClient> {
+    connect_custom_endpoint(endpoint, endpoint.port(), choices).await
+}
+
+/// connection of the endpoint with endpoint specific settings
+fn apply_endpoint(client: Result<UniClient>, endpoint: &Endpoint) -> Result<UniClient> {
+    client.map(|c| c.with_min_query_gap(endpoint.conn().min_query_gap()))
+}
+
+/// Connection string of the endpoint replaces host, port and tls settings
+pub async fn connect_by_connection_string(
+    endpoint: &Endpoint,
+    connection_string: &str,
+    database: Option<String>,
+    choices: &PasswordChoices,
+) -> Result<UniClient> {
+    let (auth, conn) = endpoint.split();
+    let client = match auth.auth_type() {
+        AuthType::SqlServer | AuthType::Windows => {
+            logon_with_candidates(auth, &conn.hostname(), choices, |credentials| {
+                logon_by_connection_string(conn, connection_string, Some(credentials), &database)
+            })
+            .await
+        }
+        _ => logon_by_connection_string(conn, connection_string, None, &database).await,
+    };
+    apply_endpoint(client, endpoint)
+}
+
+async fn logon_by_connection_string(
+    conn: &config::ms_sql::Connection,
+    connection_string: &str,
+    credentials: Option<Credentials<'_>>,
+    database: &Option<String>,
+) -> Result<UniClient> {
+    tokio::time::timeout(
+        conn.timeout(),
+        ClientBuilder::new()
+            .logon_with_connection_string(connection_string, credentials)
+            .database(database.clone())
+            .connect_options(conn.connect_options())
+            .build(),
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("Timeout: {e} when creating client from connection string"))?
+}
+
+pub async fn connect_custom_endpoint(
+    endpoint: &Endpoint,
+    port: Port,
+    choices: &PasswordChoices,
+) -> Result<UniClient> {
+    if let Some(connection_string) = endpoint.conn().connection_string() {
+        let database = endpoint.conn().database().map(str::to_string);
+        return connect_by_connection_string(endpoint, connection_string, database, choices).await;
+    }
     let (auth, conn) = endpoint.split();
     let map_elapsed_to_anyhow = |e: tokio::time::error::Elapsed| {
         log::warn!("Timeout: {e} when creating client from config");
         anyhow::anyhow!("Timeout: {e} when creating client from config")
     };
+    kerberos::ensure_delegable(auth)?;
     let client = match auth.auth_type() {
         AuthType::SqlServer | AuthType::Windows => {
-            if let Some(credentials) = obtain_config_credentials(auth) {
+            let host = conn.hostname();
+            logon_with_candidates(auth, &host, choices, |credentials| async {
                 tokio::time::timeout(
                     conn.timeout(),
                     ClientBuilder::new()
-                        .logon_on_port(&conn.hostname(), Some(port), credentials)
+                        .logon_on_port(&host, Some(port.clone()), credentials)
+                        .database(conn.database())
                         .certificate(conn.tls().map(|t| t.client_certificate().to_owned()))
                         .trust_server_certificate(conn.trust_server_certificate())
+                        .connect_options(conn.connect_options())
                         .build(),
                 )
                 .await
                 .map_err(map_elapsed_to_anyhow)?
-            } else {
-                anyhow::bail!("Not provided credentials")
-            }
+            })
+            .await
         }
 
         #[cfg(windows)]
         AuthType::Integrated => tokio::time::timeout(
             LOCAL_TIMEOUT,
             ClientBuilder::new()
-                .local_by_port(Some(port), Some(conn.hostname()))
+                .local_by_port(
+                    Some(port.clone()),
+                    Some(kerberos::target_host(auth, conn, port.value())),
+                )
+                .database(conn.database())
                 .certificate(conn.tls().map(|t| t.client_certificate().to_owned()))
                 .trust_server_certificate(conn.trust_server_certificate())
+                .connect_options(conn.connect_options())
                 .build(),
         )
         .await
@@ -302,7 +569,7 @@ pub async fn connect_custom_endpoint(endpoint: &Endpoint, port: Port) -> Result<
         _ => anyhow::bail!("Not supported authorization type"),
     };
 
-    client
+    apply_endpoint(client, endpoint)
 }
 
 #[cfg(windows)]
@@ -313,6 +580,9 @@ pub async fn connect_custom_instance(
     use crate::constants;
 
     let (auth, conn) = endpoint.split();
+    if !conn.sql_browser().enabled() {
+        anyhow::bail!("SQL Browser is disabled, named connection is impossible");
+    }
     let map_elapsed_to_anyhow = |e: tokio::time::error::Elapsed| {
         anyhow::anyhow!(
             "Timeout: {e} when creating client from config {:?}",
@@ -326,8 +596,10 @@ pub async fn connect_custom_instance(
                     conn.timeout(),
                     ClientBuilder::new()
                         .browse(&conn.hostname(), instance, conn.sql_browser_port())
+                        .database(conn.database())
                         .certificate(conn.tls().map(|t| t.client_certificate().to_owned()))
                         .trust_server_certificate(conn.trust_server_certificate())
+                        .connect_options(conn.connect_options())
                         .build(),
                 )
                 .await
@@ -341,8 +613,10 @@ pub async fn connect_custom_instance(
             conn.timeout(),
             ClientBuilder::new()
                 .browse(&constants::LOCAL_HOST, instance, conn.sql_browser_port())
+                .database(conn.database())
                 .certificate(conn.tls().map(|t| t.client_certificate().to_owned()))
                 .trust_server_certificate(conn.trust_server_certificate())
+                .connect_options(conn.connect_options())
                 .build(),
         )
         .await
@@ -351,24 +625,98 @@ pub async fn connect_custom_instance(
         _ => anyhow::bail!("Not supported authorization type"),
     };
 
-    client
+    apply_endpoint(client, endpoint)
 }
 
 pub fn obtain_config_credentials(auth: &config::ms_sql::Authentication) -> Option<Credentials> {
+    to_credentials(auth, auth.password().map(|s| s.as_str()).unwrap_or(""))
+}
+
+fn to_credentials<'a>(
+    auth: &'a config::ms_sql::Authentication,
+    password: &'a str,
+) -> Option<Credentials<'a>> {
     match auth.auth_type() {
         AuthType::SqlServer => Some(Credentials::SqlServer {
             user: auth.username(),
-            password: auth.password().map(|s| s.as_str()).unwrap_or(""),
+            password,
         }),
         #[cfg(windows)]
         AuthType::Windows => Some(Credentials::Windows {
             user: auth.username(),
-            password: auth.password().map(|s| s.as_str()).unwrap_or(""),
+            password,
         }),
         _ => None,
     }
 }
 
+fn to_password_choice_key(auth: &config::ms_sql::Authentication, host: &HostName) -> String {
+    format!("{}@{}", auth.username(), host).to_lowercase()
+}
+
+/// Salted with the key: the same password of other logins gives another fingerprint
+fn to_password_fingerprint(key: &str, password: &str) -> String {
+    crate::utils::sha256_hex(format!("{key}\t{password}").as_bytes())
+}
+
+fn is_login_failed(error: &anyhow::Error) -> bool {
+    let error = error.to_string();
+    error.contains(SQL_LOGIN_ERROR_TAG) && permission::find_error_code(&error) == Some(LOGIN_FAILED)
+}
+
+/// Indices of `count` candidates, the `preferred` one goes first
+fn to_candidate_order(count: usize, preferred: Option<usize>) -> Vec<usize> {
+    let preferred = preferred.filter(|p| *p < count).unwrap_or(0);
+    std::iter::once(preferred)
+        .chain((0..count).filter(|i| *i != preferred))
+        .take(count.max(1))
+        .collect()
+}
+
+/// Logs on trying candidate passwords one by one, the last working goes first.
+/// Only a rejected login leads to the next candidate: other problems are reported at once
+pub async fn logon_with_candidates<'a, F, Fut>(
+    auth: &'a config::ms_sql::Authentication,
+    host: &HostName,
+    choices: &PasswordChoices,
+    logon: F,
+) -> Result<UniClient>
+where
+    F: Fn(Credentials<'a>) -> Fut,
+    Fut: Future<Output = Result<UniClient>>,
+{
+    let key = to_password_choice_key(auth, host);
+    let passwords = auth.passwords();
+    let preferred = choices.get(&key).and_then(|fingerprint| {
+        passwords
+            .iter()
+            .position(|p| to_password_fingerprint(&key, p) == fingerprint)
+    });
+    let mut last_error = None;
+    for index in to_candidate_order(passwords.len(), preferred) {
+        let password = passwords.get(index).map(|s| s.as_str()).unwrap_or("");
+        let Some(credentials) = to_credentials(auth, password) else {
+            anyhow::bail!("Not provided credentials")
+        };
+        match logon(credentials).await {
+            Ok(client) => {
+                if passwords.len() > 1 {
+                    log::info!("Password candidate {index} is accepted for `{key}`");
+                    let fingerprint = to_password_fingerprint(&key, password);
+                    choices.remember(key, fingerprint);
+                }
+                return Ok(client);
+            }
+            Err(e) if is_login_failed(&e) => {
+                log::warn!("Password candidate {index} is rejected for `{key}`");
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("Not provided credentials")))
+}
+
 /// Create client for remote MS SQL
 async fn create_remote_client(tiberius_config: Config) -> Result<UniClient> {
     let mut config = tiberius_config.clone();
@@ -412,34 +760,54 @@ async fn create_named_instance_client(config: Config) -> anyhow::Result<UniClien
     StdClient::connect(config, tcp.compat_write())
         .await
         .map_err(|e| anyhow::anyhow!("Failed to access SQL Browser {}", e))
-        .map(UniClient::Std)
+        .map(|c| UniClient::Std(c, Pacing::default(), Connectivity::Direct))
 }
 
-async fn connect_via_tcp(config: Config) -> Result<UniClient> {
-    log::info!("Connecting to addr '{}'...", config.get_addr());
-    let tcp = TcpStream::connect(config.get_addr()).await.map_err(|e| {
-        anyhow::anyhow!(
-            "{} address:{} error:`{}`",
-            SQL_TCP_ERROR_TAG,
-            config.get_addr(),
-            e
-        )
-    })?;
-    log::info!("Connected to addr '{}'", config.get_addr());
-    tcp.set_nodelay(true)?; // in documentation and examples
+/// Gateways (Azure SQL redirect policy, Azure Arc, TDS proxies) and read-only routing
+/// answer the login with a routing response: the login is repeated on the target
+async fn connect_via_tcp(mut config: Config) -> Result<UniClient> {
+    let mut connectivity = Connectivity::Direct;
+    for _ in 0..=defaults::MAX_REDIRECTS {
+        log::info!("Connecting to addr '{}'...", config.get_addr());
+        let tcp = TcpStream::connect(config.get_addr()).await.map_err(|e| {
+            anyhow::anyhow!(
+                "{} address:{} error:`{}`",
+                SQL_TCP_ERROR_TAG,
+                config.get_addr(),
+                e
+            )
+        })?;
+        log::info!("Connected to addr '{}'", config.get_addr());
+        tcp.set_nodelay(true)?; // in documentation and examples
 
-    // To be able to use Tokio's tcp, we're using the `compat_write` from
-    // the `TokioAsyncWriteCompatExt` to get a stream compatible with the
-    // traits from the `futures` crate. The same is for upcoming NamedPipe
-    let result = StdClient::connect(config, tcp.compat_write())
-        .await
-        .map_err(|e| anyhow::anyhow!("{} {}", SQL_LOGIN_ERROR_TAG, e));
-    if result.is_ok() {
-        log::info!("Connection success");
-    } else {
-        log::warn!("Connection success failed");
+        // To be able to use Tokio's tcp, we're using the `compat_write` from
+        // the `TokioAsyncWriteCompatExt` to get a stream compatible with the
+        // traits from the `futures` crate. The same is for upcoming NamedPipe
+        match StdClient::connect(config.clone(), tcp.compat_write()).await {
+            Ok(client) => {
+                log::info!("Connection success");
+                return Ok(UniClient::Std(client, Pacing::default(), connectivity));
+            }
+            Err(tiberius::error::Error::Routing { host, port }) => {
+                log::info!(
+                    "Server '{}' redirected the login to '{host}:{port}'",
+                    config.get_addr()
+                );
+                config.host(&host);
+                config.port(port);
+                connectivity = Connectivity::Redirected(format!("{host}:{port}"));
+            }
+            Err(e) => {
+                log::warn!("Connection success failed");
+                return Err(anyhow::anyhow!("{} {}", SQL_LOGIN_ERROR_TAG, e));
+            }
+        }
     }
-    result.map(UniClient::Std)
+    anyhow::bail!(
+        "{} too many redirects, last target '{}'",
+        SQL_LOGIN_ERROR_TAG,
+        config.get_addr()
+    )
 }
 
 /// Create `local` connection to MS SQL `instance`
@@ -484,17 +852,19 @@ mssql:
     #[tokio::test(flavor = "multi_thread")]
     async fn test_create_client_from_config_for_error() {
         let config = make_config_with_auth_type("token");
-        assert!(connect_main_endpoint(&config.endpoint())
-            .await
-            .unwrap_err()
-            .to_string()
-            .contains("Not supported authorization type"));
+        assert!(
+            connect_main_endpoint(&config.endpoint(), &PasswordChoices::default())
+                .await
+                .unwrap_err()
+                .to_string()
+                .contains("Not supported authorization type")
+        );
     }
 
     #[tokio::test(flavor = "multi_thread")]
     async fn test_create_client_from_config_timeout() {
         let config = make_config_with_auth_type("sql_server");
-        let s = connect_main_endpoint(&config.endpoint())
+        let s = connect_main_endpoint(&config.endpoint(), &PasswordChoices::default())
             .await
             .unwrap_err()
             .to_string();
@@ -507,6 +877,98 @@ mssql:
         assert!(s.contains("Connection refused"), "{s}");
     }
 
+    #[test]
+    fn test_connectivity() {
+        assert_eq!(Connectivity::default(), Connectivity::Direct);
+        assert_eq!(Connectivity::Direct.mode(), "direct");
+        assert_eq!(Connectivity::Direct.target(), "");
+        let redirected = Connectivity::Redirected("node2.example.net:11000".to_string());
+        assert_eq!(redirected.mode(), "redirect");
+        assert_eq!(redirected.target(), "node2.example.net:11000");
+    }
+
+    #[test]
+    fn test_pacing() {
+        let now = Instant::now();
+        let mut pacing = Pacing::new(Duration::from_millis(100));
+        assert_eq!(pacing.calc_wait(now), Duration::ZERO);
+        pacing.last_query_end = Some(now);
+        assert_eq!(pacing.calc_wait(now), Duration::from_millis(100));
+        assert_eq!(
+            pacing.calc_wait(now + Duration::from_millis(30)),
+            Duration::from_millis(70)
+        );
+        assert_eq!(
+            pacing.calc_wait(now + Duration::from_millis(300)),
+            Duration::ZERO
+        );
+        assert_eq!(Pacing::default().calc_wait(now), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_make_set_language_statement() {
+        assert_eq!(
+            make_set_language_statement("us_english"),
+            "SET LANGUAGE [us_english]"
+        );
+        assert_eq!(make_set_language_statement("a]b"), "SET LANGUAGE [a]]b]");
+    }
+
+    #[test]
+    fn test_to_candidate_order() {
+        assert_eq!(to_candidate_order(0, None), vec![0]);
+        assert_eq!(to_candidate_order(1, Some(0)), vec![0]);
+        assert_eq!(to_candidate_order(3, None), vec![0, 1, 2]);
+        assert_eq!(to_candidate_order(3, Some(2)), vec![2, 0, 1]);
+        assert_eq!(to_candidate_order(2, Some(5)), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_password_choices_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PASSWORD_CHOICES_FILE);
+        let fingerprint = to_password_fingerprint("persisted@host", "secret");
+        let choices = PasswordChoices::default();
+        choices.remember("persisted@host".to_string(), fingerprint.clone());
+        choices.save(&path).unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("secret"));
+        assert_eq!(
+            PasswordChoices::load(&path).get("persisted@host"),
+            Some(fingerprint)
+        );
+        assert!(PasswordChoices::load(&dir.path().join("absent"))
+            .get("persisted@host")
+            .is_none());
+    }
+
+    #[test]
+    fn test_to_password_fingerprint() {
+        let fingerprint = to_password_fingerprint("u@host", "p");
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(fingerprint, to_password_fingerprint("u@host", "p"));
+        assert_ne!(fingerprint, to_password_fingerprint("u@host", "q"));
+        assert_ne!(fingerprint, to_password_fingerprint("v@host", "p"));
+    }
+
+    #[test]
+    fn test_is_login_failed() {
+        let rejected = anyhow::anyhow!(
+            "{SQL_LOGIN_ERROR_TAG} Token error: 'Login failed for user 'u'.' on server S \
+             executing  on line 1 (code: 18456, state: 1, class: 14)"
+        );
+        assert!(is_login_failed(&rejected));
+        for error in [
+            format!("{SQL_LOGIN_ERROR_TAG} Protocol error: TLS handshake failed"),
+            format!(
+                "{SQL_LOGIN_ERROR_TAG} Token error: 'Cannot open database \"d\" requested by \
+                 the login.' on server S executing  on line 1 (code: 4060, state: 1, class: 11)"
+            ),
+            format!("{SQL_TCP_ERROR_TAG} address:h:1433 error:`refused` (code: 18456)"),
+        ] {
+            assert!(!is_login_failed(&anyhow::anyhow!(error.clone())), "{error}");
+        }
+    }
+
     #[test]
     fn test_obtain_credentials_from_config() {
         #[cfg(windows)]
@@ -548,6 +1010,55 @@ mssql:
         }
     }
 
+    #[test]
+    fn test_client_builder_connection_string() {
+        let credentials = Credentials::SqlServer {
+            user: "u",
+            password: "p",
+        };
+        let builder = ClientBuilder::new()
+            .logon_with_connection_string(
+                "Server=tcp:host,1433;User Id={username};Password={password};Packet Size=8192",
+                Some(credentials),
+            )
+            .database(Some("db"));
+        assert!(matches!(
+            &builder.client_connection,
+            Some(ClientConnection::Raw(s)) if s == "Server=tcp:host,1433;User Id={u};Password={p};Packet Size=8192"
+        ));
+        let config = builder.make_config().unwrap();
+        assert_eq!(config.get_addr(), "host:1433");
+        assert!(ClientBuilder::new()
+            .logon_with_connection_string("Server=tcp:host,port", None)
+            .make_config()
+            .is_err());
+    }
+
+    #[test]
+    fn test_substitute_credentials() {
+        let substitute = |password: &str| {
+            substitute_credentials(
+                "Server=tcp:host,1433;User Id={username};Password={password}",
+                Some(Credentials::SqlServer {
+                    user: "u=1",
+                    password,
+                }),
+            )
+        };
+        assert_eq!(
+            substitute("a;Database=master"),
+            "Server=tcp:host,1433;User Id={u=1};Password={a;Database=master}"
+        );
+        assert_eq!(
+            substitute("a}b{c}"),
+            "Server=tcp:host,1433;User Id={u=1};Password={a}}b{c}}}"
+        );
+        assert_eq!(
+            substitute_credentials("User Id={username};Password={password}", None),
+            "User Id={};Password={}"
+        );
+    }
+
     #[test]
     fn test_client_builder_remote() {
         let credentials = Credentials::SqlServer {
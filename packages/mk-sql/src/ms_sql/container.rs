@@ -0,0 +1,189 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Discovery of SQL Server containers using Docker API of the local container runtime
+
+use super::defaults;
+use crate::config::ms_sql::ContainerDiscovery;
+use anyhow::Result;
+use std::time::Duration;
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// Running containers only, Podman provides the same endpoint
+#[cfg(unix)]
+const LIST_CONTAINERS: &str = "GET /containers/json HTTP/1.0\r\nHost: localhost\r\n\r\n";
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Container {
+    pub name: String,
+    /// address of the published SQL Server port
+    pub host: String,
+    pub port: u16,
+}
+
+#[cfg(unix)]
+pub async fn find_containers(
+    discovery: &ContainerDiscovery,
+    timeout: Duration,
+) -> Result<Vec<Container>> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let request = async {
+        let mut stream = tokio::net::UnixStream::connect(discovery.socket()).await?;
+        stream.write_all(LIST_CONTAINERS.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        anyhow::Ok(response)
+    };
+    let response = tokio::time::timeout(timeout, request)
+        .await
+        .map_err(|e| anyhow::anyhow!("Timeout: {e} when listing containers"))??;
+    let body = to_http_body(&String::from_utf8_lossy(&response))?.to_string();
+    Ok(to_containers(&body, discovery.image(), discovery.label()))
+}
+
+#[cfg(windows)]
+pub async fn find_containers(
+    _discovery: &ContainerDiscovery,
+    _timeout: Duration,
+) -> Result<Vec<Container>> {
+    anyhow::bail!("Container discovery is supported only on Linux")
+}
+
+/// body of the successful HTTP response
+fn to_http_body(response: &str) -> Result<&str> {
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow::anyhow!("Malformed response of container runtime"))?;
+    let status = head.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some("200") => Ok(body),
+        _ => anyhow::bail!("Container runtime answered `{status}`"),
+    }
+}
+
+/// JSON list of containers, YAML parser is good enough for it
+fn to_containers(body: &str, image: &str, label: Option<&str>) -> Vec<Container> {
+    let containers = match YamlLoader::load_from_str(body) {
+        Ok(docs) => docs.into_iter().next().unwrap_or(Yaml::Null),
+        Err(e) => {
+            log::error!("Bad list of containers: {e}");
+            return vec![];
+        }
+    };
+    containers
+        .as_vec()
+        .map(|list| {
+            list.iter()
+                .filter(|c| is_image_matched(c, image) && is_label_matched(c, label))
+                .filter_map(to_container)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn is_image_matched(container: &Yaml, image: &str) -> bool {
+    container["Image"]
+        .as_str()
+        .is_some_and(|i| i.to_lowercase().contains(image))
+}
+
+fn is_label_matched(container: &Yaml, label: Option<&str>) -> bool {
+    let Some(label) = label else {
+        return true;
+    };
+    let (key, value) = match label.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (label, None),
+    };
+    match (&container["Labels"][key], value) {
+        (Yaml::String(actual), Some(expected)) => actual == expected,
+        (Yaml::BadValue, _) => false,
+        (_, None) => true,
+        _ => false,
+    }
+}
+
+/// the first published SQL Server port, IPv4 mappings are preferred
+fn to_container(container: &Yaml) -> Option<Container> {
+    let name = container["Names"]
+        .as_vec()?
+        .first()?
+        .as_str()?
+        .trim_start_matches('/')
+        .to_string();
+    let mut published = container["Ports"]
+        .as_vec()?
+        .iter()
+        .filter(|p| {
+            p["PrivatePort"].as_i64() == Some(defaults::STANDARD_PORT as i64)
+                && p["Type"].as_str().unwrap_or("tcp") == "tcp"
+        })
+        .filter_map(|p| {
+            let port = u16::try_from(p["PublicPort"].as_i64()?).ok()?;
+            Some((p["IP"].as_str().unwrap_or_default().to_string(), port))
+        })
+        .collect::<Vec<_>>();
+    published.sort_by_key(|(ip, _)| ip.contains(':'));
+    let (ip, port) = published.into_iter().next()?;
+    let host = match ip.as_str() {
+        "" | "0.0.0.0" | "::" => "localhost".to_string(),
+        _ => ip,
+    };
+    Some(Container { name, host, port })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTAINERS: &str = r#"[
+{"Id": "a1", "Names": ["/sql1"], "Image": "mcr.microsoft.com/mssql/server:2022-latest",
+ "Labels": {"monitoring": "yes"},
+ "Ports": [{"IP": "::", "PrivatePort": 1433, "PublicPort": 14331, "Type": "tcp"},
+           {"IP": "0.0.0.0", "PrivatePort": 1433, "PublicPort": 14330, "Type": "tcp"}]},
+{"Id": "a2", "Names": ["/sql2"], "Image": "mcr.microsoft.com/mssql/server:2019-latest",
+ "Labels": {},
+ "Ports": [{"IP": "127.0.0.1", "PrivatePort": 1433, "PublicPort": 1533, "Type": "tcp"}]},
+{"Id": "a3", "Names": ["/sql3"], "Image": "mcr.microsoft.com/mssql/server:2022-latest",
+ "Labels": {"monitoring": "no"}, "Ports": [{"PrivatePort": 1433, "Type": "tcp"}]},
+{"Id": "a4", "Names": ["/web"], "Image": "nginx", "Labels": {},
+ "Ports": [{"IP": "0.0.0.0", "PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"}]}
+]"#;
+
+    #[test]
+    fn test_to_containers() {
+        assert_eq!(
+            to_containers(CONTAINERS, "mssql", None),
+            vec![
+                Container {
+                    name: "sql1".to_string(),
+                    host: "localhost".to_string(),
+                    port: 14330
+                },
+                Container {
+                    name: "sql2".to_string(),
+                    host: "127.0.0.1".to_string(),
+                    port: 1533
+                },
+            ]
+        );
+        let labeled = to_containers(CONTAINERS, "mssql", Some("monitoring=yes"));
+        assert_eq!(labeled.len(), 1);
+        assert_eq!(labeled[0].name, "sql1");
+        assert_eq!(to_containers(CONTAINERS, "", Some("monitoring")).len(), 1);
+        assert!(to_containers(CONTAINERS, "oracle", None).is_empty());
+        assert!(to_containers("not json", "", None).is_empty());
+    }
+
+    #[test]
+    fn test_to_http_body() {
+        assert_eq!(
+            to_http_body("HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n[]").unwrap(),
+            "[]"
+        );
+        assert!(to_http_body("HTTP/1.0 404 Not Found\r\n\r\n{}").is_err());
+        assert!(to_http_body("garbage").is_err());
+    }
+}
@@ -4,3 +4,9 @@
 
 pub const STANDARD_PORT: u16 = 1433;
 pub const SQL_BROWSER_PORT: u16 = 1434;
+/// routing responses followed during one login
+pub const MAX_REDIRECTS: usize = 3;
+/// samples of user connections taken during one run
+pub const DB_CONNECTIONS_SAMPLES: usize = 3;
+/// samples of tempdb page latch waits taken during one run
+pub const TEMPDB_CONTENTION_SAMPLES: usize = 5;
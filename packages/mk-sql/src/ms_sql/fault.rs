@@ -0,0 +1,167 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Simulated failures requested with the hidden `--fault-injection` option
+//! Spec is a comma separated list of `fault[@instance][/section]`, e.g.
+//! `login@SQLEXPRESS,malformed/jobs,timeout@MSSQLSERVER/backup`
+//! Faults without section break the connection of the instance, `malformed` damages
+//! the output of the section(s)
+
+use crate::types::InstanceName;
+use anyhow::Result;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    Timeout,
+    Login,
+    Malformed,
+}
+
+impl Fault {
+    pub fn to_error(self) -> anyhow::Error {
+        match self {
+            Fault::Timeout => anyhow::anyhow!("Timeout: simulated by fault injection"),
+            Fault::Login => anyhow::anyhow!(
+                "{} Login failed: simulated by fault injection",
+                super::instance::SQL_LOGIN_ERROR_TAG
+            ),
+            Fault::Malformed => anyhow::anyhow!("Malformed rows: simulated by fault injection"),
+        }
+    }
+}
+
+impl FromStr for Fault {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "timeout" => Ok(Fault::Timeout),
+            "login" => Ok(Fault::Login),
+            "malformed" => Ok(Fault::Malformed),
+            _ => anyhow::bail!("Unknown fault `{s}`, expected timeout, login or malformed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Injection {
+    fault: Fault,
+    instance: Option<InstanceName>,
+    section: Option<String>,
+}
+
+impl Injection {
+    fn is_for_instance(&self, instance: &InstanceName) -> bool {
+        self.instance.as_ref().map_or(true, |i| i.is_same(instance))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultSpec {
+    injections: Vec<Injection>,
+}
+
+impl FromStr for FaultSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let injections = s
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| {
+                let (rest, section) = match item.split_once('/') {
+                    Some((rest, section)) => (rest, Some(section.trim().to_string())),
+                    None => (item, None),
+                };
+                let (fault, instance) = match rest.split_once('@') {
+                    Some((fault, instance)) => (fault, Some(instance.trim())),
+                    None => (rest, None),
+                };
+                Ok(Injection {
+                    fault: fault.parse()?,
+                    instance: instance.map(|i| InstanceName::from(i.to_uppercase())),
+                    section: section.filter(|s| !s.is_empty()),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if injections.is_empty() {
+            anyhow::bail!("Empty fault injection spec");
+        }
+        Ok(Self { injections })
+    }
+}
+
+impl FaultSpec {
+    /// fault breaking the connection to the instance
+    pub fn connection_fault(&self, instance: &InstanceName) -> Option<Fault> {
+        self.injections
+            .iter()
+            .find(|i| {
+                i.section.is_none() && i.fault != Fault::Malformed && i.is_for_instance(instance)
+            })
+            .map(|i| i.fault)
+    }
+
+    /// fault of the section, `malformed` without section applies to every section
+    pub fn section_fault(&self, instance: &InstanceName, section: &str) -> Option<Fault> {
+        self.injections
+            .iter()
+            .find(|i| {
+                i.is_for_instance(instance)
+                    && match &i.section {
+                        Some(s) => s == section,
+                        None => i.fault == Fault::Malformed,
+                    }
+            })
+            .map(|i| i.fault)
+    }
+}
+
+/// the last column of every row is dropped
+pub fn malform(body: &str, sep: char) -> String {
+    body.lines()
+        .map(|l| l.rsplit_once(sep).map_or(l, |(head, _)| head).to_string() + "\n")
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_spec() {
+        let spec: FaultSpec = "login@sqlExpress, malformed/jobs,timeout@MSSQLSERVER/backup"
+            .parse()
+            .unwrap();
+        let express = InstanceName::from("SQLEXPRESS".to_string());
+        let standard = InstanceName::from("MSSQLSERVER".to_string());
+        assert_eq!(spec.connection_fault(&express), Some(Fault::Login));
+        assert_eq!(spec.connection_fault(&standard), None);
+        assert_eq!(
+            spec.section_fault(&standard, "backup"),
+            Some(Fault::Timeout)
+        );
+        assert_eq!(
+            spec.section_fault(&standard, "jobs"),
+            Some(Fault::Malformed)
+        );
+        assert_eq!(spec.section_fault(&standard, "counters"), None);
+        let spec: FaultSpec = "malformed".parse().unwrap();
+        assert_eq!(spec.connection_fault(&standard), None);
+        assert_eq!(
+            spec.section_fault(&standard, "counters"),
+            Some(Fault::Malformed)
+        );
+        assert!("".parse::<FaultSpec>().is_err());
+        assert!("crash@SQLEXPRESS".parse::<FaultSpec>().is_err());
+    }
+
+    #[test]
+    fn test_malform() {
+        assert_eq!(malform("a|b|c\nd|e\nf\n", '|'), "a|b\nd\nf\n");
+        assert_eq!(malform("", '|'), "");
+    }
+}
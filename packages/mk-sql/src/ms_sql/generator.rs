@@ -0,0 +1,210 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Registry of section generators
+//! Every known section is produced by the generator registered for its name, sections
+//! without generator are custom sections. Builtin generators are registered at startup,
+//! an application using mk-sql as a library may add extra generators under a plugin name
+//! with [`register`]: they are used only by configs listing the plugin in `options: generators`
+
+use super::client::UniClient;
+use super::instance::{self, RunContext, SqlInstance};
+use super::section::Section;
+use crate::config::ms_sql::Endpoint;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+pub type GeneratorFuture<'a> = Pin<Box<dyn Future<Output = String> + 'a>>;
+
+/// Everything a generator may use to produce the section body
+pub struct GeneratorInput<'a> {
+    pub instance: &'a SqlInstance,
+    pub client: &'a mut UniClient,
+    pub endpoint: &'a Endpoint,
+    pub section: &'a Section,
+    /// query of the section, already selected for the version of the instance
+    /// empty for the section of an extra generator without sql file
+    pub query: &'a str,
+    pub context: &'a RunContext,
+}
+
+pub trait SectionGenerator: Send + Sync {
+    /// sections served by the generator
+    fn names(&self) -> &[&'static str];
+
+    /// body of the section, header is added by the caller
+    fn generate<'a>(&'a self, input: GeneratorInput<'a>) -> GeneratorFuture<'a>;
+}
+
+/// Generator made of a plain function, enough for the builtin sections
+pub struct FnGenerator {
+    names: &'static [&'static str],
+    generate: for<'a> fn(GeneratorInput<'a>) -> GeneratorFuture<'a>,
+}
+
+impl FnGenerator {
+    pub const fn new(
+        names: &'static [&'static str],
+        generate: for<'a> fn(GeneratorInput<'a>) -> GeneratorFuture<'a>,
+    ) -> Self {
+        Self { names, generate }
+    }
+}
+
+impl SectionGenerator for FnGenerator {
+    fn names(&self) -> &[&'static str] {
+        self.names
+    }
+
+    fn generate<'a>(&'a self, input: GeneratorInput<'a>) -> GeneratorFuture<'a> {
+        (self.generate)(input)
+    }
+}
+
+#[derive(Default)]
+pub struct Registry {
+    generators: HashMap<String, Arc<dyn SectionGenerator>>,
+}
+
+impl Registry {
+    /// generator registered later replaces the previous one for the same section
+    pub fn register(&mut self, generator: Arc<dyn SectionGenerator>) {
+        for name in generator.names() {
+            if self
+                .generators
+                .insert(name.to_string(), generator.clone())
+                .is_some()
+            {
+                log::info!("Generator for section `{name}` is replaced");
+            }
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<Arc<dyn SectionGenerator>> {
+        self.generators.get(name).cloned()
+    }
+}
+
+lazy_static! {
+    static ref REGISTRY: Registry = builtin_registry();
+    static ref PLUGINS: RwLock<HashMap<String, Registry>> = RwLock::new(HashMap::new());
+}
+
+fn builtin_registry() -> Registry {
+    let mut registry = Registry::default();
+    for generator in instance::builtin_generators() {
+        registry.register(generator);
+    }
+    registry
+}
+
+/// Adds extra generator of the `plugin`, must be called at startup before the run
+pub fn register(plugin: &str, generator: Arc<dyn SectionGenerator>) {
+    PLUGINS
+        .write()
+        .unwrap()
+        .entry(plugin.to_string())
+        .or_default()
+        .register(generator);
+}
+
+/// enabled plugins which have nothing registered
+pub fn find_unknown_plugins(plugins: &[String]) -> Vec<&str> {
+    let registered = PLUGINS.read().unwrap();
+    plugins
+        .iter()
+        .filter(|p| !registered.contains_key(p.as_str()))
+        .map(String::as_str)
+        .collect()
+}
+
+/// generator of the enabled `plugins`, the last listed plugin wins
+pub fn find_extra(name: &str, plugins: &[String]) -> Option<Arc<dyn SectionGenerator>> {
+    let registered = PLUGINS.read().unwrap();
+    plugins
+        .iter()
+        .rev()
+        .find_map(|p| registered.get(p).and_then(|r| r.find(name)))
+}
+
+pub fn find(name: &str) -> Option<Arc<dyn SectionGenerator>> {
+    REGISTRY.find(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::section::names;
+
+    fn generate_nothing(_input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+        Box::pin(async { String::new() })
+    }
+
+    #[test]
+    fn test_builtin_registry() {
+        let registry = builtin_registry();
+        for name in [
+            names::INSTANCE,
+            names::COUNTERS,
+            names::CLUSTERS,
+            names::CHECKDB,
+            names::DB_OPTION_DRIFT,
+            names::LABELS,
+            names::BACKUP_URL,
+            names::SESSIONS,
+            names::FILESTREAM,
+            names::UPTIME,
+            names::VERSION_STORE,
+            names::MEMORY_FEATURES,
+            names::DISTRIBUTED_TRANSACTIONS,
+            names::COLLATION,
+            names::AGENT_HEALTH,
+            names::CERTS,
+            names::QUERY_STORE,
+            names::LOGINS,
+            names::AG_CONFIG,
+            names::HOST_SETTINGS,
+            names::VOLUMES,
+            names::DEFAULT_TRACE,
+            names::ORPHANED_FILES,
+            names::MAINTENANCE,
+            names::AUDITS,
+            names::AZURE_RESOURCES,
+        ] {
+            assert!(registry.find(name).is_some(), "{name}");
+        }
+        assert!(registry.find("my_custom_section").is_none());
+    }
+
+    #[test]
+    fn test_register() {
+        static NAMES: [&str; 2] = ["alpha", names::JOBS];
+        let mut registry = Registry::default();
+        registry.register(Arc::new(FnGenerator::new(&NAMES, generate_nothing)));
+        assert_eq!(registry.find("alpha").unwrap().names(), NAMES);
+        assert_eq!(registry.find(names::JOBS).unwrap().names(), NAMES);
+        assert!(registry.find("beta").is_none());
+    }
+
+    #[test]
+    fn test_register_plugin() {
+        static NAMES: [&str; 1] = ["test_register_plugin"];
+        let plugins = |p: &[&str]| p.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        register(
+            "test_plugin",
+            Arc::new(FnGenerator::new(&NAMES, generate_nothing)),
+        );
+        assert!(find_extra(NAMES[0], &plugins(&["test_plugin"])).is_some());
+        assert!(find_extra(NAMES[0], &plugins(&[])).is_none());
+        assert!(find_extra(NAMES[0], &plugins(&["other_plugin"])).is_none());
+        assert!(find(NAMES[0]).is_none());
+        assert_eq!(
+            find_unknown_plugins(&plugins(&["test_plugin", "other_plugin"])),
+            ["other_plugin"]
+        );
+    }
+}
@@ -2,47 +2,79 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
+use super::browser;
+use super::cache::{self, CacheStore, Clock, FileStore, SystemClock};
 #[cfg(windows)]
 use super::client::OdbcClient;
-use super::client::{self, UniClient};
+use super::client::{self, PasswordChoices, UniClient};
+use super::container;
 use super::custom::get_sql_dir;
-use super::section::{Section, SectionKind};
+use super::defaults;
+use super::fault::{self, Fault};
+use super::generator::{self, FnGenerator, GeneratorFuture, GeneratorInput, SectionGenerator};
+use super::kerberos;
+use super::permission::{self, Denial};
+use super::prelogin;
+use super::section::{
+    record_warning, track_warnings, Need, Section, SectionKind, SectionResult, SectionStatus,
+};
 use crate::config::defines::defaults::MAX_CONNECTIONS;
-use crate::config::ms_sql::{is_local_host, is_use_tcp, Discovery};
-use crate::config::section;
+use crate::config::ms_sql::{
+    calc_real_host, is_local_host, is_use_tcp, BlackoutWindow, ClusterDedup, Discovery, Emit,
+    NameEncoding, PassiveNodePolicy,
+};
 use crate::config::{
     self,
-    ms_sql::{AuthType, CustomInstance, Endpoint},
-    section::names,
+    ms_sql::{AuthType, ConnectOptions, CustomInstance, Endpoint},
+    section::{
+        is_host_heavy_section, is_row_passed, is_sampling_section, mask_row, names,
+        DatabaseBaseline,
+    },
     CheckConfig,
 };
+use crate::constants::LOCAL_HOST;
 use crate::emit;
+use crate::locale::{self, ValueLocale};
+use crate::memory;
 use crate::ms_sql::query::{
-    obtain_computer_name, obtain_instance_name, obtain_system_user, run_custom_query,
-    run_known_query, Column, UniAnswer,
+    is_client_alive, obtain_computer_name, obtain_instance_name, obtain_primary_replica,
+    obtain_server_clock, obtain_system_user, record_query_error, run_custom_query,
+    run_custom_query_with_params, run_known_query, track_query_errors, Column, UniAnswer,
 };
 use crate::ms_sql::sqls;
 #[cfg(windows)]
 use crate::platform::odbc;
 use crate::setup::Env;
+use crate::telemetry;
 use crate::types::{
     ComputerName, HostName, InstanceAlias, InstanceCluster, InstanceEdition, InstanceId,
-    InstanceName, InstanceVersion, PiggybackHostName, Port,
+    InstanceName, InstanceVersion, PiggybackHostName, Port, Version,
 };
 use crate::utils::{self, prepare_error};
 use core::fmt;
-use std::thread;
 
 use anyhow::Result;
 use futures::stream::{self, StreamExt};
-use std::collections::{HashMap, HashSet};
-use std::path::Path;
-
-use crate::platform::{get_row_value_by_idx, Block};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Semaphore;
+
+use crate::platform::{
+    cluster, get_row_value_by_idx, host, perf_counters, registry, services, Block, InstanceInfo,
+};
 use tiberius::Row;
 
 pub const SQL_LOGIN_ERROR_TAG: &str = "[SQL LOGIN ERROR]";
 pub const SQL_TCP_ERROR_TAG: &str = "[SQL TCP ERROR]";
+const MAX_SQL_TEXT_LENGTH: usize = 200;
+const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// used to read Windows performance counters if the section has no timeout
+const PERF_COUNTERS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
 #[derive(Clone, Debug, Default)]
 pub struct SqlInstanceBuilder {
@@ -54,11 +86,26 @@ pub struct SqlInstanceBuilder {
     cluster: Option<InstanceCluster>,
     port: Option<Port>,
     dynamic_port: Option<Port>,
-    endpoint: Option<Endpoint>,
+    endpoint: Option<Arc<Endpoint>>,
     computer_name: Option<ComputerName>,
     environment: Option<Env>,
     cache_dir: Option<String>,
+    cache_store: Option<Arc<dyn CacheStore>>,
+    clock: Option<Arc<dyn Clock>>,
     piggyback: Option<PiggybackHostName>,
+    piggyback_template: Option<String>,
+    cluster_dedup: ClusterDedup,
+    main_dbs: HashMap<String, String>,
+    required_databases: Vec<String>,
+    registry_only: bool,
+    blackout_windows: Vec<BlackoutWindow>,
+    qualify_with_host: bool,
+    instance_prefix: Option<String>,
+    suppress_errors_after: Option<u32>,
+    section_parallelism: u32,
+    memory_budget: Option<usize>,
+    generators: Vec<String>,
+    properties: Option<SqlInstanceProperties>,
 }
 
 impl SqlInstanceBuilder {
@@ -67,7 +114,7 @@ impl SqlInstanceBuilder {
     }
 
     pub fn name<S: Into<String>>(mut self, name: S) -> Self {
-        self.name = Some(name.into().to_uppercase().into());
+        self.name = Some(name.into().trim().to_uppercase().into());
         self
     }
     pub fn alias(mut self, alias: &Option<InstanceAlias>) -> Self {
@@ -99,13 +146,23 @@ impl SqlInstanceBuilder {
         self
     }
     pub fn endpoint(mut self, endpoint: &Endpoint) -> Self {
-        self.endpoint = Some(endpoint.clone());
+        self.endpoint = Some(Arc::new(endpoint.clone()));
+        self
+    }
+    /// endpoint is shared with other builders, e.g. instances of the same host
+    pub fn shared_endpoint(mut self, endpoint: &Arc<Endpoint>) -> Self {
+        self.endpoint = Some(Arc::clone(endpoint));
         self
     }
     pub fn computer_name(mut self, computer_name: Option<ComputerName>) -> Self {
         self.computer_name = computer_name;
         self
     }
+    /// properties obtained at discovery are reused by the run, see [`RunContext`]
+    pub fn properties(mut self, properties: &SqlInstanceProperties) -> Self {
+        self.properties = Some(properties.clone());
+        self
+    }
 
     pub fn environment(mut self, environment: &Env) -> Self {
         self.environment = environment.clone().into();
@@ -115,10 +172,72 @@ impl SqlInstanceBuilder {
         self.cache_dir = Some(cache_dir.to_owned());
         self
     }
+    /// replaces the files in the cache dir
+    pub fn cache_store(mut self, cache_store: Arc<dyn CacheStore>) -> Self {
+        self.cache_store = Some(cache_store);
+        self
+    }
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+    /// `{instance}` and `{hostname}` in the name are expanded on build
     pub fn piggyback(mut self, piggyback: Option<PiggybackHostName>) -> Self {
         self.piggyback = piggyback.map(|s| s.to_string().to_lowercase().into());
         self
     }
+    /// used when no piggyback host is set, e.g. `sql-{instance}-{hostname}`
+    pub fn piggyback_template(mut self, template: Option<&str>) -> Self {
+        self.piggyback_template = template.map(str::to_string);
+        self
+    }
+    pub fn cluster_dedup(mut self, cluster_dedup: &ClusterDedup) -> Self {
+        self.cluster_dedup = cluster_dedup.clone();
+        self
+    }
+    /// section name -> database, overrides database of the section
+    pub fn main_dbs(mut self, main_dbs: &HashMap<String, String>) -> Self {
+        self.main_dbs = main_dbs.clone();
+        self
+    }
+    /// databases which must exist, absent ones are reported in the databases section
+    pub fn required_databases(mut self, databases: &[String]) -> Self {
+        self.required_databases = databases.to_vec();
+        self
+    }
+    pub fn blackout_windows(mut self, blackout_windows: &[BlackoutWindow]) -> Self {
+        self.blackout_windows = blackout_windows.to_vec();
+        self
+    }
+    /// output name of the instance gets the host, see [`make_label`]
+    pub fn qualify_with_host(mut self, qualify_with_host: bool) -> Self {
+        self.qualify_with_host = qualify_with_host;
+        self
+    }
+    pub fn instance_prefix(mut self, instance_prefix: Option<&str>) -> Self {
+        self.instance_prefix = instance_prefix.map(str::to_string);
+        self
+    }
+    /// consecutive identical connection failures reported in full, None - all of them
+    pub fn suppress_errors_after(mut self, after: Option<u32>) -> Self {
+        self.suppress_errors_after = after;
+        self
+    }
+    /// sections running at once, 1 - one by one
+    pub fn section_parallelism(mut self, parallelism: u32) -> Self {
+        self.section_parallelism = parallelism;
+        self
+    }
+    /// bytes every section may hold, None - no limit
+    pub fn memory_budget(mut self, bytes: Option<usize>) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+    /// plugins of extra section generators, see [`generator::register`]
+    pub fn generators(mut self, plugins: &[String]) -> Self {
+        self.generators = plugins.to_vec();
+        self
+    }
 
     pub fn from_row(self, row: &Row) -> Self {
         self.name(row.get_value_by_idx(0))
@@ -138,6 +257,13 @@ impl SqlInstanceBuilder {
             )
     }
 
+    /// instance read directly from the registry, no connection was made
+    pub fn from_registry(mut self, info: &InstanceInfo) -> Self {
+        self.registry_only = true;
+        self.name(info.name.to_string())
+            .port(info.final_port().cloned())
+    }
+
     pub fn from_strings(self, row: &[String]) -> Self {
         self.name(get_row_value_by_idx(row, 0))
             .id(get_row_value_by_idx(row, 1))
@@ -153,25 +279,61 @@ impl SqlInstanceBuilder {
     }
 
     pub fn get_endpoint(&self) -> Option<&Endpoint> {
-        self.endpoint.as_ref()
+        self.endpoint.as_deref()
     }
 
     pub fn get_port(&self) -> Port {
         self.get_port_ref().cloned().unwrap_or(Port(0))
     }
 
+    /// known only from the registry: must be reported even if not accessible
+    pub fn is_registry_only(&self) -> bool {
+        self.registry_only
+    }
+
     fn get_port_ref(&self) -> Option<&Port> {
         self.port.as_ref().or(self.dynamic_port.as_ref())
     }
 
     pub fn build(self) -> SqlInstance {
-        let version_table = parse_version(&self.version);
+        let version_table = self.version.as_ref().map(Version::from).unwrap_or_default();
         let endpoint = self.endpoint.unwrap_or_default();
         let name = self.name.unwrap_or_default();
         let tcp = is_use_tcp(&name, endpoint.auth(), endpoint.conn());
+        let piggyback = match (&self.cluster_dedup, &self.cluster) {
+            (ClusterDedup::Piggyback, Some(cluster)) if !cluster.to_string().is_empty() => {
+                Some(cluster.to_string().to_lowercase().into())
+            }
+            _ => self
+                .piggyback
+                .map(|p| p.to_string())
+                .or(self.piggyback_template)
+                .map(|p| {
+                    let hostname = self
+                        .computer_name
+                        .as_ref()
+                        .map(|c| c.to_string())
+                        .unwrap_or_else(|| endpoint.hostname().to_string());
+                    expand_piggyback_template(&p, &name, &hostname)
+                }),
+        };
+        let host = if self.qualify_with_host {
+            Some(
+                self.computer_name
+                    .as_ref()
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| endpoint.hostname().to_string()),
+            )
+        } else {
+            None
+        };
+        let label = make_label(&name, self.instance_prefix.as_deref(), host.as_deref());
+        let environment = self.environment.unwrap_or_default();
+        let value_locale = obtain_value_locale(environment.emit(), &endpoint);
         SqlInstance {
             alias: self.alias,
             name,
+            label,
             id: self.id.unwrap_or_default(),
             edition: self.edition.unwrap_or_default(),
             version: self.version.unwrap_or_default(),
@@ -181,32 +343,34 @@ impl SqlInstanceBuilder {
             available: None,
             endpoint,
             computer_name: self.computer_name,
-            environment: self.environment.unwrap_or_default(),
+            environment,
+            value_locale,
             cache_dir: self.cache_dir.unwrap_or_default(),
-            piggyback: self.piggyback,
+            cache_store: self.cache_store,
+            clock: self.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+            piggyback,
             version_table,
             tcp,
+            main_dbs: self.main_dbs,
+            required_databases: self.required_databases,
+            blackout_windows: self.blackout_windows,
+            suppress_errors_after: self.suppress_errors_after,
+            section_parallelism: self.section_parallelism.max(1),
+            memory_budget: self.memory_budget,
+            generators: self.generators,
+            host_gate: None,
+            passive_owner: None,
+            properties: self.properties,
         }
     }
 }
 
-fn parse_version(version: &Option<InstanceVersion>) -> [u32; 3] {
-    if let Some(version) = version {
-        let version = version.to_string();
-        let mut parts = version.split('.');
-        let major = parts.next().and_then(|s| s.parse::<u32>().ok());
-        let minor = parts.next().and_then(|s| s.parse::<u32>().ok());
-        let build = parts.next().and_then(|s| s.parse::<u32>().ok());
-        [major.unwrap_or(0), minor.unwrap_or(0), build.unwrap_or(0)]
-    } else {
-        [0, 0, 0]
-    }
-}
-
 #[derive(Clone, Debug)]
 pub struct SqlInstance {
     pub alias: Option<InstanceAlias>,
     pub name: InstanceName,
+    /// name of the instance in the output, differs from `name` only if qualified
+    label: InstanceName,
     pub id: InstanceId,
     pub version: InstanceVersion,
     pub edition: InstanceEdition,
@@ -214,13 +378,30 @@ pub struct SqlInstance {
     port: Option<Port>,
     dynamic_port: Option<Port>,
     pub available: Option<bool>,
-    endpoint: Endpoint,
+    endpoint: Arc<Endpoint>,
     computer_name: Option<ComputerName>,
     environment: Env,
+    /// numbers and dates of the instance are formatted by this locale, None - as is
+    value_locale: Option<ValueLocale>,
     cache_dir: String,
+    cache_store: Option<Arc<dyn CacheStore>>,
+    clock: Arc<dyn Clock>,
     piggyback: Option<PiggybackHostName>,
-    version_table: [u32; 3],
+    version_table: Version,
     pub tcp: bool,
+    main_dbs: HashMap<String, String>,
+    required_databases: Vec<String>,
+    blackout_windows: Vec<BlackoutWindow>,
+    suppress_errors_after: Option<u32>,
+    section_parallelism: u32,
+    memory_budget: Option<usize>,
+    generators: Vec<String>,
+    /// shared by instances of the same host to stagger heavy sections
+    host_gate: Option<Arc<Semaphore>>,
+    /// owner node of the SQL resource if the local node is passive and the instance is skipped
+    passive_owner: Option<String>,
+    /// obtained at discovery, saves the query of the instance section
+    properties: Option<SqlInstanceProperties>,
 }
 
 impl AsRef<SqlInstance> for SqlInstance {
@@ -261,7 +442,11 @@ impl SqlInstance {
     }
 
     pub fn mssql_name(&self) -> String {
-        format!("MSSQL_{}", self.name)
+        format!("MSSQL_{}", self.label)
+    }
+
+    pub fn label(&self) -> &InstanceName {
+        &self.label
     }
 
     pub fn full_name(&self) -> String {
@@ -284,6 +469,14 @@ impl SqlInstance {
         self.endpoint.hostname()
     }
 
+    /// database for the section: instance setting has priority over section setting
+    pub fn main_db(&self, section: &Section) -> Option<String> {
+        self.main_dbs
+            .get(section.name())
+            .cloned()
+            .or_else(|| section.main_db())
+    }
+
     /// not tested, because it is a bit legacy
     pub fn legacy_name(&self) -> String {
         if self.name.to_string() != "MSSQLSERVER" {
@@ -308,42 +501,63 @@ impl SqlInstance {
         }
     }
 
+    pub fn version_table(&self) -> &Version {
+        &self.version_table
+    }
+
     pub fn version_major(&self) -> u32 {
-        self.version_table[0]
+        self.version_table.major
     }
 
     pub fn version_minor(&self) -> u32 {
-        self.version_table[1]
+        self.version_table.minor
     }
 
     pub fn version_build(&self) -> u32 {
-        self.version_table[2]
+        self.version_table.build
+    }
+
+    /// encoding of names in the data lines as configured
+    fn name_encoding(&self) -> NameEncoding {
+        self.environment.emit().names()
+    }
+
+    fn name_field(&self, name: &str) -> String {
+        emit::name_field(name, self.name_encoding())
     }
 
     pub fn generate_header(&self) -> String {
-        self.piggyback
-            .as_ref()
-            .map(emit::piggyback_header)
-            .unwrap_or_default()
-            .to_owned()
+        generate_piggyback_header(self.piggyback.as_ref())
     }
 
     pub fn generate_footer(&self) -> String {
-        self.piggyback
-            .as_ref()
-            .map(|_| emit::piggyback_footer())
-            .unwrap_or_default()
-            .to_owned()
+        generate_piggyback_footer(self.piggyback.as_ref())
     }
 
     pub async fn generate_sections(&self, sections: &[Section]) -> String {
-        let header = self.generate_header();
+        let mut piggyback = self.piggyback.clone();
+        let mut span = telemetry::span(&format!("instance {}", self.name), None);
+        span.set_attribute("mssql.instance", self.name.to_string());
 
         // if yes - call generate_section with database parameter
         // else - call generate_section without database parameter
         log::trace!("{:?} @ {:?}", self, self.endpoint);
+        if let Some(owner) = &self.passive_owner {
+            log::info!("Instance {} is skipped: owned by node `{owner}`", self.name);
+            let body = self.generate_passive_node_entry(owner)
+                + &self.generate_section_status(
+                    &sections
+                        .iter()
+                        .map(|s| (s.name(), SectionStatus::Skipped("passive_node")))
+                        .collect::<Vec<_>>(),
+                );
+            return generate_piggyback_header(piggyback.as_ref())
+                + &body
+                + &generate_piggyback_footer(piggyback.as_ref());
+        }
         let body = match self.create_client(&self.endpoint, None).await {
             Ok(mut client) => {
+                self.reset_failures();
                 let real_name = obtain_instance_name(&mut client)
                     .await
                     .ok()
@@ -359,65 +573,338 @@ impl SqlInstance {
                         .unwrap_or("???".to_string()),
                     real_name
                 );
-                if real_name.to_string().to_uppercase() != self.name.to_string().to_uppercase() {
+                if !real_name.is_same(&self.name) {
                     let error_text = format!(
                         "Instance name mismatch: expected '{}', got '{}'",
                         self.name, real_name
                     );
                     log::error!("{}", error_text);
-                    let instance_section = Section::make_instance_section(); // this is important section always present
+                    let instance_section = Section::make_instance_section(self.environment.emit()); // this is important section always present
                     instance_section.to_plain_header()
                         + &self.generate_bad_state_entry(instance_section.sep(), &error_text)
+                        + &self.generate_section_status(
+                            &sections
+                                .iter()
+                                .map(|s| (s.name(), SectionStatus::Failed("name_mismatch")))
+                                .collect::<Vec<_>>(),
+                        )
                 } else {
-                    self._generate_sections(&mut client, &self.endpoint, sections)
+                    let selected = &self.select_sections_to_run(&mut client, sections).await;
+                    let skipped = sections
+                        .iter()
+                        .filter(|s| !selected.iter().any(|x| x.name() == s.name()))
+                        .map(|s| (s.name(), SectionStatus::Skipped("blackout")))
+                        .collect::<Vec<_>>();
+                    let context = RunContext::build(self, &mut client, selected).await;
+                    if let Some(replica_host) = context.replica_piggyback_host() {
+                        piggyback = Some(replica_host);
+                    }
+                    self._generate_sections(&mut client, &self.endpoint, selected, &context, &span)
                         .await
+                        + &self.generate_section_status(&skipped)
                 }
             }
             Err(err) => {
+                span.set_attribute("mssql.error", err.to_string());
                 log::warn!("Can't access {} instance with err {err}\n", self.id);
-                let instance_section = Section::make_instance_section(); // this is important section always present
-                instance_section.to_plain_header()
-                    + &self
-                        .generate_bad_state_entry(instance_section.sep(), format!("{err}").as_str())
+                let instance_section = Section::make_instance_section(self.environment.emit()); // this is important section always present
+                let failed = SectionStatus::failed(&err.to_string());
+                let message = self.explain_connection_error(&err).await;
+                let repeated = self.count_failure(&err.to_string());
+                match self.suppress_errors_after {
+                    Some(after) if repeated > after => {
+                        log::info!("Error of {} repeated {repeated} times", self.id);
+                        instance_section.to_plain_header()
+                            + &self.generate_bad_state_entry(
+                                instance_section.sep(),
+                                &format!("{message} (repeated {repeated} times)"),
+                            )
+                    }
+                    _ => {
+                        instance_section.to_plain_header()
+                            + &self.generate_bad_state_entry(instance_section.sep(), &message)
+                            + &self.generate_section_status(
+                                &sections
+                                    .iter()
+                                    .map(|s| (s.name(), failed.clone()))
+                                    .collect::<Vec<_>>(),
+                            )
+                    }
+                }
             }
         };
-        header + &body + &self.generate_footer()
-    }
-
-    /// Gather databases based on sections content: only if any of sections is database based
-    async fn gather_databases(&self, client: &mut UniClient, sections: &[Section]) -> Vec<String> {
-        let database_based_sections = section::get_per_database_sections();
-        let need = database_based_sections.iter().any(|s| {
-            sections
-                .iter()
-                .map(|s| s.name().to_string())
-                .collect::<Vec<String>>()
-                .contains(s)
-        });
-        if need {
-            self.generate_databases(client).await
-        } else {
-            Vec::new()
-        }
+        generate_piggyback_header(piggyback.as_ref())
+            + &body
+            + &generate_piggyback_footer(piggyback.as_ref())
     }
 
+    /// Sections are distributed over `section_parallelism` lanes, every lane runs its
+    /// sections one by one with own client, the output keeps the order of `sections`
+    /// Every sampling section runs on an extra lane: it waits between samples without
+    /// holding the client of other sections
     async fn _generate_sections(
         &self,
         client: &mut UniClient,
         endpoint: &Endpoint,
         sections: &[Section],
+        context: &RunContext,
+        parent: &telemetry::Span,
     ) -> String {
+        let (mut sampling_lanes, regular) = self.create_sampling_lanes(endpoint, sections).await;
+        let mut lane_clients = self.create_lane_clients(endpoint, regular.len()).await;
+        let clients: Vec<&mut UniClient> = std::iter::once(&mut *client)
+            .chain(lane_clients.iter_mut())
+            .collect();
+        let lanes = clients.len();
+        let results = futures::future::join_all(
+            clients
+                .into_iter()
+                .enumerate()
+                .map(|(lane, client)| {
+                    let lane_sections = regular
+                        .iter()
+                        .enumerate()
+                        .filter(|(pos, _)| pos % lanes == lane)
+                        .map(|(_, section)| *section)
+                        .collect::<Vec<_>>();
+                    (client, lane_sections)
+                })
+                .chain(
+                    sampling_lanes
+                        .iter_mut()
+                        .map(|(client, section)| (client, vec![*section])),
+                )
+                .map(|(client, lane_sections)| {
+                    self.run_lane(client, endpoint, lane_sections, context, parent)
+                }),
+        )
+        .await;
+        let reconnects: usize = results.iter().map(|(r, _)| r).sum();
+        let mut outcomes = results
+            .into_iter()
+            .flat_map(|(_, outcomes)| outcomes)
+            .collect::<Vec<_>>();
+        outcomes.sort_by_key(|(num, ..)| *num);
+
         let mut data: Vec<String> = Vec::new();
-        let databases = self.gather_databases(client, sections).await;
-        for section in sections.iter() {
-            data.push(
-                self.generate_section(client, endpoint, section, &databases)
-                    .await,
+        let mut statuses: Vec<(&str, SectionStatus)> = Vec::new();
+        let mut warnings = String::new();
+        for (num, result) in outcomes {
+            let name = sections[num].name();
+            warnings += &result.to_warning_entries(
+                &self.mssql_name(),
+                name,
+                Section::make_section_status_section(self.environment.emit()).sep(),
             );
+            statuses.push((name, result.status));
+            data.push(result.body);
+        }
+        if reconnects > 0 {
+            log::warn!("Instance {} reconnected {reconnects} times", self.name);
+            data.push(self.generate_reconnects_entry(reconnects));
+        }
+        // warnings belong to the section status section
+        data.push(self.generate_section_status(&statuses) + &warnings);
+        let denials = statuses
+            .iter()
+            .filter_map(|(_, status)| match status {
+                SectionStatus::Denied(denial) => Some(denial.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !denials.is_empty() {
+            let login = obtain_system_user(client).await.ok().flatten();
+            data.push(self.generate_permission_hints(&denials, login.as_deref()));
         }
         data.join("")
     }
 
+    /// GRANT statements for the denied permissions, once per statement
+    fn generate_permission_hints(&self, denials: &[Denial], login: Option<&str>) -> String {
+        let section = Section::make_permission_hints_section(self.environment.emit());
+        let sep = section.sep();
+        let login = login.unwrap_or("<login>");
+        let mut grants: Vec<String> = Vec::new();
+        for grant in denials.iter().map(|d| d.to_grant(login)) {
+            if !grants.contains(&grant) {
+                grants.push(grant);
+            }
+        }
+        section.to_plain_header()
+            + &grants
+                .iter()
+                .map(|grant| format!("{}{sep}{grant}\n", self.mssql_name()))
+                .collect::<Vec<_>>()
+                .join("")
+    }
+
+    /// runs `sections` one by one, returns the count of reconnects and the results
+    /// together with the position of the section
+    async fn run_lane(
+        &self,
+        client: &mut UniClient,
+        endpoint: &Endpoint,
+        sections: Vec<(usize, &Section)>,
+        context: &RunContext,
+        parent: &telemetry::Span,
+    ) -> (usize, Vec<(usize, SectionResult)>) {
+        let mut results = Vec::new();
+        let mut reconnects = 0;
+        for (pos, (num, section)) in sections.into_iter().enumerate() {
+            if pos > 0 && self.reconnect_if_broken(client, endpoint).await {
+                reconnects += 1;
+            }
+            let _permit = match &self.host_gate {
+                Some(gate) if is_host_heavy_section(section.name()) => gate.acquire().await.ok(),
+                _ => None,
+            };
+            let mut span = telemetry::span(&format!("section {}", section.name()), Some(parent));
+            let result = self
+                .generate_section(client, endpoint, section, context)
+                .await;
+            if span.is_active() {
+                span.set_attribute("mssql.section", section.name());
+                span.set_attribute("mssql.query.duration_ms", result.duration.as_millis());
+                span.set_attribute("mssql.rows", result.row_count);
+            }
+            results.push((num, result));
+        }
+        (reconnects, results)
+    }
+
+    /// client for every sampling section, the sampling section without client and other
+    /// sections are returned to be run on the regular lanes
+    async fn create_sampling_lanes<'a>(
+        &self,
+        endpoint: &Endpoint,
+        sections: &'a [Section],
+    ) -> (
+        Vec<(UniClient, (usize, &'a Section))>,
+        Vec<(usize, &'a Section)>,
+    ) {
+        let mut lanes = Vec::new();
+        let mut regular = Vec::new();
+        for (num, section) in sections.iter().enumerate() {
+            if !is_sampling_section(section.name()) {
+                regular.push((num, section));
+                continue;
+            }
+            match self.create_client(endpoint, None).await {
+                Ok(client) => lanes.push((client, (num, section))),
+                Err(e) => {
+                    log::warn!("Section {} shares the client: {e}", section.name());
+                    regular.push((num, section));
+                }
+            }
+        }
+        (lanes, regular)
+    }
+
+    /// clients of the extra lanes, lanes without client are dropped
+    async fn create_lane_clients(&self, endpoint: &Endpoint, sections: usize) -> Vec<UniClient> {
+        let extra = (self.section_parallelism as usize)
+            .min(sections)
+            .saturating_sub(1);
+        let mut clients = Vec::new();
+        for _ in 0..extra {
+            match self.create_client(endpoint, None).await {
+                Ok(client) => clients.push(client),
+                Err(e) => {
+                    log::warn!("Instance {} runs fewer sections at once: {e}", self.name);
+                    break;
+                }
+            }
+        }
+        clients
+    }
+
+    /// lets the server distinguish a failed section from a section without rows
+    fn generate_section_status(&self, statuses: &[(&str, SectionStatus)]) -> String {
+        if statuses.is_empty() {
+            return String::new();
+        }
+        let section = Section::make_section_status_section(self.environment.emit());
+        let sep = section.sep();
+        section.to_plain_header()
+            + &statuses
+                .iter()
+                .map(|(name, status)| status.to_entry(&self.mssql_name(), name, sep))
+                .collect::<Vec<_>>()
+                .join("")
+    }
+
+    /// Client reused between sections is probed and replaced if broken,
+    /// returns true if the client was replaced
+    async fn reconnect_if_broken(&self, client: &mut UniClient, endpoint: &Endpoint) -> bool {
+        if !matches!(client, UniClient::Std(..)) || is_client_alive(client, PROBE_TIMEOUT).await {
+            return false;
+        }
+        match self.create_client(endpoint, None).await {
+            Ok(new_client) => {
+                log::info!("Instance {} is reconnected", self.name);
+                *client = new_client;
+                true
+            }
+            Err(e) => {
+                log::error!("Instance {} failed to reconnect: {e}", self.name);
+                false
+            }
+        }
+    }
+
+    /// reported in the instance section after all sections of the instance
+    fn generate_reconnects_entry(&self, reconnects: usize) -> String {
+        let section = Section::make_instance_section(self.environment.emit());
+        let sep = section.sep();
+        format!(
+            "{}{}{sep}reconnects{sep}{reconnects}\n",
+            section.to_plain_header(),
+            self.mssql_name()
+        )
+    }
+
+    /// the only data of the instance skipped on the passive node
+    fn generate_passive_node_entry(&self, owner: &str) -> String {
+        let section = Section::make_instance_section(self.environment.emit());
+        let sep = section.sep();
+        format!(
+            "{}{}{sep}passive_node{sep}{owner}\n",
+            section.to_plain_header(),
+            self.mssql_name()
+        )
+    }
+
+    /// During a blackout window only the instance section is generated
+    async fn select_sections_to_run(
+        &self,
+        client: &mut UniClient,
+        sections: &[Section],
+    ) -> Vec<Section> {
+        if self.blackout_windows.is_empty() {
+            return sections.to_vec();
+        }
+        match obtain_server_clock(client).await {
+            Ok((weekday, minutes))
+                if self
+                    .blackout_windows
+                    .iter()
+                    .any(|w| w.contains(weekday, minutes)) =>
+            {
+                log::info!("Blackout window for `{}`, only instance section", self.name);
+                sections
+                    .iter()
+                    .filter(|s| s.name() == names::INSTANCE)
+                    .cloned()
+                    .collect()
+            }
+            Ok(_) => sections.to_vec(),
+            Err(e) => {
+                log::warn!("Failed to obtain server clock: {e}, blackout windows are ignored");
+                sections.to_vec()
+            }
+        }
+    }
+
     /// Create a client for an Instance based on Config
     pub async fn create_client(
         &self,
@@ -431,17 +918,38 @@ impl SqlInstance {
             endpoint.auth().username(),
             endpoint.conn().hostname()
         );
+        if let Some(fault) = self.environment.faults().connection_fault(&self.name) {
+            let e = fault.to_error();
+            record_query_error(&e);
+            return Err(e);
+        }
+        let database = database.or_else(|| endpoint.conn().database().map(str::to_string));
         if self.tcp {
-            create_tcp_client(endpoint, database, self.port()).await
+            create_tcp_client(
+                endpoint,
+                database,
+                self.port(),
+                self.environment.password_choices(),
+            )
+            .await
         } else {
-            create_odbc_client(&self.name, database)
+            create_odbc_client(&self.name, database, endpoint.conn().connect_options())
         }
+        .map(|c| c.with_min_query_gap(endpoint.conn().min_query_gap()))
+        .map_err(|e| {
+            record_query_error(&e);
+            e
+        })
     }
 
     pub async fn generate_details_entry(&self, client: &mut UniClient, sep: char) -> String {
         let r = SqlInstanceProperties::obtain_by_query(client).await;
+        self.to_details_entry(&r, sep)
+    }
+
+    fn to_details_entry(&self, r: &Result<SqlInstanceProperties>, sep: char) -> String {
         match r {
-            Ok(properties) => self.process_details_rows(&properties, sep),
+            Ok(properties) => self.process_details_rows(properties, sep),
             Err(err) => {
                 log::error!("Failed to get sql instance properties: {}", err);
                 format!("{}{:?}", sep.to_string().repeat(4), err).to_string()
@@ -453,6 +961,61 @@ impl SqlInstance {
         format!("{}{sep}state{sep}1{sep}\n", self.mssql_name(),)
     }
 
+    /// physical replica behind the AG listener, empty if not a listener
+    fn generate_listener_entry(&self, context: &RunContext, sep: char) -> String {
+        match (self.endpoint.conn().listener(), context.primary_replica()) {
+            (Some(listener), replica) => format!(
+                "{}{sep}listener{sep}{}{sep}{}\n",
+                self.mssql_name(),
+                listener.availability_group(),
+                replica.unwrap_or_default()
+            ),
+            _ => String::new(),
+        }
+    }
+
+    /// `connectivity|<direct|redirect>|<target>|<azure-arc or empty>`
+    async fn generate_connectivity_entry(&self, client: &mut UniClient, sep: char) -> String {
+        let connectivity = client.connectivity();
+        let azure_arc = match run_known_query(client, sqls::Id::AzureArc)
+            .await
+            .and_then(validate_rows)
+        {
+            Ok(answers) => is_azure_arc(&answers[0]),
+            Err(err) => {
+                log::warn!("Failed to detect Azure Arc: {}", err);
+                false
+            }
+        };
+        to_connectivity_entry(&self.mssql_name(), &connectivity, azure_arc, sep)
+    }
+
+    /// pre-login and TLS details are added to negotiation errors of TCP connections
+    async fn explain_connection_error(&self, err: &anyhow::Error) -> String {
+        let text = err.to_string();
+        if kerberos::is_double_hop_error(&text) {
+            let port = self
+                .port()
+                .map(|p| p.value())
+                .unwrap_or(defaults::STANDARD_PORT);
+            let hint =
+                kerberos::delegation_hint(&self.endpoint.conn().hostname().to_string(), port);
+            log::warn!("Logon to {} failed, {hint}", self.name);
+            return format!("{text} [{hint}]");
+        }
+        if !self.tcp || !prelogin::is_negotiation_error(&text) {
+            return text;
+        }
+        let port = self
+            .port()
+            .map(|p| p.value())
+            .unwrap_or(defaults::STANDARD_PORT);
+        let host = self.endpoint.conn().hostname().to_string();
+        let details = prelogin::diagnose(&host, port, self.endpoint.conn().timeout()).await;
+        log::warn!("Negotiation with {host}:{port} failed, {details}");
+        format!("{text} [{details}]")
+    }
+
     pub fn generate_bad_state_entry(&self, sep: char, message: &str) -> String {
         format!("{}{sep}state{sep}0{sep}{}\n", self.mssql_name(), message)
     }
@@ -462,21 +1025,99 @@ impl SqlInstance {
         client: &mut UniClient,
         endpoint: &Endpoint,
         section: &Section,
-        databases: &[String],
-    ) -> String {
-        let body = match self.read_data_from_cache(section.name(), section.cache_age() as u64) {
-            Some(from_cache) => from_cache,
-            None => {
-                let from_sql = self
-                    .generate_section_body(client, endpoint, section, databases)
-                    .await;
-                if section.kind() == &SectionKind::Async {
-                    self.write_data_in_cache(section.name(), &from_sql);
-                };
-                from_sql
+        context: &RunContext,
+    ) -> SectionResult {
+        let started = std::time::Instant::now();
+        let ((body, status), warnings) = track_warnings(async {
+            match self.read_data_from_cache(section.name(), section.cache_age() as u64) {
+                Some((from_cache, age)) => (from_cache, SectionStatus::Cached(age)),
+                None => {
+                    memory::track_budget(self.memory_budget, async {
+                        let fault = self
+                            .environment
+                            .faults()
+                            .section_fault(&self.name, section.name());
+                        let (from_sql, error) = match fault {
+                            Some(Fault::Malformed) => {
+                                let (body, error) =
+                                    track_query_errors(self.generate_timed_section_body(
+                                        client, endpoint, section, context,
+                                    ))
+                                    .await;
+                                (fault::malform(&body, section.sep()), error)
+                            }
+                            Some(fault) => (String::new(), Some(fault.to_error().to_string())),
+                            None => {
+                                track_query_errors(self.generate_timed_section_body(
+                                    client, endpoint, section, context,
+                                ))
+                                .await
+                            }
+                        };
+                        let denial = error.as_deref().and_then(permission::find_denial);
+                        let from_sql = match denial {
+                            Some(_) => remove_denied_lines(from_sql),
+                            None => from_sql,
+                        };
+                        let (from_sql, truncated) = memory::truncate_to_budget(from_sql);
+                        if truncated {
+                            record_warning(&format!(
+                                "Output of section {} is truncated: {}",
+                                section.name(),
+                                memory::BUDGET_EXCEEDED
+                            ));
+                        }
+                        if section.kind() == &SectionKind::Async {
+                            self.write_data_in_cache(section.name(), &from_sql);
+                        };
+                        let status = match (error, denial) {
+                            _ if truncated => SectionStatus::failed(memory::BUDGET_EXCEEDED),
+                            (_, Some(denial)) => SectionStatus::Denied(denial),
+                            (Some(e), None) => SectionStatus::failed(&e),
+                            (None, None) => SectionStatus::Live,
+                        };
+                        (from_sql, status)
+                    })
+                    .await
+                }
             }
+        })
+        .await;
+        SectionResult {
+            row_count: body.lines().count(),
+            body: section.to_work_header() + body.as_str(),
+            status,
+            warnings,
+            duration: started.elapsed(),
+        }
+    }
+
+    /// section exceeding its timeout is reported as failed, the interrupted client is
+    /// probed and replaced before the next section
+    async fn generate_timed_section_body(
+        &self,
+        client: &mut UniClient,
+        endpoint: &Endpoint,
+        section: &Section,
+        context: &RunContext,
+    ) -> String {
+        let body = self.generate_section_body(client, endpoint, section, context);
+        let Some(timeout) = section.timeout() else {
+            return body.await;
         };
-        section.to_work_header() + body.as_str()
+        match tokio::time::timeout(timeout, body).await {
+            Ok(body) => body,
+            Err(_) => {
+                let e = anyhow::anyhow!(
+                    "Section {} timed out after {}s",
+                    section.name(),
+                    timeout.as_secs()
+                );
+                log::error!("{e}");
+                record_query_error(&e);
+                String::new()
+            }
+        }
     }
 
     async fn generate_section_body(
@@ -484,87 +1125,115 @@ impl SqlInstance {
         client: &mut UniClient,
         endpoint: &Endpoint,
         section: &Section,
-        databases: &[String],
+        context: &RunContext,
     ) -> String {
-        if let Some(query) = section.select_query(get_sql_dir(), self.version_major()) {
-            let sep = section.sep();
-            match section.name() {
-                names::INSTANCE => {
-                    self.generate_good_state_entry(sep)
-                        + &self.generate_details_entry(client, sep).await
-                }
-                names::COUNTERS => self.generate_counters_section(client, &query, sep).await,
-                names::BACKUP => self.generate_backup_section(client, &query, sep).await,
-                names::BLOCKED_SESSIONS => {
-                    self.generate_sessions_section(client, &query, sep).await
-                }
-                names::DATABASES => {
-                    self.generate_databases_section(client, databases, &query, sep)
+        let databases = context.databases();
+        let query = section.select_query(get_sql_dir(), self.version_table());
+        if let Some(generator) = generator::find_extra(section.name(), &self.generators) {
+            return generator
+                .generate(GeneratorInput {
+                    instance: self,
+                    client,
+                    endpoint,
+                    section,
+                    query: query.as_deref().unwrap_or_default(),
+                    context,
+                })
+                .await;
+        }
+        if let Some(query) = query {
+            match generator::find(section.name()) {
+                Some(generator) => {
+                    generator
+                        .generate(GeneratorInput {
+                            instance: self,
+                            client,
+                            endpoint,
+                            section,
+                            query: &query,
+                            context,
+                        })
                         .await
                 }
-                names::CONNECTIONS => self.generate_connections_section(client, &query, sep).await,
-                names::TRANSACTION_LOG
-                | names::TABLE_SPACES
-                | names::DATAFILES
-                | names::CLUSTERS => self.generate_database_indexed_section_threading(
-                    databases, endpoint, section, &query, sep,
-                ),
-                names::MIRRORING | names::JOBS | names::AVAILABILITY_GROUPS => {
-                    self.generate_unified_section(endpoint, section, None).await
-                }
-                _ => self
+                None if section.per_database() => self
+                    .generate_custom_section_per_database(endpoint, section, databases)
+                    .await
+                    .unwrap_or_else(|| {
+                        record_warning(&format!(
+                            "Can't find sql for custom section `{}`",
+                            section.name()
+                        ));
+                        String::new()
+                    }),
+                None => self
                     .generate_custom_section(endpoint, section)
                     .await
                     .unwrap_or_else(|| {
-                        format!(
-                            "Can't find sql in for custom section `{}`\n",
+                        record_warning(&format!(
+                            "Can't find sql for custom section `{}`",
                             section.name()
-                        )
-                        .to_string()
+                        ));
+                        String::new()
                     }),
             }
         } else {
             log::error!("Bad section query: {}", section.name());
+            record_query_error(&anyhow::anyhow!("Bad section query"));
             String::default()
         }
     }
 
-    fn read_data_from_cache(&self, name: &str, cache_age: u64) -> Option<String> {
+    /// returns the cached body with its age in seconds
+    fn read_data_from_cache(&self, name: &str, cache_age: u64) -> Option<(String, u64)> {
         if cache_age == 0 {
             return None;
         }
-        if let Some(path) = self
-            .environment
-            .obtain_cache_sub_dir(self.cache_dir())
-            .map(|d| d.join(self.make_cache_entry_name(name)))
-        {
-            match utils::get_modified_age(&path) {
-                Ok(file_age) if file_age <= cache_age => {
-                    log::info!("Cache file {path:?} is new enough for {cache_age} cache_age",);
-                    std::fs::read_to_string(&path)
-                        .map_err(|e| {
-                            log::error!("{e} reading cache file {:?}", &path);
-                            e
-                        })
-                        .ok()
-                }
-                _ => None,
-            }
-        } else {
-            None
-        }
+        cache::read(
+            self.obtain_cache_store()?.as_ref(),
+            self.clock.as_ref(),
+            &self.make_cache_entry_name(name),
+            cache_age,
+        )
     }
 
     fn write_data_in_cache(&self, name: &str, body: &str) {
-        if let Some(dir) = self.environment.obtain_cache_sub_dir(self.cache_dir()) {
-            let file_name = self.make_cache_entry_name(name);
-            std::fs::write(dir.join(file_name), body)
-                .unwrap_or_else(|e| log::error!("Error {e} writing cache"));
+        if let Some(store) = self.obtain_cache_store() {
+            cache::write(store.as_ref(), &self.make_cache_entry_name(name), body);
+        }
+    }
+
+    /// files of the cache dir if no store is set explicitly
+    fn obtain_cache_store(&self) -> Option<Arc<dyn CacheStore>> {
+        self.cache_store.clone().or_else(|| {
+            self.environment
+                .obtain_cache_sub_dir(self.cache_dir())
+                .map(|d| Arc::new(FileStore::new(d)) as Arc<dyn CacheStore>)
+        })
+    }
+
+    /// consecutive failures with the same error including this one, kept in the cache
+    fn count_failure(&self, error: &str) -> u32 {
+        let (Some(_), Some(store)) = (self.suppress_errors_after, self.obtain_cache_store()) else {
+            return 1;
+        };
+        let key = self.make_cache_entry_name(FAILURES);
+        let count = to_failure_count(store.read(&key).ok().as_deref(), error);
+        cache::write(store.as_ref(), &key, &format!("{count}\n{error}"));
+        count
+    }
+
+    fn reset_failures(&self) {
+        let (Some(_), Some(store)) = (self.suppress_errors_after, self.obtain_cache_store()) else {
+            return;
+        };
+        let key = self.make_cache_entry_name(FAILURES);
+        if store.read(&key).is_ok_and(|s| !s.is_empty()) {
+            cache::write(store.as_ref(), &key, "");
         }
     }
 
     fn make_cache_entry_name(&self, name: &str) -> String {
-        format!("{};{};{}.mssql", self.hostname(), self.name, name)
+        format!("{};{};{}.mssql", self.hostname(), self.label, name)
     }
 
     pub async fn generate_counters_section(
@@ -572,6 +1241,7 @@ impl SqlInstance {
         client: &mut UniClient,
         query: &str,
         sep: char,
+        timeout: Option<std::time::Duration>,
     ) -> String {
         let x = run_custom_query(client, query)
             .await
@@ -582,11 +1252,78 @@ impl SqlInstance {
             });
         match x {
             Ok(result) => result,
+            Err(err) if self.is_counters_fallback_possible(&err) => {
+                record_warning(&format!(
+                    "Failed to get counters: {err}, using Windows performance counters"
+                ));
+                self.generate_counters_from_windows(sep, timeout)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::error!("Failed to get Windows performance counters: {e}");
+                        format!("{sep}{sep}{}{sep}{}\n", self.label, err)
+                    })
+            }
             Err(err) => {
                 log::error!("Failed to get counters: {}", err);
-                format!("{sep}{sep}{}{sep}{}\n", self.name, err).to_string()
+                format!("{sep}{sep}{}{sep}{}\n", self.label, err).to_string()
+            }
+        }
+    }
+
+    /// Rates of the `/sec` counters from the sample stored in the cache dir at the previous run
+    fn append_counter_rates(&self, counters: String, sep: char) -> String {
+        let sample = to_rate_sample(&counters, sep);
+        if sample.is_empty() {
+            return counters;
+        }
+        let (Some(store), Ok(now)) = (self.obtain_cache_store(), self.clock.now()) else {
+            return counters;
+        };
+        let key = self.make_cache_entry_name(COUNTERS_SAMPLE);
+        let previous = store.read(&key).ok();
+        let stored = sample
+            .iter()
+            .map(|(key, value)| format!("{key}{sep}{value}\n"))
+            .collect::<Vec<_>>()
+            .join("");
+        store
+            .write(&key, &format!("{now}\n{stored}"))
+            .unwrap_or_else(|e| log::error!("Error {e} writing counters sample"));
+        match previous.as_deref().and_then(|p| p.split_once('\n')) {
+            Some((time, body)) => {
+                let elapsed = now.saturating_sub(time.trim().parse::<u64>().unwrap_or(now));
+                counters + &to_counter_rates(&sample, &to_rate_sample(body, sep), elapsed, sep)
             }
+            None => counters,
+        }
+    }
+
+    /// Only counters of the local host are accessible, DMV access must be denied
+    fn is_counters_fallback_possible(&self, err: &anyhow::Error) -> bool {
+        if !cfg!(windows)
+            || SectionStatus::failed(&err.to_string()) != SectionStatus::Failed("permission")
+        {
+            return false;
         }
+        let host = calc_real_host(self.endpoint.auth(), self.endpoint.conn())
+            .to_string()
+            .to_lowercase();
+        host == "localhost"
+            || host == "127.0.0.1"
+            || utils::get_local_computer_name().is_some_and(|n| n.to_lowercase() == host)
+    }
+
+    /// the same format as generated from `sys.dm_os_performance_counters`
+    async fn generate_counters_from_windows(
+        &self,
+        sep: char,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<String> {
+        let timeout = timeout.unwrap_or(PERF_COUNTERS_TIMEOUT);
+        let block = perf_counters::read(&self.name, timeout).await?;
+        let utc = utils::format_utc(utils::get_utc_now()?);
+        Ok(format!("None{sep}utc_time{sep}None{sep}{utc}\n")
+            + &self.process_counters_rows(&UniAnswer::Block(block), sep)?)
     }
 
     pub async fn generate_counters_entry(&self, client: &mut UniClient, sep: char) -> String {
@@ -598,25 +1335,234 @@ impl SqlInstance {
             Ok(result) => result,
             Err(err) => {
                 log::error!("Failed to get counters: {}", err);
-                format!("{sep}{sep}{}{sep}{}\n", self.name, err).to_string()
+                format!("{sep}{sep}{}{sep}{}\n", self.label, err).to_string()
             }
         }
     }
 
-    fn process_counters_rows(&self, answer: &UniAnswer, sep: char) -> Result<String> {
-        let z: Vec<String> = match answer {
-            UniAnswer::Rows(rows) => rows
-                .iter()
-                .map(|row| {
-                    let counter = Counter::from_row(row);
-                    counter.into_string(sep)
-                })
-                .collect(),
+    /// Age of the last known good DBCC CHECKDB of every database against `max_age_days`
+    pub async fn generate_checkdb_section(
+        &self,
+        client: &mut UniClient,
+        section: &Section,
+        query: &str,
+    ) -> String {
+        let sep = section.sep();
+        match run_custom_query(client, query).await {
+            Ok(answers) => to_checkdb_entries(
+                &self.label,
+                &answers,
+                section.max_age_days(),
+                sep,
+                self.name_encoding(),
+                self.value_locale.as_ref(),
+            ),
+            Err(err) => {
+                log::error!("Failed to get last known good CHECKDB: {}", err);
+                format!("{}{sep}error{sep}{}\n", self.label, prepare_error(&err))
+            }
+        }
+    }
+
+    /// Only options differing from the baseline of the section are reported
+    pub async fn generate_db_option_drift_section(
+        &self,
+        client: &mut UniClient,
+        section: &Section,
+        query: &str,
+    ) -> String {
+        let sep = section.sep();
+        match run_custom_query(client, query).await {
+            Ok(answers) => to_db_option_drift_entries(
+                &self.label,
+                &answers,
+                section.baseline(),
+                sep,
+                self.name_encoding(),
+            ),
+            Err(err) => {
+                log::error!("Failed to get database options: {}", err);
+                format!("{}{sep}error{sep}{}\n", self.label, prepare_error(&err))
+            }
+        }
+    }
+
+    /// Roles of the instance as host labels `{"mssql/role/<role>": "yes"}`:
+    /// every instance of the host adds own roles, they can't overwrite each other
+    pub async fn generate_labels_section(&self, client: &mut UniClient, query: &str) -> String {
+        match run_custom_query(client, query)
+            .await
+            .and_then(validate_rows)
+            .map(|answers| RoleFacts::from_answer(&answers[0]))
+        {
+            Ok(facts) => classify_roles(&facts)
+                .iter()
+                .map(|role| format!("{{\"mssql/role/{role}\": \"yes\"}}\n"))
+                .collect::<Vec<String>>()
+                .join(""),
+            Err(err) => {
+                log::error!("Failed to classify instance: {}", err);
+                String::new()
+            }
+        }
+    }
+
+    /// Backups to URL are verified against `max_age_days`, other devices are reported as is
+    pub async fn generate_backup_url_section(
+        &self,
+        client: &mut UniClient,
+        section: &Section,
+        query: &str,
+    ) -> String {
+        let sep = section.sep();
+        match run_custom_query(client, query).await {
+            Ok(answers) => to_backup_url_entries(
+                &self.label,
+                &answers,
+                section.max_age_days(),
+                sep,
+                self.name_encoding(),
+                self.value_locale.as_ref(),
+            ),
+            Err(err) => {
+                log::error!("Failed to get backup devices: {}", err);
+                format!("{}{sep}error{sep}{}\n", self.label, prepare_error(&err))
+            }
+        }
+    }
+
+    /// Two samples of the counters are taken `sample_interval` seconds apart to calculate rates
+    pub async fn generate_perf_key_metrics_section(
+        &self,
+        client: &mut UniClient,
+        section: &Section,
+        query: &str,
+    ) -> String {
+        let sep = section.sep();
+        let metrics = match sample_counters(client, query, self.name_encoding()).await {
+            Ok(first) => {
+                let started = std::time::Instant::now();
+                tokio::time::sleep(std::time::Duration::from_secs(
+                    section.sample_interval().into(),
+                ))
+                .await;
+                sample_counters(client, query, self.name_encoding())
+                    .await
+                    .map(|second| {
+                        to_perf_key_metrics(&self.label, &first, &second, started.elapsed(), sep)
+                    })
+            }
+            Err(e) => Err(e),
+        };
+        match metrics {
+            Ok(result) => result,
+            Err(err) => {
+                log::error!("Failed to get key metrics: {}", err);
+                format!("{}{sep}error{sep}None{sep}{}\n", self.label, err)
+            }
+        }
+    }
+
+    /// Two samples of the wait statistics are taken `sample_interval` seconds apart
+    pub async fn generate_waits_delta_section(
+        &self,
+        client: &mut UniClient,
+        section: &Section,
+        query: &str,
+    ) -> String {
+        let sep = section.sep();
+        let x = match sample_waits(client, query).await {
+            Ok(first) => {
+                let started = std::time::Instant::now();
+                tokio::time::sleep(std::time::Duration::from_secs(
+                    section.sample_interval().into(),
+                ))
+                .await;
+                sample_waits(client, query).await.map(|second| {
+                    to_waits_delta_entries(&self.label, &first, &second, started.elapsed(), sep)
+                })
+            }
+            Err(e) => Err(e),
+        };
+        match x {
+            Ok(result) => result,
+            Err(err) => {
+                log::error!("Failed to get waits: {}", err);
+                format!("{}{sep}error{sep}{}\n", self.label, prepare_error(&err))
+            }
+        }
+    }
+
+    /// Samples of user connections are taken `sample_interval` seconds apart,
+    /// the last sample is reported together with the maximum of all samples
+    pub async fn generate_db_connections_section(
+        &self,
+        client: &mut UniClient,
+        section: &Section,
+        query: &str,
+    ) -> String {
+        let sep = section.sep();
+        let mut samples: Vec<Vec<DbConnections>> = Vec::new();
+        for i in 0..defaults::DB_CONNECTIONS_SAMPLES {
+            if i > 0 {
+                tokio::time::sleep(std::time::Duration::from_secs(
+                    section.sample_interval().into(),
+                ))
+                .await;
+            }
+            match sample_db_connections(client, query).await {
+                Ok(sample) => samples.push(sample),
+                Err(err) => {
+                    log::error!("Failed to get connections: {}", err);
+                    return format!("{}{sep}error{sep}{}\n", self.label, prepare_error(&err));
+                }
+            }
+        }
+        to_db_connections_entries(&self.label, &samples, sep, self.name_encoding())
+    }
+
+    /// Samples of page latch waits in tempdb are taken `sample_interval` seconds apart,
+    /// waits on allocation pages are counted per page type
+    pub async fn generate_tempdb_contention_section(
+        &self,
+        client: &mut UniClient,
+        section: &Section,
+        query: &str,
+    ) -> String {
+        let sep = section.sep();
+        let mut samples: Vec<Vec<AllocationWait>> = Vec::new();
+        for i in 0..defaults::TEMPDB_CONTENTION_SAMPLES {
+            if i > 0 {
+                tokio::time::sleep(std::time::Duration::from_secs(
+                    section.sample_interval().into(),
+                ))
+                .await;
+            }
+            match sample_tempdb_contention(client, query).await {
+                Ok(sample) => samples.push(sample),
+                Err(err) => {
+                    log::error!("Failed to get tempdb contention: {}", err);
+                    return format!("{}{sep}error{sep}{}\n", self.label, prepare_error(&err));
+                }
+            }
+        }
+        to_tempdb_contention_entries(&self.label, &samples, sep)
+    }
+
+    fn process_counters_rows(&self, answer: &UniAnswer, sep: char) -> Result<String> {
+        let z: Vec<String> = match answer {
+            UniAnswer::Rows(rows) => rows
+                .iter()
+                .map(|row| {
+                    let counter = Counter::from_row(row, self.name_encoding());
+                    counter.into_string(sep)
+                })
+                .collect(),
             UniAnswer::Block(block) => block
                 .rows
                 .iter()
                 .map(|row| {
-                    let counter = Counter::from_block(row);
+                    let counter = Counter::from_block(row, self.name_encoding());
                     counter.into_string(sep)
                 })
                 .collect(),
@@ -634,13 +1580,13 @@ impl SqlInstance {
             Ok(rows) => {
                 if rows.is_empty() || rows[0].is_empty() {
                     log::info!("No blocking sessions");
-                    return format!("{}{sep}No blocking sessions\n", self.name).to_string();
+                    return format!("{}{sep}No blocking sessions\n", self.label).to_string();
                 }
                 self.process_blocked_sessions_rows(&rows, sep)
             }
             Err(err) => {
                 log::info!("No blocking sessions: {}", err);
-                format!("{}{sep}{err:?}\n", self.name).to_string()
+                format!("{}{sep}{err:?}\n", self.label).to_string()
             }
         }
     }
@@ -651,65 +1597,61 @@ impl SqlInstance {
         databases: &[String],
         query: &str,
         sep: char,
-    ) -> String {
-        let tasks = databases.iter().map(move |database| {
-            self.generate_table_spaces_section_database(endpoint, database, query, sep)
-        });
-
-        let results = stream::iter(tasks)
-            .buffer_unordered(MAX_CONNECTIONS as usize)
-            .collect::<Vec<_>>()
-            .await;
-
-        results.join("")
-    }
-
-    pub async fn generate_table_spaces_section_database(
-        &self,
-        endpoint: &Endpoint,
-        database: &str,
-        query: &str,
-        sep: char,
     ) -> String {
         let format_error = |d: &str, e: &anyhow::Error| {
             format!(
                 "{} {} - - - - - - - - - - - - {}\n",
                 self.mssql_name(),
-                d.replace(' ', "_"),
+                self.name_field(d),
                 prepare_error(e)
             )
-            .to_string()
         };
-        match self
-            .create_client(endpoint, Some(database.to_owned()))
-            .await
-        {
-            Ok(mut c) => match run_custom_query(&mut c, query).await {
-                Ok(rows) => to_table_spaces_entry(&self.mssql_name(), database, &rows, sep),
-                Err(err) => {
-                    // fallback on simple query sp_spaceused for very old SQL Servers
-                    log::info!("Failed to get table spaces: {}", err);
-                    run_custom_query(&mut c, sqls::query::SPACE_USED_SIMPLE)
-                        .await
-                        .map(|rows| to_table_spaces_entry(&self.mssql_name(), database, &rows, sep))
-                        .unwrap_or_else(|e| format_error(database, &e))
+        self.for_each_database(
+            endpoint,
+            databases,
+            |mut c, database| async move {
+                match run_custom_query(&mut c, query).await {
+                    Ok(rows) => Ok(to_table_spaces_entry(
+                        &self.mssql_name(),
+                        database,
+                        &rows,
+                        sep,
+                        self.name_encoding(),
+                    )),
+                    Err(err) => {
+                        // fallback on simple query sp_spaceused for very old SQL Servers
+                        record_warning(&format!(
+                            "Failed to get table spaces of {database}: {err}, using sp_spaceused"
+                        ));
+                        run_custom_query(&mut c, sqls::query::SPACE_USED_SIMPLE)
+                            .await
+                            .map(|rows| {
+                                to_table_spaces_entry(
+                                    &self.mssql_name(),
+                                    database,
+                                    &rows,
+                                    sep,
+                                    self.name_encoding(),
+                                )
+                            })
+                    }
                 }
             },
-            Err(err) => format_error(database, &err),
-        }
+            format_error,
+        )
+        .await
     }
 
     pub async fn generate_backup_section(
         &self,
         client: &mut UniClient,
+        databases: &[String],
         query: &str,
         sep: char,
     ) -> String {
-        let databases = self.generate_databases(client).await;
-
         let result = run_custom_query(client, query)
             .await
-            .map(|rows| self.process_backup_rows(&rows, &databases, sep));
+            .map(|rows| self.process_backup_rows(&rows, databases, sep));
         match result {
             Ok(output) => output,
             Err(err) => {
@@ -720,7 +1662,7 @@ impl SqlInstance {
                         format!(
                             "{}{sep}{}{sep}-{sep}-{sep}-{sep}{}\n",
                             self.mssql_name(),
-                            d.replace(' ', "_"),
+                            self.name_field(d),
                             prepare_error(&err)
                         )
                     })
@@ -730,56 +1672,6 @@ impl SqlInstance {
         }
     }
 
-    pub fn generate_database_indexed_section_threading(
-        &self,
-        databases: &[String],
-        endpoint: &Endpoint,
-        section: &Section,
-        query: &str,
-        sep: char,
-    ) -> String {
-        let chunks = if databases.len() >= 64 {
-            let max_chunk = (databases.len() + 3usize) / 4usize;
-            let min_chunk = 16usize;
-            databases.chunks(std::cmp::max(min_chunk, max_chunk))
-        } else if databases.len() >= 8 {
-            let max_chunk = (databases.len() + 1usize) / 2usize;
-            let min_chunk = 4usize;
-            databases.chunks(std::cmp::max(min_chunk, max_chunk))
-        } else {
-            databases.chunks(databases.len())
-        };
-        thread::scope(|s| {
-            let s: Vec<_> = chunks
-                .into_iter()
-                .map(|chunk| {
-                    s.spawn(|| {
-                        let rt = tokio::runtime::Runtime::new().unwrap();
-                        match section.name() {
-                            names::TRANSACTION_LOG => rt.block_on(
-                                self.generate_transaction_logs_section(endpoint, chunk, query, sep),
-                            ),
-                            names::TABLE_SPACES => rt.block_on(
-                                self.generate_table_spaces_section(endpoint, chunk, query, sep),
-                            ),
-                            names::DATAFILES => rt.block_on(
-                                self.generate_datafiles_section(endpoint, chunk, query, sep),
-                            ),
-                            names::CLUSTERS => rt.block_on(
-                                self.generate_transaction_logs_section(endpoint, chunk, query, sep),
-                            ),
-                            _ => format!("{} not implemented\n", section.name()).to_string(),
-                        }
-                    })
-                })
-                .collect();
-            s.into_iter()
-                .map(|h| h.join().unwrap_or("ERROR: failed to join".to_string()))
-                .collect::<Vec<String>>()
-                .join("")
-        })
-    }
-
     pub async fn generate_database_indexed_section_async(
         &self,
         databases: &[String],
@@ -805,7 +1697,26 @@ impl SqlInstance {
                 self.generate_clusters_section(endpoint, databases, query, sep)
                     .await
             }
-            _ => format!("{} not implemented\n", section.name()).to_string(),
+            names::PERMISSIONS => {
+                self.generate_permissions_section(endpoint, databases, query, sep)
+                    .await
+            }
+            names::FILEGROUPS => {
+                self.generate_filegroups_section(endpoint, databases, query, sep)
+                    .await
+            }
+            names::CONSTRAINTS => {
+                self.generate_constraints_section(endpoint, databases, query, sep)
+                    .await
+            }
+            names::TOP_TABLES => {
+                self.generate_top_tables_section(endpoint, databases, query, sep)
+                    .await
+            }
+            _ => {
+                record_warning(&format!("{} not implemented", section.name()));
+                String::new()
+            }
         }
     }
 
@@ -816,41 +1727,30 @@ impl SqlInstance {
         query: &str,
         sep: char,
     ) -> String {
-        let tasks = databases.iter().map(move |database| {
-            self.generate_transaction_logs_section_database(endpoint, database, query, sep)
-        });
-
-        let results = stream::iter(tasks)
-            .buffer_unordered(MAX_CONNECTIONS as usize)
-            .collect::<Vec<_>>()
-            .await;
-
-        results.join("")
-    }
-    pub async fn generate_transaction_logs_section_database(
-        &self,
-        endpoint: &Endpoint,
-        database: &str,
-        query: &str,
-        sep: char,
-    ) -> String {
-        match self
-            .create_client(endpoint, Some(database.to_owned()))
-            .await
-        {
-            Ok(mut c) => run_custom_query(&mut c, query)
-                .await
-                .map(|rows| to_transaction_logs_entries(&self.name, database, &rows, sep))
-                .unwrap_or_else(|e| self.format_some_file_error(database, &e, sep)),
-            Err(err) => self.format_some_file_error(database, &err, sep),
-        }
+        self.for_each_database(
+            endpoint,
+            databases,
+            |mut c, database| async move {
+                run_custom_query(&mut c, query).await.map(|rows| {
+                    to_transaction_logs_entries(
+                        &self.label,
+                        database,
+                        &rows,
+                        sep,
+                        self.name_encoding(),
+                    )
+                })
+            },
+            |d, e| self.format_some_file_error(d, e, sep),
+        )
+        .await
     }
 
     fn format_some_file_error(&self, d: &str, e: &anyhow::Error, sep: char) -> String {
         format!(
             "{}{sep}{}|-|-|-|-|-|-|{:?}\n",
-            self.name,
-            d.replace(' ', "_"),
+            self.label,
+            self.name_field(d),
             prepare_error(e)
         )
         .to_string()
@@ -863,130 +1763,248 @@ impl SqlInstance {
         query: &str,
         sep: char,
     ) -> String {
-        let tasks = databases.iter().map(move |database| {
-            self.generate_datafiles_section_database(endpoint, database, query, sep)
-        });
-
-        let results = stream::iter(tasks)
-            .buffer_unordered(MAX_CONNECTIONS as usize)
-            .collect::<Vec<_>>()
-            .await;
-
-        results.join("")
+        self.for_each_database(
+            endpoint,
+            databases,
+            |mut c, database| async move {
+                run_custom_query(&mut c, query).await.map(|rows| {
+                    to_datafiles_entries(&self.label, database, &rows, sep, self.name_encoding())
+                })
+            },
+            |d, e| self.format_some_file_error(d, e, sep),
+        )
+        .await
     }
 
-    pub async fn generate_datafiles_section_database(
+    pub async fn generate_permissions_section(
         &self,
         endpoint: &Endpoint,
-        database: &str,
+        databases: &[String],
         query: &str,
         sep: char,
     ) -> String {
-        match self
-            .create_client(endpoint, Some(database.to_owned()))
-            .await
-        {
-            Ok(mut c) => run_custom_query(&mut c, query)
-                .await
-                .map(|rows| to_datafiles_entries(&self.name, database, &rows, sep))
-                .unwrap_or_else(|e| self.format_some_file_error(database, &e, sep)),
-            Err(err) => self.format_some_file_error(database, &err, sep),
-        }
+        let format_error = |d: &str, e: &anyhow::Error| {
+            format!(
+                "{}{sep}{}{sep}error{sep}{}\n",
+                self.label,
+                self.name_field(d),
+                prepare_error(e)
+            )
+        };
+        self.for_each_database(
+            endpoint,
+            databases,
+            |mut c, database| async move {
+                run_custom_query(&mut c, query).await.map(|rows| {
+                    to_permissions_entries(&self.label, database, &rows, sep, self.name_encoding())
+                })
+            },
+            format_error,
+        )
+        .await
     }
 
-    pub async fn generate_databases_section(
+    pub async fn generate_filegroups_section(
         &self,
-        client: &mut UniClient,
+        endpoint: &Endpoint,
         databases: &[String],
         query: &str,
         sep: char,
     ) -> String {
-        run_custom_query(client, query)
-            .await
-            .map(|rows| to_databases_entries(&self.name, &rows, sep))
-            .unwrap_or_else(|e| {
-                databases
-                    .iter()
-                    .map(|d| self.format_databases_error(d, &e, sep))
-                    .collect::<Vec<String>>()
-                    .join("")
-            })
-    }
-
-    fn format_databases_error(&self, d: &str, e: &anyhow::Error, sep: char) -> String {
-        format!(
-            "{}{sep}{}{sep}{}{}\n",
-            self.name,
-            d.replace(' ', "_"),
-            prepare_error(e),
-            format!("{sep}-").repeat(3),
+        let format_error = |d: &str, e: &anyhow::Error| {
+            format!(
+                "{}{sep}{}{sep}error{sep}-{sep}-{sep}-{sep}-{sep}-{sep}{}\n",
+                self.label,
+                self.name_field(d),
+                prepare_error(e)
+            )
+        };
+        self.for_each_database(
+            endpoint,
+            databases,
+            |mut c, database| async move {
+                run_custom_query(&mut c, query).await.map(|rows| {
+                    to_filegroups_entries(&self.label, database, &rows, sep, self.name_encoding())
+                })
+            },
+            format_error,
         )
+        .await
     }
 
-    /// doesn't return error - the same behavior as plugin
-    pub async fn generate_databases(&self, client: &mut UniClient) -> Vec<String> {
-        let result = run_known_query(client, sqls::Id::DatabaseNames)
-            .await
-            .and_then(validate_rows)
-            .map(|rows| self.process_databases_rows(&rows));
-        match result {
-            Ok(result) => result,
-            Err(err) => {
-                log::error!("Failed to get databases: {}", err);
-                vec![]
-            }
-        }
+    pub async fn generate_constraints_section(
+        &self,
+        endpoint: &Endpoint,
+        databases: &[String],
+        query: &str,
+        sep: char,
+    ) -> String {
+        let format_error = |d: &str, e: &anyhow::Error| {
+            format!(
+                "{}{sep}{}{sep}error{sep}-{sep}-{sep}-{sep}-{sep}{}\n",
+                self.label,
+                self.name_field(d),
+                prepare_error(e)
+            )
+        };
+        self.for_each_database(
+            endpoint,
+            databases,
+            |mut c, database| async move {
+                run_custom_query(&mut c, query).await.map(|rows| {
+                    to_constraints_entries(&self.label, database, &rows, sep, self.name_encoding())
+                })
+            },
+            format_error,
+        )
+        .await
     }
 
-    pub async fn generate_clusters_section(
+    pub async fn generate_top_tables_section(
         &self,
         endpoint: &Endpoint,
         databases: &[String],
         query: &str,
         sep: char,
     ) -> String {
-        let tasks = databases.iter().map(move |database| {
-            self.generate_clusters_section_database(endpoint, database, query, sep)
+        let format_error = |d: &str, e: &anyhow::Error| {
+            format!(
+                "{}{sep}{}{sep}error{sep}-{sep}-{sep}-{sep}{}\n",
+                self.label,
+                self.name_field(d),
+                prepare_error(e)
+            )
+        };
+        self.for_each_database(
+            endpoint,
+            databases,
+            |mut c, database| async move {
+                run_custom_query(&mut c, query).await.map(|rows| {
+                    to_top_tables_entries(&self.label, database, &rows, sep, self.name_encoding())
+                })
+            },
+            format_error,
+        )
+        .await
+    }
+
+    /// Connects to every database and calls `f` with the client, at most
+    /// MAX_CONNECTIONS databases are processed concurrently.
+    /// Connection error as well as error of `f` is reported using `format_error`
+    pub async fn for_each_database<'a, F, Fut>(
+        &'a self,
+        endpoint: &'a Endpoint,
+        databases: &'a [String],
+        f: F,
+        format_error: impl Fn(&str, &anyhow::Error) -> String,
+    ) -> String
+    where
+        F: Fn(UniClient, &'a str) -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        let f = &f;
+        let format_error = &format_error;
+        let tasks = databases.iter().map(|database| async move {
+            let result = match self
+                .create_client(endpoint, Some(database.to_owned()))
+                .await
+            {
+                Ok(client) => f(client, database).await,
+                Err(err) => Err(err),
+            };
+            result.unwrap_or_else(|e| {
+                record_query_error(&e);
+                format_error(database, &e)
+            })
         });
 
-        let results = stream::iter(tasks)
+        stream::iter(tasks)
             .buffer_unordered(MAX_CONNECTIONS as usize)
             .collect::<Vec<_>>()
-            .await;
+            .await
+            .join("")
+    }
+
+    pub async fn generate_databases_section(
+        &self,
+        client: &mut UniClient,
+        databases: &[String],
+        query: &str,
+        sep: char,
+    ) -> String {
+        run_custom_query(client, query)
+            .await
+            .map(|rows| {
+                to_databases_entries(&self.label, &rows, sep, self.name_encoding())
+                    + &to_missing_databases_entries(
+                        &self.label,
+                        &self.required_databases,
+                        &get_database_names(&rows),
+                        sep,
+                        self.name_encoding(),
+                    )
+            })
+            .unwrap_or_else(|e| {
+                databases
+                    .iter()
+                    .map(|d| self.format_databases_error(d, &e, sep))
+                    .collect::<Vec<String>>()
+                    .join("")
+            })
+    }
+
+    fn format_databases_error(&self, d: &str, e: &anyhow::Error, sep: char) -> String {
+        format!(
+            "{}{sep}{}{sep}{}{}\n",
+            self.label,
+            self.name_field(d),
+            prepare_error(e),
+            format!("{sep}-").repeat(3),
+        )
+    }
 
-        results.join("")
+    /// doesn't return error - the same behavior as plugin
+    pub async fn generate_databases(&self, client: &mut UniClient) -> Vec<String> {
+        let result = run_known_query(client, sqls::Id::DatabaseNames)
+            .await
+            .and_then(validate_rows)
+            .map(|rows| self.process_databases_rows(&rows));
+        match result {
+            Ok(result) => result,
+            Err(err) => {
+                log::error!("Failed to get databases: {}", err);
+                vec![]
+            }
+        }
     }
 
     /// Todo(sk): write a test
-    pub async fn generate_clusters_section_database(
+    pub async fn generate_clusters_section(
         &self,
         endpoint: &Endpoint,
-        database: &str,
+        databases: &[String],
         query: &str,
         sep: char,
     ) -> String {
         let format_error = |d: &str, e: &anyhow::Error| {
             format!(
                 "{}{sep}{}{sep}{sep}{sep}{:?}\n",
-                self.name,
-                d.replace(' ', "_"),
+                self.label,
+                self.name_field(d),
                 e
             )
         };
-        match self
-            .create_client(endpoint, Some(database.to_owned()))
-            .await
-        {
-            Ok(mut c) => match self
-                .generate_clusters_entry(&mut c, database, query, sep)
-                .await
-            {
-                Ok(None) => String::default(),
-                Ok(Some(entry)) => entry,
-                Err(err) => format_error(database, &err),
+        self.for_each_database(
+            endpoint,
+            databases,
+            |mut c, database| async move {
+                self.generate_clusters_entry(&mut c, database, query, sep)
+                    .await
+                    .map(Option::unwrap_or_default)
             },
-            Err(err) => format_error(database, &err),
-        }
+            format_error,
+        )
+        .await
     }
 
     async fn generate_clusters_entry(
@@ -1002,13 +2020,42 @@ impl SqlInstance {
         let (nodes, active_node) = self.get_cluster_nodes(client, query).await?;
         Ok(Some(format!(
             "{}{sep}{}{sep}{}{sep}{}",
-            self.name,
-            database.replace(' ', "_"),
+            self.label,
+            self.name_field(database),
             active_node,
             nodes
         )))
     }
 
+    /// false only if the instance is clustered and the active node is not the local host
+    async fn is_on_active_node(&self) -> bool {
+        if self.passive_owner.is_some() {
+            return false;
+        }
+        let mut client = match self.create_client(&self.endpoint, None).await {
+            Ok(client) => client,
+            Err(_) => return true, // error is to be reported by the instance itself
+        };
+        if !self
+            .is_database_clustered(&mut client)
+            .await
+            .unwrap_or(false)
+        {
+            return true;
+        }
+        let active_node = run_known_query(&mut client, sqls::Id::ClusterActiveNodes)
+            .await
+            .and_then(validate_rows)
+            .map(|answers| answers[0].get_active_node());
+        match (active_node, utils::get_local_computer_name()) {
+            (Ok(active_node), Some(local)) => {
+                log::info!("Instance {} active node `{active_node}`", self.name);
+                active_node.trim().eq_ignore_ascii_case(&local)
+            }
+            _ => true,
+        }
+    }
+
     async fn is_database_clustered(&self, client: &mut UniClient) -> Result<bool> {
         let answers = &run_known_query(client, sqls::Id::IsClustered)
             .await
@@ -1037,7 +2084,7 @@ impl SqlInstance {
         run_custom_query(client, query)
             .await
             .map(|rows| self.to_connections_entries(&rows, sep))
-            .unwrap_or_else(|e| format!("{}{sep}{}\n", self.name, prepare_error(&e)))
+            .unwrap_or_else(|e| format!("{}{sep}{}\n", self.label, prepare_error(&e)))
     }
 
     fn to_connections_entries(&self, answers: &[UniAnswer], sep: char) -> String {
@@ -1047,8 +2094,8 @@ impl SqlInstance {
                 .map(|row| {
                     format!(
                         "{}{sep}{}{sep}{}\n",
-                        self.name,
-                        row.get_value_by_idx(0).replace(' ', "_"), // for unknown reason we can't get it by name
+                        self.label,
+                        self.name_field(&row.get_value_by_idx(0)), // for unknown reason we can't get it by name
                         row.get_bigint_by_name("NumberOfConnections")
                     )
                 })
@@ -1060,12 +2107,14 @@ impl SqlInstance {
                 .map(|row| {
                     format!(
                         "{}{sep}{}{sep}{}\n",
-                        self.name,
-                        get_row_value_by_idx(row, 0).replace(' ', "_"), // for unknown reason we can't get it by name
-                        block
-                            .get_value_by_name(row, "NumberOfConnections")
-                            .parse::<i64>()
-                            .unwrap_or_default()
+                        self.label,
+                        self.name_field(&get_row_value_by_idx(row, 0)), // for unknown reason we can't get it by name
+                        locale::to_invariant_number(
+                            block.get_value_by_name(row, "NumberOfConnections"),
+                            self.value_locale.as_ref()
+                        )
+                        .parse::<i64>()
+                        .unwrap_or_default()
                     )
                 })
                 .collect::<Vec<String>>()
@@ -1081,11 +2130,11 @@ impl SqlInstance {
         section: &Section,
         query: Option<&str>,
     ) -> String {
-        match self.create_client(endpoint, section.main_db()).await {
+        match self.create_client(endpoint, self.main_db(section)).await {
             Ok(mut c) => {
                 let q = query.map(|q| q.to_owned()).unwrap_or_else(|| {
                     section
-                        .select_query(get_sql_dir(), self.version_major())
+                        .select_query(get_sql_dir(), self.version_table())
                         .unwrap_or_default()
                 });
                 run_custom_query(&mut c, q)
@@ -1094,13 +2143,13 @@ impl SqlInstance {
                     .map(|rows| {
                         format!(
                             "{}{}",
-                            section.first_line(Some(&self.name)),
+                            section.first_line(Some(&self.label)),
                             self.to_entries(rows, section.sep(), section),
                         )
                     })
-                    .unwrap_or_else(|e| format!("{} {}\n", self.name, prepare_error(&e)))
+                    .unwrap_or_else(|e| format!("{} {}\n", self.label, prepare_error(&e)))
             }
-            Err(err) => format!("{} {}\n", self.name, err),
+            Err(err) => format!("{} {}\n", self.label, err),
         }
     }
 
@@ -1112,29 +2161,73 @@ impl SqlInstance {
         match self.create_client(endpoint, None).await {
             Ok(mut c) => {
                 if let Some(query) =
-                    section.find_provided_query(get_sql_dir(), self.version_major())
+                    section.find_provided_query(get_sql_dir(), self.version_table())
                 {
                     Some(
-                        run_custom_query(&mut c, query)
+                        run_custom_query_with_params(&mut c, query, &section.params())
                             .await
                             .and_then(|r| section.validate_rows(r))
                             .map(|rows| {
                                 format!(
                                     "{}{}",
-                                    section.first_line(Some(&self.name)),
+                                    section.first_line(Some(&self.label)),
                                     self.to_entries(rows, section.sep(), section)
                                 )
                             })
-                            .unwrap_or_else(|e| format!("{} {}\n", self.name, prepare_error(&e))),
+                            .unwrap_or_else(|e| format!("{} {}\n", self.label, prepare_error(&e))),
                     )
                 } else {
                     None
                 }
             }
-            Err(err) => Some(format!("{} {}\n", self.name, err)),
+            Err(err) => Some(format!("{} {}\n", self.label, err)),
         }
     }
 
+    /// same as custom section, but the query is executed in every database
+    /// and every row is prefixed with the database name
+    pub async fn generate_custom_section_per_database(
+        &self,
+        endpoint: &Endpoint,
+        section: &Section,
+        databases: &[String],
+    ) -> Option<String> {
+        let query = section.find_provided_query(get_sql_dir(), self.version_table())?;
+        let query = query.as_str();
+        let params = &section.params();
+        let sep = section.sep();
+        let format_error = |d: &str, e: &anyhow::Error| {
+            format!(
+                "{}{sep}{}{sep}{}\n",
+                self.label,
+                self.name_field(d),
+                prepare_error(e)
+            )
+        };
+        let body = self
+            .for_each_database(
+                endpoint,
+                databases,
+                |mut c, database| async move {
+                    run_custom_query_with_params(&mut c, query, params)
+                        .await
+                        .and_then(|r| section.validate_rows(r))
+                        .map(|rows| {
+                            prefix_entries(
+                                &self.to_entries(rows, sep, section),
+                                database,
+                                sep,
+                                self.name_encoding(),
+                            )
+                        })
+                },
+                format_error,
+            )
+            .await;
+
+        Some(section.first_line(Some(&self.label)) + &body)
+    }
+
     /// rows must be not empty
     fn to_entries(&self, answers: Vec<UniAnswer>, sep: char, section: &Section) -> String {
         // just a safety guard, the function should not get empty rows
@@ -1142,8 +2235,20 @@ impl SqlInstance {
             return String::new();
         }
 
-        let mut answers_copy = answers;
-        let answer = answers_copy.remove(0);
+        let count = if section.is_multi_result() {
+            answers.len()
+        } else {
+            1
+        };
+        answers
+            .into_iter()
+            .take(count)
+            .map(|answer| self.to_answer_entries(answer, sep, section))
+            .collect::<Vec<String>>()
+            .join("")
+    }
+
+    fn to_answer_entries(&self, answer: UniAnswer, sep: char, section: &Section) -> String {
         // AVAILABILITY section should have a crlf after every row
         let additional_row = if section.name() == names::AVAILABILITY_GROUPS {
             "\n"
@@ -1151,7 +2256,27 @@ impl SqlInstance {
             ""
         };
 
+        let filters = section.filters();
+        let masking = section.masking();
         let result = match answer {
+            UniAnswer::Rows(rows) if !filters.is_empty() || !masking.is_empty() => {
+                let headline = rows
+                    .first()
+                    .map(|r| {
+                        r.columns()
+                            .iter()
+                            .map(|c| c.name().to_string())
+                            .collect::<Vec<String>>()
+                    })
+                    .unwrap_or_default();
+                rows.into_iter()
+                    .map(|r| r.get_all_values())
+                    .filter(|r| is_row_passed(filters, &headline, r))
+                    .map(|r| mask_row(masking, &headline, r))
+                    .map(|r| r.join(&sep.to_string()) + additional_row)
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
             UniAnswer::Rows(rows) => rows
                 .into_iter()
                 .map(|r| r.get_all(sep) + additional_row)
@@ -1160,6 +2285,8 @@ impl SqlInstance {
             UniAnswer::Block(block) => block
                 .rows
                 .iter()
+                .filter(|r| is_row_passed(filters, &block.headline, r))
+                .map(|r| mask_row(masking, &block.headline, r.clone()))
                 .map(|r| r.join(&sep.to_string()) + additional_row)
                 .collect::<Vec<String>>()
                 .join("\n"),
@@ -1176,13 +2303,13 @@ impl SqlInstance {
         match answers.first() {
             Some(UniAnswer::Rows(rows)) => rows
                 .iter()
-                .map(|row| to_blocked_session_entry(&self.name, row, sep))
+                .map(|row| to_blocked_session_entry(&self.label, row, sep))
                 .collect::<Vec<String>>()
                 .join(""),
             Some(UniAnswer::Block(block)) => block
                 .rows
                 .iter()
-                .map(|row| to_blocked_session_entry_odbc(&self.name, row, sep))
+                .map(|row| to_blocked_session_entry_odbc(&self.label, row, sep))
                 .collect::<Vec<String>>()
                 .join(""),
             None => {
@@ -1253,7 +2380,14 @@ impl SqlInstance {
                     let database_name = row.get_value_by_name("database_name");
                     if databases.contains(&database_name) {
                         found_databases.insert(database_name.to_lowercase());
-                        to_backup_entry(&self.mssql_name(), &database_name, row, sep)
+                        to_backup_entry(
+                            &self.mssql_name(),
+                            &database_name,
+                            row,
+                            sep,
+                            self.name_encoding(),
+                            self.value_locale.as_ref(),
+                        )
                     } else {
                         None
                     }
@@ -1266,7 +2400,15 @@ impl SqlInstance {
                     let database_name = block.get_value_by_name(row, "database_name");
                     if databases.contains(&database_name) {
                         found_databases.insert(database_name.to_lowercase());
-                        to_backup_entry_odbc(&self.mssql_name(), &database_name, block, row, sep)
+                        to_backup_entry_odbc(
+                            &self.mssql_name(),
+                            &database_name,
+                            block,
+                            row,
+                            sep,
+                            self.name_encoding(),
+                            self.value_locale.as_ref(),
+                        )
                     } else {
                         None
                     }
@@ -1289,7 +2431,7 @@ impl SqlInstance {
                 format!(
                     "{}{sep}{}{sep}-{sep}-{sep}-{sep}no backup found\n",
                     self.mssql_name(),
-                    db.replace(' ', "_")
+                    self.name_field(db)
                 )
             })
             .collect()
@@ -1302,50 +2444,482 @@ impl SqlInstance {
     pub fn computer_name(&self) -> &Option<ComputerName> {
         &self.computer_name
     }
+
+    /// the host of the instance: computer name if known, otherwise the endpoint host
+    fn host_key(&self) -> String {
+        self.computer_name
+            .as_ref()
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| self.endpoint.hostname().to_string())
+            .to_lowercase()
+    }
 }
 
 pub async fn create_tcp_client(
     endpoint: &Endpoint,
     database: Option<String>,
     port: Option<Port>,
+    choices: &PasswordChoices,
 ) -> Result<UniClient> {
+    if let Some(connection_string) = endpoint.conn().connection_string() {
+        return client::connect_by_connection_string(
+            endpoint,
+            connection_string,
+            database,
+            choices,
+        )
+        .await;
+    }
     let (auth, conn) = endpoint.split();
-    let client = match auth.auth_type() {
+    match auth.auth_type() {
         AuthType::SqlServer | AuthType::Windows => {
-            if let Some(credentials) = client::obtain_config_credentials(auth) {
+            let host = conn.hostname();
+            client::logon_with_candidates(auth, &host, choices, |credentials| {
                 client::ClientBuilder::new()
-                    .logon_on_port(&conn.hostname(), port, credentials)
-                    .database(database)
-            } else {
-                anyhow::bail!("Not provided credentials")
-            }
+                    .logon_on_port(&host, port.clone(), credentials)
+                    .database(database.clone())
+                    .connect_options(conn.connect_options())
+                    .build()
+            })
+            .await
         }
 
         #[cfg(windows)]
-        AuthType::Integrated => client::ClientBuilder::new()
-            .local_by_port(port, Some(conn.hostname()))
-            .database(database),
+        AuthType::Integrated => {
+            client::ClientBuilder::new()
+                .local_by_port(port, Some(conn.hostname()))
+                .database(database)
+                .connect_options(conn.connect_options())
+                .build()
+                .await
+        }
 
         _ => anyhow::bail!("Not supported authorization type"),
-    };
-    client.build().await
+    }
 }
 
 pub fn create_odbc_client(
     instance_name: &InstanceName,
     database: Option<String>,
+    options: &ConnectOptions,
 ) -> Result<UniClient> {
     #[cfg(unix)]
-    anyhow::bail!("ODBC Not supported `{}` db:`{:?}`", instance_name, database);
+    anyhow::bail!(
+        "ODBC Not supported `{}` db:`{:?}` options:`{}`",
+        instance_name,
+        database,
+        options.to_odbc_attributes()
+    );
     #[cfg(windows)]
     {
         let connection_string =
-            odbc::make_connection_string(instance_name, database.as_deref(), None);
-        Ok(UniClient::Odbc(OdbcClient::new(connection_string)))
+            odbc::make_connection_string(instance_name, database.as_deref(), None)
+                + &options.to_odbc_attributes();
+        Ok(UniClient::Odbc(
+            OdbcClient::new(connection_string),
+            client::Pacing::default(),
+        ))
+    }
+}
+
+/// Generators of the builtin sections, see [`generator`]
+pub fn builtin_generators() -> Vec<Arc<dyn SectionGenerator>> {
+    vec![
+        Arc::new(FnGenerator::new(&[names::INSTANCE], generate_instance_body)),
+        Arc::new(FnGenerator::new(&[names::COUNTERS], generate_counters_body)),
+        Arc::new(FnGenerator::new(&[names::BACKUP], generate_backup_body)),
+        Arc::new(FnGenerator::new(
+            &[names::BLOCKED_SESSIONS],
+            generate_blocked_sessions_body,
+        )),
+        Arc::new(FnGenerator::new(
+            &[names::DATABASES],
+            generate_databases_body,
+        )),
+        Arc::new(FnGenerator::new(
+            &[names::CONNECTIONS],
+            generate_connections_body,
+        )),
+        Arc::new(FnGenerator::new(
+            &[
+                names::TRANSACTION_LOG,
+                names::TABLE_SPACES,
+                names::DATAFILES,
+                names::PERMISSIONS,
+                names::FILEGROUPS,
+                names::CONSTRAINTS,
+                names::TOP_TABLES,
+            ],
+            generate_database_indexed_body,
+        )),
+        Arc::new(FnGenerator::new(&[names::CLUSTERS], generate_clusters_body)),
+        Arc::new(FnGenerator::new(
+            &[names::PERF_KEY_METRICS],
+            generate_perf_key_metrics_body,
+        )),
+        Arc::new(FnGenerator::new(
+            &[names::WAITS_DELTA],
+            generate_waits_delta_body,
+        )),
+        Arc::new(FnGenerator::new(
+            &[names::DB_CONNECTIONS],
+            generate_db_connections_body,
+        )),
+        Arc::new(FnGenerator::new(
+            &[names::TEMPDB_CONTENTION],
+            generate_tempdb_contention_body,
+        )),
+        Arc::new(FnGenerator::new(&[names::CHECKDB], generate_checkdb_body)),
+        Arc::new(FnGenerator::new(
+            &[names::DB_OPTION_DRIFT],
+            generate_db_option_drift_body,
+        )),
+        Arc::new(FnGenerator::new(&[names::LABELS], generate_labels_body)),
+        Arc::new(FnGenerator::new(
+            &[names::BACKUP_URL],
+            generate_backup_url_body,
+        )),
+        Arc::new(FnGenerator::new(
+            &[names::HOST_SETTINGS],
+            generate_host_settings_body,
+        )),
+        Arc::new(FnGenerator::new(
+            &[
+                names::MIRRORING,
+                names::JOBS,
+                names::AVAILABILITY_GROUPS,
+                names::TRACE_FLAGS,
+                names::ENCRYPTION,
+                names::SCHEDULERS,
+                names::SESSIONS,
+                names::FILESTREAM,
+                names::UPTIME,
+                names::VERSION_STORE,
+                names::MEMORY_FEATURES,
+                names::DISTRIBUTED_TRANSACTIONS,
+                names::COLLATION,
+                names::AGENT_HEALTH,
+                names::CERTS,
+                names::QUERY_STORE,
+                names::LOGINS,
+                names::AG_CONFIG,
+                names::VOLUMES,
+                names::DEFAULT_TRACE,
+                names::ORPHANED_FILES,
+                names::MAINTENANCE,
+                names::AUDITS,
+                names::AZURE_RESOURCES,
+            ],
+            generate_unified_body,
+        )),
+    ]
+}
+
+fn generate_instance_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        let GeneratorInput {
+            instance,
+            client,
+            section,
+            context,
+            ..
+        } = input;
+        let sep = section.sep();
+        let details = match context.properties() {
+            Some(properties) => instance.to_details_entry(properties, sep),
+            None => instance.generate_details_entry(client, sep).await,
+        };
+        let connectivity = instance.generate_connectivity_entry(client, sep).await;
+        instance.generate_good_state_entry(sep)
+            + &instance.generate_listener_entry(context, sep)
+            + &connectivity
+            + &details
+    })
+}
+
+fn generate_counters_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        let counters = input
+            .instance
+            .generate_counters_section(
+                input.client,
+                input.query,
+                input.section.sep(),
+                input.section.timeout(),
+            )
+            .await;
+        if input.section.compute_rates() {
+            input
+                .instance
+                .append_counter_rates(counters, input.section.sep())
+        } else {
+            counters
+        }
+    })
+}
+
+fn generate_backup_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_backup_section(
+                input.client,
+                input.context.databases(),
+                input.query,
+                input.section.sep(),
+            )
+            .await
+    })
+}
+
+fn generate_blocked_sessions_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_sessions_section(input.client, input.query, input.section.sep())
+            .await
+    })
+}
+
+fn generate_databases_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_databases_section(
+                input.client,
+                input.context.databases(),
+                input.query,
+                input.section.sep(),
+            )
+            .await
+    })
+}
+
+fn generate_connections_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_connections_section(input.client, input.query, input.section.sep())
+            .await
+    })
+}
+
+fn generate_database_indexed_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_database_indexed_section_async(
+                input.context.databases(),
+                input.endpoint,
+                input.section,
+                input.query,
+                input.section.sep(),
+            )
+            .await
+    })
+}
+
+/// clusters are reported only if the instance is clustered or this is unknown
+fn generate_clusters_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    if input.context.is_clustered() == Some(false) {
+        return Box::pin(async { String::new() });
+    }
+    generate_database_indexed_body(input)
+}
+
+fn generate_perf_key_metrics_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_perf_key_metrics_section(input.client, input.section, input.query)
+            .await
+    })
+}
+
+fn generate_waits_delta_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_waits_delta_section(input.client, input.section, input.query)
+            .await
+    })
+}
+
+fn generate_db_connections_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_db_connections_section(input.client, input.section, input.query)
+            .await
+    })
+}
+
+fn generate_tempdb_contention_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_tempdb_contention_section(input.client, input.section, input.query)
+            .await
+    })
+}
+
+fn generate_db_option_drift_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_db_option_drift_section(input.client, input.section, input.query)
+            .await
+    })
+}
+
+fn generate_labels_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_labels_section(input.client, input.query)
+            .await
+    })
+}
+
+fn generate_checkdb_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_checkdb_section(input.client, input.section, input.query)
+            .await
+    })
+}
+
+fn generate_backup_url_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_backup_url_section(input.client, input.section, input.query)
+            .await
+    })
+}
+
+/// power plan is known only for the local host
+fn generate_host_settings_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        let endpoint = input.endpoint;
+        let power_plan = if calc_real_host(endpoint.auth(), endpoint.conn()) == *LOCAL_HOST {
+            host::read_power_plan().unwrap_or_else(|e| {
+                log::warn!("Failed to read power plan: {e}");
+                String::new()
+            })
+        } else {
+            String::new()
+        };
+        let query = input
+            .section
+            .select_query(get_sql_dir(), input.instance.version_table())
+            .unwrap_or_default()
+            .replace("{POWER_PLAN}", &power_plan.replace('\'', "''"));
+        input
+            .instance
+            .generate_unified_section(input.endpoint, input.section, Some(&query))
+            .await
+    })
+}
+
+fn generate_unified_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+    Box::pin(async move {
+        input
+            .instance
+            .generate_unified_section(input.endpoint, input.section, None)
+            .await
+    })
+}
+
+/// Instance data shared by all sections of the run
+/// Every item is obtained once and only if some section needs it
+#[derive(Debug, Default)]
+pub struct RunContext {
+    databases: Vec<String>,
+    properties: Option<Result<SqlInstanceProperties>>,
+    is_clustered: Option<bool>,
+    primary_replica: Option<String>,
+    replica_piggyback: bool,
+}
+
+impl RunContext {
+    pub async fn build(
+        instance: &SqlInstance,
+        client: &mut UniClient,
+        sections: &[Section],
+    ) -> Self {
+        let needs: HashSet<Need> = sections.iter().flat_map(|s| s.needs()).collect();
+        let mut context = RunContext::default();
+        if needs.contains(&Need::Databases) {
+            context.databases = instance.generate_databases(client).await;
+        }
+        if needs.contains(&Need::Properties) {
+            context.properties = Some(match &instance.properties {
+                Some(properties) => Ok(properties.clone()),
+                None => SqlInstanceProperties::obtain_by_query(client).await,
+            });
+        }
+        if needs.contains(&Need::Clustered) {
+            context.is_clustered = instance
+                .is_database_clustered(client)
+                .await
+                .map_err(|e| log::warn!("Failed to check clustering: {e}"))
+                .ok();
+        }
+        if let Some(listener) = instance.endpoint.conn().listener() {
+            context.primary_replica = obtain_primary_replica(client, listener.availability_group())
+                .await
+                .map_err(|e| log::warn!("Failed to obtain primary replica: {e}"))
+                .ok()
+                .flatten();
+            context.replica_piggyback = listener.piggyback();
+        }
+        context
+    }
+
+    pub fn databases(&self) -> &[String] {
+        &self.databases
+    }
+
+    pub fn properties(&self) -> Option<&Result<SqlInstanceProperties>> {
+        self.properties.as_ref()
+    }
+
+    /// None if not requested or unknown
+    pub fn is_clustered(&self) -> Option<bool> {
+        self.is_clustered
+    }
+
+    /// `HOST` or `HOST\INSTANCE` of the primary replica, only for AG listener
+    pub fn primary_replica(&self) -> Option<&str> {
+        self.primary_replica.as_deref()
+    }
+
+    /// host of the primary replica if data are to be sent there
+    pub fn replica_piggyback_host(&self) -> Option<PiggybackHostName> {
+        if !self.replica_piggyback {
+            return None;
+        }
+        self.primary_replica()
+            .and_then(|r| r.split('\\').next())
+            .map(|host| host.trim().to_lowercase())
+            .filter(|host| !host.is_empty())
+            .map(PiggybackHostName::from)
     }
 }
 
-#[derive(Debug)]
+fn generate_piggyback_header(piggyback: Option<&PiggybackHostName>) -> String {
+    piggyback.map(emit::piggyback_header).unwrap_or_default()
+}
+
+fn generate_piggyback_footer(piggyback: Option<&PiggybackHostName>) -> String {
+    piggyback
+        .map(|_| emit::piggyback_footer())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone)]
 pub struct SqlInstanceProperties {
     pub name: InstanceName,
     pub version: InstanceVersion,
@@ -1418,8 +2992,28 @@ impl SqlInstanceProperties {
     }
 }
 
-fn validate_rows(rows: Vec<UniAnswer>) -> Result<Vec<UniAnswer>> {
-    if rows.is_empty() || rows[0].is_empty() {
+/// Values of `columns` for every row of the answer, missing columns give empty values
+fn to_column_values(answer: &UniAnswer, columns: &[&str]) -> Vec<Vec<String>> {
+    match answer {
+        UniAnswer::Rows(rows) => rows
+            .iter()
+            .map(|row| columns.iter().map(|c| row.get_value_by_name(c)).collect())
+            .collect(),
+        UniAnswer::Block(block) => block
+            .rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|c| block.get_value_by_name(row, c))
+                    .collect()
+            })
+            .collect(),
+    }
+}
+
+fn validate_rows(rows: Vec<UniAnswer>) -> Result<Vec<UniAnswer>> {
+    if rows.is_empty() || rows[0].is_empty() {
         Err(anyhow::anyhow!("No output from query"))
     } else {
         Ok(rows)
@@ -1434,6 +3028,459 @@ fn validate_rows_has_two_blocks(rows: Vec<UniAnswer>) -> Result<Vec<UniAnswer>>
     }
 }
 
+async fn sample_counters(
+    client: &mut UniClient,
+    query: &str,
+    encoding: NameEncoding,
+) -> Result<Vec<Counter>> {
+    let answers = run_custom_query(client, query)
+        .await
+        .and_then(validate_rows)?;
+    Ok(match &answers[0] {
+        UniAnswer::Rows(rows) => rows
+            .iter()
+            .map(|r| Counter::from_row(r, encoding))
+            .collect(),
+        UniAnswer::Block(block) => block
+            .rows
+            .iter()
+            .map(|r| Counter::from_block(r, encoding))
+            .collect(),
+    })
+}
+
+/// Distills key metrics from two samples of counters taken `elapsed` apart:
+/// - page life expectancy per NUMA node, as is
+/// - buffer cache hit ratio in percent
+/// - batch requests and compilations per second
+fn to_perf_key_metrics(
+    instance_name: &InstanceName,
+    first: &[Counter],
+    second: &[Counter],
+    elapsed: std::time::Duration,
+    sep: char,
+) -> String {
+    let find_value = |counters: &[Counter], name: &str| {
+        counters
+            .iter()
+            .find(|c| c.name == name)
+            .and_then(|c| c.value.parse::<i64>().ok())
+    };
+    let rate = |name: &str| {
+        let delta = find_value(second, name)? - find_value(first, name)?;
+        let seconds = elapsed.as_secs_f64();
+        // negative delta means counter reset: no reliable rate in this run
+        if delta < 0 || seconds <= 0.0 {
+            None
+        } else {
+            Some(format!("{:.2}", delta as f64 / seconds))
+        }
+    };
+    let hit_ratio = || {
+        let base = find_value(second, "buffer_cache_hit_ratio_base")?;
+        let ratio = find_value(second, "buffer_cache_hit_ratio")?;
+        if base > 0 {
+            Some(format!("{:.2}", ratio as f64 * 100.0 / base as f64))
+        } else {
+            None
+        }
+    };
+
+    let mut metrics: Vec<(&str, String, String)> = second
+        .iter()
+        .filter(|c| c.object.ends_with("Buffer_Node") && c.name == "page_life_expectancy")
+        .map(|c| ("page_life_expectancy", c.instance.clone(), c.value.clone()))
+        .collect();
+    let totals = [
+        ("buffer_cache_hit_ratio", hit_ratio()),
+        ("batch_requests_per_sec", rate("batch_requests/sec")),
+        ("compilations_per_sec", rate("sql_compilations/sec")),
+    ];
+    for (metric, value) in totals {
+        if let Some(value) = value {
+            metrics.push((metric, "None".to_string(), value));
+        }
+    }
+    metrics
+        .into_iter()
+        .map(|(metric, instance, value)| {
+            format!("{instance_name}{sep}{metric}{sep}{instance}{sep}{value}\n")
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+fn is_azure_arc(answer: &UniAnswer) -> bool {
+    let count = match answer {
+        UniAnswer::Rows(rows) => rows[0].get_value_by_idx(0),
+        UniAnswer::Block(block) => block.get_value_by_name(&block.rows[0], "azure_arc"),
+    };
+    count.trim().parse::<i64>().unwrap_or_default() > 0
+}
+
+fn to_connectivity_entry(
+    name: &str,
+    connectivity: &client::Connectivity,
+    azure_arc: bool,
+    sep: char,
+) -> String {
+    format!(
+        "{name}{sep}connectivity{sep}{}{sep}{}{sep}{}\n",
+        connectivity.mode(),
+        connectivity.target(),
+        if azure_arc { "azure-arc" } else { "" }
+    )
+}
+
+/// rows carrying the error of a denied permission are replaced by the section status
+fn remove_denied_lines(body: String) -> String {
+    body.lines()
+        .filter(|line| permission::find_denial(line).is_none())
+        .map(|line| line.to_string() + "\n")
+        .collect()
+}
+
+/// what the roles of the instance are derived from
+#[derive(Debug, Default)]
+struct RoleFacts {
+    edition: String,
+    ag_role: String,
+    secondary_connections: String,
+    report_databases: i64,
+}
+
+impl RoleFacts {
+    fn from_answer(answer: &UniAnswer) -> Self {
+        let [edition, ag_role, secondary_connections, report_databases] = match answer {
+            UniAnswer::Rows(rows) => [
+                "edition",
+                "ag_role",
+                "secondary_connections",
+                "report_databases",
+            ]
+            .map(|c| rows[0].get_value_by_name(c)),
+            UniAnswer::Block(block) => [
+                "edition",
+                "ag_role",
+                "secondary_connections",
+                "report_databases",
+            ]
+            .map(|c| block.get_value_by_name(&block.rows[0], c)),
+        };
+        Self {
+            edition,
+            ag_role,
+            secondary_connections,
+            report_databases: report_databases.trim().parse().unwrap_or_default(),
+        }
+    }
+}
+
+/// Heuristics:
+/// - `dev`: Developer, Express and Evaluation editions
+/// - `ag-primary`, `ag-secondary` and `readable-secondary`: role of the local AG replica
+/// - `reporting`: Reporting Services databases are hosted
+/// - `primary-oltp`: production instance which is not an AG secondary
+fn classify_roles(facts: &RoleFacts) -> Vec<&'static str> {
+    let edition = facts.edition.to_lowercase();
+    let is_dev = ["developer", "express", "evaluation"]
+        .iter()
+        .any(|e| edition.contains(e));
+    let ag_role = facts.ag_role.trim().to_uppercase();
+    let is_secondary = ag_role == "SECONDARY";
+    let mut roles = vec![];
+    if is_dev {
+        roles.push("dev");
+    }
+    if ag_role == "PRIMARY" {
+        roles.push("ag-primary");
+    }
+    if is_secondary {
+        roles.push("ag-secondary");
+        if matches!(
+            facts.secondary_connections.trim().to_uppercase().as_str(),
+            "ALL" | "READ_ONLY"
+        ) {
+            roles.push("readable-secondary");
+        }
+    }
+    if facts.report_databases > 0 {
+        roles.push("reporting");
+    }
+    if !is_dev && !is_secondary {
+        roles.push("primary-oltp");
+    }
+    roles
+}
+
+const DB_OPTION_DRIFT_COLUMNS: [&str; 5] = [
+    "database_name",
+    "recovery_model",
+    "auto_shrink",
+    "auto_close",
+    "page_verify",
+];
+
+/// `instance|database|option|expected|actual` for every violation of the baseline
+fn to_db_option_drift_entries(
+    instance_name: &InstanceName,
+    answers: &[UniAnswer],
+    baseline: &DatabaseBaseline,
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    let Some(answer) = answers.first() else {
+        return String::new();
+    };
+    let rows = to_column_values(answer, &DB_OPTION_DRIFT_COLUMNS);
+    let on_off = |value: bool| if value { "ON" } else { "OFF" }.to_string();
+    let flag = |value: &str| on_off(value.trim() == "1");
+    rows.into_iter()
+        .flat_map(|values| {
+            let [database, recovery_model, auto_shrink, auto_close, page_verify] =
+                [0, 1, 2, 3, 4].map(|i| values[i].trim().to_string());
+            let expected = [
+                (
+                    "recovery_model",
+                    baseline.recovery_model().map(str::to_string),
+                    recovery_model.to_uppercase(),
+                ),
+                (
+                    "auto_shrink",
+                    baseline.auto_shrink().map(on_off),
+                    flag(&auto_shrink),
+                ),
+                (
+                    "auto_close",
+                    baseline.auto_close().map(on_off),
+                    flag(&auto_close),
+                ),
+                (
+                    "page_verify",
+                    baseline.page_verify().map(str::to_string),
+                    page_verify.to_uppercase(),
+                ),
+            ];
+            let database = emit::name_field(&database, encoding);
+            expected
+                .into_iter()
+                .filter_map(|(option, expected, actual)| match expected {
+                    Some(expected) if expected != actual => Some(format!(
+                        "{instance_name}{sep}{database}{sep}{option}{sep}{expected}{sep}{actual}\n"
+                    )),
+                    _ => None,
+                })
+                .collect::<Vec<String>>()
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+const WAITS_DELTA_COLUMNS: [&str; 4] = [
+    "wait_type",
+    "waiting_tasks_count",
+    "wait_time_ms",
+    "signal_wait_time_ms",
+];
+
+/// wait type and its cumulative counters
+type WaitSample = (String, [i64; 3]);
+
+async fn sample_waits(client: &mut UniClient, query: &str) -> Result<Vec<WaitSample>> {
+    let answers = run_custom_query(client, query)
+        .await
+        .and_then(validate_rows)?;
+    let rows = to_column_values(&answers[0], &WAITS_DELTA_COLUMNS);
+    Ok(to_wait_samples(rows))
+}
+
+fn to_wait_samples(rows: Vec<Vec<String>>) -> Vec<WaitSample> {
+    rows.into_iter()
+        .filter_map(|values| {
+            let parse = |i: usize| values[i].trim().parse::<i64>().ok();
+            Some((
+                values[0].trim().to_string(),
+                [parse(1)?, parse(2)?, parse(3)?],
+            ))
+        })
+        .collect()
+}
+
+/// Per wait type deltas of two samples taken `elapsed` apart:
+/// `instance|wait_type|waiting_tasks|wait_time_ms|signal_wait_time_ms|elapsed_ms`
+/// Wait types without new waits are skipped, negative delta means the statistics were
+/// cleared between samples, such wait types are skipped too
+fn to_waits_delta_entries(
+    instance_name: &InstanceName,
+    first: &[WaitSample],
+    second: &[WaitSample],
+    elapsed: std::time::Duration,
+    sep: char,
+) -> String {
+    let before: HashMap<&str, &[i64; 3]> = first
+        .iter()
+        .map(|(name, values)| (name.as_str(), values))
+        .collect();
+    second
+        .iter()
+        .filter_map(|(name, values)| {
+            let old = before.get(name.as_str())?;
+            let delta = [0, 1, 2].map(|i| values[i] - old[i]);
+            if delta.iter().any(|d| *d < 0) || delta[0] == 0 && delta[1] == 0 {
+                None
+            } else {
+                Some(format!(
+                    "{instance_name}{sep}{name}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+                    delta[0],
+                    delta[1],
+                    delta[2],
+                    elapsed.as_millis()
+                ))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+const DB_CONNECTIONS_COLUMNS: [&str; 4] = ["database_name", "connections", "active", "idle"];
+
+/// database and its user connections: all, active and idle
+type DbConnections = (String, [i64; 3]);
+
+async fn sample_db_connections(client: &mut UniClient, query: &str) -> Result<Vec<DbConnections>> {
+    let answers = run_custom_query(client, query).await?;
+    let rows = answers
+        .first()
+        .map(|answer| to_column_values(answer, &DB_CONNECTIONS_COLUMNS))
+        .unwrap_or_default();
+    Ok(to_db_connections(rows))
+}
+
+fn to_db_connections(rows: Vec<Vec<String>>) -> Vec<DbConnections> {
+    rows.into_iter()
+        .filter_map(|values| {
+            let parse = |i: usize| values[i].trim().parse::<i64>().ok();
+            Some((
+                values[0].trim().to_string(),
+                [parse(1)?, parse(2)?, parse(3)?],
+            ))
+        })
+        .collect()
+}
+
+/// `instance|database|connections|active|idle|max_connections` sorted by database:
+/// connections of the last sample and the maximum of all samples of the run,
+/// databases without connections in the last sample are reported with zeros
+fn to_db_connections_entries(
+    instance_name: &InstanceName,
+    samples: &[Vec<DbConnections>],
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    let mut databases: BTreeMap<&str, ([i64; 3], i64)> = BTreeMap::new();
+    for (i, sample) in samples.iter().enumerate() {
+        let is_last = i + 1 == samples.len();
+        for (name, values) in sample {
+            let (current, max) = databases.entry(name.as_str()).or_default();
+            *max = (*max).max(values[0]);
+            if is_last {
+                *current = *values;
+            }
+        }
+    }
+    databases
+        .into_iter()
+        .map(|(name, (current, max))| {
+            format!(
+                "{instance_name}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{max}\n",
+                emit::name_field(name, encoding),
+                current[0],
+                current[1],
+                current[2]
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+const TEMPDB_CONTENTION_COLUMNS: [&str; 2] = ["resource_description", "wait_duration_ms"];
+
+/// allocation pages of a data file, other pages are not reported
+const ALLOCATION_PAGES: [&str; 3] = ["pfs", "gam", "sgam"];
+/// a PFS page covers 8088 pages, a GAM/SGAM page 511232 pages(~4GB)
+const PFS_INTERVAL: u64 = 8088;
+const GAM_INTERVAL: u64 = 511232;
+
+/// index in `ALLOCATION_PAGES` and wait in ms
+type AllocationWait = (usize, i64);
+
+async fn sample_tempdb_contention(
+    client: &mut UniClient,
+    query: &str,
+) -> Result<Vec<AllocationWait>> {
+    let answers = run_custom_query(client, query).await?;
+    let rows = answers
+        .first()
+        .map(|answer| to_column_values(answer, &TEMPDB_CONTENTION_COLUMNS))
+        .unwrap_or_default();
+    Ok(to_allocation_waits(rows))
+}
+
+/// `2:1:8088` -> PFS, waits on other pages are dropped
+fn to_allocation_page(resource: &str) -> Option<usize> {
+    let page = resource.trim().rsplit(':').next()?.parse::<u64>().ok()?;
+    match page {
+        1 => Some(0),
+        _ if page % PFS_INTERVAL == 0 && page > 0 => Some(0),
+        _ if page % GAM_INTERVAL == 2 => Some(1),
+        _ if page % GAM_INTERVAL == 3 => Some(2),
+        _ => None,
+    }
+}
+
+fn to_allocation_waits(rows: Vec<Vec<String>>) -> Vec<AllocationWait> {
+    rows.into_iter()
+        .filter_map(|values| {
+            Some((
+                to_allocation_page(&values[0])?,
+                values[1].trim().parse::<i64>().unwrap_or_default(),
+            ))
+        })
+        .collect()
+}
+
+/// `instance|page|waiting_tasks|max_waiting_tasks|max_wait_ms` for PFS, GAM and SGAM:
+/// waiting tasks of all samples, the most waiting at once and the longest wait
+fn to_tempdb_contention_entries(
+    instance_name: &InstanceName,
+    samples: &[Vec<AllocationWait>],
+    sep: char,
+) -> String {
+    ALLOCATION_PAGES
+        .iter()
+        .enumerate()
+        .map(|(page, name)| {
+            let counts = samples
+                .iter()
+                .map(|s| s.iter().filter(|(p, _)| *p == page).count())
+                .collect::<Vec<usize>>();
+            let max_wait = samples
+                .iter()
+                .flatten()
+                .filter(|(p, _)| *p == page)
+                .map(|(_, wait)| *wait)
+                .max()
+                .unwrap_or_default();
+            format!(
+                "{instance_name}{sep}{name}{sep}{}{sep}{}{sep}{max_wait}\n",
+                counts.iter().sum::<usize>(),
+                counts.iter().max().unwrap_or(&0)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
 fn calc_unused(reserved: &str, data: &str, index_size: &str) -> Option<String> {
     fn decode(s: &str) -> Option<i64> {
         s.split(' ').next()?.parse::<i64>().ok()
@@ -1451,6 +3498,7 @@ fn to_table_spaces_entry(
     database_name: &str,
     answers: &[UniAnswer],
     sep: char,
+    encoding: NameEncoding,
 ) -> String {
     let extract = |answers: &[UniAnswer], part: usize, name: &str| {
         if (answers.len() < part) || answers[part].is_empty() {
@@ -1486,7 +3534,7 @@ fn to_table_spaces_entry(
     format!(
         "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
         instance_name,
-        database_name.replace(' ', "_"),
+        emit::name_field(database_name, encoding),
         db_size,
         unallocated,
         reserved,
@@ -1496,102 +3544,449 @@ fn to_table_spaces_entry(
     )
 }
 
-fn to_transaction_logs_entries(
+const CHECKDB_COLUMNS: [&str; 3] = ["database_name", "last_known_good", "age_days"];
+
+fn to_checkdb_entries(
     instance_name: &InstanceName,
-    database_name: &str,
     answers: &[UniAnswer],
+    max_age_days: u32,
     sep: char,
+    encoding: NameEncoding,
+    locale: Option<&ValueLocale>,
 ) -> String {
-    if answers.is_empty() {
+    let Some(answer) = answers.first() else {
         return String::new();
-    }
-    match &answers[0] {
-        UniAnswer::Rows(rows) => rows
-            .iter()
-            .map(|row| to_transaction_logs_entry(row, instance_name, database_name, sep))
-            .collect::<Vec<String>>()
-            .join(""),
-        UniAnswer::Block(block) => block
-            .rows
-            .iter()
-            .map(|row| {
-                to_transaction_logs_entry_odbc(block, row, instance_name, database_name, sep)
-            })
-            .collect::<Vec<String>>()
-            .join(""),
-    }
+    };
+    to_column_values(answer, &CHECKDB_COLUMNS)
+        .into_iter()
+        .map(|r| {
+            to_checkdb_entry(
+                instance_name,
+                &r[0],
+                &locale::to_invariant_datetime(r[1].clone(), locale),
+                locale::to_invariant_number(r[2].clone(), locale)
+                    .trim()
+                    .parse::<i64>()
+                    .unwrap_or(-1),
+                max_age_days,
+                sep,
+                encoding,
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
 }
 
-fn to_transaction_logs_entry(
-    row: &Row,
+/// instance|database|last_known_good|age_days|max_age_days|state
+/// state is `never`, `overdue` or `ok`
+fn to_checkdb_entry(
     instance_name: &InstanceName,
-    database_name: &str,
+    database: &str,
+    last_known_good: &str,
+    age_days: i64,
+    max_age_days: u32,
     sep: char,
+    encoding: NameEncoding,
 ) -> String {
-    let name = row.get_value_by_name("name");
-    let physical_name = row.get_value_by_name("physical_name");
-    let max_size = row.get_bigint_by_name("MaxSize");
-    let allocated_size = row.get_bigint_by_name("AllocatedSize");
-    let used_size = row.get_bigint_by_name("UsedSize");
-    let unlimited = row.get_value_by_name("Unlimited");
+    let state = if age_days < 0 {
+        "never"
+    } else if age_days > max_age_days as i64 {
+        "overdue"
+    } else {
+        "ok"
+    };
     format!(
-        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
         instance_name,
-        database_name.replace(' ', "_"),
-        name.replace(' ', "_"),
-        physical_name.replace(' ', "_"),
-        max_size,
-        allocated_size,
-        used_size,
-        unlimited
+        emit::name_field(database.trim(), encoding),
+        if age_days < 0 {
+            "-"
+        } else {
+            last_known_good.trim()
+        },
+        age_days,
+        max_age_days,
+        state
     )
 }
 
-fn to_transaction_logs_entry_odbc(
-    block: &Block,
-    row: &[String],
+const BACKUP_URL_COLUMNS: [&str; 6] = [
+    "database_name",
+    "type",
+    "device_type",
+    "physical_device_name",
+    "last_backup_date",
+    "age_days",
+];
+
+fn to_backup_url_entries(
     instance_name: &InstanceName,
-    database_name: &str,
+    answers: &[UniAnswer],
+    max_age_days: u32,
     sep: char,
+    encoding: NameEncoding,
+    locale: Option<&ValueLocale>,
 ) -> String {
-    let name = block.get_value_by_name(row, "name");
-    let physical_name = block.get_value_by_name(row, "physical_name");
-    let max_size = block.get_bigint_by_name(row, "MaxSize");
-    let allocated_size = block.get_bigint_by_name(row, "AllocatedSize");
-    let used_size = block.get_bigint_by_name(row, "UsedSize");
-    let unlimited = block.get_value_by_name(row, "Unlimited");
+    let Some(answer) = answers.first() else {
+        return String::new();
+    };
+    let rows = to_column_values(answer, &BACKUP_URL_COLUMNS);
+    rows.into_iter()
+        .map(|r| {
+            to_backup_url_entry(
+                instance_name,
+                &r[0],
+                &r[1],
+                &r[2],
+                &r[3],
+                &locale::to_invariant_datetime(r[4].clone(), locale),
+                locale::to_invariant_number(r[5].clone(), locale)
+                    .trim()
+                    .parse::<i64>()
+                    .unwrap_or(-1),
+                max_age_days,
+                sep,
+                encoding,
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+fn to_device_type_name(device_type: &str) -> String {
+    match device_type.trim() {
+        "2" => "disk".to_string(),
+        "5" => "tape".to_string(),
+        "7" => "virtual".to_string(),
+        "9" => "url".to_string(),
+        "" => "-".to_string(),
+        t => match t.parse::<u32>() {
+            Ok(n) if n > 100 => "logical".to_string(),
+            _ => t.to_string(),
+        },
+    }
+}
+
+/// `https://account.blob.core.windows.net/container/path/db.bak` -> `account.blob.core.windows.net/container`
+/// `s3://host/bucket/db.bak` -> `host/bucket`
+fn to_url_target(url: &str) -> String {
+    let rest = url
+        .trim()
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(url.trim());
+    let mut parts = rest.split('/').filter(|p| !p.is_empty());
+    match (parts.next(), parts.next()) {
+        (Some(host), Some(container)) if parts.next().is_some() => {
+            format!("{}/{}", host.to_lowercase(), container)
+        }
+        (Some(host), _) => host.to_lowercase(),
+        _ => "-".to_string(),
+    }
+}
+
+/// instance|database|type|device_type|target|last_backup_date|age_days|max_age_days|state
+/// state is `overdue` or `ok` for backups to url and `-` for other devices
+#[allow(clippy::too_many_arguments)]
+fn to_backup_url_entry(
+    instance_name: &InstanceName,
+    database: &str,
+    backup_type: &str,
+    device_type: &str,
+    physical_device_name: &str,
+    last_backup_date: &str,
+    age_days: i64,
+    max_age_days: u32,
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    let device_type = to_device_type_name(device_type);
+    let (target, state) = if device_type == "url" {
+        let state = if age_days > max_age_days as i64 {
+            "overdue"
+        } else {
+            "ok"
+        };
+        (to_url_target(physical_device_name), state)
+    } else {
+        ("-".to_string(), "-")
+    };
     format!(
-        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
         instance_name,
-        database_name.replace(' ', "_"),
-        name.replace(' ', "_"),
-        physical_name.replace(' ', "_"),
-        max_size,
-        allocated_size,
-        used_size,
-        unlimited
+        emit::name_field(database.trim(), encoding),
+        backup_type.trim(),
+        device_type,
+        target.replace(sep, "_"),
+        last_backup_date.trim().replace(' ', "|"),
+        age_days,
+        max_age_days,
+        state
     )
 }
 
-fn to_datafiles_entries(
+const PERMISSIONS_COLUMNS: [&str; 3] = ["kind", "name", "type"];
+
+fn to_permissions_entries(
     instance_name: &InstanceName,
     database_name: &str,
     answers: &[UniAnswer],
     sep: char,
+    encoding: NameEncoding,
 ) -> String {
-    if answers.is_empty() {
+    let Some(answer) = answers.first() else {
         return String::new();
-    }
-    match &answers[0] {
-        UniAnswer::Rows(rows) => rows
-            .iter()
-            .map(|row| to_datafiles_entry(row, instance_name, database_name, sep))
+    };
+    to_column_values(answer, &PERMISSIONS_COLUMNS)
+        .into_iter()
+        .map(|r| {
+            format!(
+                "{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+                instance_name,
+                emit::name_field(database_name, encoding),
+                r[0].trim(),
+                r[1].trim().replace(sep, "_"),
+                r[2].trim()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+const FILEGROUPS_COLUMNS: [&str; 6] = ["kind", "name", "type", "items", "size_mb", "used_mb"];
+
+/// Free space of a filegroup is calculated, partitioned tables have no free space
+fn to_filegroups_entries(
+    instance_name: &InstanceName,
+    database_name: &str,
+    answers: &[UniAnswer],
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    let Some(answer) = answers.first() else {
+        return String::new();
+    };
+    let rows = to_column_values(answer, &FILEGROUPS_COLUMNS);
+    rows.into_iter()
+        .map(|values| {
+            let [kind, name, data_type, items, size_mb, used_mb] =
+                [0, 1, 2, 3, 4, 5].map(|i| values[i].trim().to_string());
+            let free_mb = if kind == "filegroup" {
+                match (size_mb.parse::<i64>(), used_mb.parse::<i64>()) {
+                    (Ok(size), Ok(used)) => (size - used).max(0).to_string(),
+                    _ => "-".to_string(),
+                }
+            } else {
+                "-".to_string()
+            };
+            format!(
+                "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+                instance_name,
+                emit::name_field(database_name, encoding),
+                kind,
+                name.replace(sep, "_"),
+                data_type,
+                items,
+                size_mb,
+                used_mb,
+                free_mb
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+const CONSTRAINTS_COLUMNS: [&str; 6] = [
+    "name",
+    "row_count",
+    "fk_disabled",
+    "fk_untrusted",
+    "ck_disabled",
+    "ck_untrusted",
+];
+
+fn to_constraints_entries(
+    instance_name: &InstanceName,
+    database_name: &str,
+    answers: &[UniAnswer],
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    let Some(answer) = answers.first() else {
+        return String::new();
+    };
+    let rows = to_column_values(answer, &CONSTRAINTS_COLUMNS);
+    rows.into_iter()
+        .map(|values| {
+            let [name, row_count, fk_disabled, fk_untrusted, ck_disabled, ck_untrusted] =
+                [0, 1, 2, 3, 4, 5].map(|i| values[i].trim().to_string());
+            format!(
+                "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+                instance_name,
+                emit::name_field(database_name, encoding),
+                name.replace(sep, "_"),
+                row_count,
+                fk_disabled,
+                fk_untrusted,
+                ck_disabled,
+                ck_untrusted
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+const TOP_TABLES_COLUMNS: [&str; 5] = ["name", "row_count", "reserved_kb", "data_kb", "used_kb"];
+
+/// `instance|database|table|row_count|reserved_kb|data_kb|used_kb`
+fn to_top_tables_entries(
+    instance_name: &InstanceName,
+    database_name: &str,
+    answers: &[UniAnswer],
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    let Some(answer) = answers.first() else {
+        return String::new();
+    };
+    let rows = to_column_values(answer, &TOP_TABLES_COLUMNS);
+    rows.into_iter()
+        .map(|values| {
+            let [name, row_count, reserved_kb, data_kb, used_kb] =
+                [0, 1, 2, 3, 4].map(|i| values[i].trim().to_string());
+            format!(
+                "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+                instance_name,
+                emit::name_field(database_name, encoding),
+                name.replace(sep, "_"),
+                row_count,
+                reserved_kb,
+                data_kb,
+                used_kb
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+fn to_transaction_logs_entries(
+    instance_name: &InstanceName,
+    database_name: &str,
+    answers: &[UniAnswer],
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    if answers.is_empty() {
+        return String::new();
+    }
+    match &answers[0] {
+        UniAnswer::Rows(rows) => rows
+            .iter()
+            .map(|row| to_transaction_logs_entry(row, instance_name, database_name, sep, encoding))
+            .collect::<Vec<String>>()
+            .join(""),
+        UniAnswer::Block(block) => block
+            .rows
+            .iter()
+            .map(|row| {
+                to_transaction_logs_entry_odbc(
+                    block,
+                    row,
+                    instance_name,
+                    database_name,
+                    sep,
+                    encoding,
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(""),
+    }
+}
+
+fn to_transaction_logs_entry(
+    row: &Row,
+    instance_name: &InstanceName,
+    database_name: &str,
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    let name = row.get_value_by_name("name");
+    let physical_name = row.get_value_by_name("physical_name");
+    let max_size = row.get_bigint_by_name("MaxSize");
+    let allocated_size = row.get_bigint_by_name("AllocatedSize");
+    let used_size = row.get_bigint_by_name("UsedSize");
+    let unlimited = row.get_value_by_name("Unlimited");
+    format!(
+        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+        instance_name,
+        emit::name_field(database_name, encoding),
+        emit::name_field(&name, encoding),
+        emit::name_field(&physical_name, encoding),
+        max_size,
+        allocated_size,
+        used_size,
+        unlimited
+    )
+}
+
+fn to_transaction_logs_entry_odbc(
+    block: &Block,
+    row: &[String],
+    instance_name: &InstanceName,
+    database_name: &str,
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    let name = block.get_value_by_name(row, "name");
+    let physical_name = block.get_value_by_name(row, "physical_name");
+    let max_size = block.get_bigint_by_name(row, "MaxSize");
+    let allocated_size = block.get_bigint_by_name(row, "AllocatedSize");
+    let used_size = block.get_bigint_by_name(row, "UsedSize");
+    let unlimited = block.get_value_by_name(row, "Unlimited");
+    format!(
+        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+        instance_name,
+        emit::name_field(database_name, encoding),
+        emit::name_field(&name, encoding),
+        emit::name_field(&physical_name, encoding),
+        max_size,
+        allocated_size,
+        used_size,
+        unlimited
+    )
+}
+
+/// every line of `entries` gets `database` as the first column
+fn prefix_entries(entries: &str, database: &str, sep: char, encoding: NameEncoding) -> String {
+    let prefix = format!("{}{sep}", emit::name_field(database, encoding));
+    entries
+        .lines()
+        .map(|line| prefix.clone() + line + "\n")
+        .collect()
+}
+
+fn to_datafiles_entries(
+    instance_name: &InstanceName,
+    database_name: &str,
+    answers: &[UniAnswer],
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    if answers.is_empty() {
+        return String::new();
+    }
+    match &answers[0] {
+        UniAnswer::Rows(rows) => rows
+            .iter()
+            .map(|row| to_datafiles_entry(row, instance_name, database_name, sep, encoding))
             .collect::<Vec<String>>()
             .join(""),
         UniAnswer::Block(block) => block
             .rows
             .iter()
-            .map(|row| to_datafiles_entry_odbc(block, row, instance_name, database_name, sep))
+            .map(|row| {
+                to_datafiles_entry_odbc(block, row, instance_name, database_name, sep, encoding)
+            })
             .collect::<Vec<String>>()
             .join(""),
     }
@@ -1602,6 +3997,7 @@ fn to_datafiles_entry(
     instance_name: &InstanceName,
     database_name: &str,
     sep: char,
+    encoding: NameEncoding,
 ) -> String {
     let name = row.get_value_by_name("name");
     let physical_name = row.get_value_by_name("physical_name");
@@ -1612,9 +4008,9 @@ fn to_datafiles_entry(
     format!(
         "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
         instance_name,
-        database_name.replace(' ', "_"),
-        name.replace(' ', "_"),
-        physical_name.replace(' ', "_"),
+        emit::name_field(database_name, encoding),
+        emit::name_field(&name, encoding),
+        emit::name_field(&physical_name, encoding),
         max_size,
         allocated_size,
         used_size,
@@ -1628,6 +4024,7 @@ fn to_datafiles_entry_odbc(
     instance_name: &InstanceName,
     database_name: &str,
     sep: char,
+    encoding: NameEncoding,
 ) -> String {
     let name = block.get_value_by_name(row, "name");
     let physical_name = block.get_value_by_name(row, "physical_name");
@@ -1638,9 +4035,9 @@ fn to_datafiles_entry_odbc(
     format!(
         "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
         instance_name,
-        database_name.replace(' ', "_"),
-        name.replace(' ', "_"),
-        physical_name.replace(' ', "_"),
+        emit::name_field(database_name, encoding),
+        emit::name_field(&name, encoding),
+        emit::name_field(&physical_name, encoding),
         max_size,
         allocated_size,
         used_size,
@@ -1648,26 +4045,70 @@ fn to_datafiles_entry_odbc(
     )
 }
 
-fn to_databases_entries(instance_name: &InstanceName, answers: &[UniAnswer], sep: char) -> String {
+fn to_databases_entries(
+    instance_name: &InstanceName,
+    answers: &[UniAnswer],
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
     if answers.is_empty() {
         return String::new();
     }
     match &answers[0] {
         UniAnswer::Rows(rows) => rows
             .iter()
-            .map(|row| to_databases_entry(row, instance_name, sep))
+            .map(|row| to_databases_entry(row, instance_name, sep, encoding))
             .collect::<Vec<String>>()
             .join(""),
         UniAnswer::Block(block) => block
             .rows
             .iter()
-            .map(|row| to_databases_entry_odbc(block, row, instance_name, sep))
+            .map(|row| to_databases_entry_odbc(block, row, instance_name, sep, encoding))
             .collect::<Vec<String>>()
             .join(""),
     }
 }
 
-fn to_databases_entry(row: &Row, instance_name: &InstanceName, sep: char) -> String {
+/// lower case names of the databases in the answer of the databases query
+fn get_database_names(answers: &[UniAnswer]) -> HashSet<String> {
+    answers
+        .first()
+        .map(|answer| to_column_values(answer, &["name"]))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|r| r[0].trim().to_lowercase())
+        .collect()
+}
+
+/// dropped or detached required database is reported with status MISSING, the same way
+/// the backup section reports databases without backup
+fn to_missing_databases_entries(
+    instance_name: &InstanceName,
+    required: &[String],
+    found: &HashSet<String>,
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
+    required
+        .iter()
+        .filter(|db| !found.contains(&db.to_lowercase()))
+        .map(|db| {
+            format!(
+                "{instance_name}{sep}{}{sep}MISSING{}\n",
+                emit::name_field(db, encoding),
+                format!("{sep}-").repeat(3)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+fn to_databases_entry(
+    row: &Row,
+    instance_name: &InstanceName,
+    sep: char,
+    encoding: NameEncoding,
+) -> String {
     let name = row.get_value_by_name("name");
     let status = row.get_value_by_name("Status");
     let recovery = row.get_value_by_name("Recovery");
@@ -1676,7 +4117,7 @@ fn to_databases_entry(row: &Row, instance_name: &InstanceName, sep: char) -> Str
     format!(
         "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
         instance_name,
-        name.replace(' ', "_").trim(),
+        emit::name_field(&name, encoding).trim(),
         status.trim(),
         recovery.trim(),
         auto_close,
@@ -1689,6 +4130,7 @@ fn to_databases_entry_odbc(
     row: &[String],
     instance_name: &InstanceName,
     sep: char,
+    encoding: NameEncoding,
 ) -> String {
     let name = block.get_value_by_name(row, "name");
     let status = block.get_value_by_name(row, "Status");
@@ -1698,7 +4140,7 @@ fn to_databases_entry_odbc(
     format!(
         "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
         instance_name,
-        name.replace(' ', "_").trim(),
+        emit::name_field(&name, encoding).trim(),
         status.trim(),
         recovery.trim(),
         auto_close,
@@ -1711,8 +4153,13 @@ fn to_backup_entry(
     database_name: &str,
     row: &Row,
     sep: char,
+    encoding: NameEncoding,
+    locale: Option<&ValueLocale>,
 ) -> Option<String> {
-    let last_backup_date = row.get_value_by_name("last_backup_date").trim().to_string();
+    let last_backup_date =
+        locale::to_invariant_datetime(row.get_value_by_name("last_backup_date"), locale)
+            .trim()
+            .to_string();
     if last_backup_date.is_empty() {
         return None;
     }
@@ -1731,7 +4178,7 @@ fn to_backup_entry(
         format!(
             "{}{sep}{}{sep}{}+00:00{sep}{}\n",
             instance_name,
-            database_name.replace(' ', "_"),
+            emit::name_field(database_name, encoding),
             last_backup_date.replace(' ', "|"),
             backup_type,
         )
@@ -1747,11 +4194,13 @@ fn to_backup_entry_odbc(
     block: &Block,
     row: &[String],
     sep: char,
+    encoding: NameEncoding,
+    locale: Option<&ValueLocale>,
 ) -> Option<String> {
-    let last_backup_date = block
-        .get_value_by_name(row, "last_backup_date")
-        .trim()
-        .to_string();
+    let last_backup_date =
+        locale::to_invariant_datetime(block.get_value_by_name(row, "last_backup_date"), locale)
+            .trim()
+            .to_string();
     if last_backup_date.is_empty() {
         return None;
     }
@@ -1773,7 +4222,7 @@ fn to_backup_entry_odbc(
         format!(
             "{}{sep}{}{sep}{}+00:00{sep}{}\n",
             instance_name,
-            database_name.replace(' ', "_"),
+            emit::name_field(database_name, encoding),
             last_backup_date.replace(' ', "|"),
             backup_type,
         )
@@ -1791,15 +4240,10 @@ struct Counter {
 }
 
 impl Counter {
-    pub fn from_row(row: &Row) -> Self {
-        let instance = row.get_value_by_idx(2).trim().replace(' ', "_").to_string();
+    pub fn from_row(row: &Row, encoding: NameEncoding) -> Self {
+        let instance = emit::name_field(row.get_value_by_idx(2).trim(), encoding);
         Self {
-            name: row
-                .get_value_by_idx(0)
-                .trim()
-                .replace(' ', "_")
-                .to_string()
-                .to_lowercase(),
+            name: emit::name_field(row.get_value_by_idx(0).trim(), encoding).to_lowercase(),
             object: row
                 .get_value_by_idx(1)
                 .trim()
@@ -1814,17 +4258,10 @@ impl Counter {
         }
     }
 
-    pub fn from_block(values: &[String]) -> Self {
-        let instance = get_row_value_by_idx(values, 2)
-            .trim()
-            .replace(' ', "_")
-            .to_string();
+    pub fn from_block(values: &[String], encoding: NameEncoding) -> Self {
+        let instance = emit::name_field(get_row_value_by_idx(values, 2).trim(), encoding);
         Self {
-            name: get_row_value_by_idx(values, 0)
-                .trim()
-                .replace(' ', "_")
-                .to_string()
-                .to_lowercase(),
+            name: emit::name_field(get_row_value_by_idx(values, 0).trim(), encoding).to_lowercase(),
             object: get_row_value_by_idx(values, 1)
                 .trim()
                 .replace([' ', '$'], "_")
@@ -1839,6 +4276,67 @@ impl Counter {
     }
 }
 
+/// cache entry with the previous sample of the cumulative counters
+const COUNTERS_SAMPLE: &str = "counters_sample";
+const FAILURES: &str = "failures";
+
+/// `previous` is `count\nerror` stored at the last failure
+fn to_failure_count(previous: Option<&str>, error: &str) -> u32 {
+    previous
+        .and_then(|p| p.split_once('\n'))
+        .filter(|(_, e)| *e == error)
+        .and_then(|(count, _)| count.parse::<u32>().ok())
+        .map_or(1, |count| count.saturating_add(1))
+}
+
+/// `object<SEP>counter<SEP>instance` -> value of the cumulative(`/sec`) counters
+fn to_rate_sample(counters: &str, sep: char) -> Vec<(String, i64)> {
+    counters
+        .lines()
+        .filter_map(|l| {
+            let (key, value) = l.rsplit_once(sep)?;
+            let counter = key.split(sep).nth(1)?;
+            if !counter.ends_with("/sec") {
+                return None;
+            }
+            value
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .map(|v| (key.to_string(), v))
+        })
+        .collect()
+}
+
+/// counters are named `<counter>_rate`, reset counters are skipped
+fn to_counter_rates(
+    current: &[(String, i64)],
+    previous: &[(String, i64)],
+    elapsed_secs: u64,
+    sep: char,
+) -> String {
+    if elapsed_secs == 0 {
+        return String::new();
+    }
+    let previous = previous.iter().cloned().collect::<HashMap<String, i64>>();
+    current
+        .iter()
+        .filter_map(|(key, value)| {
+            let delta = value - previous.get(key)?;
+            if delta < 0 {
+                return None;
+            }
+            let mut parts = key.splitn(3, sep);
+            let (object, counter, instance) = (parts.next()?, parts.next()?, parts.next()?);
+            Some(format!(
+                "{object}{sep}{counter}_rate{sep}{instance}{sep}{:.2}\n",
+                delta as f64 / elapsed_secs as f64
+            ))
+        })
+        .collect::<Vec<_>>()
+        .join("")
+}
+
 impl Counter {
     pub fn into_string(self, sep: char) -> String {
         format!(
@@ -1860,7 +4358,12 @@ fn to_blocked_session_entry(instance_name: &InstanceName, row: &Row, sep: char)
     let wait_duration_ms = row.get_bigint_by_idx(1).to_string();
     let wait_type = row.get_value_by_idx(2).trim().to_string();
     let blocking_session_id = row.get_value_by_idx(3).trim().to_string();
-    format!("{instance_name}{sep}{session_id}{sep}{wait_duration_ms}{sep}{wait_type}{sep}{blocking_session_id}\n",)
+    let sql_texts = if row.len() > 4 {
+        to_sql_text_columns(&row.get_value_by_idx(4), &row.get_value_by_idx(5), sep)
+    } else {
+        String::new()
+    };
+    format!("{instance_name}{sep}{session_id}{sep}{wait_duration_ms}{sep}{wait_type}{sep}{blocking_session_id}{sql_texts}\n",)
 }
 
 fn to_blocked_session_entry_odbc(
@@ -1875,35 +4378,74 @@ fn to_blocked_session_entry_odbc(
         .to_string();
     let wait_type = get_row_value_by_idx(row, 2).trim().to_string();
     let blocking_session_id = get_row_value_by_idx(row, 3).trim().to_string();
-    format!("{instance_name}{sep}{session_id}{sep}{wait_duration_ms}{sep}{wait_type}{sep}{blocking_session_id}\n",)
+    let sql_texts = if row.len() > 4 {
+        to_sql_text_columns(
+            &get_row_value_by_idx(row, 4),
+            &get_row_value_by_idx(row, 5),
+            sep,
+        )
+    } else {
+        String::new()
+    };
+    format!("{instance_name}{sep}{session_id}{sep}{wait_duration_ms}{sep}{wait_type}{sep}{blocking_session_id}{sql_texts}\n",)
+}
+
+/// statements of blocked and blocking session as additional columns
+fn to_sql_text_columns(blocked: &str, blocking: &str, sep: char) -> String {
+    format!(
+        "{sep}{}{sep}{}",
+        sanitize_sql_text(blocked, sep),
+        sanitize_sql_text(blocking, sep)
+    )
+}
+
+/// Makes statement safe for the line based output: whitespace and separators are
+/// collapsed into single space, the result is truncated to [`MAX_SQL_TEXT_LENGTH`] chars
+fn sanitize_sql_text(text: &str, sep: char) -> String {
+    let text = text
+        .split(|c: char| c.is_whitespace() || c.is_control() || c == sep)
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if text.chars().count() > MAX_SQL_TEXT_LENGTH {
+        text.chars()
+            .take(MAX_SQL_TEXT_LENGTH - 3)
+            .chain("...".chars())
+            .collect()
+    } else {
+        text
+    }
 }
 
 impl CheckConfig {
     pub async fn exec(&self, environment: &Env) -> Result<String> {
+        let mut output: Vec<u8> = Vec::new();
+        self.exec_to(environment, &mut output).await?;
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+
+    /// Writes data into `writer` as soon as every instance is processed
+    pub async fn exec_to<W: AsyncWrite + Unpin>(
+        &self,
+        environment: &Env,
+        writer: &mut W,
+    ) -> Result<()> {
         if let Some(ms_sql) = self.ms_sql() {
             CheckConfig::prepare_cache_sub_dir(environment, &ms_sql.config_cache_dir());
             log::info!("Generating main data");
-            let mut output: Vec<String> = Vec::new();
-            output.push(
-                generate_data(ms_sql, environment)
-                    .await
-                    .unwrap_or_else(|e| {
-                        log::error!("Error generating data at main config: {e}");
-                        format!("{e}\n")
-                    }),
-            );
+            if let Err(e) = write_data(ms_sql, environment, writer).await {
+                log::error!("Error generating data at main config: {e}");
+                write_chunk(writer, &format!("{e}\n")).await?;
+            }
             for (num, config) in std::iter::zip(0.., ms_sql.configs()) {
                 log::info!("Generating configs data");
                 CheckConfig::prepare_cache_sub_dir(environment, &config.config_cache_dir());
-                let configs_data = generate_data(config, environment)
-                    .await
-                    .unwrap_or_else(|e| {
-                        log::error!("Error generating data at config {num}: {e}");
-                        format!("{e}\n")
-                    });
-                output.push(configs_data);
+                if let Err(e) = write_data(config, environment, writer).await {
+                    log::error!("Error generating data at config {num}: {e}");
+                    write_chunk(writer, &format!("{e}\n")).await?;
+                }
             }
-            Ok(output.join(""))
+            Ok(())
         } else {
             log::error!("No config");
             anyhow::bail!("No Config")
@@ -1924,7 +4466,11 @@ fn generate_dumb_header(ms_sql: &config::ms_sql::Config) -> String {
     ms_sql
         .valid_sections()
         .iter()
-        .map(|s| Section::new(s, Some(ms_sql.cache_age())).to_plain_header())
+        .map(|s| {
+            Section::from_config(s, ms_sql.sections(), false)
+                .with_emit(ms_sql.emit())
+                .to_plain_header()
+        })
         .collect::<Vec<_>>()
         .join("")
 }
@@ -1944,7 +4490,8 @@ fn generate_signaling_block(
     ms_sql: &config::ms_sql::Config,
     piggyback_host: &Option<PiggybackHostName>,
 ) -> String {
-    let body = generate_dumb_header(ms_sql) + &Section::make_instance_section().to_plain_header();
+    let body = generate_dumb_header(ms_sql)
+        + &Section::make_instance_section(ms_sql.emit()).to_plain_header();
     if let Some(piggyback_host) = piggyback_host.as_ref() {
         emit::piggyback_header(piggyback_host) + &body + &emit::piggyback_footer()
     } else {
@@ -1952,54 +4499,270 @@ fn generate_signaling_block(
     }
 }
 
-/// Generate data as defined by config
+async fn write_chunk<W: AsyncWrite + Unpin>(writer: &mut W, data: &str) -> Result<()> {
+    writer.write_all(data.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Write data as defined by config
 /// Consists from two parts: instance entries + sections for every instance
-async fn generate_data(ms_sql: &config::ms_sql::Config, environment: &Env) -> Result<String> {
-    let instances = find_working_instances(ms_sql, environment).await?;
-    if instances.is_empty() {
-        return Ok(generate_signaling_block(ms_sql, &None)
-            + "ERROR: Failed to gather SQL server instances\n");
-    }
-    log::info!(
-        "Found {} SQL server instances: [ {} ]",
-        instances.len(),
-        instances
-            .iter()
-            .map(|i| format!("{}", i))
-            .collect::<Vec<_>>()
-            .join(", ")
+async fn write_data<W: AsyncWrite + Unpin>(
+    ms_sql: &config::ms_sql::Config,
+    environment: &Env,
+    writer: &mut W,
+) -> Result<()> {
+    let password_choices = obtain_password_choices_path(ms_sql, environment);
+    let choices = Arc::new(
+        password_choices
+            .as_deref()
+            .map(PasswordChoices::load)
+            .unwrap_or_default(),
     );
-
-    let sections = ms_sql
-        .valid_sections()
-        .into_iter()
-        .map(|s| {
-            Section::new(
-                s,
-                if environment.disable_caching() {
-                    None
-                } else {
-                    Some(ms_sql.cache_age())
-                },
-            )
-        })
-        .collect::<Vec<_>>();
-
-    Ok(generate_signaling_blocks(ms_sql, &instances)
-        + &generate_instance_entries(&instances)
-        + &generate_result(&instances, &sections, ms_sql).await?)
+    let environment = &environment
+        .clone()
+        .with_emit(ms_sql.emit())
+        .with_password_choices(choices.clone());
+    for plugin in generator::find_unknown_plugins(ms_sql.options().generators()) {
+        log::warn!("No section generators are registered for plugin `{plugin}`");
+    }
+    let otlp_endpoint = ms_sql.telemetry().otlp_endpoint();
+    let trace = otlp_endpoint.map(|_| telemetry::start_trace("mk-sql run"));
+    let result = write_instances_data(ms_sql, environment, writer).await;
+    if let Some(path) = password_choices.as_ref() {
+        choices
+            .save(path)
+            .unwrap_or_else(|e| log::warn!("Failed to remember passwords in {path:?}: {e}"));
+    }
+    let result = write_host_sections(result, ms_sql, environment, writer).await;
+    if let (Some(root), Some(endpoint)) = (trace, otlp_endpoint) {
+        telemetry::finish_trace(root, endpoint)
+            .await
+            .unwrap_or_else(|e| log::warn!("Failed to export spans: {e}"));
+    }
+    result
 }
 
-fn generate_instance_entries<P: AsRef<SqlInstance>>(instances: &[P]) -> String {
-    instances
-        .iter()
-        .map(|i| generate_instance_entry(i.as_ref()))
+/// Sections of the host don't depend on instances: written even if those failed
+/// Returns the error of the instances if any
+async fn write_host_sections<W: AsyncWrite + Unpin>(
+    instances_result: Result<()>,
+    ms_sql: &config::ms_sql::Config,
+    environment: &Env,
+    writer: &mut W,
+) -> Result<()> {
+    if environment.skeleton() {
+        return instances_result;
+    }
+    let scan = ms_sql.discovery().scan();
+    let scan_result = if scan.is_empty() {
+        Ok(())
+    } else {
+        let _span = telemetry::span("browser scan", None);
+        let section = browser::generate_scan_section(
+            scan,
+            ms_sql.conn().timeout(),
+            ms_sql.emit().section_prefix(),
+        )
+        .await;
+        write_chunk(writer, &section).await
+    };
+    let services_result = if ms_sql.discovery().services() {
+        write_chunk(
+            writer,
+            &generate_services_section(ms_sql.emit().section_prefix()),
+        )
+        .await
+    } else {
+        Ok(())
+    };
+    instances_result.and(scan_result).and(services_result)
+}
+
+const SERVICES_SECTION_NAME: &str = "services";
+
+/// Reporting, Integration and Analysis services of the host, not bound to an instance
+fn generate_services_section(prefix: Option<&str>) -> String {
+    let body = match services::read() {
+        Ok(services) => to_services_entries(&services, '|'),
+        Err(e) => format!("{}\n", prepare_error(&e)),
+    };
+    emit::header(prefix, SERVICES_SECTION_NAME, '|') + &body
+}
+
+fn to_services_entries(services: &[services::Service], sep: char) -> String {
+    services
+        .iter()
+        .map(|s| {
+            format!(
+                "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+                s.kind,
+                s.name,
+                s.instance,
+                s.state,
+                s.start_mode,
+                s.version.as_deref().unwrap_or("-")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+/// only if some authentication asks to remember the working password
+fn obtain_password_choices_path(
+    ms_sql: &config::ms_sql::Config,
+    environment: &Env,
+) -> Option<PathBuf> {
+    let remember = ms_sql.auth().remember_password()
+        || ms_sql
+            .instances()
+            .iter()
+            .any(|i| i.auth().remember_password());
+    remember
+        .then(|| environment.obtain_cache_sub_dir(&ms_sql.config_cache_dir()))
+        .flatten()
+        .map(|d| d.join(client::PASSWORD_CHOICES_FILE))
+}
+
+async fn write_instances_data<W: AsyncWrite + Unpin>(
+    ms_sql: &config::ms_sql::Config,
+    environment: &Env,
+    writer: &mut W,
+) -> Result<()> {
+    let mut span = telemetry::span("discovery", None);
+    let instances = find_working_instances(ms_sql, environment).await?;
+    let instances = if ms_sql.cluster_dedup() == &ClusterDedup::ActiveNode {
+        select_active_node_instances(instances).await
+    } else {
+        instances
+    };
+    if instances.is_empty() {
+        return write_chunk(
+            writer,
+            &(generate_signaling_block(ms_sql, &None)
+                + "ERROR: Failed to gather SQL server instances\n"),
+        )
+        .await;
+    }
+    span.set_attribute("mssql.instances", instances.len());
+    span.end();
+    log::info!(
+        "Found {} SQL server instances: [ {} ]",
+        instances.len(),
+        instances
+            .iter()
+            .map(|i| format!("{}", i))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    write_chunk(writer, &generate_skeleton(ms_sql, &instances)).await?;
+    if environment.skeleton() {
+        log::info!("Skeleton only, sections are not generated");
+        return Ok(());
+    }
+    write_result(&instances, ms_sql, environment, writer).await?;
+    let piggyback_hosts = instances
+        .iter()
+        .map(|i| i.piggyback().clone())
+        .collect::<Vec<_>>();
+    write_chunk(
+        writer,
+        &generate_static_sections(ms_sql.static_sections(), &piggyback_hosts, |path| {
+            std::fs::read_to_string(path)
+        }),
+    )
+    .await
+}
+
+/// Per instance sections are repeated for every piggyback host of the instances,
+/// instances without piggyback host share one copy in the main data
+/// Unreadable file is logged and skipped: its format is unknown
+fn generate_static_sections<F>(
+    static_sections: &[config::ms_sql::StaticSection],
+    piggyback_hosts: &[Option<PiggybackHostName>],
+    read: F,
+) -> String
+where
+    F: Fn(&Path) -> std::io::Result<String>,
+{
+    let mut hosts: Vec<Option<&PiggybackHostName>> = vec![];
+    for host in piggyback_hosts.iter().map(Option::as_ref) {
+        if !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+    static_sections
+        .iter()
+        .filter_map(|s| match read(s.file()) {
+            Ok(content) => {
+                let mut body = emit::plain_header(s.name(), s.sep()) + &content;
+                if !body.ends_with('\n') {
+                    body.push('\n');
+                }
+                Some((s.per_instance(), body))
+            }
+            Err(e) => {
+                log::error!(
+                    "Can't read static section `{}` {:?}: {e}",
+                    s.name(),
+                    s.file()
+                );
+                None
+            }
+        })
+        .map(|(per_instance, body)| {
+            if !per_instance {
+                return body;
+            }
+            hosts
+                .iter()
+                .map(|host| match host {
+                    Some(host) => emit::piggyback_header(host) + &body + &emit::piggyback_footer(),
+                    None => body.clone(),
+                })
+                .collect::<Vec<String>>()
+                .join("")
+        })
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+/// headers of all sections and config lines of instances, no queries are required
+fn generate_skeleton(ms_sql: &config::ms_sql::Config, instances: &[SqlInstance]) -> String {
+    generate_signaling_blocks(ms_sql, instances) + &generate_instance_entries(instances)
+}
+
+/// drops clustered instances which are active on another node
+async fn select_active_node_instances(instances: Vec<SqlInstance>) -> Vec<SqlInstance> {
+    let active = futures::future::join_all(instances.iter().map(|i| i.is_on_active_node())).await;
+    instances
+        .into_iter()
+        .zip(active)
+        .filter_map(|(instance, active)| {
+            if active {
+                Some(instance)
+            } else {
+                log::info!(
+                    "Instance {} is skipped: passive cluster node",
+                    instance.name
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+fn generate_instance_entries<P: AsRef<SqlInstance>>(instances: &[P]) -> String {
+    instances
+        .iter()
+        .map(|i| generate_instance_entry(i.as_ref()))
         .collect::<Vec<String>>()
         .join("")
 }
 
 fn generate_instance_entry<P: AsRef<SqlInstance>>(instance: &P) -> String {
-    let section = Section::make_instance_section();
+    let section = Section::make_instance_section(instance.as_ref().environment.emit());
     [
         instance.as_ref().generate_header(),
         section.to_plain_header(),
@@ -2012,31 +4775,168 @@ fn generate_instance_entry<P: AsRef<SqlInstance>>(instance: &P) -> String {
     .join("")
 }
 
+/// `<prefix><HOST>_<NAME>`, the instance name as is without prefix and host
+fn expand_piggyback_template(
+    template: &str,
+    name: &InstanceName,
+    hostname: &str,
+) -> PiggybackHostName {
+    template
+        .replace("{instance}", &name.to_string())
+        .replace("{hostname}", hostname)
+        .to_lowercase()
+        .into()
+}
+
+/// explicitly configured locale wins over the language of the instance session
+fn obtain_value_locale(emit: &Emit, endpoint: &Endpoint) -> Option<ValueLocale> {
+    let language = emit
+        .values_locale()
+        .or(endpoint.conn().connect_options().language())?;
+    let locale = ValueLocale::from_language(language);
+    if locale.is_none() {
+        log::warn!("Formats of language `{language}` are unknown, values are kept as is");
+    }
+    locale
+}
+
+fn make_label(name: &InstanceName, prefix: Option<&str>, host: Option<&str>) -> InstanceName {
+    let host = host
+        .map(|h| format!("{}_", h.trim().to_uppercase()))
+        .unwrap_or_default();
+    format!("{}{host}{name}", prefix.unwrap_or_default()).into()
+}
+
 async fn find_working_instances(
     ms_sql: &config::ms_sql::Config,
     environment: &Env,
 ) -> Result<Vec<SqlInstance>> {
-    let builders = find_allowed_instance_builders(ms_sql).await?;
+    let builders = find_allowed_instance_builders(ms_sql, environment.password_choices()).await?;
     if builders.is_empty() {
         log::warn!("Found NO allowed SQL server instances");
         return Ok(Vec::new());
     }
 
     log::info!("Found {} working SQL server instances", builders.len());
-    Ok(builders
+    let containers = find_container_instance_builders(ms_sql).await;
+    let mut instances = builders
         .into_iter()
-        .map(|b: SqlInstanceBuilder| {
+        .map(|b| (b, false))
+        .chain(containers.into_iter().map(|b| (b, true)))
+        .map(|(b, is_container): (SqlInstanceBuilder, bool)| {
             b.environment(environment)
                 .cache_dir(&ms_sql.config_cache_dir())
+                .cluster_dedup(ms_sql.cluster_dedup())
+                .blackout_windows(ms_sql.options().blackout_windows())
+                // containers run the default instance: the container name distinguishes them
+                .qualify_with_host(is_container || ms_sql.qualify_with_host())
+                .instance_prefix(ms_sql.instance_prefix())
+                .piggyback_template(ms_sql.piggyback_template())
+                .suppress_errors_after(ms_sql.error_suppression().after())
+                .section_parallelism(ms_sql.options().section_parallelism())
+                .memory_budget(ms_sql.limits().max_memory())
+                .generators(ms_sql.options().generators())
                 .build()
         })
-        .collect::<Vec<SqlInstance>>())
+        .collect::<Vec<SqlInstance>>();
+    assign_host_gates(&mut instances, ms_sql.options().host_heavy_sections());
+    if ms_sql.passive_node_policy() == &PassiveNodePolicy::Skip {
+        mark_passive_instances(&mut instances);
+    }
+    Ok(instances)
+}
+
+/// Clustered instances whose SQL resource is owned by another node are not queried
+fn mark_passive_instances(instances: &mut [SqlInstance]) {
+    if !instances.iter().any(is_clustered_instance) {
+        return;
+    }
+    match (cluster::read_owners(), utils::get_local_computer_name()) {
+        (Ok(owners), Some(local)) => apply_owners(instances, &owners, &local),
+        (Err(e), _) => log::warn!("Failed to read cluster owners: {e}, instances are queried"),
+        (_, None) => log::warn!("Unknown local computer name, instances are queried"),
+    }
+}
+
+fn is_clustered_instance(instance: &SqlInstance) -> bool {
+    instance
+        .cluster
+        .as_ref()
+        .is_some_and(|c| !c.to_string().is_empty())
+}
+
+fn apply_owners(instances: &mut [SqlInstance], owners: &HashMap<String, String>, local: &str) {
+    for instance in instances.iter_mut().filter(|i| is_clustered_instance(i)) {
+        match owners.get(&instance.name.to_string().to_uppercase()) {
+            Some(owner) if !owner.eq_ignore_ascii_case(local) => {
+                instance.passive_owner = Some(owner.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Default instances of the containers publishing the SQL Server port
+async fn find_container_instance_builders(
+    ms_sql: &config::ms_sql::Config,
+) -> Vec<SqlInstanceBuilder> {
+    let Some(discovery) = ms_sql.discovery().containers() else {
+        return vec![];
+    };
+    let containers = container::find_containers(discovery, ms_sql.conn().timeout())
+        .await
+        .unwrap_or_else(|e| {
+            log::warn!("Error discovering containers: {e}");
+            vec![]
+        });
+    log::info!(
+        "Found {} SQL server containers: [ {} ]",
+        containers.len(),
+        containers
+            .iter()
+            .map(|c| format!("{}:{}", c.name, c.port))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let endpoint = ms_sql.endpoint();
+    containers
+        .into_iter()
+        .map(|c| {
+            SqlInstanceBuilder::new()
+                .name("MSSQLSERVER")
+                .endpoint(&endpoint.with_address(c.host.into(), Port::from(c.port)))
+                .computer_name(Some(c.name.clone().into()))
+                .piggyback(discovery.piggyback().then(|| c.name.into()))
+        })
+        .collect()
+}
+
+/// Instances sharing a host get the same gate limiting their heavy sections
+fn assign_host_gates(instances: &mut [SqlInstance], permits: u32) {
+    if permits == 0 {
+        return;
+    }
+    let mut per_host: HashMap<String, usize> = HashMap::new();
+    for instance in instances.iter() {
+        *per_host.entry(instance.host_key()).or_default() += 1;
+    }
+    let mut gates: HashMap<String, Arc<Semaphore>> = HashMap::new();
+    for instance in instances.iter_mut() {
+        let host = instance.host_key();
+        if per_host.get(&host).copied().unwrap_or_default() > 1 {
+            let gate = gates
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(permits as usize)));
+            instance.host_gate = Some(gate.clone());
+        }
+    }
 }
 
 async fn find_allowed_instance_builders(
     ms_sql: &config::ms_sql::Config,
+    choices: &PasswordChoices,
 ) -> Result<Vec<SqlInstanceBuilder>> {
-    let builders = find_all_instance_builders(ms_sql).await?;
+    let builders = find_all_instance_builders(ms_sql, choices).await?;
     Ok(builders
         .into_iter()
         .filter(|i| ms_sql.is_instance_allowed(&i.get_name()))
@@ -2045,8 +4945,9 @@ async fn find_allowed_instance_builders(
 
 pub async fn find_all_instance_builders(
     ms_sql: &config::ms_sql::Config,
+    choices: &PasswordChoices,
 ) -> Result<Vec<SqlInstanceBuilder>> {
-    let found = find_detectable_instance_builders(ms_sql).await;
+    let found = find_detectable_instance_builders(ms_sql, choices).await;
     log::info!(
         "Found {} instances by discovery: [ {} ]",
         found.len(),
@@ -2057,7 +4958,7 @@ pub async fn find_all_instance_builders(
             .join(", ")
     );
 
-    let detected = if ms_sql.discovery().detect() {
+    let detected: Vec<SqlInstanceBuilder> = if ms_sql.discovery().detect() {
         found
     } else {
         ms_sql
@@ -2066,30 +4967,54 @@ pub async fn find_all_instance_builders(
             .iter()
             .map(|name| SqlInstanceBuilder::new().name(name))
             .collect::<Vec<SqlInstanceBuilder>>()
-    }
-    .into_iter()
-    .map(|b| b.piggyback(ms_sql.piggyback_host().map(|h| h.to_string().into())))
-    .collect();
-    let customizations: HashMap<&InstanceName, &CustomInstance> = ms_sql
+    };
+    let by_ports = find_port_range_instance_builders(ms_sql, &detected, choices).await;
+    let detected: Vec<SqlInstanceBuilder> = detected
+        .into_iter()
+        .chain(by_ports)
+        .filter(|b| ms_sql.is_instance_allowed(&b.get_name()))
+        .map(|b| b.piggyback(ms_sql.piggyback_host().map(|h| h.to_string().into())))
+        .collect();
+    let customizations: HashMap<String, &CustomInstance> = ms_sql
         .instances()
         .iter()
         .filter_map(|i| {
             if ms_sql.is_instance_allowed(i.name()) {
-                Some((i.name(), i))
+                Some((i.name().normalized(), i))
             } else {
                 None
             }
         })
         .collect();
+    warn_near_misses(&detected, &customizations);
     let builders = apply_customizations(detected, &customizations);
-    add_custom_instance_builders(builders, &customizations).await
+    add_custom_instance_builders(builders, &customizations, choices).await
+}
+
+/// custom instance which doesn't match any detected instance, but differs from one
+/// only by a single character, is most probably a typo in the config
+fn warn_near_misses(
+    detected: &[SqlInstanceBuilder],
+    customizations: &HashMap<String, &CustomInstance>,
+) {
+    let detected_names: Vec<InstanceName> = detected.iter().map(|b| b.get_name()).collect();
+    for customization in customizations.values() {
+        let name = customization.name();
+        if detected_names.iter().any(|d| d.is_same(name)) {
+            continue;
+        }
+        if let Some(similar) = detected_names.iter().find(|d| d.is_near_miss(name)) {
+            log::warn!("Custom instance `{name}` is not detected, did you mean `{similar}`?");
+        }
+    }
 }
 
 /// find instances described in the config but not detected by the discovery
 async fn find_detectable_instance_builders(
     ms_sql: &config::ms_sql::Config,
+    choices: &PasswordChoices,
 ) -> Vec<SqlInstanceBuilder> {
-    obtain_instance_builders(&ms_sql.endpoint(), &[], ms_sql.discovery())
+    obtain_instance_builders(&ms_sql.endpoint(), &[], ms_sql.discovery(), choices)
         .await
         .unwrap_or_else(|e| {
             log::warn!("Error discovering instances: {e}");
@@ -2097,36 +5022,105 @@ async fn find_detectable_instance_builders(
         })
 }
 
+/// Instances answering TDS pre-login on the configured ports of the endpoint host,
+/// instances from `known` are skipped
+async fn find_port_range_instance_builders(
+    ms_sql: &config::ms_sql::Config,
+    known: &[SqlInstanceBuilder],
+    choices: &PasswordChoices,
+) -> Vec<SqlInstanceBuilder> {
+    let ports = ms_sql.discovery().ports();
+    if ports.is_empty() {
+        return vec![];
+    }
+    let endpoint = ms_sql.endpoint();
+    let host = endpoint.conn().hostname();
+    let timeout = endpoint.conn().timeout();
+    log::info!("Probing {} ports of {host}", ports.len());
+    let found = stream::iter(ports.iter().copied())
+        .map(|port| {
+            let endpoint = endpoint.with_address(host.clone(), Port::from(port));
+            async move {
+                prelogin::probe(&endpoint.conn().hostname().to_string(), port, timeout)
+                    .await
+                    .map_err(|e| log::debug!("Port {port} doesn't answer: {e}"))
+                    .ok()?;
+                let mut client =
+                    client::connect_custom_endpoint(&endpoint, Port::from(port), choices)
+                        .await
+                        .map_err(|e| log::warn!("SQL Server at port {port} is not accessible: {e}"))
+                        .ok()?;
+                SqlInstanceProperties::obtain_by_query(&mut client)
+                    .await
+                    .map_err(|e| log::warn!("Failed to get instance at port {port}: {e}"))
+                    .ok()
+                    .map(|p| to_instance_builder(&endpoint, &p))
+            }
+        })
+        .buffered(MAX_CONNECTIONS as usize)
+        .collect::<Vec<_>>()
+        .await;
+    let mut builders: Vec<SqlInstanceBuilder> = Vec::new();
+    for builder in found.into_iter().flatten() {
+        let name = builder.get_name();
+        if known
+            .iter()
+            .chain(builders.iter())
+            .any(|b| b.get_name().is_same(&name))
+        {
+            continue;
+        }
+        log::info!("Instance `{name}` found at port {}", builder.get_port());
+        builders.push(builder);
+    }
+    builders
+}
+
 /// find instances described in the config but not detected by the discovery
 /// may NOT work - should be approved during testing
 async fn add_custom_instance_builders(
     input_builders: Vec<SqlInstanceBuilder>,
-    customizations: &HashMap<&InstanceName, &CustomInstance>,
+    customizations: &HashMap<String, &CustomInstance>,
+    choices: &PasswordChoices,
 ) -> Result<Vec<SqlInstanceBuilder>> {
     let reconnects = determine_reconnect(input_builders, customizations);
 
-    let mut builders: Vec<SqlInstanceBuilder> = Vec::new();
-    for (builder, endpoint) in reconnects.into_iter() {
-        if let Some(endpoint) = endpoint {
-            if let Some(b) = get_custom_instance_builder(&builder, &endpoint).await {
-                builders.push(b);
+    // properties of reconnected instances are obtained in parallel, order is kept
+    let builders = stream::iter(reconnects)
+        .map(|(builder, endpoint)| async move {
+            match endpoint {
+                Some(endpoint) => {
+                    match get_custom_instance_builder(&builder, &endpoint, choices).await {
+                        Some(b) => Some(b),
+                        None if builder.is_registry_only() => {
+                            log::warn!(
+                                "Instance `{}` is not accessible, to be reported as down",
+                                builder.get_name()
+                            );
+                            Some(builder.shared_endpoint(&endpoint))
+                        }
+                        None => None,
+                    }
+                }
+                None => Some(builder),
             }
-        } else {
-            builders.push(builder);
-        }
-    }
-    Ok(builders)
+        })
+        .buffered(MAX_CONNECTIONS as usize)
+        .collect::<Vec<_>>()
+        .await;
+    Ok(builders.into_iter().flatten().collect())
 }
 
 async fn get_custom_instance_builder(
     builder: &SqlInstanceBuilder,
     endpoint: &Endpoint,
+    choices: &PasswordChoices,
 ) -> Option<SqlInstanceBuilder> {
     let instance_name = &builder.get_name();
     let auth = endpoint.auth();
     let conn = endpoint.conn();
     if is_local_host(auth, conn) && !is_use_tcp(instance_name, auth, conn) {
-        if let Ok(mut client) = create_odbc_client(instance_name, None) {
+        if let Ok(mut client) = create_odbc_client(instance_name, None, conn.connect_options()) {
             log::debug!("Trying to connect to `{instance_name}` using ODBC");
             let b = obtain_properties(&mut client, instance_name)
                 .await
@@ -2139,14 +5133,14 @@ async fn get_custom_instance_builder(
     }
     let port = get_reasonable_port(builder, endpoint);
     log::debug!("Trying to connect to `{instance_name}` using config port {port}");
-    let result = match client::connect_custom_endpoint(endpoint, port.clone()).await {
+    let result = match client::connect_custom_endpoint(endpoint, port.clone(), choices).await {
         Ok(mut client) => {
             let b = obtain_properties(&mut client, instance_name)
                 .await
                 .map(|p| to_instance_builder(endpoint, &p));
             if b.is_none() {
                 log::info!("Instance `{instance_name}` not found. Try to find it");
-                find_custom_instance(endpoint, instance_name).await
+                find_custom_instance(endpoint, instance_name, choices).await
             } else {
                 b
             }
@@ -2160,7 +5154,7 @@ async fn get_custom_instance_builder(
     return result;
 
     #[cfg(windows)]
-    if result.is_none() {
+    if result.is_none() && conn.sql_browser().enabled() {
         log::info!(
             "Instance `{instance_name}` at port {} not found. Try to use named connection.",
             port.clone()
@@ -2177,7 +5171,7 @@ async fn get_custom_instance_builder(
             }
             Err(e) => {
                 log::warn!("Error creating client for `{instance_name}`: {e}");
-                find_custom_instance(endpoint, instance_name).await
+                find_custom_instance(endpoint, instance_name, choices).await
             }
         }
     } else {
@@ -2188,17 +5182,21 @@ async fn get_custom_instance_builder(
 async fn find_custom_instance(
     endpoint: &Endpoint,
     instance_name: &InstanceName,
+    choices: &PasswordChoices,
 ) -> Option<SqlInstanceBuilder> {
-    let builders = obtain_instance_builders(endpoint, &[instance_name], &Discovery::default())
-        .await
-        .unwrap_or_else(|e| {
-            log::error!("Error creating client for instance `{instance_name}`: {e}",);
-            Vec::<SqlInstanceBuilder>::new()
-        });
+    let builders =
+        obtain_instance_builders(endpoint, &[instance_name], &Discovery::default(), choices)
+            .await
+            .unwrap_or_else(|e| {
+                log::error!("Error creating client for instance `{instance_name}`: {e}",);
+                Vec::<SqlInstanceBuilder>::new()
+            });
     match detect_instance_port(instance_name, &builders) {
         Some(port) => {
             log::info!("Instance `{instance_name}` found at port {port}");
-            if let Ok(mut client) = client::connect_custom_endpoint(endpoint, port.clone()).await {
+            if let Ok(mut client) =
+                client::connect_custom_endpoint(endpoint, port.clone(), choices).await
+            {
                 obtain_properties(&mut client, instance_name)
                     .await
                     .map(|p| to_instance_builder(endpoint, &p).port(Some(port)))
@@ -2246,7 +5244,7 @@ pub async fn obtain_properties(
 ) -> Option<SqlInstanceProperties> {
     match SqlInstanceProperties::obtain_by_query(client).await {
         Ok(properties) => {
-            if properties.name == *name {
+            if properties.name.is_same(name) {
                 log::info!("Custom instance `{name}` added in query");
                 return Some(properties);
             }
@@ -2272,26 +5270,29 @@ fn to_instance_builder(
         .computer_name(Some(properties.computer_name.clone()))
         .version(&properties.version)
         .edition(&properties.edition)
+        .properties(properties)
         .endpoint(endpoint)
         .port(Some(endpoint.conn().port()))
 }
 /// returns
 /// - SQL instances with custom endpoint if any
+///
+/// endpoint of a customization is made once and shared by reconnect and resulting builder
 fn determine_reconnect(
     builders: Vec<SqlInstanceBuilder>,
-    customizations: &HashMap<&InstanceName, &CustomInstance>,
-) -> Vec<(SqlInstanceBuilder, Option<Endpoint>)> {
-    let mut found: HashSet<InstanceName> = HashSet::new();
+    customizations: &HashMap<String, &CustomInstance>,
+) -> Vec<(SqlInstanceBuilder, Option<Arc<Endpoint>>)> {
+    let mut found: HashSet<String> = HashSet::with_capacity(builders.len());
     let mut b = builders
         .into_iter()
         .map(|instance_builder| {
-            found.insert(instance_builder.get_name());
-            match customizations.get(&instance_builder.get_name()) {
-                Some(customization)
-                    if Some(&customization.endpoint()) != instance_builder.get_endpoint() =>
-                {
+            let key = instance_builder.get_name().normalized();
+            let endpoint = customizations.get(&key).map(|c| c.endpoint());
+            found.insert(key);
+            match endpoint {
+                Some(endpoint) if Some(&endpoint) != instance_builder.get_endpoint() => {
                     log::info!("Instance {} to be reconnected", instance_builder.get_name(),);
-                    (instance_builder, Some(customization.endpoint()))
+                    (instance_builder, Some(Arc::new(endpoint)))
                 }
                 _ => {
                     log::info!(
@@ -2302,17 +5303,18 @@ fn determine_reconnect(
                 }
             }
         })
-        .collect::<Vec<(SqlInstanceBuilder, Option<Endpoint>)>>();
+        .collect::<Vec<(SqlInstanceBuilder, Option<Arc<Endpoint>>)>>();
 
     customizations
         .iter()
-        .filter(|(&k, _)| !found.contains(k))
-        .map(|(&name, customization)| {
+        .filter(|(k, _)| !found.contains(*k))
+        .map(|(_, customization)| {
+            let name = customization.name();
             log::info!("Add custom instance {} ", name);
             let builder = SqlInstanceBuilder::new().name(name.clone());
             (
                 apply_customization(builder, customization),
-                Some(customization.endpoint()),
+                Some(Arc::new(customization.endpoint())),
             )
         })
         .for_each(|a| b.push(a));
@@ -2322,16 +5324,16 @@ fn determine_reconnect(
 
 fn apply_customizations(
     detected: Vec<SqlInstanceBuilder>,
-    customizations: &HashMap<&InstanceName, &CustomInstance>,
+    customizations: &HashMap<String, &CustomInstance>,
 ) -> Vec<SqlInstanceBuilder> {
     detected
         .into_iter()
-        .map(
-            |instance_builder| match customizations.get(&instance_builder.get_name()) {
+        .map(|instance_builder| {
+            match customizations.get(&instance_builder.get_name().normalized()) {
                 Some(customization) => apply_customization(instance_builder, customization),
-                None => instance_builder.clone(),
-            },
-        )
+                None => instance_builder,
+            }
+        })
         .collect::<Vec<SqlInstanceBuilder>>()
 }
 
@@ -2347,46 +5349,128 @@ fn apply_customization(
                 .map(|h| h.clone().into()),
         )
         .alias(customization.alias())
+        .main_dbs(customization.main_dbs())
+        .required_databases(customization.required_databases())
 }
 
 /// Intelligent async processing of the data
-async fn generate_result(
+/// Data of an instance is written as a whole: piggyback blocks can't be interleaved
+async fn write_result<W: AsyncWrite + Unpin>(
     instances: &[SqlInstance],
-    sections: &[Section],
     ms_sql: &config::ms_sql::Config,
-) -> Result<String> {
+    environment: &Env,
+    writer: &mut W,
+) -> Result<()> {
     // place all futures now in vector for future asynchronous processing
-    let tasks = instances
+    // instances are started in order of their delays
+    let spread_seconds = ms_sql.options().spread_seconds();
+    let start = tokio::time::Instant::now();
+    let mut delayed = instances
         .iter()
-        .map(move |instance| instance.generate_sections(sections));
+        .map(|instance| (calc_start_delay(&instance.name, spread_seconds), instance))
+        .collect::<Vec<_>>();
+    delayed.sort_by_key(|(delay, _)| *delay);
+    let tasks = delayed
+        .into_iter()
+        .map(move |(delay, instance)| async move {
+            tokio::time::sleep_until(start + delay).await;
+            let sections = make_instance_sections(ms_sql, &instance.name, environment);
+            instance.generate_sections(&sections).await
+        });
 
     // processing here
     let s: u32 = ms_sql.options().max_connections().into();
-    let results = stream::iter(tasks)
-        .buffer_unordered(s as usize)
-        .collect::<Vec<_>>()
-        .await;
+    let mut results = stream::iter(tasks).buffer_unordered(s as usize);
+    while let Some(result) = results.next().await {
+        write_chunk(writer, &result).await?;
+    }
+    Ok(())
+}
+
+/// valid sections of the instance with options resolved by the config
+fn make_instance_sections(
+    ms_sql: &config::ms_sql::Config,
+    name: &InstanceName,
+    environment: &Env,
+) -> Vec<Section> {
+    let sections = ms_sql.instance_sections(name);
+    sections
+        .select(&[
+            config::section::SectionKind::Sync,
+            config::section::SectionKind::Async,
+        ])
+        .into_iter()
+        .map(|s| {
+            Section::from_config(s, sections, !environment.disable_caching())
+                .with_security(ms_sql.security())
+                .with_emit(ms_sql.emit())
+        })
+        .collect()
+}
 
-    Ok(results.join(""))
+/// Stable per instance delay in range [0, spread_seconds): the same instance starts
+/// at the same offset every run, different instances are distributed over the period
+fn calc_start_delay(name: &InstanceName, spread_seconds: u32) -> std::time::Duration {
+    if spread_seconds == 0 {
+        return std::time::Duration::ZERO;
+    }
+    let mut hasher = DefaultHasher::new();
+    name.normalized().hash(&mut hasher);
+    std::time::Duration::from_millis(hasher.finish() % (spread_seconds as u64 * 1000))
 }
 
 // TODO(sk):probably normal SQL query  is better than registry reading SQL query
 /// obtain all instances from endpoint, on Windows can try SQL Browser
+/// if the local host is not accessible at all, instances are read directly from the registry
 pub async fn obtain_instance_builders(
     endpoint: &Endpoint,
     instances: &[&InstanceName],
     discovery: &Discovery,
+    choices: &PasswordChoices,
 ) -> Result<Vec<SqlInstanceBuilder>> {
     log::info!("Finding instances...");
-    match client::connect_main_endpoint(endpoint).await {
+    match client::connect_main_endpoint(endpoint, choices).await {
         Ok(mut client) => Ok(_obtain_instance_builders(&mut client, endpoint, discovery).await),
         Err(err) => {
             log::error!("Failed to create main client: {err}");
-            obtain_instance_builders_by_sql_browser(endpoint, instances, discovery).await
+            let by_browser = if endpoint.conn().sql_browser().enabled() {
+                obtain_instance_builders_by_sql_browser(endpoint, instances, discovery).await
+            } else {
+                Err(anyhow::anyhow!("SQL Browser is disabled"))
+            };
+            match by_browser {
+                Err(err) if is_local_host(endpoint.auth(), endpoint.conn()) => {
+                    log::warn!("{err}, reading instances from registry");
+                    Ok(to_registry_instance_builders(
+                        &registry::get_instances(),
+                        endpoint,
+                        discovery,
+                    ))
+                }
+                result => result,
+            }
         }
     }
 }
 
+fn to_registry_instance_builders(
+    infos: &[InstanceInfo],
+    endpoint: &Endpoint,
+    discovery: &Discovery,
+) -> Vec<SqlInstanceBuilder> {
+    let endpoint = Arc::new(endpoint.clone());
+    let builders = infos
+        .iter()
+        .map(|info| {
+            SqlInstanceBuilder::new()
+                .from_registry(info)
+                .shared_endpoint(&endpoint)
+        })
+        .collect::<Vec<_>>();
+    log::info!("Instances found in registry directly {}", builders.len());
+    filter_builders(builders, discovery)
+}
+
 #[cfg(windows)]
 pub async fn obtain_instance_builders_by_sql_browser(
     endpoint: &Endpoint,
@@ -2394,13 +5478,28 @@ pub async fn obtain_instance_builders_by_sql_browser(
     discovery: &Discovery,
 ) -> Result<Vec<SqlInstanceBuilder>> {
     log::info!("Finding instances by SQL Browser");
-    for instance in instances {
+    let host = endpoint.conn().hostname();
+    let silent = browser::SilentBrowsers::default();
+    let ports = futures::future::join_all(instances.iter().map(|instance| {
+        browser::resolve_port(
+            &host.to_string(),
+            endpoint.conn().sql_browser_port(),
+            &instance.to_string(),
+            endpoint.conn().browser_timeout(),
+            &silent,
+        )
+    }))
+    .await;
+    for (instance, port) in instances.iter().zip(ports) {
+        let port = match port {
+            Ok(port) => port,
+            Err(err) => {
+                log::warn!("Failed to resolve port of {instance}: {err}");
+                continue;
+            }
+        };
         match client::ClientBuilder::new()
-            .browse(
-                &endpoint.conn().hostname(),
-                instance,
-                endpoint.conn().sql_browser_port(),
-            )
+            .local_by_port(Some(Port::from(port)), Some(host.clone()))
             .build()
             .await
         {
@@ -2425,13 +5524,12 @@ pub async fn obtain_instance_builders_by_sql_browser(
 }
 
 fn filter_builders(
-    builders: &[SqlInstanceBuilder],
+    builders: Vec<SqlInstanceBuilder>,
     discovery: &Discovery,
 ) -> Vec<SqlInstanceBuilder> {
     builders
-        .iter()
+        .into_iter()
         .filter(|b| discovery.is_instance_allowed(&b.get_name()))
-        .cloned()
         .collect()
 }
 
@@ -2452,7 +5550,8 @@ async fn _obtain_instance_builders(
                 if let Ok(properties) = SqlInstanceProperties::obtain_by_query(client).await {
                     builder = builder
                         .version(&properties.version)
-                        .edition(&properties.edition);
+                        .edition(&properties.edition)
+                        .properties(&properties);
                 }
                 builders = vec![builder];
             }
@@ -2467,13 +5566,16 @@ async fn _obtain_instance_builders(
             }
         };
     }
-    builders = filter_builders(&builders, discovery);
+    builders = filter_builders(builders, discovery);
+    if builders.is_empty() {
+        return builders;
+    }
     let computer_name = obtain_computer_name(client).await.unwrap_or_default();
+    let endpoint = Arc::new(endpoint.clone());
     builders
-        .iter()
+        .into_iter()
         .map(|i| {
-            i.clone()
-                .endpoint(endpoint)
+            i.shared_endpoint(&endpoint)
                 .computer_name(computer_name.clone())
         })
         .collect()
@@ -2537,11 +5639,25 @@ fn to_sql_instance(answers: &UniAnswer) -> Vec<SqlInstanceBuilder> {
 #[cfg(test)]
 mod tests {
     use super::{
-        generate_instance_entries, generate_signaling_blocks, SqlInstance, SqlInstanceBuilder,
+        apply_owners, assign_host_gates, calc_start_delay, generate_instance_entries,
+        generate_signaling_blocks, generate_skeleton, prefix_entries, sanitize_sql_text,
+        to_backup_url_entry, to_blocked_session_entry_odbc, to_checkdb_entry, to_column_values,
+        to_constraints_entries, to_counter_rates, to_db_option_drift_entries,
+        to_filegroups_entries, to_perf_key_metrics, to_permissions_entries, to_rate_sample,
+        to_services_entries, to_top_tables_entries, to_url_target, to_wait_samples,
+        to_waits_delta_entries, write_host_sections, Counter, RunContext, Section, SectionStatus,
+        SqlInstance, SqlInstanceBuilder,
     };
     use crate::args::Args;
+    use crate::config::ms_sql::{ClusterDedup, Emit, NameEncoding};
+    use crate::config::section::{names, SectionBuilder};
+    use crate::ms_sql::browser;
+    use crate::ms_sql::query::UniAnswer;
+    use crate::ms_sql::query::{record_query_error, track_query_errors};
+    use crate::platform::Block;
     use crate::setup::Env;
-    use crate::types::Port;
+    use crate::types::{InstanceName, Port};
+    use std::collections::{HashMap, HashSet};
     use std::path::Path;
 
     #[test]
@@ -2638,22 +5754,671 @@ mssql:
             "{}",
             blocks
         );
+        let skeleton = generate_skeleton(&ms_sql, &instances);
+        assert!(skeleton.starts_with(&blocks));
+        assert!(skeleton.ends_with(&generate_instance_entries(&instances)));
     }
 
-    #[test]
-    fn test_instance_header_footer() {
-        let normal = SqlInstanceBuilder::new().name("test_name").build();
-        assert_eq!(normal.generate_header(), "");
-        assert_eq!(normal.generate_footer(), "");
-        let piggyback = SqlInstanceBuilder::new()
-            .name("test_name")
-            .piggyback(Some("Y".to_string().into()))
-            .build();
-        assert_eq!(piggyback.generate_header(), "<<<<y>>>>\n");
-        assert_eq!(piggyback.generate_footer(), "<<<<>>>>\n");
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_write_host_sections_after_failed_instances() {
+        const CONFIG: &str = r#"---
+mssql:
+  main:
+    authentication:
+      username: u
+      password: u
+      type: sql_server
+    connection:
+      hostname: 127.0.0.1
+      timeout: 1
+    discovery:
+      scan: ["127.0.0.1/33"]
+"#;
+        let ms_sql = crate::config::ms_sql::Config::from_string(CONFIG)
+            .unwrap()
+            .unwrap();
+        let mut writer: Vec<u8> = Vec::new();
+        let result = write_host_sections(
+            Err(anyhow::anyhow!("instances failed")),
+            &ms_sql,
+            &Env::default(),
+            &mut writer,
+        )
+        .await;
+        assert_eq!(result.unwrap_err().to_string(), "instances failed");
+        let output = String::from_utf8(writer).unwrap();
+        assert!(
+            output.starts_with(&crate::emit::header(None, browser::SECTION_NAME, '|')),
+            "{output}"
+        );
+        assert!(output.contains("ERROR: "), "{output}");
+
+        let mut writer: Vec<u8> = Vec::new();
+        let skeleton = Env::new(&Args {
+            skeleton: true,
+            ..Default::default()
+        });
+        let result = write_host_sections(Ok(()), &ms_sql, &skeleton, &mut writer).await;
+        assert!(result.is_ok() && writer.is_empty());
     }
 
-    #[test]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_for_each_database_reports_errors() {
+        const CONFIG: &str = r#"---
+mssql:
+  main:
+    authentication:
+      username: u
+      password: u
+      type: sql_server
+    connection:
+      hostname: 127.0.0.1
+      port: 1
+      timeout: 1
+"#;
+        let ms_sql = crate::config::ms_sql::Config::from_string(CONFIG)
+            .unwrap()
+            .unwrap();
+        let instance = SqlInstanceBuilder::new()
+            .name("a")
+            .endpoint(&ms_sql.endpoint())
+            .port(Some(Port::from(1)))
+            .build();
+        let databases = vec!["d 1".to_string(), "d2".to_string()];
+        let result = instance
+            .for_each_database(
+                &ms_sql.endpoint(),
+                &databases,
+                |_, database| async move { Ok(format!("{database} ok\n")) },
+                |d, _| format!("{d} failed\n"),
+            )
+            .await;
+        let mut lines = result.lines().collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(lines, vec!["d 1 failed", "d2 failed"]);
+
+        let section = Section::make_instance_section(&Emit::default());
+        let tablespaces =
+            Section::new(&SectionBuilder::new(names::TABLE_SPACES).build(), Some(100));
+        let (body, error) = track_query_errors(instance.generate_database_indexed_section_async(
+            &databases[1..],
+            &ms_sql.endpoint(),
+            &tablespaces,
+            "SELECT 1",
+            tablespaces.sep(),
+        ))
+        .await;
+        assert!(
+            body.starts_with("MSSQL_A d2 - - - - - - - - - - - - "),
+            "{body}"
+        );
+        let status = SectionStatus::failed(&error.unwrap());
+        assert_eq!(
+            status.to_entry("MSSQL_A", tablespaces.name(), section.sep()),
+            "MSSQL_A|tablespaces|failed|connection\n"
+        );
+    }
+
+    #[test]
+    fn test_instance_header_footer() {
+        let normal = SqlInstanceBuilder::new().name("test_name").build();
+        assert_eq!(normal.generate_header(), "");
+        assert_eq!(normal.generate_footer(), "");
+        let piggyback = SqlInstanceBuilder::new()
+            .name("test_name")
+            .piggyback(Some("Y".to_string().into()))
+            .build();
+        assert_eq!(piggyback.generate_header(), "<<<<y>>>>\n");
+        assert_eq!(piggyback.generate_footer(), "<<<<>>>>\n");
+    }
+
+    #[test]
+    fn test_piggyback_template() {
+        let builder = || {
+            SqlInstanceBuilder::new()
+                .name("SQLEXPRESS")
+                .computer_name(Some("Srv1".to_string().into()))
+                .piggyback_template(Some("sql-{instance}-{hostname}"))
+        };
+        assert_eq!(
+            builder().build().generate_header(),
+            "<<<<sql-sqlexpress-srv1>>>>\n"
+        );
+        assert_eq!(
+            builder()
+                .piggyback(Some("Y".to_string().into()))
+                .build()
+                .generate_header(),
+            "<<<<y>>>>\n"
+        );
+        assert_eq!(
+            SqlInstanceBuilder::new()
+                .name("A")
+                .piggyback_template(Some("{instance}-{hostname}"))
+                .build()
+                .generate_header(),
+            "<<<<a-localhost>>>>\n"
+        );
+    }
+
+    #[test]
+    fn test_perf_key_metrics() {
+        let make_counters = |batches: &str, compilations: &str| {
+            [
+                [
+                    "Page life expectancy",
+                    "SQLServer:Buffer Node",
+                    "000",
+                    "300",
+                ],
+                [
+                    "Page life expectancy",
+                    "SQLServer:Buffer Node",
+                    "001",
+                    "400",
+                ],
+                [
+                    "Page life expectancy",
+                    "SQLServer:Buffer Manager",
+                    "",
+                    "350",
+                ],
+                [
+                    "Buffer cache hit ratio",
+                    "SQLServer:Buffer Manager",
+                    "",
+                    "99",
+                ],
+                [
+                    "Buffer cache hit ratio base",
+                    "SQLServer:Buffer Manager",
+                    "",
+                    "100",
+                ],
+                [
+                    "Batch Requests/sec",
+                    "SQLServer:SQL Statistics",
+                    "",
+                    batches,
+                ],
+                [
+                    "SQL Compilations/sec",
+                    "SQLServer:SQL Statistics",
+                    "",
+                    compilations,
+                ],
+            ]
+            .iter()
+            .map(|r| Counter::from_block(&r.map(str::to_string), NameEncoding::Keep))
+            .collect::<Vec<Counter>>()
+        };
+        let name = InstanceName::from("MSSQLSERVER");
+        let elapsed = std::time::Duration::from_secs(2);
+        assert_eq!(
+            to_perf_key_metrics(
+                &name,
+                &make_counters("100", "10"),
+                &make_counters("150", "13"),
+                elapsed,
+                '|'
+            ),
+            "\
+            MSSQLSERVER|page_life_expectancy|000|300\n\
+            MSSQLSERVER|page_life_expectancy|001|400\n\
+            MSSQLSERVER|buffer_cache_hit_ratio|None|99.00\n\
+            MSSQLSERVER|batch_requests_per_sec|None|25.00\n\
+            MSSQLSERVER|compilations_per_sec|None|1.50\n"
+        );
+        assert!(!to_perf_key_metrics(
+            &name,
+            &make_counters("150", "10"),
+            &make_counters("100", "13"),
+            elapsed,
+            '|'
+        )
+        .contains("batch_requests_per_sec"));
+    }
+
+    #[test]
+    fn test_to_column_values() {
+        let block = Block {
+            headline: vec!["name".to_string(), "size".to_string()],
+            rows: vec![
+                vec!["a".to_string(), "1".to_string()],
+                vec!["b".to_string(), "2".to_string()],
+            ],
+        };
+        assert_eq!(
+            to_column_values(&UniAnswer::Block(block), &["size", "missing", "name"]),
+            vec![
+                vec!["1".to_string(), String::new(), "a".to_string()],
+                vec!["2".to_string(), String::new(), "b".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_permissions_entries() {
+        let block = Block {
+            headline: vec!["kind".to_string(), "name".to_string(), "type".to_string()],
+            rows: vec![
+                vec![
+                    "db_owner".to_string(),
+                    "dbo".to_string(),
+                    "SQL_USER".to_string(),
+                ],
+                vec![
+                    "orphaned".to_string(),
+                    "old_app".to_string(),
+                    "SQL_USER".to_string(),
+                ],
+                vec![
+                    "db_datareader".to_string(),
+                    "app|reader".to_string(),
+                    "SQL_USER".to_string(),
+                ],
+            ],
+        };
+        assert_eq!(
+            to_permissions_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                "my db",
+                &[UniAnswer::Block(block)],
+                '|',
+                NameEncoding::Keep
+            ),
+            "\
+            MSSQLSERVER|my_db|db_owner|dbo|SQL_USER\n\
+            MSSQLSERVER|my_db|orphaned|old_app|SQL_USER\n\
+            MSSQLSERVER|my_db|db_datareader|app_reader|SQL_USER\n"
+        );
+    }
+
+    #[test]
+    fn test_filegroups_entries() {
+        let block = Block {
+            headline: ["kind", "name", "type", "items", "size_mb", "used_mb"]
+                .map(str::to_string)
+                .to_vec(),
+            rows: vec![
+                [
+                    "filegroup",
+                    "PRIMARY",
+                    "ROWS_FILEGROUP",
+                    "2",
+                    "1024",
+                    "1000",
+                ]
+                .map(str::to_string)
+                .to_vec(),
+                ["filegroup", "ARCHIVE", "ROWS_FILEGROUP", "0", "0", "0"]
+                    .map(str::to_string)
+                    .to_vec(),
+                [
+                    "partitioned_table",
+                    "dbo.Orders",
+                    "psYear",
+                    "12",
+                    "300",
+                    "250",
+                ]
+                .map(str::to_string)
+                .to_vec(),
+            ],
+        };
+        assert_eq!(
+            to_filegroups_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                "my db",
+                &[UniAnswer::Block(block)],
+                '|',
+                NameEncoding::Keep
+            ),
+            "\
+            MSSQLSERVER|my_db|filegroup|PRIMARY|ROWS_FILEGROUP|2|1024|1000|24\n\
+            MSSQLSERVER|my_db|filegroup|ARCHIVE|ROWS_FILEGROUP|0|0|0|0\n\
+            MSSQLSERVER|my_db|partitioned_table|dbo.Orders|psYear|12|300|250|-\n"
+        );
+    }
+
+    #[test]
+    fn test_classify_roles() {
+        use super::{classify_roles, RoleFacts};
+        let facts = |edition: &str, ag_role: &str, connections: &str, reports: i64| RoleFacts {
+            edition: edition.to_string(),
+            ag_role: ag_role.to_string(),
+            secondary_connections: connections.to_string(),
+            report_databases: reports,
+        };
+        assert_eq!(
+            classify_roles(&facts("Enterprise Edition (64-bit)", "", "", 0)),
+            ["primary-oltp"]
+        );
+        assert_eq!(
+            classify_roles(&facts("Enterprise Edition", "PRIMARY", "NO", 0)),
+            ["ag-primary", "primary-oltp"]
+        );
+        assert_eq!(
+            classify_roles(&facts("Enterprise Edition", "SECONDARY", "READ_ONLY", 0)),
+            ["ag-secondary", "readable-secondary"]
+        );
+        assert_eq!(
+            classify_roles(&facts("Enterprise Edition", "SECONDARY", "NO", 0)),
+            ["ag-secondary"]
+        );
+        assert_eq!(
+            classify_roles(&facts("Developer Edition (64-bit)", "", "", 2)),
+            ["dev", "reporting"]
+        );
+    }
+
+    #[test]
+    fn test_db_option_drift_entries() {
+        use crate::config::section::DatabaseBaseline;
+        let block = Block {
+            headline: super::DB_OPTION_DRIFT_COLUMNS.map(str::to_string).to_vec(),
+            rows: vec![
+                ["good", "SIMPLE", "0", "0", "CHECKSUM"]
+                    .map(str::to_string)
+                    .to_vec(),
+                ["my db", "FULL", "1", "0", "TORN_PAGE_DETECTION"]
+                    .map(str::to_string)
+                    .to_vec(),
+            ],
+        };
+        assert_eq!(
+            to_db_option_drift_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                &[UniAnswer::Block(block)],
+                &DatabaseBaseline::default(),
+                '|',
+                NameEncoding::Keep
+            ),
+            "\
+            MSSQLSERVER|my_db|auto_shrink|OFF|ON\n\
+            MSSQLSERVER|my_db|page_verify|CHECKSUM|TORN_PAGE_DETECTION\n"
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_names() {
+        let entries = |database: &str| {
+            let block = Block {
+                headline: super::CONSTRAINTS_COLUMNS.map(str::to_string).to_vec(),
+                rows: vec![["dbo.Заказы", "1", "0", "0", "0", "0"]
+                    .map(str::to_string)
+                    .to_vec()],
+            };
+            to_constraints_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                database,
+                &[UniAnswer::Block(block)],
+                '\t',
+                NameEncoding::Keep,
+            )
+        };
+        for (database, expected) in [
+            ("База данных", "База_данных"),
+            ("销售\u{3000}数据", "销售_数据"),
+            ("tab\tname", "tab_name"),
+        ] {
+            let line = entries(database);
+            assert_eq!(line.matches('\t').count(), 7, "{database}");
+            assert_eq!(line.split('\t').nth(1), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_waits_delta_entries() {
+        let to_rows = |rows: &[[&str; 4]]| {
+            to_wait_samples(
+                rows.iter()
+                    .map(|r| r.map(str::to_string).to_vec())
+                    .collect(),
+            )
+        };
+        let first = to_rows(&[
+            ["PAGEIOLATCH_SH", "100", "2000", "10"],
+            ["LCK_M_X", "5", "500", "0"],
+            ["CXPACKET", "50", "900", "30"],
+            ["BAD", "x", "1", "1"],
+        ]);
+        assert_eq!(first.len(), 3);
+        let second = to_rows(&[
+            ["PAGEIOLATCH_SH", "110", "2600", "14"],
+            ["LCK_M_X", "5", "500", "0"],
+            ["CXPACKET", "0", "0", "0"],
+            ["WRITELOG", "3", "30", "1"],
+        ]);
+        assert_eq!(
+            to_waits_delta_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                &first,
+                &second,
+                std::time::Duration::from_millis(5000),
+                '|'
+            ),
+            "MSSQLSERVER|PAGEIOLATCH_SH|10|600|4|5000\n"
+        );
+    }
+
+    #[test]
+    fn test_db_connections_entries() {
+        use super::{to_db_connections, to_db_connections_entries};
+        let sample = |rows: &[[&str; 4]]| {
+            to_db_connections(
+                rows.iter()
+                    .map(|r| r.map(str::to_string).to_vec())
+                    .collect(),
+            )
+        };
+        let samples = [
+            sample(&[["master", "3", "1", "2"], ["Sales DB", "40", "10", "30"]]),
+            sample(&[["master", "5", "2", "3"], ["Sales DB", "25", "5", "20"]]),
+            sample(&[["Sales DB", "30", "12", "18"], ["bad", "x", "1", "1"]]),
+        ];
+        assert_eq!(
+            to_db_connections_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                &samples,
+                '|',
+                NameEncoding::Keep
+            ),
+            "\
+            MSSQLSERVER|Sales_DB|30|12|18|40\n\
+            MSSQLSERVER|master|0|0|0|5\n"
+        );
+        assert_eq!(
+            to_db_connections_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                &[],
+                '|',
+                NameEncoding::Keep
+            ),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_missing_databases_entries() {
+        use super::{get_database_names, to_missing_databases_entries};
+        let block = Block {
+            headline: vec!["name".to_string(), "Status".to_string()],
+            rows: vec![
+                vec!["master".to_string(), "ONLINE".to_string()],
+                vec!["Sales ".to_string(), "OFFLINE".to_string()],
+            ],
+        };
+        let found = get_database_names(&[UniAnswer::Block(block)]);
+        let required = ["sales", "HR DB", "master"].map(str::to_string);
+        assert_eq!(
+            to_missing_databases_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                &required,
+                &found,
+                '|',
+                NameEncoding::Keep
+            ),
+            "MSSQLSERVER|HR_DB|MISSING|-|-|-\n"
+        );
+        assert_eq!(
+            to_missing_databases_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                &[],
+                &found,
+                '|',
+                NameEncoding::Keep
+            ),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_tempdb_contention_entries() {
+        use super::{to_allocation_waits, to_tempdb_contention_entries};
+        let sample = |rows: &[[&str; 2]]| {
+            to_allocation_waits(
+                rows.iter()
+                    .map(|r| r.map(str::to_string).to_vec())
+                    .collect(),
+            )
+        };
+        let samples = [
+            sample(&[["2:1:1", "10"], ["2:3:1", "30"], ["2:1:3", "5"]]),
+            sample(&[]),
+            sample(&[
+                ["2:1:16176", "20"],
+                ["2:4:511234", "7"],
+                ["2:1:120", "900"],
+                ["2:1:x", "1"],
+            ]),
+        ];
+        assert_eq!(
+            to_tempdb_contention_entries(&InstanceName::from("MSSQLSERVER"), &samples, '|'),
+            "\
+            MSSQLSERVER|pfs|3|2|30\n\
+            MSSQLSERVER|gam|1|1|7\n\
+            MSSQLSERVER|sgam|1|1|5\n"
+        );
+    }
+
+    #[test]
+    fn test_connectivity_entry() {
+        use super::{client::Connectivity, is_azure_arc, to_connectivity_entry};
+        assert_eq!(
+            to_connectivity_entry("MSSQL_SQLEXPRESS", &Connectivity::Direct, false, '|'),
+            "MSSQL_SQLEXPRESS|connectivity|direct||\n"
+        );
+        assert_eq!(
+            to_connectivity_entry(
+                "MSSQL_SQLEXPRESS",
+                &Connectivity::Redirected("node2.example.net:11000".to_string()),
+                true,
+                '|'
+            ),
+            "MSSQL_SQLEXPRESS|connectivity|redirect|node2.example.net:11000|azure-arc\n"
+        );
+        let block = Block {
+            headline: vec!["azure_arc".to_string()],
+            rows: vec![vec!["1".to_string()]],
+        };
+        assert!(is_azure_arc(&UniAnswer::Block(block)));
+        let block = Block {
+            headline: vec!["azure_arc".to_string()],
+            rows: vec![vec!["0".to_string()]],
+        };
+        assert!(!is_azure_arc(&UniAnswer::Block(block)));
+    }
+
+    #[test]
+    fn test_remove_denied_lines() {
+        use super::remove_denied_lines;
+        let body = "MSSQL_A|master|ONLINE\n\
+                    |MSSQL_A|Token error: 'VIEW DATABASE STATE permission was denied on object \
+                    'Sales', database 'Sales'.' on server A executing  on line 1 (code: 300, state: 1, class: 14)\n\
+                    MSSQL_A|Sales|Invalid object name 'x' (code: 208, state: 1, class: 16)\n";
+        assert_eq!(
+            remove_denied_lines(body.to_string()),
+            "MSSQL_A|master|ONLINE\n\
+             MSSQL_A|Sales|Invalid object name 'x' (code: 208, state: 1, class: 16)\n"
+        );
+    }
+
+    #[test]
+    fn test_top_tables_entries() {
+        let block = Block {
+            headline: super::TOP_TABLES_COLUMNS.map(str::to_string).to_vec(),
+            rows: vec![
+                ["dbo.Orders", "1000000", "204800", "180000", "200000"]
+                    .map(str::to_string)
+                    .to_vec(),
+                ["dbo.Log|Old", "10", "72", "16", "24"]
+                    .map(str::to_string)
+                    .to_vec(),
+            ],
+        };
+        assert_eq!(
+            to_top_tables_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                "my db",
+                &[UniAnswer::Block(block)],
+                '|',
+                NameEncoding::Keep
+            ),
+            "\
+            MSSQLSERVER|my_db|dbo.Orders|1000000|204800|180000|200000\n\
+            MSSQLSERVER|my_db|dbo.Log_Old|10|72|16|24\n"
+        );
+        assert_eq!(
+            to_top_tables_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                "db",
+                &[],
+                '|',
+                NameEncoding::Keep
+            ),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_constraints_entries() {
+        let block = Block {
+            headline: super::CONSTRAINTS_COLUMNS.map(str::to_string).to_vec(),
+            rows: vec![
+                ["dbo.Orders", "1000000", "1", "2", "0", "0"]
+                    .map(str::to_string)
+                    .to_vec(),
+                ["sales.Items|Old", "10", "0", "0", "0", "3"]
+                    .map(str::to_string)
+                    .to_vec(),
+            ],
+        };
+        assert_eq!(
+            to_constraints_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                "my db",
+                &[UniAnswer::Block(block)],
+                '|',
+                NameEncoding::Keep
+            ),
+            "\
+            MSSQLSERVER|my_db|dbo.Orders|1000000|1|2|0|0\n\
+            MSSQLSERVER|my_db|sales.Items_Old|10|0|0|0|3\n"
+        );
+        assert_eq!(
+            to_constraints_entries(
+                &InstanceName::from("MSSQLSERVER"),
+                "db",
+                &[],
+                '|',
+                NameEncoding::Keep
+            ),
+            ""
+        );
+    }
+
+    #[test]
     fn test_calc_unused() {
         use crate::ms_sql::instance::calc_unused;
         assert_eq!(
@@ -2668,6 +6433,19 @@ mssql:
         assert!(calc_unused("500 KB", "A", "500 KB").is_none());
     }
 
+    #[test]
+    fn test_name_encoding_of_environment() {
+        let yaml = &yaml_rust2::YamlLoader::load_from_str("emit:\n  names: escape\n").unwrap()[0];
+        let emit = Emit::from_yaml(yaml).unwrap().unwrap();
+        let escaping = SqlInstanceBuilder::new()
+            .name("a")
+            .environment(&Env::default().with_emit(&emit))
+            .build();
+        assert_eq!(escaping.name_field("Бд 1"), "_x0411__x0434__1");
+        let keeping = SqlInstanceBuilder::new().name("a").build();
+        assert_eq!(keeping.name_field("Бд 1"), "Бд_1");
+    }
+
     #[test]
     fn test_sql_builder() {
         let args = Args {
@@ -2711,12 +6489,606 @@ mssql:
             "MSSQL_NAME.config.version.edition.\n"
         );
 
-        let c = cluster.build();
+        let c = cluster.clone().build();
         assert_eq!(c.cluster, Some("cluster".to_string().into()));
         assert_eq!(c.legacy_name(), "cluster/NAME");
         assert_eq!(
             c.generate_leading_entry('.'),
             "MSSQL_NAME.config.version.edition.cluster\n"
         );
+        assert_eq!(c.piggyback(), &Some("piggyback".to_string().into()));
+
+        let c = cluster.cluster_dedup(&ClusterDedup::Piggyback).build();
+        assert_eq!(c.piggyback(), &Some("cluster".to_string().into()));
+    }
+
+    #[test]
+    fn test_static_sections() {
+        use super::generate_static_sections;
+        use crate::config::ms_sql::StaticSection;
+        use crate::config::yaml::test_tools::create_yaml;
+        let make = |source: &str| StaticSection::from_yaml(&create_yaml(source)).unwrap();
+        let sections = [
+            make("name: site\nfile: site.txt\n"),
+            make("name: owner\nfile: owner.txt\nsep: '|'\nper_instance: yes\n"),
+            make("name: absent\nfile: absent.txt\n"),
+        ];
+        let read = |path: &Path| match path.to_str() {
+            Some("site.txt") => Ok("a b\n".to_string()),
+            Some("owner.txt") => Ok("team|dba".to_string()),
+            _ => Err(std::io::Error::from(std::io::ErrorKind::NotFound)),
+        };
+        let hosts = [
+            Some("h1".to_string().into()),
+            None,
+            Some("h1".to_string().into()),
+            None,
+        ];
+        assert_eq!(
+            generate_static_sections(&sections, &hosts, read),
+            "<<<site>>>\na b\n\
+             <<<<h1>>>>\n<<<owner:sep(124)>>>\nteam|dba\n<<<<>>>>\n\
+             <<<owner:sep(124)>>>\nteam|dba\n"
+        );
+        assert_eq!(generate_static_sections(&[], &hosts, read), "");
+    }
+
+    #[test]
+    fn test_discovered_properties() {
+        use super::{to_instance_builder, SqlInstanceProperties};
+        use crate::config::ms_sql::Endpoint;
+        let properties = SqlInstanceProperties {
+            name: "SQLEXPRESS".to_string().into(),
+            version: "16.0.1000.6".to_string().into(),
+            computer_name: "HOST".to_string().into(),
+            edition: "Express Edition".to_string().into(),
+            product_level: "RTM".to_string(),
+            net_bios: "HOST".to_string(),
+        };
+        let instance = to_instance_builder(&Endpoint::default(), &properties).build();
+        let kept = instance.properties.as_ref().unwrap();
+        assert_eq!(kept.name, properties.name);
+        assert_eq!(kept.version, properties.version);
+        assert!(SqlInstanceBuilder::new().build().properties.is_none());
+    }
+
+    #[test]
+    fn test_shared_endpoint() {
+        use crate::config::ms_sql::Endpoint;
+        use std::sync::Arc;
+        let endpoint = Arc::new(Endpoint::default());
+        let a = SqlInstanceBuilder::new()
+            .name("a")
+            .shared_endpoint(&endpoint);
+        let b = SqlInstanceBuilder::new()
+            .name("b")
+            .shared_endpoint(&endpoint);
+        assert_eq!(a.get_endpoint(), Some(endpoint.as_ref()));
+        let (a, b) = (a.build(), b.build());
+        assert!(Arc::ptr_eq(&a.endpoint, &b.endpoint));
+        assert_eq!(Arc::strong_count(&endpoint), 3);
+    }
+
+    #[test]
+    fn test_cache_store() {
+        use crate::ms_sql::cache::MemoryStore;
+        let s = SqlInstanceBuilder::new()
+            .name("name")
+            .cache_store(std::sync::Arc::new(MemoryStore::default()))
+            .build();
+        assert!(s.read_data_from_cache("jobs", 100).is_none());
+        s.write_data_in_cache("jobs", "body");
+        assert_eq!(
+            s.read_data_from_cache("jobs", 100).map(|(body, _)| body),
+            Some("body".to_string())
+        );
+        assert!(s.read_data_from_cache("jobs", 0).is_none());
+    }
+
+    #[test]
+    fn test_to_services_entries() {
+        use crate::platform::services::Service;
+        let services = [Service {
+            kind: "ssrs",
+            name: "ReportServer$RS".to_string(),
+            instance: "RS".to_string(),
+            state: "running".to_string(),
+            start_mode: "auto".to_string(),
+            version: None,
+        }];
+        assert_eq!(
+            to_services_entries(&services, '|'),
+            "ssrs|ReportServer$RS|RS|running|auto|-\n"
+        );
+        assert_eq!(to_services_entries(&[], '|'), "");
+    }
+
+    #[test]
+    fn test_to_failure_count() {
+        use crate::ms_sql::instance::to_failure_count;
+        assert_eq!(to_failure_count(None, "timeout"), 1);
+        assert_eq!(to_failure_count(Some(""), "timeout"), 1);
+        assert_eq!(to_failure_count(Some("2\ntimeout"), "timeout"), 3);
+        assert_eq!(to_failure_count(Some("2\nlogin failed"), "timeout"), 1);
+        assert_eq!(to_failure_count(Some("x\ntimeout"), "timeout"), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_section_parallelism() {
+        let make = |parallelism| {
+            SqlInstanceBuilder::new()
+                .name("name")
+                .section_parallelism(parallelism)
+                .build()
+        };
+        assert_eq!(SqlInstanceBuilder::new().build().section_parallelism, 1);
+        assert_eq!(make(0).section_parallelism, 1);
+        assert_eq!(make(4).section_parallelism, 4);
+        let s = make(1);
+        assert!(s.create_lane_clients(&s.endpoint, 10).await.is_empty());
+        let s = make(4);
+        assert!(s.create_lane_clients(&s.endpoint, 1).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_extra_generator() {
+        use crate::ms_sql::client::{OdbcClient, Pacing, UniClient};
+        use crate::ms_sql::generator::{self, FnGenerator, GeneratorFuture, GeneratorInput};
+        fn generate_plugin_body(input: GeneratorInput<'_>) -> GeneratorFuture<'_> {
+            Box::pin(async move { format!("{}|plugin\n", input.section.name()) })
+        }
+        static NAMES: [&str; 2] = ["test_extra_generator", names::JOBS];
+        generator::register(
+            "test_extra_plugin",
+            std::sync::Arc::new(FnGenerator::new(&NAMES, generate_plugin_body)),
+        );
+        async fn body(plugins: &[&str], name: &str) -> String {
+            let instance = SqlInstanceBuilder::new()
+                .name("name")
+                .generators(&plugins.iter().map(|p| p.to_string()).collect::<Vec<_>>())
+                .build();
+            let mut client = UniClient::Odbc(OdbcClient::new(""), Pacing::default());
+            let section = Section::new(&SectionBuilder::new(name).build(), None);
+            instance
+                .generate_section_body(
+                    &mut client,
+                    &instance.endpoint,
+                    &section,
+                    &RunContext::default(),
+                )
+                .await
+        }
+        assert_eq!(
+            body(&["test_extra_plugin"], "test_extra_generator").await,
+            "test_extra_generator|plugin\n"
+        );
+        assert_eq!(
+            body(&["test_extra_plugin"], names::JOBS).await,
+            "jobs|plugin\n"
+        );
+        assert_eq!(body(&[], "test_extra_generator").await, "");
+    }
+
+    #[test]
+    fn test_obtain_value_locale() {
+        use crate::config::ms_sql::{Authentication, Connection, Endpoint};
+        use crate::config::yaml::test_tools::create_yaml;
+        use crate::locale::ValueLocale;
+        use crate::ms_sql::instance::obtain_value_locale;
+        let endpoint = |language: &str| {
+            let conn = Connection::from_yaml(
+                &create_yaml(&format!(
+                    "connection:\n  connect_options:\n    language: \"{language}\"\n"
+                )),
+                None,
+            )
+            .unwrap()
+            .unwrap();
+            Endpoint::new(&Authentication::default(), &conn)
+        };
+        let emit = |source: &str| Emit::from_yaml(&create_yaml(source)).unwrap().unwrap();
+        let german = ValueLocale::from_language("Deutsch");
+        let french = ValueLocale::from_language("Français");
+        assert_eq!(
+            obtain_value_locale(&Emit::default(), &endpoint("Deutsch")),
+            german
+        );
+        assert_eq!(
+            obtain_value_locale(&Emit::default(), &endpoint("Français")),
+            french
+        );
+        assert_eq!(
+            obtain_value_locale(
+                &emit("emit:\n  values_locale: Deutsch\n"),
+                &endpoint("Français")
+            ),
+            german
+        );
+        assert_eq!(
+            obtain_value_locale(&Emit::default(), &Endpoint::default()),
+            None
+        );
+        assert_eq!(
+            obtain_value_locale(&Emit::default(), &endpoint("Klingon")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_count_failure() {
+        use crate::ms_sql::cache::MemoryStore;
+        let make = |after| {
+            SqlInstanceBuilder::new()
+                .name("name")
+                .cache_store(std::sync::Arc::new(MemoryStore::default()))
+                .suppress_errors_after(after)
+                .build()
+        };
+        let s = make(Some(2));
+        assert_eq!(s.count_failure("timeout"), 1);
+        assert_eq!(s.count_failure("timeout"), 2);
+        assert_eq!(s.count_failure("timeout"), 3);
+        assert_eq!(s.count_failure("login failed"), 1);
+        s.reset_failures();
+        assert_eq!(s.count_failure("login failed"), 1);
+        let s = make(None);
+        assert_eq!(s.count_failure("timeout"), 1);
+        assert_eq!(s.count_failure("timeout"), 1);
+    }
+
+    #[test]
+    fn test_to_backup_url_entry() {
+        let name = InstanceName::from("MSSQL_SQLEXPRESS");
+        let url = "https://acc.blob.core.windows.net/backups/sql/my_db.bak";
+        assert_eq!(
+            to_backup_url_entry(&name, "my db", "D", "9", url, "2024-01-10 03:00:00", 3, 7, ';', NameEncoding::Keep),
+            "MSSQL_SQLEXPRESS;my_db;D;url;acc.blob.core.windows.net/backups;2024-01-10|03:00:00;3;7;ok\n"
+        );
+        assert_eq!(
+            to_backup_url_entry(&name, "db", "L", "9", url, "2024-01-01 03:00:00", 9, 7, ';', NameEncoding::Keep),
+            "MSSQL_SQLEXPRESS;db;L;url;acc.blob.core.windows.net/backups;2024-01-01|03:00:00;9;7;overdue\n"
+        );
+        assert_eq!(
+            to_backup_url_entry(
+                &name,
+                "db",
+                "D",
+                "2",
+                "c:\\b\\db.bak",
+                "2024-01-01 03:00:00",
+                9,
+                7,
+                ';',
+                NameEncoding::Keep
+            ),
+            "MSSQL_SQLEXPRESS;db;D;disk;-;2024-01-01|03:00:00;9;7;-\n"
+        );
+        assert_eq!(
+            to_backup_url_entry(
+                &name,
+                "db",
+                "D",
+                "105",
+                "dev",
+                "2024-01-01 03:00:00",
+                1,
+                7,
+                ';',
+                NameEncoding::Keep
+            ),
+            "MSSQL_SQLEXPRESS;db;D;logical;-;2024-01-01|03:00:00;1;7;-\n"
+        );
+    }
+
+    #[test]
+    fn test_to_url_target() {
+        assert_eq!(
+            to_url_target("https://Acc.blob.core.windows.net/backups/db.bak"),
+            "acc.blob.core.windows.net/backups"
+        );
+        assert_eq!(
+            to_url_target("s3://host:9000/bucket/x/db.bak"),
+            "host:9000/bucket"
+        );
+        assert_eq!(to_url_target("https://host/db.bak"), "host");
+        assert_eq!(to_url_target(""), "-");
+    }
+
+    #[test]
+    fn test_to_checkdb_entry() {
+        let name = InstanceName::from("SQL1");
+        assert_eq!(
+            to_checkdb_entry(
+                &name,
+                "my db",
+                "2024-01-10 03:00:00.000",
+                3,
+                7,
+                '|',
+                NameEncoding::Keep
+            ),
+            "SQL1|my_db|2024-01-10 03:00:00.000|3|7|ok\n"
+        );
+        assert_eq!(
+            to_checkdb_entry(
+                &name,
+                "db",
+                "2024-01-10 03:00:00.000",
+                8,
+                7,
+                '|',
+                NameEncoding::Keep
+            ),
+            "SQL1|db|2024-01-10 03:00:00.000|8|7|overdue\n"
+        );
+        assert_eq!(
+            to_checkdb_entry(
+                &name,
+                "db",
+                "1900-01-01 00:00:00.000",
+                -1,
+                7,
+                '|',
+                NameEncoding::Keep
+            ),
+            "SQL1|db|-|-1|7|never\n"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_sql_text() {
+        assert_eq!(sanitize_sql_text("", '|'), "");
+        assert_eq!(
+            sanitize_sql_text("SELECT a,\r\n\tb  FROM t|x WHERE c = 1 ", '|'),
+            "SELECT a, b FROM t x WHERE c = 1"
+        );
+        let long = sanitize_sql_text(&"ä".repeat(300), '|');
+        assert_eq!(long.chars().count(), super::MAX_SQL_TEXT_LENGTH);
+        assert!(long.ends_with("ä..."));
+    }
+
+    #[test]
+    fn test_to_blocked_session_entry() {
+        let row = ["51", "1200", "LCK_M_X", "52"].map(str::to_string);
+        assert_eq!(
+            to_blocked_session_entry_odbc(&InstanceName::from("I"), &row, '|'),
+            "I|51|1200|LCK_M_X|52\n"
+        );
+        let row = [
+            "51",
+            "1200",
+            "LCK_M_X",
+            "52",
+            "UPDATE t\nSET a = 1",
+            "BEGIN TRAN; UPDATE t SET a = 2",
+        ]
+        .map(str::to_string);
+        assert_eq!(
+            to_blocked_session_entry_odbc(&InstanceName::from("I"), &row, '|'),
+            "I|51|1200|LCK_M_X|52|UPDATE t SET a = 1|BEGIN TRAN; UPDATE t SET a = 2\n"
+        );
+    }
+
+    #[test]
+    fn test_prefix_entries() {
+        assert_eq!(
+            prefix_entries("a|1\nb|2\n", "my db", '|', NameEncoding::Keep),
+            "my_db|a|1\nmy_db|b|2\n"
+        );
+        assert_eq!(prefix_entries("", "db", '|', NameEncoding::Keep), "");
+    }
+
+    #[test]
+    fn test_passive_node_entry() {
+        let i = SqlInstanceBuilder::new().name("a").build();
+        assert_eq!(
+            i.generate_passive_node_entry("NODE2"),
+            "<<<mssql_instance:sep(124)>>>\nMSSQL_A|passive_node|NODE2\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_owners() {
+        let make = |name: &str, cluster: Option<&str>| {
+            SqlInstanceBuilder::new()
+                .name(name)
+                .cluster(cluster.map(|c| c.to_string().into()))
+                .build()
+        };
+        let mut instances = vec![
+            make("a", Some("clu")),
+            make("b", Some("clu")),
+            make("c", None),
+            make("d", Some("clu")),
+        ];
+        let owners = HashMap::from([
+            ("A".to_string(), "node1".to_string()),
+            ("B".to_string(), "NODE2".to_string()),
+            ("C".to_string(), "NODE2".to_string()),
+        ]);
+        apply_owners(&mut instances, &owners, "NODE1");
+        assert_eq!(instances[0].passive_owner, None);
+        assert_eq!(instances[1].passive_owner, Some("NODE2".to_string()));
+        assert_eq!(instances[2].passive_owner, None);
+        assert_eq!(instances[3].passive_owner, None);
+    }
+
+    #[test]
+    fn test_counter_rates() {
+        let previous = to_rate_sample(
+            "None|utc_time|None|2024-01-01 10:00:00\n\
+             SQLServer:SQL_Statistics|batch_requests/sec|None|100\n\
+             SQLServer:Locks|lock_requests/sec|_Total|500\n\
+             SQLServer:Buffer_Manager|page_life_expectancy|None|300\n",
+            '|',
+        );
+        assert_eq!(previous.len(), 2);
+        let current = to_rate_sample(
+            "SQLServer:SQL_Statistics|batch_requests/sec|None|160\n\
+             SQLServer:Locks|lock_requests/sec|_Total|400\n\
+             SQLServer:Locks|lock_timeouts/sec|_Total|7\n",
+            '|',
+        );
+        assert_eq!(
+            to_counter_rates(&current, &previous, 60, '|'),
+            "SQLServer:SQL_Statistics|batch_requests/sec_rate|None|1.00\n"
+        );
+        assert_eq!(to_counter_rates(&current, &previous, 0, '|'), "");
+    }
+
+    #[test]
+    fn test_reconnects_entry() {
+        let i = SqlInstanceBuilder::new().name("a").build();
+        assert_eq!(
+            i.generate_reconnects_entry(2),
+            "<<<mssql_instance:sep(124)>>>\nMSSQL_A|reconnects|2\n"
+        );
+    }
+
+    #[test]
+    fn test_label() {
+        let plain = SqlInstanceBuilder::new().name("mssqlserver").build();
+        assert_eq!(plain.label().to_string(), "MSSQLSERVER");
+        assert_eq!(plain.mssql_name(), "MSSQL_MSSQLSERVER");
+        let qualified = SqlInstanceBuilder::new()
+            .name("mssqlserver")
+            .computer_name(Some("srv1".to_string().into()))
+            .qualify_with_host(true)
+            .instance_prefix(Some("PROD_"))
+            .build();
+        assert_eq!(qualified.name.to_string(), "MSSQLSERVER");
+        assert_eq!(qualified.label().to_string(), "PROD_SRV1_MSSQLSERVER");
+        assert_eq!(qualified.mssql_name(), "MSSQL_PROD_SRV1_MSSQLSERVER");
+        assert_eq!(
+            qualified.generate_good_state_entry('|'),
+            "MSSQL_PROD_SRV1_MSSQLSERVER|state|1|\n"
+        );
+        let by_endpoint = SqlInstanceBuilder::new()
+            .name("inst")
+            .qualify_with_host(true)
+            .build();
+        assert_eq!(by_endpoint.label().to_string(), "LOCALHOST_INST");
+    }
+
+    #[test]
+    fn test_generate_section_status() {
+        let instance = SqlInstanceBuilder::new().name("inst").build();
+        assert_eq!(instance.generate_section_status(&[]), "");
+        assert_eq!(
+            instance.generate_section_status(&[
+                (names::INSTANCE, SectionStatus::Live),
+                (names::JOBS, SectionStatus::Cached(30)),
+                (names::BACKUP, SectionStatus::failed("Connection timed out")),
+            ]),
+            "<<<mssql_section_status:sep(124)>>>\n\
+             MSSQL_INST|instance|live|-\n\
+             MSSQL_INST|jobs|cache|30\n\
+             MSSQL_INST|backup|failed|timeout\n"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_track_query_errors() {
+        let (_, error) = track_query_errors(async { 1 }).await;
+        assert!(error.is_none());
+        let (_, error) = track_query_errors(async {
+            record_query_error(&anyhow::anyhow!("first"));
+            record_query_error(&anyhow::anyhow!("second"));
+        })
+        .await;
+        assert_eq!(error.as_deref(), Some("first"));
+        // noop outside of tracking
+        record_query_error(&anyhow::anyhow!("lost"));
+    }
+
+    #[test]
+    fn test_assign_host_gates() {
+        let make = |name: &str, host: &str| {
+            SqlInstanceBuilder::new()
+                .name(name)
+                .computer_name(Some(host.to_string().into()))
+                .build()
+        };
+        let mut instances = vec![
+            make("a", "SRV1"),
+            make("b", "srv1"),
+            make("c", "srv2"),
+            SqlInstanceBuilder::new().name("d").build(),
+        ];
+        assign_host_gates(&mut instances, 1);
+        let gate = instances[0].host_gate.as_ref().unwrap();
+        assert!(std::sync::Arc::ptr_eq(
+            gate,
+            instances[1].host_gate.as_ref().unwrap()
+        ));
+        assert_eq!(gate.available_permits(), 1);
+        assert!(instances[2].host_gate.is_none());
+        assert!(instances[3].host_gate.is_none());
+
+        let mut instances = vec![make("a", "srv1"), make("b", "srv1")];
+        assign_host_gates(&mut instances, 0);
+        assert!(instances.iter().all(|i| i.host_gate.is_none()));
+    }
+
+    #[test]
+    fn test_replica_piggyback_host() {
+        let context = |replica: Option<&str>, piggyback: bool| RunContext {
+            primary_replica: replica.map(str::to_string),
+            replica_piggyback: piggyback,
+            ..Default::default()
+        };
+        assert_eq!(
+            context(Some("NODE1\\AGINST"), true).replica_piggyback_host(),
+            Some("node1".to_string().into())
+        );
+        assert_eq!(
+            context(Some("Node2"), true).replica_piggyback_host(),
+            Some("node2".to_string().into())
+        );
+        assert_eq!(context(Some("NODE1"), false).replica_piggyback_host(), None);
+        assert_eq!(context(None, true).replica_piggyback_host(), None);
+    }
+
+    #[test]
+    fn test_calc_start_delay() {
+        let a = InstanceName::from("SQL1");
+        assert_eq!(calc_start_delay(&a, 0), std::time::Duration::ZERO);
+        assert!(calc_start_delay(&a, 10) < std::time::Duration::from_secs(10));
+        assert_eq!(calc_start_delay(&a, 10), calc_start_delay(&a, 10));
+        assert_eq!(
+            calc_start_delay(&a, 10),
+            calc_start_delay(&InstanceName::from("sql1 "), 10)
+        );
+        let delays: HashSet<_> = (0..20)
+            .map(|i| calc_start_delay(&InstanceName::from(format!("SQL{i}").as_str()), 60))
+            .collect();
+        assert!(delays.len() > 1);
+    }
+
+    #[test]
+    fn test_main_db() {
+        let jobs = Section::new(&SectionBuilder::new(names::JOBS).build(), None);
+        let mirroring = Section::new(
+            &SectionBuilder::new(names::MIRRORING)
+                .main_db(Some("monitoring".to_string()))
+                .build(),
+            None,
+        );
+        let databases = Section::new(&SectionBuilder::new(names::DATABASES).build(), None);
+        let s = SqlInstanceBuilder::new().name("name").build();
+        assert_eq!(s.main_db(&jobs).as_deref(), Some("msdb"));
+        assert_eq!(s.main_db(&mirroring).as_deref(), Some("monitoring"));
+        assert_eq!(s.main_db(&databases), None);
+
+        let s = SqlInstanceBuilder::new()
+            .name("name")
+            .main_dbs(&HashMap::from([
+                (names::JOBS.to_string(), "msdb2".to_string()),
+                (names::MIRRORING.to_string(), "mon2".to_string()),
+            ]))
+            .build();
+        assert_eq!(s.main_db(&jobs).as_deref(), Some("msdb2"));
+        assert_eq!(s.main_db(&mirroring).as_deref(), Some("mon2"));
+        assert_eq!(s.main_db(&databases), None);
     }
 }
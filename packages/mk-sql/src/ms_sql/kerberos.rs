@@ -0,0 +1,109 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Kerberos for gateway hosts monitoring remote instances with Windows authentication
+//! SSPI uses Kerberos only if the SPN `MSSQLSvc/<fqdn>:<port>` is registered, otherwise
+//! it silently falls back to NTLM: such a logon can't be delegated(double hop)
+
+use crate::config::ms_sql::{AuthType, Authentication, Connection};
+use crate::types::HostName;
+use anyhow::Result;
+use std::net::IpAddr;
+
+/// The only text of SQL Server hinting that the delegation failed
+const ANONYMOUS_LOGON: &str = "anonymous logon";
+
+pub fn to_spn(host: &str, port: u16) -> String {
+    format!("MSSQLSvc/{host}:{port}")
+}
+
+/// why SSPI can't use Kerberos for the host
+pub fn spn_problem(host: &str) -> Option<&'static str> {
+    let host = host.trim();
+    if host.parse::<IpAddr>().is_ok() {
+        Some("is an IP address")
+    } else if host.eq_ignore_ascii_case("localhost") || host == "." {
+        Some("is local")
+    } else if !host.contains('.') {
+        Some("is not a fully qualified domain name")
+    } else {
+        None
+    }
+}
+
+/// Explicit windows credentials are used by NTLM only, Kerberos requires `integrated`
+pub fn ensure_delegable(auth: &Authentication) -> Result<()> {
+    match (auth.kerberos(), auth.auth_type()) {
+        (Some(_), AuthType::Windows) => anyhow::bail!(
+            "Kerberos is configured, but type `windows` uses NTLM which can't be delegated: use type `integrated`"
+        ),
+        (Some(_), AuthType::SqlServer | AuthType::Token) => {
+            log::warn!("Kerberos is configured, but ignored for {:?}", auth.auth_type());
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Host to connect to, it defines the SPN; problems of the SPN are logged
+pub fn target_host(auth: &Authentication, conn: &Connection, port: u16) -> HostName {
+    let host = auth
+        .kerberos()
+        .and_then(|k| k.spn_host())
+        .cloned()
+        .unwrap_or_else(|| conn.hostname());
+    if auth.kerberos().is_some() {
+        let spn = to_spn(&host.to_string(), port);
+        match spn_problem(&host.to_string()) {
+            Some(problem) => log::warn!(
+                "Kerberos is not possible: host of {spn} {problem}, NTLM is used, set spn_host"
+            ),
+            None => log::info!("Kerberos logon with SPN {spn}"),
+        }
+    }
+    host
+}
+
+pub fn is_double_hop_error(text: &str) -> bool {
+    text.to_lowercase().contains(ANONYMOUS_LOGON)
+}
+
+pub fn delegation_hint(host: &str, port: u16) -> String {
+    let spn = to_spn(host, port);
+    format!(
+        "credentials were not delegated: use Kerberos(authentication: kerberos), register the SPN \
+         (`setspn -Q {spn}`) and trust the gateway account for constrained delegation to {spn}"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spn_problem() {
+        assert_eq!(
+            to_spn("sql1.corp.local", 1433),
+            "MSSQLSvc/sql1.corp.local:1433"
+        );
+        assert_eq!(spn_problem("sql1.corp.local"), None);
+        assert_eq!(spn_problem("10.1.2.3"), Some("is an IP address"));
+        assert_eq!(spn_problem("::1"), Some("is an IP address"));
+        assert_eq!(spn_problem("LocalHost"), Some("is local"));
+        assert_eq!(
+            spn_problem("sql1"),
+            Some("is not a fully qualified domain name")
+        );
+    }
+
+    #[test]
+    fn test_double_hop_error() {
+        assert!(is_double_hop_error(
+            "Login failed for user 'NT AUTHORITY\\ANONYMOUS LOGON'."
+        ));
+        assert!(!is_double_hop_error("Login failed for user 'sa'."));
+        assert!(delegation_hint("sql1.corp.local", 1433)
+            .contains("setspn -Q MSSQLSvc/sql1.corp.local:1433"));
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Missing permissions recognized by the error codes of SQL Server and the GRANT
+//! statements fixing them
+
+/// Permission missing for a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Denial {
+    /// server level permission, e.g. `VIEW SERVER STATE`
+    Server(String),
+    /// database level permission, e.g. `VIEW DATABASE STATE`
+    Database {
+        permission: String,
+        database: String,
+    },
+    /// permission on an object, e.g. `SELECT` on `msdb.dbo.backupset`
+    Object {
+        permission: String,
+        database: String,
+        schema: String,
+        object: String,
+    },
+    /// the login has no user in the database
+    DatabaseAccess(String),
+}
+
+/// `The %ls permission was denied on the object ...`, 230 is the same for a column
+const OBJECT_PERMISSION_DENIED: [u32; 2] = [229, 230];
+/// `The user does not have permission to perform this action.`
+const ACTION_NOT_PERMITTED: u32 = 297;
+/// `%ls permission was denied on object '%.*ls', database '%.*ls'.`
+const PERMISSION_DENIED: u32 = 300;
+/// `The server principal "%.*ls" is not able to access the database "%.*ls" ...`
+const DATABASE_NOT_ACCESSIBLE: u32 = 916;
+
+impl Denial {
+    /// missing permission as reported in the section status
+    pub fn permission(&self) -> &str {
+        match self {
+            Denial::Server(permission)
+            | Denial::Database { permission, .. }
+            | Denial::Object { permission, .. } => permission,
+            Denial::DatabaseAccess(_) => "CONNECT",
+        }
+    }
+
+    /// the database user is expected to have the name of the login
+    pub fn to_grant(&self, login: &str) -> String {
+        let login = quote(login);
+        match self {
+            Denial::Server(permission) => format!("GRANT {permission} TO {login}"),
+            Denial::Database {
+                permission,
+                database,
+            } => format!("USE {}; GRANT {permission} TO {login}", quote(database)),
+            Denial::Object {
+                permission,
+                database,
+                schema,
+                object,
+            } => format!(
+                "USE {}; GRANT {permission} ON {}.{} TO {login}",
+                quote(database),
+                quote(schema),
+                quote(object)
+            ),
+            Denial::DatabaseAccess(database) => format!(
+                "USE {}; CREATE USER {login} FOR LOGIN {login}",
+                quote(database)
+            ),
+        }
+    }
+}
+
+/// Only errors of the TDS client carry the code: `... (code: 300, state: 1, class: 14)`
+pub fn find_error_code(error: &str) -> Option<u32> {
+    error.split_once("code: ").and_then(|(_, rest)| {
+        rest.split(|c: char| !c.is_ascii_digit())
+            .next()?
+            .parse::<u32>()
+            .ok()
+    })
+}
+
+pub fn find_denial(error: &str) -> Option<Denial> {
+    let code = find_error_code(error)?;
+    match code {
+        _ if OBJECT_PERMISSION_DENIED.contains(&code) => Some(Denial::Object {
+            permission: word_before(error, " permission was denied")?,
+            database: quoted_after(error, "database ")?,
+            schema: quoted_after(error, "schema ")?,
+            object: quoted_after(error, "object ")?,
+        }),
+        ACTION_NOT_PERMITTED => Some(Denial::Server("VIEW SERVER STATE".to_string())),
+        PERMISSION_DENIED => {
+            let permission = words_before(error, " permission was denied")?;
+            match quoted_after(error, "database ") {
+                Some(database) if quoted_after(error, "object ").as_deref() != Some("server") => {
+                    Some(Denial::Database {
+                        permission,
+                        database,
+                    })
+                }
+                _ => Some(Denial::Server(permission)),
+            }
+        }
+        DATABASE_NOT_ACCESSIBLE => Some(Denial::DatabaseAccess(quoted_after(error, "database ")?)),
+        _ => None,
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("[{}]", name.replace(']', "]]"))
+}
+
+/// `'name'` or `"name"` following `marker`
+fn quoted_after(text: &str, marker: &str) -> Option<String> {
+    let rest = &text[text.find(marker)? + marker.len()..];
+    let quote = rest.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let rest = &rest[1..];
+    Some(rest[..rest.find(quote)?].to_string())
+}
+
+/// `The SELECT permission was denied` -> `SELECT`
+fn word_before(text: &str, marker: &str) -> Option<String> {
+    let head = &text[..text.find(marker)?];
+    head.split_whitespace().last().map(str::to_string)
+}
+
+/// `...'VIEW SERVER STATE permission was denied` -> `VIEW SERVER STATE`: upper case words
+fn words_before(text: &str, marker: &str) -> Option<String> {
+    let head = &text[..text.find(marker)?];
+    let words = head
+        .split(|c: char| c.is_whitespace() || c == '\'')
+        .rev()
+        .take_while(|w| !w.is_empty() && w.chars().all(|c| c.is_ascii_uppercase()))
+        .collect::<Vec<&str>>();
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.into_iter().rev().collect::<Vec<&str>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_denial() {
+        let server = "Token error: 'VIEW SERVER STATE permission was denied on object 'server', \
+                      database 'master'.' on server SQL executing  on line 1 (code: 300, state: 1, class: 14)";
+        assert_eq!(
+            find_denial(server),
+            Some(Denial::Server("VIEW SERVER STATE".to_string()))
+        );
+        let database = "Token error: 'VIEW DATABASE STATE permission was denied on object 'Sales', \
+                        database 'Sales'.' on server SQL executing  on line 1 (code: 300, state: 1, class: 14)";
+        assert_eq!(
+            find_denial(database),
+            Some(Denial::Database {
+                permission: "VIEW DATABASE STATE".to_string(),
+                database: "Sales".to_string()
+            })
+        );
+        let object = "Token error: 'The SELECT permission was denied on the object 'backupset', \
+                      database 'msdb', schema 'dbo'.' on server SQL executing  on line 1 (code: 229, state: 5, class: 14)";
+        assert_eq!(
+            find_denial(object),
+            Some(Denial::Object {
+                permission: "SELECT".to_string(),
+                database: "msdb".to_string(),
+                schema: "dbo".to_string(),
+                object: "backupset".to_string()
+            })
+        );
+        let access = "Token error: 'The server principal \"monitor\" is not able to access the database \
+                      \"Sales\" under the current security context.' on server SQL executing  on line 1 (code: 916, state: 1, class: 14)";
+        assert_eq!(
+            find_denial(access),
+            Some(Denial::DatabaseAccess("Sales".to_string()))
+        );
+        assert_eq!(
+            find_denial("The user does not have permission to perform this action. (code: 297, state: 1, class: 16)"),
+            Some(Denial::Server("VIEW SERVER STATE".to_string()))
+        );
+        assert_eq!(
+            find_denial("Invalid object name 'x' (code: 208, state: 1, class: 16)"),
+            None
+        );
+        assert_eq!(find_denial("The SELECT permission was denied"), None);
+    }
+
+    #[test]
+    fn test_to_grant() {
+        assert_eq!(
+            Denial::Server("VIEW SERVER STATE".to_string()).to_grant("NT SERVICE\\Agent"),
+            "GRANT VIEW SERVER STATE TO [NT SERVICE\\Agent]"
+        );
+        assert_eq!(
+            Denial::Object {
+                permission: "SELECT".to_string(),
+                database: "msdb".to_string(),
+                schema: "dbo".to_string(),
+                object: "backup]set".to_string()
+            }
+            .to_grant("monitor"),
+            "USE [msdb]; GRANT SELECT ON [dbo].[backup]]set] TO [monitor]"
+        );
+        let access = Denial::DatabaseAccess("Sales".to_string());
+        assert_eq!(access.permission(), "CONNECT");
+        assert_eq!(
+            access.to_grant("monitor"),
+            "USE [Sales]; CREATE USER [monitor] FOR LOGIN [monitor]"
+        );
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Diagnostics of TDS pre-login and TLS negotiation for failed logins
+//! The server is asked directly, without tiberius, to learn what it offers
+
+use anyhow::Result;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode, SslVersion};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// PRELOGIN packet, also carries TLS handshake
+const PACKET_PRELOGIN: u8 = 0x12;
+/// tabular result, the answer of the server to PRELOGIN
+const PACKET_REPLY: u8 = 0x04;
+const STATUS_EOM: u8 = 0x01;
+const HEADER_SIZE: usize = 8;
+
+const OPTION_VERSION: u8 = 0x00;
+const OPTION_ENCRYPTION: u8 = 0x01;
+const OPTION_INSTOPT: u8 = 0x02;
+const OPTION_THREADID: u8 = 0x03;
+const OPTION_MARS: u8 = 0x04;
+const OPTION_TERMINATOR: u8 = 0xFF;
+
+/// ENCRYPT_ON: the client is able to encrypt
+const ENCRYPT_ON: u8 = 0x01;
+
+/// only TLS versions which may be wrapped in TDS 7.x pre-login
+const PROBED_TLS: [(SslVersion, &str); 3] = [
+    (SslVersion::TLS1_2, "1.2"),
+    (SslVersion::TLS1_1, "1.1"),
+    (SslVersion::TLS1, "1.0"),
+];
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Encryption {
+    Off,
+    On,
+    NotSupported,
+    Required,
+    Unknown(u8),
+}
+
+impl From<u8> for Encryption {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => Encryption::Off,
+            0x01 => Encryption::On,
+            0x02 => Encryption::NotSupported,
+            0x03 => Encryption::Required,
+            v => Encryption::Unknown(v),
+        }
+    }
+}
+
+impl std::fmt::Display for Encryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Encryption::Off => write!(f, "off"),
+            Encryption::On => write!(f, "on"),
+            Encryption::NotSupported => write!(f, "not_supported"),
+            Encryption::Required => write!(f, "required"),
+            Encryption::Unknown(v) => write!(f, "unknown({v})"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct PreLogin {
+    /// version of SQL Server reported in pre-login, e.g. `10.50.1600`
+    pub version: String,
+    pub encryption: Encryption,
+}
+
+/// Errors happening before authentication, i.e. during pre-login or TLS handshake
+pub fn is_negotiation_error(text: &str) -> bool {
+    const MARKERS: [&str; 7] = [
+        "tls",
+        "ssl",
+        "handshake",
+        "prelogin",
+        "pre-login",
+        "encryption",
+        "protocol",
+    ];
+    let text = text.to_lowercase();
+    MARKERS.iter().any(|m| text.contains(m))
+}
+
+/// Fast check whether SQL Server listens on the port
+pub async fn probe(host: &str, port: u16, timeout: Duration) -> Result<PreLogin> {
+    let host = host.to_string();
+    tokio::task::spawn_blocking(move || ask_prelogin(&host, port, timeout)).await?
+}
+
+/// Human readable summary of pre-login and accepted TLS versions, never fails
+pub async fn diagnose(host: &str, port: u16, timeout: Duration) -> String {
+    let host = host.to_string();
+    let result = tokio::task::spawn_blocking(move || -> Result<(PreLogin, Vec<&'static str>)> {
+        let prelogin = ask_prelogin(&host, port, timeout)?;
+        let accepted = if prelogin.encryption == Encryption::NotSupported {
+            vec![]
+        } else {
+            PROBED_TLS
+                .iter()
+                .filter(|(version, name)| {
+                    probe_tls(&host, port, timeout, *version)
+                        .map_err(|e| log::info!("TLS {name} is refused by {host}:{port}: {e}"))
+                        .is_ok()
+                })
+                .map(|(_, name)| *name)
+                .collect()
+        };
+        Ok((prelogin, accepted))
+    })
+    .await;
+    let summary = match result {
+        Ok(Ok((prelogin, accepted))) => to_summary(&prelogin, &accepted),
+        Ok(Err(e)) => format!("pre-login failed: {e}"),
+        Err(e) => format!("pre-login failed: {e}"),
+    };
+    format!("{summary}; client: {}", client_tls())
+}
+
+fn to_summary(prelogin: &PreLogin, accepted: &[&str]) -> String {
+    let tls = match (prelogin.encryption, accepted) {
+        (Encryption::NotSupported, _) => "-".to_string(),
+        (_, []) => "none".to_string(),
+        (_, versions) => versions.join(","),
+    };
+    format!(
+        "server: version {}, encryption {}, TLS {}",
+        prelogin.version, prelogin.encryption, tls
+    )
+}
+
+#[cfg(unix)]
+fn client_tls() -> String {
+    openssl::version::version().to_string()
+}
+
+#[cfg(windows)]
+fn client_tls() -> String {
+    "SChannel".to_string()
+}
+
+fn connect(host: &str, port: u16, timeout: Duration) -> Result<TcpStream> {
+    let addr = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("No address for {host}"))?;
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
+
+fn ask_prelogin(host: &str, port: u16, timeout: Duration) -> Result<PreLogin> {
+    let mut stream = connect(host, port, timeout)?;
+    exchange_prelogin(&mut stream)
+}
+
+fn exchange_prelogin(stream: &mut TcpStream) -> Result<PreLogin> {
+    stream.write_all(&make_packet(PACKET_PRELOGIN, &make_prelogin_payload()))?;
+    let (packet_type, payload) = read_packet(stream)?;
+    if packet_type != PACKET_REPLY {
+        anyhow::bail!("Unexpected packet type {packet_type:#04x}");
+    }
+    to_prelogin(&payload)
+}
+
+/// pre-login followed by TLS handshake limited to the given version
+fn probe_tls(host: &str, port: u16, timeout: Duration, version: SslVersion) -> Result<()> {
+    let mut stream = connect(host, port, timeout)?;
+    exchange_prelogin(&mut stream)?;
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+    builder.set_verify(SslVerifyMode::NONE);
+    // old protocols are disabled by the default security level
+    builder.set_security_level(0);
+    builder.set_cipher_list("DEFAULT:@SECLEVEL=0")?;
+    builder.set_min_proto_version(Some(version))?;
+    builder.set_max_proto_version(Some(version))?;
+    let connector = builder.build();
+    let mut config = connector.configure()?;
+    config.set_verify_hostname(false);
+    config.set_use_server_name_indication(false);
+    config
+        .connect(host, TdsStream::new(stream))
+        .map(|_| ())
+        .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
+/// TLS handshake wrapped into PRELOGIN packets, as TDS 7.x requires
+#[derive(Debug)]
+struct TdsStream {
+    tcp: TcpStream,
+    pending: Vec<u8>,
+}
+
+impl TdsStream {
+    fn new(tcp: TcpStream) -> Self {
+        Self {
+            tcp,
+            pending: vec![],
+        }
+    }
+}
+
+impl Read for TdsStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            let (_, payload) = read_packet(&mut self.tcp)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            self.pending = payload;
+        }
+        let size = std::cmp::min(buf.len(), self.pending.len());
+        buf[..size].copy_from_slice(&self.pending[..size]);
+        self.pending.drain(..size);
+        Ok(size)
+    }
+}
+
+impl Write for TdsStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // TLS records are small enough to fit into one packet
+        let size = std::cmp::min(buf.len(), u16::MAX as usize - HEADER_SIZE);
+        self.tcp
+            .write_all(&make_packet(PACKET_PRELOGIN, &buf[..size]))?;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.tcp.flush()
+    }
+}
+
+fn make_packet(packet_type: u8, payload: &[u8]) -> Vec<u8> {
+    let length = (payload.len() + HEADER_SIZE) as u16;
+    let mut packet = vec![packet_type, STATUS_EOM];
+    packet.extend_from_slice(&length.to_be_bytes());
+    // spid, packet id and window
+    packet.extend_from_slice(&[0, 0, 1, 0]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn read_packet<R: Read>(stream: &mut R) -> Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    if length < HEADER_SIZE {
+        anyhow::bail!("Bad packet length {length}");
+    }
+    let mut payload = vec![0u8; length - HEADER_SIZE];
+    stream.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
+
+fn make_prelogin_payload() -> Vec<u8> {
+    let options: [(u8, Vec<u8>); 5] = [
+        // client version is not checked by the server
+        (OPTION_VERSION, vec![0, 0, 0, 0, 0, 0]),
+        (OPTION_ENCRYPTION, vec![ENCRYPT_ON]),
+        (OPTION_INSTOPT, vec![0]),
+        (OPTION_THREADID, vec![0, 0, 0, 0]),
+        (OPTION_MARS, vec![0]),
+    ];
+    let mut offset = options.len() * 5 + 1;
+    let mut tokens = vec![];
+    let mut data = vec![];
+    for (token, value) in options {
+        tokens.push(token);
+        tokens.extend_from_slice(&(offset as u16).to_be_bytes());
+        tokens.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        offset += value.len();
+        data.extend(value);
+    }
+    tokens.push(OPTION_TERMINATOR);
+    tokens.extend(data);
+    tokens
+}
+
+fn to_prelogin(payload: &[u8]) -> Result<PreLogin> {
+    let mut version = None;
+    let mut encryption = None;
+    let mut pos = 0;
+    while let Some(&token) = payload.get(pos) {
+        if token == OPTION_TERMINATOR {
+            break;
+        }
+        let header = payload
+            .get(pos + 1..pos + 5)
+            .ok_or_else(|| anyhow::anyhow!("Truncated pre-login answer"))?;
+        let offset = u16::from_be_bytes([header[0], header[1]]) as usize;
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let value = payload
+            .get(offset..offset + length)
+            .ok_or_else(|| anyhow::anyhow!("Bad pre-login option {token:#04x}"))?;
+        match (token, value) {
+            (OPTION_VERSION, [major, minor, b1, b2, ..]) => {
+                version = Some(format!(
+                    "{major}.{minor}.{}",
+                    u16::from_be_bytes([*b1, *b2])
+                ))
+            }
+            (OPTION_ENCRYPTION, [e, ..]) => encryption = Some(Encryption::from(*e)),
+            _ => {}
+        }
+        pos += 5;
+    }
+    Ok(PreLogin {
+        version: version.ok_or_else(|| anyhow::anyhow!("No version in pre-login answer"))?,
+        encryption: encryption
+            .ok_or_else(|| anyhow::anyhow!("No encryption in pre-login answer"))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_prelogin_packet() {
+        let packet = make_packet(PACKET_PRELOGIN, &make_prelogin_payload());
+        assert_eq!(packet.len(), 8 + 26 + 13);
+        assert_eq!(&packet[..4], &[0x12, 0x01, 0x00, 47]);
+        let (packet_type, payload) = read_packet(&mut packet.as_slice()).unwrap();
+        assert_eq!(packet_type, PACKET_PRELOGIN);
+        // version option points to data right after the terminator
+        assert_eq!(&payload[..5], &[OPTION_VERSION, 0, 26, 0, 6]);
+        assert_eq!(payload[25], OPTION_TERMINATOR);
+        assert_eq!(payload[32], ENCRYPT_ON);
+        assert!(read_packet(&mut [0x04u8, 1, 0, 4, 0, 0, 1, 0].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_to_prelogin() {
+        let answer = [
+            0x00, 0x00, 0x0B, 0x00, 0x06, // version
+            0x01, 0x00, 0x11, 0x00, 0x01, // encryption
+            0xFF, 0x0A, 0x32, 0x06, 0x40, 0x00, 0x00, // 10.50.1600
+            0x03,
+        ];
+        assert_eq!(
+            to_prelogin(&answer).unwrap(),
+            PreLogin {
+                version: "10.50.1600".to_string(),
+                encryption: Encryption::Required
+            }
+        );
+        assert!(to_prelogin(&answer[..12]).is_err());
+        assert!(to_prelogin(&[0xFF]).is_err());
+    }
+
+    #[test]
+    fn test_to_summary() {
+        let prelogin = |encryption| PreLogin {
+            version: "10.50.1600".to_string(),
+            encryption,
+        };
+        assert_eq!(
+            to_summary(&prelogin(Encryption::Required), &["1.0"]),
+            "server: version 10.50.1600, encryption required, TLS 1.0"
+        );
+        assert_eq!(
+            to_summary(&prelogin(Encryption::Off), &[]),
+            "server: version 10.50.1600, encryption off, TLS none"
+        );
+        assert_eq!(
+            to_summary(&prelogin(Encryption::NotSupported), &[]),
+            "server: version 10.50.1600, encryption not_supported, TLS -"
+        );
+    }
+
+    #[test]
+    fn test_is_negotiation_error() {
+        assert!(is_negotiation_error(
+            "[SQL LOGIN ERROR] Protocol error: TLS handshake failed"
+        ));
+        assert!(is_negotiation_error("error:0A000102:SSL routines"));
+        assert!(!is_negotiation_error(
+            "[SQL LOGIN ERROR] Login failed for user 'u'"
+        ));
+    }
+}
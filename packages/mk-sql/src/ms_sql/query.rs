@@ -9,6 +9,8 @@ use crate::platform::Block;
 #[cfg(windows)]
 use crate::platform::odbc;
 
+use crate::config::section::ParamValue;
+use crate::memory;
 use crate::types::{ComputerName, InstanceName};
 
 use super::sqls::find_known_query;
@@ -16,11 +18,37 @@ use super::{client::UniClient, sqls};
 use std::borrow::Borrow;
 
 use anyhow::Result;
-use std::time::Instant;
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
-use tiberius::{ColumnData, Query, Row};
+use futures::TryStreamExt;
+use tiberius::{ColumnData, Query, QueryItem, QueryStream, Row};
 
 pub type SqlRows = Vec<Row>;
+
+tokio::task_local! {
+    /// the first error of the queries run within [`track_query_errors`]
+    static QUERY_ERROR: RefCell<Option<String>>;
+}
+
+/// Runs `f` returning its output and the first error of queries run by `f`
+/// Errors are reported in the output as text, this is the only way to detect them
+pub async fn track_query_errors<F: Future>(f: F) -> (F::Output, Option<String>) {
+    QUERY_ERROR
+        .scope(RefCell::new(None), async move {
+            let output = f.await;
+            (output, QUERY_ERROR.with(|e| e.borrow_mut().take()))
+        })
+        .await
+}
+
+/// Noop outside of [`track_query_errors`]
+pub fn record_query_error(err: &anyhow::Error) {
+    let _ = QUERY_ERROR.try_with(|e| {
+        e.borrow_mut().get_or_insert_with(|| err.to_string());
+    });
+}
 pub enum UniAnswer {
     Rows(SqlRows),
     Block(Block),
@@ -74,6 +102,7 @@ pub trait Column<'a> {
     fn get_value_by_name(&self, idx: &str) -> String;
     fn get_optional_value_by_name(&self, idx: &str) -> Option<String>;
     fn get_all(self, sep: char) -> String;
+    fn get_all_values(self) -> Vec<String>;
 }
 
 impl<'a> Column<'a> for Row {
@@ -118,6 +147,10 @@ impl<'a> Column<'a> for Row {
     /// more or less correct method to extract all data from the tiberius.Row
     /// unfortunately tiberius::Row implements only into_iter -> we are using `self``, not `&self``
     fn get_all(self, sep: char) -> String {
+        self.get_all_values().join(&sep.to_string())
+    }
+
+    fn get_all_values(self) -> Vec<String> {
         self.into_iter()
             .map(|c| match c {
                 ColumnData::Guid(v) => v
@@ -134,7 +167,6 @@ impl<'a> Column<'a> for Row {
                 _ => format!("Unsupported '{:?}'", c),
             })
             .collect::<Vec<String>>()
-            .join(&sep.to_string())
     }
 }
 
@@ -155,13 +187,22 @@ pub async fn run_known_query<T: Borrow<sqls::Id>>(
 pub async fn run_custom_query<T: AsRef<str>>(
     client: &mut UniClient,
     query: T,
+) -> Result<Vec<UniAnswer>> {
+    run_custom_query_with_params(client, query, &[]).await
+}
+
+/// Runs query with bound parameters, `@P1`, `@P2`, ... are placeholders in the query
+pub async fn run_custom_query_with_params<T: AsRef<str>>(
+    client: &mut UniClient,
+    query: T,
+    params: &[ParamValue],
 ) -> Result<Vec<UniAnswer>> {
     let query = query.as_ref();
     if query.is_empty() {
         anyhow::bail!("Empty custom query");
     }
     let start = Instant::now();
-    let result = exec_sql(client, query).await;
+    let result = exec_sql(client, query, params).await;
     log_query(start, &result, make_short_query(query));
     log::trace!("Full query: `{}`", query);
     result
@@ -172,7 +213,8 @@ fn log_query(start: Instant, result: &Result<Vec<UniAnswer>>, query_body: &str)
     match result {
         Ok(_) => log::info!("Query [SUCCESS], took {total} ms, `{query_body}`"),
         Err(err) => {
-            log::info!("Query [ERROR], took {total} ms, error: `{err}`, query: `{query_body}`",)
+            log::info!("Query [ERROR], took {total} ms, error: `{err}`, query: `{query_body}`",);
+            record_query_error(err);
         }
     }
 }
@@ -183,20 +225,47 @@ async fn _run_known_query<T: Borrow<sqls::Id>>(
 ) -> Result<Vec<UniAnswer>> {
     log::debug!("Query name: `{:?}`", id.borrow());
     let query = find_known_query(id)?;
-    exec_sql(client, query).await
+    exec_sql(client, query, &[]).await
 }
 
-async fn exec_sql(client: &mut UniClient, query: &str) -> Result<Vec<UniAnswer>> {
+async fn exec_sql(
+    client: &mut UniClient,
+    query: &str,
+    params: &[ParamValue],
+) -> Result<Vec<UniAnswer>> {
     log::debug!("Query to run short: `{}`", make_short_query(query));
     log::trace!("Query to run: `{}`", query);
+    client.pacing().wait().await;
+    let result = _exec_sql(client, query, params).await;
+    client.pacing().mark_query_end();
+    result
+}
+
+async fn _exec_sql(
+    client: &mut UniClient,
+    query: &str,
+    params: &[ParamValue],
+) -> Result<Vec<UniAnswer>> {
     match client {
-        UniClient::Std(client) => {
-            let stream = Query::new(query).query(client).await?;
-            let tiberius_rows: Vec<Vec<Row>> = stream.into_results().await?;
+        UniClient::Std(client, ..) => {
+            let mut q = Query::new(query);
+            for param in params {
+                match param {
+                    ParamValue::Int(v) => q.bind(*v),
+                    ParamValue::Float(v) => q.bind(*v),
+                    ParamValue::Bool(v) => q.bind(*v),
+                    ParamValue::Str(v) => q.bind(v.clone()),
+                }
+            }
+            let stream = q.query(client).await?;
+            let tiberius_rows = collect_results(stream).await?;
             let answers: Vec<UniAnswer> = tiberius_rows.into_iter().map(UniAnswer::Rows).collect();
             Ok(answers)
         }
-        UniClient::Odbc(client) => {
+        UniClient::Odbc(client, _) => {
+            if !params.is_empty() {
+                anyhow::bail!("Parameters are not supported by ODBC");
+            }
             #[cfg(windows)]
             {
                 let blocks =
@@ -210,6 +279,45 @@ async fn exec_sql(client: &mut UniClient, query: &str) -> Result<Vec<UniAnswer>>
     }
 }
 
+/// Same as `QueryStream::into_results`, but rows above the memory budget of the section
+/// are dropped: the stream is read to the end to keep the connection usable
+async fn collect_results(mut stream: QueryStream<'_>) -> Result<Vec<Vec<Row>>> {
+    let mut results: Vec<Vec<Row>> = Vec::new();
+    let mut exceeded = false;
+    while let Some(item) = stream.try_next().await? {
+        match item {
+            QueryItem::Metadata(_) => results.push(Vec::new()),
+            QueryItem::Row(_) if exceeded => {}
+            QueryItem::Row(row) if !memory::account(row_size(&row)) => exceeded = true,
+            QueryItem::Row(row) => match results.last_mut() {
+                Some(result) => result.push(row),
+                None => results.push(vec![row]),
+            },
+        }
+    }
+    if exceeded {
+        anyhow::bail!("{}", memory::BUDGET_EXCEEDED);
+    }
+    Ok(results)
+}
+
+/// Approximate heap size of the row: fixed cells plus text and binary data
+fn row_size(row: &Row) -> usize {
+    (0..row.len())
+        .map(|i| {
+            let data = match row.try_get::<&str, usize>(i) {
+                Ok(text) => text.map(str::len),
+                Err(_) => row
+                    .try_get::<&[u8], usize>(i)
+                    .ok()
+                    .flatten()
+                    .map(<[u8]>::len),
+            };
+            std::mem::size_of::<ColumnData>() + data.unwrap_or_default()
+        })
+        .sum()
+}
+
 fn make_short_query(query: &str) -> &str {
     query
         .get(0..std::cmp::min(16, query.len() - 1))
@@ -244,6 +352,58 @@ pub async fn obtain_instance_name(client: &mut UniClient) -> Result<Option<Insta
     Ok(result.map(InstanceName::from))
 }
 
+/// `replica_server_name` of the primary replica of the availability group
+pub async fn obtain_primary_replica(
+    client: &mut UniClient,
+    availability_group: &str,
+) -> Result<Option<String>> {
+    let query = sqls::query::AG_PRIMARY_REPLICA
+        .replace("{AG_NAME}", &availability_group.replace('\'', "''"));
+    let answers = run_custom_query(client, query).await?;
+    let result = match answers.first() {
+        Some(UniAnswer::Rows(rows)) => get_first_row_column(rows, 0),
+        Some(UniAnswer::Block(block)) => block.get_first_row_column(0),
+        None => None,
+    };
+    if result.is_none() {
+        log::warn!("Primary replica of `{availability_group}` not found");
+    };
+    Ok(result)
+}
+
+/// Cheap round trip to check that the connection is still usable
+pub async fn is_client_alive(client: &mut UniClient, timeout: Duration) -> bool {
+    match tokio::time::timeout(timeout, run_custom_query(client, "SELECT 1")).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            log::warn!("Connection is broken: {e}");
+            false
+        }
+        Err(_) => {
+            log::warn!("Connection doesn't answer in {} ms", timeout.as_millis());
+            false
+        }
+    }
+}
+
+/// Local weekday(0 is Sunday) and minutes since midnight of the SQL Server
+pub async fn obtain_server_clock(client: &mut UniClient) -> Result<(u32, u32)> {
+    let answers = run_known_query(client, sqls::Id::ServerClock).await?;
+    let row = match answers.first() {
+        Some(UniAnswer::Rows(rows)) => {
+            get_first_row_column(rows, 0).zip(get_first_row_column(rows, 1))
+        }
+        Some(UniAnswer::Block(block)) => block
+            .get_first_row_column(0)
+            .zip(block.get_first_row_column(1)),
+        None => None,
+    };
+    match row {
+        Some((weekday, minutes)) => Ok((weekday.trim().parse()?, minutes.trim().parse()?)),
+        None => anyhow::bail!("Server clock not found"),
+    }
+}
+
 pub async fn obtain_system_user(client: &mut UniClient) -> Result<Option<String>> {
     let answers = run_custom_query(client, "select System_User").await?;
     let result = match answers.first() {
@@ -2,16 +2,45 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
+use super::permission::Denial;
 use super::query::UniAnswer;
 use super::sqls::{self, find_known_query};
-use crate::config::section::get_plain_section_names;
+use crate::config::ms_sql::{Emit, Security};
+use crate::config::section::{
+    get_plain_section_names, DatabaseBaseline, MaskRule, ParamValue, Procedure, RowFilter,
+};
 use crate::config::{self, section, section::names};
-use crate::emit::header;
-use crate::{constants, types::InstanceName, utils};
+use crate::emit::{header, plain_header};
+use crate::types::{InstanceName, Version};
+use crate::{constants, utils};
 use anyhow::Result;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs::read_to_string;
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+tokio::task_local! {
+    /// warnings of the section generated within [`track_warnings`]
+    static WARNINGS: RefCell<Vec<String>>;
+}
+
+/// Runs `f` returning its output and the warnings recorded by `f`
+pub async fn track_warnings<F: Future>(f: F) -> (F::Output, Vec<String>) {
+    WARNINGS
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = f.await;
+            (output, WARNINGS.with(|w| w.take()))
+        })
+        .await
+}
+
+/// Logs the warning, outside of [`track_warnings`] the warning is only logged
+pub fn record_warning(warning: &str) {
+    log::warn!("{warning}");
+    let _ = WARNINGS.try_with(|w| w.borrow_mut().push(warning.to_string()));
+}
 
 #[derive(Debug, PartialEq)]
 pub enum SectionKind {
@@ -19,13 +48,113 @@ pub enum SectionKind {
     Async,
 }
 
+/// Instance data required by a section, obtained once per run and shared between sections
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Need {
+    Databases,
+    Properties,
+    Clustered,
+}
+
+/// How the body of a section was obtained, reported in the `section_status` section
+#[derive(Debug, Clone, PartialEq)]
+pub enum SectionStatus {
+    Live,
+    /// age of the cache file in seconds
+    Cached(u64),
+    Skipped(&'static str),
+    /// error kind
+    Failed(&'static str),
+    /// the query requires a permission the login doesn't have
+    Denied(Denial),
+}
+
+impl SectionStatus {
+    pub fn failed(error: &str) -> Self {
+        SectionStatus::Failed(to_error_kind(error))
+    }
+
+    /// instance|section|status|detail
+    pub fn to_entry(&self, instance: &str, section: &str, sep: char) -> String {
+        let (status, detail) = match self {
+            SectionStatus::Live => ("live", "-".to_string()),
+            SectionStatus::Cached(age) => ("cache", age.to_string()),
+            SectionStatus::Skipped(reason) => ("skipped", reason.to_string()),
+            SectionStatus::Failed(kind) => ("failed", kind.to_string()),
+            SectionStatus::Denied(denial) => ("denied", denial.permission().to_string()),
+        };
+        format!("{instance}{sep}{section}{sep}{status}{sep}{detail}\n")
+    }
+}
+
+/// Output of a section with information how it was generated
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionResult {
+    /// header included
+    pub body: String,
+    pub status: SectionStatus,
+    /// problems which didn't prevent the output: fallbacks, truncations, etc.
+    pub warnings: Vec<String>,
+    pub duration: Duration,
+    /// data lines, header excluded
+    pub row_count: usize,
+}
+
+impl SectionResult {
+    /// instance|section|warning|text
+    pub fn to_warning_entries(&self, instance: &str, section: &str, sep: char) -> String {
+        self.warnings
+            .iter()
+            .map(|w| {
+                format!(
+                    "{instance}{sep}{section}{sep}warning{sep}{}\n",
+                    w.replace([sep, '\n', '\r'], " ")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+}
+
+fn to_error_kind(error: &str) -> &'static str {
+    let error = error.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| error.contains(w));
+    if has(&["timed out", "timeout"]) {
+        "timeout"
+    } else if has(&["memory budget"]) {
+        "memory"
+    } else if has(&["permission", "denied"]) {
+        "permission"
+    } else if has(&["login"]) {
+        "login"
+    } else if has(&["connection", "broken pipe", "reset by peer", "i/o error"]) {
+        "connection"
+    } else {
+        "query"
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Section {
     name: String,
     sep: char,
     cache_age: Option<u32>,
+    timeout: Option<Duration>,
     decorated: bool,
     header_name: String,
+    sample_interval: u32,
+    main_db: Option<String>,
+    per_database: bool,
+    max_age_days: u32,
+    include_sql_text: bool,
+    compute_rates: bool,
+    filters: Vec<RowFilter>,
+    masking: Vec<MaskRule>,
+    baseline: DatabaseBaseline,
+    procedure: Option<Procedure>,
+    security: Security,
+    /// replacement of `mssql` in the header
+    prefix: Option<String>,
 }
 
 fn to_header_name(name: &str) -> &str {
@@ -36,9 +165,36 @@ fn to_header_name(name: &str) -> &str {
 }
 
 impl Section {
-    pub fn make_instance_section() -> Self {
+    pub fn make_instance_section(emit: &Emit) -> Self {
         let config_section = config::section::SectionBuilder::new(section::names::INSTANCE).build();
-        Self::new(&config_section, None)
+        Self::new(&config_section, None).with_emit(emit)
+    }
+
+    pub fn make_section_status_section(emit: &Emit) -> Self {
+        let config_section =
+            config::section::SectionBuilder::new(section::names::SECTION_STATUS).build();
+        Self::new(&config_section, None).with_emit(emit)
+    }
+
+    pub fn make_permission_hints_section(emit: &Emit) -> Self {
+        let config_section =
+            config::section::SectionBuilder::new(section::names::PERMISSION_HINTS).build();
+        Self::new(&config_section, None).with_emit(emit)
+    }
+
+    /// options are resolved by the config of the instance, `caching` is off for the run
+    /// if caching is disabled
+    pub fn from_config(
+        section: &config::section::Section,
+        sections: &config::section::Sections,
+        caching: bool,
+    ) -> Self {
+        let options = sections.options(section);
+        Self {
+            sep: options.sep(),
+            timeout: options.timeout(),
+            ..Self::new(section, caching.then_some(options.cache_age()))
+        }
     }
 
     pub fn new(section: &config::section::Section, global_cache_age: Option<u32>) -> Self {
@@ -51,20 +207,50 @@ impl Section {
             name: section.name().into(),
             sep: section.sep(),
             cache_age,
+            timeout: None,
             decorated: !get_plain_section_names().contains(section.name()),
             header_name: to_header_name(section.name()).into(),
+            sample_interval: section.sample_interval(),
+            main_db: section.main_db().map(str::to_string),
+            per_database: section.per_database(),
+            max_age_days: section.max_age_days(),
+            include_sql_text: section.include_sql_text(),
+            compute_rates: section.compute_rates(),
+            filters: section.filters().to_vec(),
+            masking: section.masking().to_vec(),
+            baseline: section.baseline().clone(),
+            procedure: section.procedure().cloned(),
+            security: Security::default(),
+            prefix: None,
         }
     }
 
+    /// restrictions for custom sql files
+    pub fn with_security(mut self, security: &Security) -> Self {
+        self.security = security.clone();
+        self
+    }
+
+    /// naming of the output
+    pub fn with_emit(mut self, emit: &Emit) -> Self {
+        self.prefix = emit.section_prefix().map(str::to_string);
+        self
+    }
+
     pub fn to_plain_header(&self) -> String {
-        header(&self.header_name, self.sep)
+        self.make_header(&self.header_name)
     }
 
     pub fn to_work_header(&self) -> String {
-        header(
-            &(self.header_name.clone() + &self.cached_header()),
-            self.sep,
-        )
+        self.make_header(&(self.header_name.clone() + &self.cached_header()))
+    }
+
+    fn make_header(&self, name: &str) -> String {
+        if section::is_agent_section(&self.name) {
+            plain_header(name, self.sep)
+        } else {
+            header(self.prefix.as_deref(), name, self.sep)
+        }
     }
 
     fn cached_header(&self) -> String {
@@ -107,6 +293,77 @@ impl Section {
         }
     }
 
+    /// the section is reported as failed if it takes longer
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn sample_interval(&self) -> u32 {
+        self.sample_interval
+    }
+
+    /// custom section executed in every database
+    pub fn per_database(&self) -> bool {
+        self.per_database
+    }
+
+    pub fn max_age_days(&self) -> u32 {
+        self.max_age_days
+    }
+
+    pub fn include_sql_text(&self) -> bool {
+        self.include_sql_text
+    }
+
+    pub fn compute_rates(&self) -> bool {
+        self.compute_rates
+    }
+
+    /// filters of result rows, applied before formatting
+    pub fn filters(&self) -> &[RowFilter] {
+        &self.filters
+    }
+
+    /// masking of sensitive values, applied after filters
+    pub fn masking(&self) -> &[MaskRule] {
+        &self.masking
+    }
+
+    pub fn baseline(&self) -> &DatabaseBaseline {
+        &self.baseline
+    }
+
+    /// values bound to the placeholders of the query
+    pub fn params(&self) -> Vec<ParamValue> {
+        self.procedure
+            .as_ref()
+            .map(|p| p.params().iter().map(|p| p.value().clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// all result sets are reported, otherwise only the first one
+    pub fn is_multi_result(&self) -> bool {
+        self.procedure.is_some()
+    }
+
+    pub fn needs(&self) -> Vec<Need> {
+        match self.name.as_ref() {
+            names::INSTANCE => vec![Need::Properties],
+            names::BACKUP => vec![Need::Databases],
+            names::CLUSTERS => vec![Need::Databases, Need::Clustered],
+            name if section::get_per_database_sections()
+                .iter()
+                .any(|s| s == name) =>
+            {
+                vec![Need::Databases]
+            }
+            name if self.per_database && !section::get_known_section_names().contains(&name) => {
+                vec![Need::Databases]
+            }
+            _ => vec![],
+        }
+    }
+
     pub fn first_line(&self, value: Option<&InstanceName>) -> String {
         if self.decorated {
             value.map(|v| format!("{}\n", v)).unwrap_or_default()
@@ -117,16 +374,25 @@ impl Section {
 
     /// try to find the section's query in the sql directory for instance with the given version
     /// or in the known queries if custom sql query is not provided
-    pub fn select_query(&self, sql_dir: Option<PathBuf>, instance_version: u32) -> Option<String> {
+    pub fn select_query(
+        &self,
+        sql_dir: Option<PathBuf>,
+        instance_version: &Version,
+    ) -> Option<String> {
         match self.name.as_ref() {
             names::INSTANCE => find_known_query(sqls::Id::InstanceProperties)
                 .map(str::to_string)
                 .ok(),
+            names::BLOCKED_SESSIONS if self.include_sql_text => self
+                .find_provided_query(sql_dir, instance_version)
+                .or_else(|| {
+                    Self::find_known_query(sqls::Id::BlockedSessionsWithSqlText).map(str::to_string)
+                }),
             _ => self.find_query(sql_dir, instance_version),
         }
     }
 
-    fn find_query(&self, sql_dir: Option<PathBuf>, instance_version: u32) -> Option<String> {
+    fn find_query(&self, sql_dir: Option<PathBuf>, instance_version: &Version) -> Option<String> {
         self.find_provided_query(sql_dir, instance_version)
             .or_else(|| {
                 get_sql_id(&self.name)
@@ -135,27 +401,71 @@ impl Section {
             })
     }
 
+    /// file name is `name@min_version.sql`, min_version is either major version or full
+    /// build number, e.g. `jobs@15.sql` or `jobs@15.0.4153.sql`
+    /// configured procedure takes precedence over files
     pub fn find_provided_query(
         &self,
         sql_dir: Option<PathBuf>,
-        instance_version: u32,
+        instance_version: &Version,
     ) -> Option<String> {
+        if let Some(procedure) = &self.procedure {
+            if self.security.query_allowlist() {
+                log::error!(
+                    "Refused to run procedure `{}`: not allowed in safe mode",
+                    procedure.name()
+                );
+                return None;
+            }
+            return Some(procedure.statement());
+        }
         if let Some(dir) = sql_dir {
             if let Ok(versioned_files) = find_sql_files(&dir, &self.name) {
                 for (min_version, sql_file) in versioned_files {
-                    if instance_version >= min_version {
+                    if *instance_version >= min_version {
                         return read_to_string(&sql_file)
                             .map_err(|e| {
                                 log::error!("Can't read file {:?} {}", &sql_file, &e);
                                 e
                             })
-                            .ok();
+                            .ok()
+                            .filter(|content| {
+                                self.check_provided_query(&sql_file, content)
+                                    .map_err(|e| log::error!("Refused to run {:?}: {e}", &sql_file))
+                                    .is_ok()
+                            });
                     }
                 }
             };
         }
         None
     }
+    /// in safe mode the file must be in the allowlist with matching checksum
+    /// and must not modify data unless permitted
+    fn check_provided_query(&self, sql_file: &Path, content: &str) -> Result<()> {
+        if !self.security.query_allowlist() {
+            return Ok(());
+        }
+        let file_name = sql_file
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let allowed = self
+            .security
+            .find_allowed_query(&file_name)
+            .ok_or_else(|| anyhow::anyhow!("`{file_name}` is not in the query allowlist"))?;
+        let sha256 = utils::sha256_hex(content.as_bytes());
+        if sha256 != allowed.sha256() {
+            anyhow::bail!("checksum {sha256} of `{file_name}` doesn't match the allowlist");
+        }
+        match sqls::find_write_statement(content) {
+            Some(statement) if !allowed.allow_write() => {
+                anyhow::bail!("`{file_name}` contains not allowed {statement}")
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn find_known_query(id: sqls::Id) -> Option<&'static str> {
         sqls::find_known_query(id)
             .map_err(|e| {
@@ -165,33 +475,40 @@ impl Section {
             .ok()
     }
 
+    /// configured database or the default one of the section
     pub fn main_db(&self) -> Option<String> {
-        match self.name.as_ref() {
-            section::names::JOBS => Some("msdb"),
-            section::names::MIRRORING => Some("master"),
-            _ => None,
-        }
-        .map(|s| s.to_string())
+        self.main_db.clone().or_else(|| {
+            match self.name.as_ref() {
+                section::names::JOBS => Some("msdb"),
+                section::names::MIRRORING => Some("master"),
+                _ => None,
+            }
+            .map(|s| s.to_string())
+        })
     }
 
     pub fn validate_rows(&self, rows: Vec<UniAnswer>) -> Result<Vec<UniAnswer>> {
-        const ALLOW_TO_HAVE_EMPTY_OUTPUT: [&str; 2] = [
+        const ALLOW_TO_HAVE_EMPTY_OUTPUT: [&str; 6] = [
             section::names::MIRRORING,
             section::names::AVAILABILITY_GROUPS,
+            section::names::TRACE_FLAGS,
+            section::names::DEFAULT_TRACE,
+            section::names::ORPHANED_FILES,
+            section::names::MAINTENANCE,
         ];
         if (!rows.is_empty() && !rows[0].is_empty())
             || (ALLOW_TO_HAVE_EMPTY_OUTPUT.contains(&self.name()))
         {
             Ok(rows)
         } else {
-            log::warn!("No output from query");
+            record_warning(&format!("No output from query of section {}", self.name()));
             Err(anyhow::anyhow!("No output from query"))
         }
     }
 }
 
-fn find_sql_files(dir: &Path, section_name: &str) -> Result<Vec<(u32, PathBuf)>> {
-    let mut paths: Vec<(u32, PathBuf)> = std::fs::read_dir(dir)?
+fn find_sql_files(dir: &Path, section_name: &str) -> Result<Vec<(Version, PathBuf)>> {
+    let mut paths: Vec<(Version, PathBuf)> = std::fs::read_dir(dir)?
         .filter_map(|res| res.ok())
         .map(|dir_entry| dir_entry.path())
         .filter_map(|path| {
@@ -211,17 +528,17 @@ fn find_sql_files(dir: &Path, section_name: &str) -> Result<Vec<(u32, PathBuf)>>
     Ok(paths)
 }
 
-fn get_file_version(path: &Path, section_name: &str) -> Option<u32> {
+fn get_file_version(path: &Path, section_name: &str) -> Option<Version> {
     if let Some(stem) = path.file_stem().map(|n| n.to_string_lossy().to_string()) {
         match stem.rsplitn(2, '@').collect::<Vec<&str>>().as_slice() {
             [min_version, name] => {
                 if name.to_lowercase() == section_name.to_lowercase() {
-                    return Some(min_version.parse::<u32>().unwrap_or(0));
+                    return Some(Version::from(*min_version));
                 }
             }
             [stem] => {
                 if stem.to_lowercase() == section_name.to_lowercase() {
-                    return Some(0);
+                    return Some(Version::default());
                 }
             }
             _ => {}
@@ -247,6 +564,44 @@ lazy_static::lazy_static! {
         (names::JOBS, sqls::Id::Jobs),
         (names::MIRRORING, sqls::Id::Mirroring),
         (names::AVAILABILITY_GROUPS, sqls::Id::AvailabilityGroups),
+
+        (names::PERF_KEY_METRICS, sqls::Id::PerfKeyMetrics),
+        (names::WAITS_DELTA, sqls::Id::WaitsDelta),
+        (names::DB_CONNECTIONS, sqls::Id::DbConnections),
+        (names::TEMPDB_CONTENTION, sqls::Id::TempdbContention),
+        (names::DB_OPTION_DRIFT, sqls::Id::DbOptionDrift),
+        (names::LABELS, sqls::Id::Labels),
+        (names::TRACE_FLAGS, sqls::Id::TraceFlags),
+        (names::ENCRYPTION, sqls::Id::Encryption),
+        (names::SCHEDULERS, sqls::Id::Schedulers),
+        (names::SESSIONS, sqls::Id::Sessions),
+        (names::CHECKDB, sqls::Id::CheckDb),
+        (names::BACKUP_URL, sqls::Id::BackupUrl),
+        (names::PERMISSIONS, sqls::Id::Permissions),
+        (names::FILEGROUPS, sqls::Id::Filegroups),
+        (names::CONSTRAINTS, sqls::Id::Constraints),
+        (names::TOP_TABLES, sqls::Id::TopTables),
+        (names::FILESTREAM, sqls::Id::Filestream),
+        (names::UPTIME, sqls::Id::Uptime),
+        (names::VERSION_STORE, sqls::Id::VersionStore),
+        (names::MEMORY_FEATURES, sqls::Id::MemoryFeatures),
+        (
+            names::DISTRIBUTED_TRANSACTIONS,
+            sqls::Id::DistributedTransactions,
+        ),
+        (names::COLLATION, sqls::Id::Collation),
+        (names::AGENT_HEALTH, sqls::Id::AgentHealth),
+        (names::CERTS, sqls::Id::Certs),
+        (names::QUERY_STORE, sqls::Id::QueryStore),
+        (names::LOGINS, sqls::Id::Logins),
+        (names::AG_CONFIG, sqls::Id::AgConfig),
+        (names::HOST_SETTINGS, sqls::Id::HostSettings),
+        (names::VOLUMES, sqls::Id::Volumes),
+        (names::DEFAULT_TRACE, sqls::Id::DefaultTrace),
+        (names::ORPHANED_FILES, sqls::Id::OrphanedFiles),
+        (names::MAINTENANCE, sqls::Id::Maintenance),
+        (names::AUDITS, sqls::Id::Audits),
+        (names::AZURE_RESOURCES, sqls::Id::AzureResources),
     ]);
 }
 
@@ -261,9 +616,208 @@ mod tests {
     use crate::config::section;
     use crate::ms_sql::custom;
 
+    #[test]
+    fn test_section_status() {
+        let entry = |status: SectionStatus| status.to_entry("MSSQL_A", "jobs", '|');
+        assert_eq!(entry(SectionStatus::Live), "MSSQL_A|jobs|live|-\n");
+        assert_eq!(
+            entry(SectionStatus::Cached(120)),
+            "MSSQL_A|jobs|cache|120\n"
+        );
+        assert_eq!(
+            entry(SectionStatus::Skipped("blackout")),
+            "MSSQL_A|jobs|skipped|blackout\n"
+        );
+        assert_eq!(
+            entry(SectionStatus::failed("Connection timed out")),
+            "MSSQL_A|jobs|failed|timeout\n"
+        );
+        assert_eq!(
+            SectionStatus::failed("The SELECT permission was denied"),
+            SectionStatus::Failed("permission")
+        );
+        assert_eq!(
+            SectionStatus::failed("Login failed for user 'x'"),
+            SectionStatus::Failed("login")
+        );
+        assert_eq!(
+            SectionStatus::failed("Connection reset by peer"),
+            SectionStatus::Failed("connection")
+        );
+        assert_eq!(
+            SectionStatus::failed("Invalid object name 'x'"),
+            SectionStatus::Failed("query")
+        );
+        assert_eq!(
+            SectionStatus::failed(crate::memory::BUDGET_EXCEEDED),
+            SectionStatus::Failed("memory")
+        );
+        assert_eq!(
+            entry(SectionStatus::Denied(Denial::Server(
+                "VIEW SERVER STATE".to_string()
+            ))),
+            "MSSQL_A|jobs|denied|VIEW SERVER STATE\n"
+        );
+        assert_eq!(
+            Section::make_section_status_section(&Emit::default()).to_plain_header(),
+            "<<<mssql_section_status:sep(124)>>>\n"
+        );
+        assert_eq!(
+            Section::make_permission_hints_section(&Emit::default()).to_plain_header(),
+            "<<<mssql_permission_hints:sep(124)>>>\n"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_track_warnings() {
+        let (output, warnings) = track_warnings(async {
+            record_warning("first");
+            record_warning("second|line\nnext");
+            1
+        })
+        .await;
+        assert_eq!(output, 1);
+        let result = SectionResult {
+            body: String::new(),
+            status: SectionStatus::Live,
+            warnings,
+            duration: Duration::default(),
+            row_count: 0,
+        };
+        assert_eq!(
+            result.to_warning_entries("MSSQL_A", "jobs", '|'),
+            "MSSQL_A|jobs|warning|first\n\
+             MSSQL_A|jobs|warning|second line next\n"
+        );
+        record_warning("lost");
+        let (_, warnings) = track_warnings(async {}).await;
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_check_provided_query() {
+        let security = Security::from_yaml(&crate::config::yaml::test_tools::create_yaml(
+            r#"
+security:
+  query_allowlist: yes
+  allowed_queries:
+    - file: "read.sql"
+      sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    - file: "write.sql"
+      sha256: "993fca30823bd8069e35a94552c4b73be636d0d388108309f42cf1522e064f7b"
+      allow_write: yes
+    - file: "no_write.sql"
+      sha256: "993fca30823bd8069e35a94552c4b73be636d0d388108309f42cf1522e064f7b"
+"#,
+        ))
+        .unwrap()
+        .unwrap();
+        let section = Section::new(&section::SectionBuilder::new("custom").build(), None);
+        assert!(section
+            .check_provided_query(Path::new("any.sql"), "DELETE FROM t")
+            .is_ok());
+        let section = section.with_security(&security);
+        assert!(section
+            .check_provided_query(Path::new("dir/read.sql"), "abc")
+            .is_ok());
+        assert!(section
+            .check_provided_query(Path::new("dir/read.sql"), "abcd")
+            .is_err());
+        assert!(section
+            .check_provided_query(Path::new("dir/other.sql"), "abc")
+            .is_err());
+        assert!(section
+            .check_provided_query(Path::new("write.sql"), "DELETE FROM t")
+            .is_ok());
+        assert!(section
+            .check_provided_query(Path::new("no_write.sql"), "DELETE FROM t")
+            .is_err());
+    }
+
+    #[test]
+    fn test_procedure_query() {
+        let procedure =
+            section::Procedure::from_yaml(&crate::config::yaml::test_tools::create_yaml(
+                "procedure: dbo.check\nparams:\n  - name: days\n    value: 7\n",
+            ))
+            .unwrap();
+        let section = Section::new(
+            &section::SectionBuilder::new("custom")
+                .procedure(procedure)
+                .build(),
+            None,
+        );
+        assert!(section.is_multi_result());
+        assert_eq!(section.params(), [ParamValue::Int(7)]);
+        assert_eq!(
+            section
+                .find_provided_query(None, &Version::default())
+                .unwrap(),
+            "EXEC [dbo].[check] @days = @P1"
+        );
+        let safe = Security::from_yaml(&crate::config::yaml::test_tools::create_yaml(
+            "security:\n  query_allowlist: yes\n",
+        ))
+        .unwrap()
+        .unwrap();
+        assert!(section
+            .with_security(&safe)
+            .find_provided_query(None, &Version::default())
+            .is_none());
+        let plain = Section::new(&section::SectionBuilder::new("custom").build(), None);
+        assert!(!plain.is_multi_result());
+        assert!(plain.params().is_empty());
+    }
+
+    #[test]
+    fn test_find_write_statement() {
+        assert_eq!(sqls::find_write_statement("SELECT * FROM t"), None);
+        assert_eq!(
+            sqls::find_write_statement("select 1; delete from t"),
+            Some("DELETE")
+        );
+        assert_eq!(
+            sqls::find_write_statement("SELECT 1\n;Exec sp_who"),
+            Some("EXEC")
+        );
+        assert_eq!(
+            sqls::find_write_statement("INSERT INTO @t SELECT 1"),
+            Some("INSERT")
+        );
+        assert_eq!(
+            sqls::find_write_statement(
+                "-- update statistics\n/* insert */ SELECT 'delete', [update], \"exec\", @insert, last_update FROM t"
+            ),
+            None
+        );
+        assert_eq!(
+            sqls::find_write_statement("SELECT 'it''s' AS x; UPDATE t SET a = 1"),
+            Some("UPDATE")
+        );
+        for (sql, statement) in [
+            ("DROP TABLE t", "DROP"),
+            ("alter table t add c int", "ALTER"),
+            ("SELECT 1; Truncate Table t", "TRUNCATE"),
+            (
+                "MERGE t USING s ON t.a = s.a WHEN MATCHED THEN DELETE;",
+                "MERGE",
+            ),
+            ("create table #t (a int)", "CREATE"),
+            ("GRANT VIEW SERVER STATE TO monitor", "GRANT"),
+            ("EXEC('SELECT 1')", "EXEC"),
+            ("execute sp_who", "EXECUTE"),
+        ] {
+            assert_eq!(sqls::find_write_statement(sql), Some(statement), "{sql}");
+        }
+        assert_eq!(
+            sqls::find_write_statement("SELECT create_date, is_dropped FROM sys.objects"),
+            None
+        );
+    }
+
     #[test]
     fn test_section_header() {
-        let section = Section::make_instance_section();
+        let section = Section::make_instance_section(&Emit::default());
         assert_eq!(section.to_plain_header(), "<<<mssql_instance:sep(124)>>>\n");
         assert_eq!(section.to_work_header(), "<<<mssql_instance:sep(124)>>>\n");
 
@@ -279,6 +833,9 @@ mod tests {
             .starts_with("<<<mssql_backup:cached("));
         assert!(section.to_work_header().ends_with("100):sep(124)>>>\n"));
 
+        let section = Section::new(&section::SectionBuilder::new("labels").build(), None);
+        assert_eq!(section.to_work_header(), "<<<labels:sep(00)>>>\n");
+
         let section = Section::new(&section::SectionBuilder::new("jobs").build(), Some(100));
         assert!(section
             .to_work_header()
@@ -290,6 +847,64 @@ mod tests {
             Some(100),
         );
         assert_eq!(section.to_work_header(), "<<<mssql_jobs:sep(09)>>>\n");
+
+        let yaml = &yaml_rust2::YamlLoader::load_from_str("emit:\n  section_prefix: mssql_v2\n")
+            .unwrap()[0];
+        let emit = Emit::from_yaml(yaml).unwrap().unwrap();
+        let section = Section::make_instance_section(&emit);
+        assert_eq!(
+            section.to_plain_header(),
+            "<<<mssql_v2_instance:sep(124)>>>\n"
+        );
+        let section = Section::new(&section::SectionBuilder::new("labels").build(), None);
+        assert_eq!(
+            section.with_emit(&emit).to_work_header(),
+            "<<<labels:sep(00)>>>\n"
+        );
+    }
+
+    #[test]
+    fn test_section_from_config() {
+        let jobs = section::SectionBuilder::new("jobs")
+            .cache_age(Some(60))
+            .timeout(Some(5))
+            .build();
+        let sections = section::Sections::default();
+        let section = Section::from_config(&jobs, &sections, true);
+        assert_eq!(section.cache_age(), 60);
+        assert_eq!(section.timeout(), Some(Duration::from_secs(5)));
+        assert_eq!(section.sep(), '\t');
+        assert_eq!(Section::from_config(&jobs, &sections, false).cache_age(), 0);
+        let instance = section::SectionBuilder::new("instance").build();
+        let section = Section::from_config(&instance, &sections, true);
+        assert_eq!(section.kind(), &SectionKind::Sync);
+        assert_eq!(section.timeout(), None);
+    }
+
+    #[test]
+    fn test_section_needs() {
+        let needs =
+            |name: &str| Section::new(&section::SectionBuilder::new(name).build(), None).needs();
+        assert_eq!(needs(names::INSTANCE), vec![Need::Properties]);
+        assert_eq!(needs(names::BACKUP), vec![Need::Databases]);
+        assert_eq!(needs(names::DATAFILES), vec![Need::Databases]);
+        assert_eq!(
+            needs(names::CLUSTERS),
+            vec![Need::Databases, Need::Clustered]
+        );
+        assert!(needs(names::JOBS).is_empty());
+        assert!(needs("custom").is_empty());
+        let per_database_needs = |name: &str| {
+            Section::new(
+                &section::SectionBuilder::new(name)
+                    .per_database(true)
+                    .build(),
+                None,
+            )
+            .needs()
+        };
+        assert_eq!(per_database_needs("custom"), vec![Need::Databases]);
+        assert!(per_database_needs(names::JOBS).is_empty());
     }
 
     #[test]
@@ -314,17 +929,65 @@ mod tests {
             (names::DATAFILES, sqls::Id::Datafiles),
             (names::BACKUP, sqls::Id::Backup),
             (names::JOBS, sqls::Id::Jobs),
+            (names::PERF_KEY_METRICS, sqls::Id::PerfKeyMetrics),
+            (names::WAITS_DELTA, sqls::Id::WaitsDelta),
+            (names::DB_CONNECTIONS, sqls::Id::DbConnections),
+            (names::TEMPDB_CONTENTION, sqls::Id::TempdbContention),
+            (names::DB_OPTION_DRIFT, sqls::Id::DbOptionDrift),
+            (names::LABELS, sqls::Id::Labels),
+            (names::TRACE_FLAGS, sqls::Id::TraceFlags),
+            (names::ENCRYPTION, sqls::Id::Encryption),
+            (names::SCHEDULERS, sqls::Id::Schedulers),
+            (names::SESSIONS, sqls::Id::Sessions),
+            (names::CHECKDB, sqls::Id::CheckDb),
+            (names::BACKUP_URL, sqls::Id::BackupUrl),
+            (names::PERMISSIONS, sqls::Id::Permissions),
+            (names::FILEGROUPS, sqls::Id::Filegroups),
+            (names::CONSTRAINTS, sqls::Id::Constraints),
+            (names::TOP_TABLES, sqls::Id::TopTables),
+            (names::FILESTREAM, sqls::Id::Filestream),
+            (names::UPTIME, sqls::Id::Uptime),
+            (names::VERSION_STORE, sqls::Id::VersionStore),
+            (names::MEMORY_FEATURES, sqls::Id::MemoryFeatures),
+            (
+                names::DISTRIBUTED_TRANSACTIONS,
+                sqls::Id::DistributedTransactions,
+            ),
+            (names::COLLATION, sqls::Id::Collation),
+            (names::AGENT_HEALTH, sqls::Id::AgentHealth),
+            (names::CERTS, sqls::Id::Certs),
+            (names::QUERY_STORE, sqls::Id::QueryStore),
+            (names::LOGINS, sqls::Id::Logins),
+            (names::AG_CONFIG, sqls::Id::AgConfig),
+            (names::HOST_SETTINGS, sqls::Id::HostSettings),
+            (names::VOLUMES, sqls::Id::Volumes),
+            (names::DEFAULT_TRACE, sqls::Id::DefaultTrace),
+            (names::ORPHANED_FILES, sqls::Id::OrphanedFiles),
+            (names::MAINTENANCE, sqls::Id::Maintenance),
+            (names::AUDITS, sqls::Id::Audits),
+            (names::AZURE_RESOURCES, sqls::Id::AzureResources),
         ];
         for (name, ids) in test_set {
             assert_eq!(
                 make_section(name)
-                    .select_query(custom::get_sql_dir(), 0)
+                    .select_query(custom::get_sql_dir(), &Version::default())
                     .unwrap(),
                 find_known_query(ids).unwrap()
             );
         }
         assert_eq!(
-            make_section("no_name").select_query(custom::get_sql_dir(), 0),
+            Section::new(
+                &config::section::SectionBuilder::new(names::BLOCKED_SESSIONS)
+                    .include_sql_text(true)
+                    .build(),
+                None
+            )
+            .select_query(custom::get_sql_dir(), &Version::default())
+            .unwrap(),
+            find_known_query(sqls::Id::BlockedSessionsWithSqlText).unwrap()
+        );
+        assert_eq!(
+            make_section("no_name").select_query(custom::get_sql_dir(), &Version::default()),
             None
         )
     }
@@ -30,8 +30,45 @@ pub enum Id {
     BadQuery,
     WaitingTasks,
     BlockedSessions,
+    BlockedSessionsWithSqlText,
     Counters,
     Clusters,
+    PerfKeyMetrics,
+    WaitsDelta,
+    DbConnections,
+    TempdbContention,
+    DbOptionDrift,
+    Labels,
+    AzureArc,
+    TraceFlags,
+    Permissions,
+    Filegroups,
+    Constraints,
+    TopTables,
+    Encryption,
+    Schedulers,
+    Sessions,
+    CheckDb,
+    BackupUrl,
+    Filestream,
+    Uptime,
+    VersionStore,
+    MemoryFeatures,
+    DistributedTransactions,
+    Collation,
+    AgentHealth,
+    Certs,
+    QueryStore,
+    Logins,
+    AgConfig,
+    HostSettings,
+    Volumes,
+    DefaultTrace,
+    OrphanedFiles,
+    Maintenance,
+    Audits,
+    AzureResources,
+    ServerClock,
 }
 
 pub mod query {
@@ -141,6 +178,859 @@ SELECT InstanceNames, InstanceIds, EditionNames, VersionNames, ClusterNames,Port
                 cntr_value \
      FROM sys.dm_os_performance_counters WHERE object_name NOT LIKE '%Deprecated%'";
 
+    /// Counters required to calculate key performance metrics, the query is executed twice
+    pub const PERF_KEY_METRICS: &str =
+        "SELECT cast(counter_name as NVARCHAR(100)) as counter_name, \
+                cast(object_name as NVARCHAR(100)) as object_name, \
+                cast(instance_name as NVARCHAR(100)) as instance_name, \
+                cntr_value \
+     FROM sys.dm_os_performance_counters \
+     WHERE (object_name LIKE '%Buffer Node%' AND counter_name = 'Page life expectancy') \
+        OR (object_name LIKE '%Buffer Manager%' \
+            AND counter_name IN ('Buffer cache hit ratio', 'Buffer cache hit ratio base')) \
+        OR (object_name LIKE '%SQL Statistics%' \
+            AND counter_name IN ('Batch Requests/sec', 'SQL Compilations/sec'))";
+
+    /// User connections per database split into active and idle(sleeping) ones
+    /// The query is executed several times during the run to find the maximum
+    pub const DB_CONNECTIONS: &str =
+        "SELECT cast(DB_NAME(database_id) as NVARCHAR(128)) as database_name, \
+           cast(COUNT(*) as bigint) as connections, \
+           cast(SUM(CASE WHEN status = 'sleeping' THEN 0 ELSE 1 END) as bigint) as active, \
+           cast(SUM(CASE WHEN status = 'sleeping' THEN 1 ELSE 0 END) as bigint) as idle \
+    FROM sys.dm_exec_sessions \
+    WHERE is_user_process = 1 AND database_id > 0 \
+    GROUP BY database_id";
+
+    /// Tasks waiting for a page latch in tempdb, `resource_description` is `2:file:page`
+    /// The query is executed several times during the run to count the contention
+    pub const TEMPDB_CONTENTION: &str =
+        "SELECT cast(resource_description as NVARCHAR(256)) as resource_description, \
+           cast(wait_duration_ms as bigint) as wait_duration_ms \
+    FROM sys.dm_os_waiting_tasks \
+    WHERE wait_type LIKE 'PAGELATCH_%' AND resource_description LIKE '2:%'";
+
+    /// Cumulative server waits, the query is executed twice to calculate deltas
+    /// Benign waits of idle background tasks are excluded
+    pub const WAITS_DELTA: &str = "SELECT cast(wait_type as NVARCHAR(60)) as wait_type, \
+                waiting_tasks_count, wait_time_ms, signal_wait_time_ms \
+     FROM sys.dm_os_wait_stats \
+     WHERE wait_type NOT IN ( \
+        'BROKER_EVENTHANDLER', 'BROKER_RECEIVE_WAITFOR', 'BROKER_TASK_STOP', \
+        'BROKER_TO_FLUSH', 'BROKER_TRANSMITTER', 'CHECKPOINT_QUEUE', 'CHKPT', \
+        'CLR_AUTO_EVENT', 'CLR_MANUAL_EVENT', 'CLR_SEMAPHORE', 'DIRTY_PAGE_POLL', \
+        'DISPATCHER_QUEUE_SEMAPHORE', 'FT_IFTS_SCHEDULER_IDLE_WAIT', 'FT_IFTSHC_MUTEX', \
+        'HADR_FILESTREAM_IOMGR_IOCOMPLETION', 'HADR_WORK_QUEUE', 'LAZYWRITER_SLEEP', \
+        'LOGMGR_QUEUE', 'ONDEMAND_TASK_QUEUE', 'REQUEST_FOR_DEADLOCK_SEARCH', \
+        'RESOURCE_QUEUE', 'SERVER_IDLE_CHECK', 'SLEEP_BPOOL_FLUSH', 'SLEEP_DBSTARTUP', \
+        'SLEEP_DCOMSTARTUP', 'SLEEP_MASTERDBREADY', 'SLEEP_MASTERMDREADY', \
+        'SLEEP_MASTERUPGRADED', 'SLEEP_MSDBSTARTUP', 'SLEEP_SYSTEMTASK', 'SLEEP_TASK', \
+        'SLEEP_TEMPDBSTARTUP', 'SNI_HTTP_ACCEPT', 'SP_SERVER_DIAGNOSTICS_SLEEP', \
+        'SQLTRACE_BUFFER_FLUSH', 'SQLTRACE_INCREMENTAL_FLUSH_SLEEP', \
+        'SQLTRACE_WAIT_ENTRIES', 'WAIT_FOR_RESULTS', 'WAITFOR', 'WAITFOR_TASKSHUTDOWN', \
+        'WAIT_XTP_HOST_WAIT', 'WAIT_XTP_OFFLINE_CKPT_NEW_LOG', 'WAIT_XTP_CKPT_CLOSE', \
+        'XE_DISPATCHER_JOIN', 'XE_DISPATCHER_WAIT', 'XE_TIMER_EVENT', \
+        'QDS_PERSIST_TASK_MAIN_LOOP_SLEEP', 'QDS_ASYNC_QUEUE', \
+        'QDS_CLEANUP_STALE_QUERIES_TASK_MAIN_LOOP_SLEEP', 'PWAIT_ALL_COMPONENTS_INITIALIZED', \
+        'PREEMPTIVE_XE_GETTARGETSTATE', 'PREEMPTIVE_OS_FLUSHFILEBUFFERS')";
+
+    /// Options of online user databases to be compared with the baseline, snapshots are skipped
+    pub const DB_OPTION_DRIFT: &str = "SELECT cast(name as NVARCHAR(128)) as database_name, \
+           cast(recovery_model_desc as NVARCHAR(60)) as recovery_model, \
+           cast(is_auto_shrink_on as NVARCHAR(1)) as auto_shrink, \
+           cast(is_auto_close_on as NVARCHAR(1)) as auto_close, \
+           cast(page_verify_option_desc as NVARCHAR(60)) as page_verify \
+    FROM sys.databases \
+    WHERE database_id > 4 AND state = 0 AND source_database_id IS NULL";
+
+    /// Azure Arc enrollment: the Azure extension for SQL Server logs on with own service login
+    pub const AZURE_ARC: &str = "SELECT COUNT(*) as azure_arc FROM sys.server_principals \
+    WHERE name = N'NT SERVICE\\SQLServerExtension'";
+
+    /// Facts to classify the instance into roles, AG columns are empty if not a replica
+    pub const LABELS: &str = "SELECT cast(SERVERPROPERTY('Edition') as NVARCHAR(128)) as edition, \
+           ISNULL((SELECT TOP 1 cast(rs.role_desc as NVARCHAR(60)) \
+                   FROM sys.dm_hadr_availability_replica_states rs \
+                   WHERE rs.is_local = 1 ORDER BY rs.role), '') as ag_role, \
+           ISNULL((SELECT TOP 1 cast(ar.secondary_role_allow_connections_desc as NVARCHAR(60)) \
+                   FROM sys.availability_replicas ar \
+                   JOIN sys.dm_hadr_availability_replica_states rs ON rs.replica_id = ar.replica_id \
+                   WHERE rs.is_local = 1), '') as secondary_connections, \
+           (SELECT COUNT(*) FROM sys.databases \
+            WHERE name LIKE 'ReportServer%' AND name NOT LIKE '%TempDB') as report_databases";
+
+    /// Globally enabled trace flags and startup parameters
+    pub const TRACE_FLAGS: &str =
+        "DECLARE @flags TABLE (TraceFlag int, Status int, Global int, Session int); \
+    INSERT INTO @flags EXEC('DBCC TRACESTATUS(-1) WITH NO_INFOMSGS'); \
+    SELECT 'traceflag' as kind, \
+           cast(TraceFlag as NVARCHAR(20)) as name, \
+           cast(Status as NVARCHAR(20)) as value \
+    FROM @flags WHERE Global = 1 \
+    UNION ALL \
+    SELECT 'startup_parameter' as kind, \
+           cast(value_name as NVARCHAR(256)) as name, \
+           cast(value_data as NVARCHAR(1024)) as value \
+    FROM sys.dm_server_registry WHERE value_name LIKE N'SQLArg%'";
+
+    /// Members of db_owner and SQL users without login, executed per database
+    pub const PERMISSIONS: &str = "SELECT 'db_owner' as kind, \
+           cast(m.name as NVARCHAR(256)) as name, \
+           cast(m.type_desc as NVARCHAR(60)) as type \
+    FROM sys.database_role_members rm \
+    JOIN sys.database_principals r ON rm.role_principal_id = r.principal_id \
+    JOIN sys.database_principals m ON rm.member_principal_id = m.principal_id \
+    WHERE r.name = 'db_owner' \
+    UNION ALL \
+    SELECT 'orphaned' as kind, \
+           cast(dp.name as NVARCHAR(256)) as name, \
+           cast(dp.type_desc as NVARCHAR(60)) as type \
+    FROM sys.database_principals dp \
+    LEFT JOIN sys.server_principals sp ON dp.sid = sp.sid \
+    WHERE dp.type = 'S' \
+      AND dp.principal_id > 4 \
+      AND dp.authentication_type_desc = 'INSTANCE' \
+      AND sp.sid IS NULL";
+
+    /// Filegroups with used/free space and the largest partitioned tables, executed per database
+    /// `items` is the count of files for a filegroup and of partitions for a table
+    pub const FILEGROUPS: &str = "SELECT 'filegroup' as kind, \
+           cast(fg.name as NVARCHAR(128)) as name, \
+           cast(fg.type_desc as NVARCHAR(60)) as type, \
+           cast(count(df.file_id) as bigint) as items, \
+           cast(isnull(sum(cast(df.size as bigint)), 0) * 8 / 1024 as bigint) as size_mb, \
+           cast(isnull(sum(cast(FILEPROPERTY(df.name, 'SpaceUsed') as bigint)), 0) * 8 / 1024 as bigint) as used_mb \
+    FROM sys.filegroups fg \
+    LEFT JOIN sys.database_files df ON df.data_space_id = fg.data_space_id \
+    GROUP BY fg.name, fg.type_desc \
+    UNION ALL \
+    SELECT kind, name, type, items, size_mb, used_mb FROM ( \
+        SELECT TOP 10 'partitioned_table' as kind, \
+               cast(s.name + '.' + t.name as NVARCHAR(256)) as name, \
+               cast(ps.name as NVARCHAR(128)) as type, \
+               cast(count(*) as bigint) as items, \
+               cast(sum(p.reserved_page_count) * 8 / 1024 as bigint) as size_mb, \
+               cast(sum(p.used_page_count) * 8 / 1024 as bigint) as used_mb \
+        FROM sys.dm_db_partition_stats p \
+        JOIN sys.tables t ON p.object_id = t.object_id \
+        JOIN sys.schemas s ON t.schema_id = s.schema_id \
+        JOIN sys.indexes i ON i.object_id = p.object_id AND i.index_id = p.index_id \
+        JOIN sys.partition_schemes ps ON i.data_space_id = ps.data_space_id \
+        WHERE p.index_id IN (0, 1) \
+        GROUP BY s.name, t.name, ps.name \
+        ORDER BY sum(p.reserved_page_count) DESC \
+    ) largest";
+
+    /// Disabled and untrusted foreign keys and check constraints of the 50 largest user tables,
+    /// executed per database, tables without such constraints are not reported
+    /// Untrusted are enabled constraints created or re-enabled WITH NOCHECK
+    pub const CONSTRAINTS: &str = "SELECT name, row_count, fk_disabled, fk_untrusted, ck_disabled, ck_untrusted FROM ( \
+        SELECT cast(s.name + '.' + t.name as NVARCHAR(256)) as name, \
+               cast(l.row_count as bigint) as row_count, \
+               cast((SELECT count(*) FROM sys.foreign_keys fk \
+                     WHERE fk.parent_object_id = t.object_id AND fk.is_disabled = 1) as bigint) as fk_disabled, \
+               cast((SELECT count(*) FROM sys.foreign_keys fk \
+                     WHERE fk.parent_object_id = t.object_id AND fk.is_disabled = 0 \
+                       AND fk.is_not_trusted = 1) as bigint) as fk_untrusted, \
+               cast((SELECT count(*) FROM sys.check_constraints cc \
+                     WHERE cc.parent_object_id = t.object_id AND cc.is_disabled = 1) as bigint) as ck_disabled, \
+               cast((SELECT count(*) FROM sys.check_constraints cc \
+                     WHERE cc.parent_object_id = t.object_id AND cc.is_disabled = 0 \
+                       AND cc.is_not_trusted = 1) as bigint) as ck_untrusted \
+        FROM ( \
+            SELECT TOP 50 p.object_id, sum(p.row_count) as row_count \
+            FROM sys.dm_db_partition_stats p \
+            WHERE p.index_id IN (0, 1) \
+            GROUP BY p.object_id \
+            ORDER BY sum(p.row_count) DESC \
+        ) l \
+        JOIN sys.tables t ON t.object_id = l.object_id \
+        JOIN sys.schemas s ON s.schema_id = t.schema_id \
+        WHERE t.is_ms_shipped = 0 \
+    ) tables \
+    WHERE fk_disabled + fk_untrusted + ck_disabled + ck_untrusted > 0 \
+    ORDER BY row_count DESC";
+
+    /// The 20 largest user tables by reserved space, executed per database
+    /// Sizes are in KB, growth is the difference between two runs
+    pub const TOP_TABLES: &str = "SELECT TOP 20 \
+           cast(s.name + '.' + t.name as NVARCHAR(256)) as name, \
+           cast(sum(CASE WHEN p.index_id IN (0, 1) THEN p.row_count ELSE 0 END) as bigint) as row_count, \
+           cast(sum(p.reserved_page_count) * 8 as bigint) as reserved_kb, \
+           cast(sum(CASE WHEN p.index_id IN (0, 1) \
+                    THEN p.in_row_data_page_count + p.lob_used_page_count + p.row_overflow_used_page_count \
+                    ELSE p.lob_used_page_count + p.row_overflow_used_page_count END) * 8 as bigint) as data_kb, \
+           cast(sum(p.used_page_count) * 8 as bigint) as used_kb \
+    FROM sys.dm_db_partition_stats p \
+    JOIN sys.tables t ON t.object_id = p.object_id \
+    JOIN sys.schemas s ON s.schema_id = t.schema_id \
+    WHERE t.is_ms_shipped = 0 \
+    GROUP BY s.name, t.name \
+    ORDER BY sum(p.reserved_page_count) DESC";
+
+    /// TDE state of every database with the expiry of the protecting certificate
+    /// Databases without encryption key are reported as `unencrypted`
+    pub const ENCRYPTION: &str = "SELECT \
+      d.name as database_name, \
+      CASE ISNULL(k.encryption_state, 0) \
+        WHEN 0 THEN 'unencrypted' \
+        WHEN 1 THEN 'unencrypted' \
+        WHEN 2 THEN 'encryption_in_progress' \
+        WHEN 3 THEN 'encrypted' \
+        WHEN 4 THEN 'key_change_in_progress' \
+        WHEN 5 THEN 'decryption_in_progress' \
+        WHEN 6 THEN 'protection_change_in_progress' \
+        ELSE 'unknown' END as encryption_state, \
+      cast(ISNULL(k.percent_complete, 0) as NVARCHAR(20)) as percent_complete, \
+      ISNULL(k.key_algorithm, '') as key_algorithm, \
+      ISNULL(c.name, '') as certificate_name, \
+      ISNULL(CONVERT(NVARCHAR(30), c.expiry_date, 120), '') as certificate_expiry \
+    FROM sys.databases d \
+    LEFT JOIN sys.dm_database_encryption_keys k ON k.database_id = d.database_id \
+    LEFT JOIN master.sys.certificates c ON c.thumbprint = k.encryptor_thumbprint \
+    WHERE d.name <> 'tempdb'";
+
+    /// Load of the visible online schedulers and the `total` row with the worker limit:
+    /// runnable tasks and work queue are growing while workers are exhausted(THREADPOOL)
+    pub const SCHEDULERS: &str = "SELECT \
+      cast(s.scheduler_id as NVARCHAR(20)) as scheduler_id, \
+      cast(s.current_tasks_count as NVARCHAR(20)) as current_tasks_count, \
+      cast(s.runnable_tasks_count as NVARCHAR(20)) as runnable_tasks_count, \
+      cast(s.current_workers_count as NVARCHAR(20)) as current_workers_count, \
+      cast(s.active_workers_count as NVARCHAR(20)) as active_workers_count, \
+      cast(s.work_queue_count as NVARCHAR(20)) as work_queue_count, \
+      cast(s.pending_disk_io_count as NVARCHAR(20)) as pending_disk_io_count, \
+      cast(i.max_workers_count as NVARCHAR(20)) as max_workers_count \
+    FROM sys.dm_os_schedulers s CROSS JOIN sys.dm_os_sys_info i \
+    WHERE s.status = 'VISIBLE ONLINE' \
+    UNION ALL \
+    SELECT 'total', \
+      cast(sum(s.current_tasks_count) as NVARCHAR(20)), \
+      cast(sum(s.runnable_tasks_count) as NVARCHAR(20)), \
+      cast(sum(s.current_workers_count) as NVARCHAR(20)), \
+      cast(sum(s.active_workers_count) as NVARCHAR(20)), \
+      cast(sum(s.work_queue_count) as NVARCHAR(20)), \
+      cast(sum(s.pending_disk_io_count) as NVARCHAR(20)), \
+      cast(max(i.max_workers_count) as NVARCHAR(20)) \
+    FROM sys.dm_os_schedulers s CROSS JOIN sys.dm_os_sys_info i \
+    WHERE s.status = 'VISIBLE ONLINE'";
+
+    /// user sessions grouped by login, host and program
+    /// idle time is counted for sleeping sessions only, orphaned are sleeping with open transaction
+    pub const SESSIONS: &str = "SELECT \
+      s.login_name, \
+      ISNULL(s.host_name, '') as host_name, \
+      ISNULL(s.program_name, '') as program_name, \
+      cast(count(*) as NVARCHAR(20)) as sessions, \
+      cast(sum(case when s.status = 'running' then 1 else 0 end) as NVARCHAR(20)) as running, \
+      cast(sum(case when s.status = 'sleeping' and s.idle < 60 then 1 else 0 end) as NVARCHAR(20)) as idle_below_1m, \
+      cast(sum(case when s.status = 'sleeping' and s.idle >= 60 and s.idle < 600 then 1 else 0 end) as NVARCHAR(20)) as idle_1m_10m, \
+      cast(sum(case when s.status = 'sleeping' and s.idle >= 600 and s.idle < 3600 then 1 else 0 end) as NVARCHAR(20)) as idle_10m_1h, \
+      cast(sum(case when s.status = 'sleeping' and s.idle >= 3600 then 1 else 0 end) as NVARCHAR(20)) as idle_above_1h, \
+      cast(sum(case when s.status = 'sleeping' and s.open_transaction_count > 0 then 1 else 0 end) as NVARCHAR(20)) as orphaned, \
+      cast(ISNULL(max(case when s.status = 'sleeping' then s.idle end), 0) as NVARCHAR(20)) as max_idle_seconds \
+    FROM (SELECT login_name, host_name, program_name, status, open_transaction_count, \
+            DATEDIFF(second, last_request_end_time, GETDATE()) as idle \
+          FROM sys.dm_exec_sessions WHERE is_user_process = 1) s \
+    GROUP BY s.login_name, s.host_name, s.program_name \
+    ORDER BY count(*) DESC";
+
+    /// latest backup of every database per backup type and device type
+    /// device_type: 2 - disk, 5 - tape, 7 - virtual, 9 - url, 102.. - logical devices
+    pub const BACKUP_URL: &str = r"SELECT
+    cast(b.database_name as nvarchar(128)) AS database_name,
+    cast(b.type as nvarchar(8)) AS type,
+    cast(b.device_type as nvarchar(8)) AS device_type,
+    cast(b.physical_device_name as nvarchar(512)) AS physical_device_name,
+    CONVERT(NVARCHAR(19), b.backup_finish_date, 120) AS last_backup_date,
+    cast(DATEDIFF(day, b.backup_finish_date, GETDATE()) as nvarchar(12)) AS age_days
+FROM (
+    SELECT bs.database_name, bs.type, bs.backup_finish_date, mf.device_type, mf.physical_device_name,
+        ROW_NUMBER() OVER (
+            PARTITION BY bs.database_name, bs.type, mf.device_type
+            ORDER BY bs.backup_finish_date DESC) AS rn
+    FROM msdb.dbo.backupset bs
+    INNER JOIN msdb.dbo.backupmediafamily mf ON mf.media_set_id = bs.media_set_id
+    WHERE UPPER(bs.machine_name) = UPPER(CAST(SERVERPROPERTY('MachineName') AS NVARCHAR(128)))
+) b
+WHERE b.rn = 1
+ORDER BY b.database_name, b.type, b.device_type";
+
+    /// last successful DBCC CHECKDB of every online database, from the boot page
+    /// `age_days` is -1 if the check was never done
+    pub const CHECKDB: &str = r"SET NOCOUNT ON;
+DECLARE @dbinfo TABLE (ParentObject NVARCHAR(255), Object NVARCHAR(255), Field NVARCHAR(255), Value NVARCHAR(255));
+DECLARE @result TABLE (database_name NVARCHAR(128), last_known_good NVARCHAR(255));
+DECLARE @name NVARCHAR(128);
+DECLARE @cmd NVARCHAR(400);
+
+DECLARE db_cursor CURSOR LOCAL FAST_FORWARD FOR
+SELECT name FROM sys.databases WHERE state = 0 AND name <> 'tempdb';
+
+OPEN db_cursor;
+FETCH NEXT FROM db_cursor INTO @name;
+WHILE @@FETCH_STATUS = 0
+BEGIN
+    DELETE FROM @dbinfo;
+    SET @cmd = N'DBCC DBINFO(' + QUOTENAME(@name, CHAR(39)) + N') WITH TABLERESULTS, NO_INFOMSGS';
+    BEGIN TRY
+        INSERT INTO @dbinfo EXECUTE (@cmd);
+    END TRY
+    BEGIN CATCH
+    END CATCH
+    INSERT INTO @result
+    SELECT @name, ISNULL((SELECT TOP 1 Value FROM @dbinfo WHERE Field = 'dbi_dbccLastKnownGood'), '');
+    FETCH NEXT FROM db_cursor INTO @name;
+END
+CLOSE db_cursor;
+DEALLOCATE db_cursor;
+
+SELECT database_name, last_known_good,
+  cast(case
+    when last_known_good = '' or last_known_good like '1900-01-01%' then -1
+    else DATEDIFF(day, TRY_CAST(last_known_good as datetime), GETDATE())
+  end as NVARCHAR(20)) as age_days
+FROM @result;";
+
+    /// FILESTREAM containers, FileTables and full-text catalogs of every online database
+    /// `size_kb` is empty for FileTables, full-text catalog size is reported by SQL Server in MB
+    pub const FILESTREAM: &str = r"SET NOCOUNT ON;
+DECLARE @result TABLE (database_name NVARCHAR(128), kind NVARCHAR(32), name NVARCHAR(256), status NVARCHAR(128), size_kb NVARCHAR(20));
+DECLARE @name NVARCHAR(128);
+DECLARE @cmd NVARCHAR(MAX);
+
+DECLARE db_cursor CURSOR LOCAL FAST_FORWARD FOR
+SELECT name FROM sys.databases WHERE state = 0 AND name <> 'tempdb';
+
+OPEN db_cursor;
+FETCH NEXT FROM db_cursor INTO @name;
+WHILE @@FETCH_STATUS = 0
+BEGIN
+    SET @cmd = N'USE ' + QUOTENAME(@name) + N';
+SELECT DB_NAME(), ''filestream_container'', name, state_desc, cast(cast(size as bigint) * 8 as NVARCHAR(20))
+FROM sys.database_files WHERE type = 2
+UNION ALL
+SELECT DB_NAME(), ''filetable'', OBJECT_SCHEMA_NAME(object_id) + ''.'' + OBJECT_NAME(object_id),
+  case when is_enabled = 1 then ''enabled'' else ''disabled'' end, ''''
+FROM sys.filetables
+UNION ALL
+SELECT DB_NAME(), ''fulltext_catalog'', name,
+  case cast(FULLTEXTCATALOGPROPERTY(name, ''PopulateStatus'') as int)
+    when 0 then ''idle''
+    when 1 then ''full_population''
+    when 2 then ''paused''
+    when 3 then ''throttled''
+    when 4 then ''recovering''
+    when 5 then ''shutdown''
+    when 6 then ''incremental_population''
+    when 7 then ''building_index''
+    when 8 then ''disk_full''
+    when 9 then ''change_tracking''
+    else ''unknown''
+  end,
+  cast(cast(ISNULL(FULLTEXTCATALOGPROPERTY(name, ''IndexSize''), 0) as bigint) * 1024 as NVARCHAR(20))
+FROM sys.fulltext_catalogs';
+    BEGIN TRY
+        INSERT INTO @result EXECUTE (@cmd);
+    END TRY
+    BEGIN CATCH
+    END CATCH
+    FETCH NEXT FROM db_cursor INTO @name;
+END
+CLOSE db_cursor;
+DEALLOCATE db_cursor;
+
+SELECT r.database_name, r.kind, r.name, r.status, r.size_kb,
+  ISNULL(o.non_transacted_access_desc, '') as non_transacted_access,
+  ISNULL(o.directory_name, '') as directory_name
+FROM @result r
+LEFT JOIN sys.database_filestream_options o ON o.database_id = DB_ID(r.database_name)
+ORDER BY r.database_name, r.kind, r.name;";
+
+    /// restart is `os` if SQL Server started within 10 minutes after the OS
+    /// `pending_patch` is 1 if the resource database doesn't match the binaries
+    pub const UPTIME: &str = "SELECT \
+      convert(NVARCHAR(30), i.sqlserver_start_time, 120) as sqlserver_start_time, \
+      convert(NVARCHAR(30), DATEADD(second, -cast(i.ms_ticks / 1000 as int), GETDATE()), 120) as os_start_time, \
+      cast(DATEDIFF(second, i.sqlserver_start_time, GETDATE()) as NVARCHAR(20)) as uptime_seconds, \
+      case when DATEDIFF(second, DATEADD(second, -cast(i.ms_ticks / 1000 as int), GETDATE()), i.sqlserver_start_time) < 600 \
+        then 'os' else 'service' end as restart, \
+      cast(SERVERPROPERTY('ProductVersion') as NVARCHAR(128)) as product_version, \
+      cast(ISNULL(SERVERPROPERTY('ProductLevel'), '') as NVARCHAR(128)) as product_level, \
+      cast(ISNULL(SERVERPROPERTY('ProductUpdateLevel'), '') as NVARCHAR(128)) as product_update_level, \
+      cast(ISNULL(SERVERPROPERTY('ProductUpdateReference'), '') as NVARCHAR(128)) as product_update_reference, \
+      case when cast(ISNULL(SERVERPROPERTY('ResourceVersion'), SERVERPROPERTY('ProductVersion')) as NVARCHAR(128)) \
+        <> cast(SERVERPROPERTY('ProductVersion') as NVARCHAR(128)) then '1' else '0' end as pending_patch \
+    FROM sys.dm_os_sys_info i";
+
+    /// weekday is 0 for Sunday independently of DATEFIRST: 1900-01-01 was Monday
+    pub const SERVER_CLOCK: &str = "SELECT \
+      cast((DATEDIFF(day, '19000101', GETDATE()) + 1) % 7 as NVARCHAR(2)) as weekday, \
+      cast(DATEPART(hour, GETDATE()) * 60 + DATEPART(minute, GETDATE()) as NVARCHAR(5)) as minutes";
+
+    /// Version store of tempdb and persistent version store(ADR), one row per item:
+    /// - `total`: tempdb version store, reserved size
+    /// - `database`: tempdb version store used by the database, SQL Server 2019+
+    /// - `snapshot_transaction`: ten longest running snapshot transactions, value in seconds
+    /// - `pvs`: persistent version store of databases with ADR, SQL Server 2019+
+    /// Queries of the newer DMVs are dynamic to be compiled only by the versions having them
+    pub const VERSION_STORE: &str = r"SET NOCOUNT ON;
+DECLARE @result TABLE (kind NVARCHAR(32), database_name NVARCHAR(128), name NVARCHAR(128), value NVARCHAR(20));
+DECLARE @major INT = cast(PARSENAME(cast(SERVERPROPERTY('ProductVersion') as NVARCHAR(128)), 4) as INT);
+
+INSERT INTO @result
+SELECT 'total', 'tempdb', 'version_store_kb',
+  cast(ISNULL(SUM(version_store_reserved_page_count), 0) * 8 as NVARCHAR(20))
+FROM tempdb.sys.dm_db_file_space_usage;
+
+INSERT INTO @result
+SELECT TOP 10 'snapshot_transaction', ISNULL(DB_NAME(s.database_id), ''),
+  cast(t.session_id as NVARCHAR(20)), cast(t.elapsed_time_seconds as NVARCHAR(20))
+FROM sys.dm_tran_active_snapshot_database_transactions t
+LEFT JOIN sys.dm_exec_sessions s ON s.session_id = t.session_id
+ORDER BY t.elapsed_time_seconds DESC;
+
+IF @major >= 15
+BEGIN
+    BEGIN TRY
+        INSERT INTO @result EXECUTE (N'
+SELECT ''database'', DB_NAME(database_id), ''version_store_kb'',
+  cast(reserved_space_kb as NVARCHAR(20))
+FROM sys.dm_tran_version_store_space_usage
+UNION ALL
+SELECT ''pvs'', d.name, ''pvs_size_kb'',
+  cast(cast(s.persistent_version_store_size_kb as bigint) as NVARCHAR(20))
+FROM sys.dm_tran_persistent_version_store_stats s
+JOIN sys.databases d ON d.database_id = s.database_id
+WHERE d.is_accelerated_database_recovery_on = 1');
+    END TRY
+    BEGIN CATCH
+    END CATCH
+END
+
+SELECT kind, database_name, name, value FROM @result;";
+
+    /// Buffer pool extension and memory-optimized tempdb metadata, one row per item:
+    /// - `bpe`, `bpe_size_kb`: state and size of the extension file, SQL Server 2014+
+    /// - `bpe_usage`: allocated pages and used percentage of the extension
+    /// - `tempdb_metadata`: memory-optimized state, configured value and pending restart,
+    ///   SQL Server 2019+
+    pub const MEMORY_FEATURES: &str = r"SET NOCOUNT ON;
+DECLARE @result TABLE (kind NVARCHAR(32), name NVARCHAR(512), value NVARCHAR(128));
+DECLARE @major INT = cast(PARSENAME(cast(SERVERPROPERTY('ProductVersion') as NVARCHAR(128)), 4) as INT);
+
+IF @major >= 12
+BEGIN
+    BEGIN TRY
+        INSERT INTO @result EXECUTE (N'
+SELECT ''bpe'', ISNULL(path, ''''), state_description
+FROM sys.dm_os_buffer_pool_extension_configuration
+UNION ALL
+SELECT ''bpe_size_kb'', ISNULL(path, ''''), cast(ISNULL(current_size_in_kb, 0) as NVARCHAR(20))
+FROM sys.dm_os_buffer_pool_extension_configuration');
+    END TRY
+    BEGIN CATCH
+    END CATCH
+
+    INSERT INTO @result
+    SELECT 'bpe_usage', rtrim(counter_name), cast(cntr_value as NVARCHAR(20))
+    FROM sys.dm_os_performance_counters
+    WHERE object_name LIKE '%Buffer Manager%'
+      AND counter_name IN ('Extension allocated pages', 'Extension in use as percentage');
+END
+
+IF @major >= 15
+BEGIN
+    INSERT INTO @result
+    SELECT 'tempdb_metadata', 'memory_optimized',
+      cast(ISNULL(SERVERPROPERTY('IsTempDBMetadataMemoryOptimized'), 0) as NVARCHAR(10));
+
+    INSERT INTO @result
+    SELECT 'tempdb_metadata', 'configured', cast(value as NVARCHAR(10))
+    FROM sys.configurations WHERE name = 'tempdb metadata memory-optimized'
+    UNION ALL
+    SELECT 'tempdb_metadata', 'pending_restart',
+      case when value <> value_in_use then '1' else '0' end
+    FROM sys.configurations WHERE name = 'tempdb metadata memory-optimized';
+END
+
+SELECT kind, name, value FROM @result;";
+
+    /// Distributed transactions, one row per item:
+    /// - `in_doubt`: orphaned unit of work(session -2) holding locks, database, locks
+    /// - `active`: distributed transactions by DTC state, count and age of the oldest
+    /// - `wait`: DTC related waits since start, waiting tasks and wait time
+    pub const DISTRIBUTED_TRANSACTIONS: &str = "SELECT \
+      'in_doubt' as kind, \
+      cast(l.request_owner_guid as NVARCHAR(64)) as name, \
+      ISNULL(DB_NAME(l.resource_database_id), '') as detail, \
+      cast(count(*) as NVARCHAR(20)) as value \
+    FROM sys.dm_tran_locks l \
+    WHERE l.request_session_id = -2 \
+    GROUP BY l.request_owner_guid, l.resource_database_id \
+    UNION ALL \
+    SELECT 'active', \
+      case t.dtc_state \
+        when 1 then 'active' when 2 then 'prepared' when 3 then 'committed' \
+        when 4 then 'aborted' when 5 then 'recovered' else 'unknown' end, \
+      cast(count(*) as NVARCHAR(20)), \
+      cast(max(DATEDIFF(second, t.transaction_begin_time, GETDATE())) as NVARCHAR(20)) \
+    FROM sys.dm_tran_active_transactions t \
+    WHERE t.transaction_type = 4 \
+    GROUP BY t.dtc_state \
+    UNION ALL \
+    SELECT 'wait', w.wait_type, \
+      cast(w.waiting_tasks_count as NVARCHAR(20)), \
+      cast(w.wait_time_ms as NVARCHAR(20)) \
+    FROM sys.dm_os_wait_stats w \
+    WHERE (w.wait_type LIKE 'DTC%' OR w.wait_type LIKE 'PREEMPTIVE_DTC%' \
+      OR w.wait_type LIKE 'PREEMPTIVE_TRANSIMPORT%' OR w.wait_type = 'MSQL_XACT_MGR_MUTEX') \
+      AND w.waiting_tasks_count > 0";
+
+    /// Server and database collations, one row per item:
+    /// - `server`: default collation of the server
+    /// - `database`: collation of the database, mismatch is 1 if differs from the server one
+    /// Offline databases have no collation and are never reported as mismatched
+    pub const COLLATION: &str = "SELECT \
+      'server' as kind, \
+      '' as name, \
+      cast(SERVERPROPERTY('Collation') as NVARCHAR(128)) as collation, \
+      '0' as mismatch \
+    UNION ALL \
+    SELECT 'database', d.name, ISNULL(d.collation_name, ''), \
+      case when d.collation_name IS NOT NULL \
+        AND d.collation_name <> cast(SERVERPROPERTY('Collation') as NVARCHAR(128)) \
+        then '1' else '0' end \
+    FROM sys.databases d";
+
+    /// SQL Server Agent service state with alerting configuration, one row:
+    /// service status(`not installed` if absent), startup type, failsafe operator,
+    /// enabled operators, enabled alerts and enabled alerts nobody is notified about
+    pub const AGENT_HEALTH: &str = "SELECT \
+      ISNULL((SELECT TOP 1 cast(status_desc as NVARCHAR(60)) FROM sys.dm_server_services \
+        WHERE servicename LIKE N'SQL Server Agent%'), 'not installed') as status, \
+      ISNULL((SELECT TOP 1 cast(startup_type_desc as NVARCHAR(60)) FROM sys.dm_server_services \
+        WHERE servicename LIKE N'SQL Server Agent%'), '') as startup_type, \
+      ISNULL((SELECT TOP 1 cast(value_data as NVARCHAR(128)) FROM sys.dm_server_registry \
+        WHERE registry_key LIKE N'%SQLServerAgent' AND value_name = N'AlertFailSafeOperator'), '') \
+        as failsafe_operator, \
+      (SELECT cast(count(*) as NVARCHAR(20)) FROM msdb.dbo.sysoperators WHERE enabled = 1) \
+        as operators, \
+      (SELECT cast(count(*) as NVARCHAR(20)) FROM msdb.dbo.sysalerts WHERE enabled = 1) as alerts, \
+      (SELECT cast(count(*) as NVARCHAR(20)) FROM msdb.dbo.sysalerts \
+        WHERE enabled = 1 AND has_notification = 0) as alerts_without_notification";
+
+    /// Certificates of the server, one row per certificate:
+    /// - `endpoint`: used by a mirroring/AG or service broker endpoint
+    /// - `certificate`: other user certificate of master
+    /// - `tls`: thumbprint of the certificate configured for TLS, expiry is not visible in SQL
+    /// - `service_master_key`: presence of the key, it never expires
+    /// `days_to_expiry` is negative for expired certificates
+    pub const CERTS: &str = "SELECT \
+      case when e.name IS NULL then 'certificate' else 'endpoint' end as usage, \
+      c.name as name, \
+      ISNULL(e.name, '') as endpoint, \
+      ISNULL(c.subject, '') as subject, \
+      convert(NVARCHAR(19), c.expiry_date, 120) as expiry_date, \
+      cast(DATEDIFF(day, GETUTCDATE(), c.expiry_date) as NVARCHAR(20)) as days_to_expiry \
+    FROM master.sys.certificates c \
+    LEFT JOIN (SELECT name, certificate_id FROM sys.database_mirroring_endpoints \
+      UNION ALL SELECT name, certificate_id FROM sys.service_broker_endpoints) e \
+      ON e.certificate_id = c.certificate_id \
+    WHERE c.name NOT LIKE '##%' \
+    UNION ALL \
+    SELECT 'tls', '', '', cast(value_data as NVARCHAR(256)), '', '' \
+    FROM sys.dm_server_registry \
+    WHERE value_name = N'Certificate' AND cast(value_data as NVARCHAR(256)) <> '' \
+    UNION ALL \
+    SELECT 'service_master_key', name, '', ISNULL(algorithm_desc, ''), '', '' \
+    FROM master.sys.symmetric_keys WHERE name = '##MS_ServiceMasterKey##'";
+
+    /// Query Store health of every database having it enabled, one row per database:
+    /// actual and desired state, reason of read-only state(0 if none), storage in MB,
+    /// size based cleanup mode, stale query threshold, forced plans and forced plans failed
+    /// at least once. Requires SQL Server 2016 or newer
+    pub const QUERY_STORE: &str = r"SET NOCOUNT ON;
+DECLARE @result TABLE (database_name NVARCHAR(128), actual_state NVARCHAR(60), desired_state NVARCHAR(60),
+  readonly_reason NVARCHAR(20), current_storage_mb NVARCHAR(20), max_storage_mb NVARCHAR(20),
+  cleanup_mode NVARCHAR(60), stale_query_days NVARCHAR(20), forced_plans NVARCHAR(20),
+  forced_plan_failures NVARCHAR(20));
+DECLARE @name NVARCHAR(128);
+DECLARE @cmd NVARCHAR(MAX);
+
+DECLARE db_cursor CURSOR LOCAL FAST_FORWARD FOR
+SELECT name FROM sys.databases WHERE state = 0 AND is_query_store_on = 1;
+
+OPEN db_cursor;
+FETCH NEXT FROM db_cursor INTO @name;
+WHILE @@FETCH_STATUS = 0
+BEGIN
+    SET @cmd = N'USE ' + QUOTENAME(@name) + N';
+    SELECT DB_NAME(), o.actual_state_desc, o.desired_state_desc,
+      cast(o.readonly_reason as NVARCHAR(20)),
+      cast(o.current_storage_size_mb as NVARCHAR(20)), cast(o.max_storage_size_mb as NVARCHAR(20)),
+      o.size_based_cleanup_mode_desc, cast(o.stale_query_threshold_days as NVARCHAR(20)),
+      (SELECT cast(count(*) as NVARCHAR(20)) FROM sys.query_store_plan WHERE is_forced_plan = 1),
+      (SELECT cast(count(*) as NVARCHAR(20)) FROM sys.query_store_plan
+        WHERE is_forced_plan = 1 AND force_failure_count > 0)
+    FROM sys.database_query_store_options o';
+    BEGIN TRY
+        INSERT INTO @result EXECUTE (@cmd);
+    END TRY
+    BEGIN CATCH
+    END CATCH
+    FETCH NEXT FROM db_cursor INTO @name;
+END
+CLOSE db_cursor;
+DEALLOCATE db_cursor;
+
+SELECT database_name, actual_state, desired_state, readonly_reason, current_storage_mb,
+  max_storage_mb, cleanup_mode, stale_query_days, forced_plans, forced_plan_failures
+FROM @result";
+
+    /// SQL logins, one row per login: disabled, expiration checked, policy checked,
+    /// locked out, expired, days until password expiry(empty if expiration is not checked)
+    /// and the time of the last password change
+    pub const LOGINS: &str = "SELECT \
+      l.name, \
+      cast(l.is_disabled as NVARCHAR(1)) as is_disabled, \
+      cast(l.is_expiration_checked as NVARCHAR(1)) as is_expiration_checked, \
+      cast(l.is_policy_checked as NVARCHAR(1)) as is_policy_checked, \
+      ISNULL(cast(LOGINPROPERTY(l.name, 'IsLocked') as NVARCHAR(10)), '') as is_locked, \
+      ISNULL(cast(LOGINPROPERTY(l.name, 'IsExpired') as NVARCHAR(10)), '') as is_expired, \
+      case when l.is_expiration_checked = 1 \
+        then ISNULL(cast(LOGINPROPERTY(l.name, 'DaysUntilExpiration') as NVARCHAR(20)), '') \
+        else '' end as days_until_expiration, \
+      ISNULL(convert(NVARCHAR(19), cast(LOGINPROPERTY(l.name, 'PasswordLastSetTime') as datetime), 120), '') \
+        as password_last_set \
+    FROM sys.sql_logins l \
+    WHERE l.name NOT LIKE '##%'";
+
+    /// listeners, local mirroring endpoint and seeding mode of every replica of every AG
+    pub const AG_CONFIG: &str = "SELECT \
+      ag.name as ag_name, \
+      ar.replica_server_name, \
+      ISNULL(ar.endpoint_url, '') as endpoint_url, \
+      ar.availability_mode_desc, \
+      ar.failover_mode_desc, \
+      ISNULL(ar.seeding_mode_desc, '') as seeding_mode, \
+      ISNULL(l.dns_name, '') as listener, \
+      ISNULL(cast(l.port as NVARCHAR(10)), '') as listener_port, \
+      ISNULL(STUFF((SELECT ',' + ip.ip_address \
+        FROM sys.availability_group_listener_ip_addresses ip \
+        WHERE ip.listener_id = l.listener_id \
+        FOR XML PATH('')), 1, 1, ''), '') as listener_ips, \
+      ISNULL(cast(e.is_encryption_enabled as NVARCHAR(1)), '') as endpoint_encryption, \
+      ISNULL(e.encryption_algorithm_desc, '') as endpoint_algorithm \
+    FROM sys.availability_groups ag \
+    JOIN sys.availability_replicas ar ON ar.group_id = ag.group_id \
+    LEFT JOIN sys.availability_group_listeners l ON l.group_id = ag.group_id \
+    OUTER APPLY (SELECT TOP 1 is_encryption_enabled, encryption_algorithm_desc \
+      FROM sys.database_mirroring_endpoints) e \
+    ORDER BY ag.name, ar.replica_server_name";
+
+    /// `{POWER_PLAN}` is to be replaced with the escaped power plan of the host
+    /// memory model is LOCK_PAGES when SQL Server has the lock pages in memory privilege
+    pub const HOST_SETTINGS: &str = "SELECT \
+      N'{POWER_PLAN}' as power_plan, \
+      (SELECT sql_memory_model_desc FROM sys.dm_os_sys_info) as memory_model, \
+      ISNULL((SELECT TOP 1 instant_file_initialization_enabled FROM sys.dm_server_services \
+        WHERE servicename LIKE 'SQL Server (%'), '') as instant_file_initialization";
+
+    /// every database file with the volume it resides on, databases sharing
+    /// a volume have the same mount point
+    pub const VOLUMES: &str = "SELECT \
+      vs.volume_mount_point, \
+      ISNULL(vs.logical_volume_name, '') as logical_volume_name, \
+      cast(vs.total_bytes as bigint) as volume_total_bytes, \
+      cast(vs.available_bytes as bigint) as volume_available_bytes, \
+      DB_NAME(mf.database_id) as database_name, \
+      mf.name as file_name, \
+      mf.type_desc as file_type, \
+      cast(mf.size as bigint) * 8192 as file_size_bytes, \
+      case when mf.max_size = -1 then -1 else cast(mf.max_size as bigint) * 8192 end as file_max_size_bytes, \
+      case when mf.is_percent_growth = 1 then cast(mf.growth as NVARCHAR(20)) + '%' \
+        else cast(cast(mf.growth as bigint) * 8192 as NVARCHAR(20)) end as file_growth \
+    FROM sys.master_files mf \
+    CROSS APPLY sys.dm_os_volume_stats(mf.database_id, mf.file_id) vs \
+    WHERE DATABASEPROPERTYEX(DB_NAME(mf.database_id), 'Status') = 'ONLINE' \
+    ORDER BY vs.volume_mount_point, database_name, mf.file_id";
+
+    /// significant events of the default trace for the last 24 hours, one row per event
+    /// - object created/deleted/altered outside of tempdb
+    /// - security audit: logins, roles, permissions
+    /// - sort and hash warnings, file auto growth and DBCC
+    /// empty if the default trace is disabled
+    pub const DEFAULT_TRACE: &str = "DECLARE @path NVARCHAR(260); \
+    SELECT @path = REVERSE(SUBSTRING(REVERSE(path), CHARINDEX(N'\\', REVERSE(path)), 260)) + N'log.trc' \
+    FROM sys.traces WHERE is_default = 1; \
+    IF @path IS NOT NULL \
+    SELECT \
+      te.name as event_name, \
+      tc.name as category, \
+      cast(count(*) as NVARCHAR(20)) as events, \
+      convert(NVARCHAR(19), max(t.StartTime), 120) as last_time \
+    FROM sys.fn_trace_gettable(@path, DEFAULT) t \
+    JOIN sys.trace_events te ON te.trace_event_id = t.EventClass \
+    JOIN sys.trace_categories tc ON tc.category_id = te.category_id \
+    WHERE t.StartTime > DATEADD(hour, -24, GETDATE()) \
+      AND (t.EventClass IN (46, 47, 164) AND ISNULL(t.DatabaseID, 0) <> 2 \
+        OR t.EventClass IN (55, 69, 92, 93, 116) \
+        OR tc.name = 'Security Audit') \
+    GROUP BY te.name, tc.name \
+    ORDER BY tc.name, te.name";
+
+    /// Files in the default data and log directories which are not used by any database
+    /// Size is unknown(`-`) if only `xp_dirtree` is available, i.e. before SQL Server 2017
+    pub const ORPHANED_FILES: &str = "SET NOCOUNT ON; \
+    DECLARE @dirs TABLE (dir NVARCHAR(512)); \
+    DECLARE @tree TABLE (name NVARCHAR(512), depth INT, is_file BIT); \
+    DECLARE @files TABLE (path NVARCHAR(512), size_bytes BIGINT NULL); \
+    DECLARE @dir NVARCHAR(512); \
+    INSERT INTO @dirs \
+    SELECT DISTINCT dir FROM ( \
+      SELECT cast(SERVERPROPERTY('InstanceDefaultDataPath') as NVARCHAR(512)) as dir \
+      UNION SELECT cast(SERVERPROPERTY('InstanceDefaultLogPath') as NVARCHAR(512)) \
+    ) d WHERE dir IS NOT NULL; \
+    SELECT TOP 1 @dir = dir FROM @dirs; \
+    WHILE @dir IS NOT NULL \
+    BEGIN \
+      IF RIGHT(@dir, 1) NOT IN (N'\\', N'/') SET @dir = @dir + N'\\'; \
+      IF OBJECT_ID('sys.dm_os_enumerate_filesystem') IS NOT NULL \
+        INSERT INTO @files EXEC sp_executesql \
+          N'SELECT full_filesystem_path, size_in_bytes FROM sys.dm_os_enumerate_filesystem(@dir, N''*'') WHERE is_directory = 0', \
+          N'@dir NVARCHAR(512)', @dir; \
+      ELSE \
+      BEGIN \
+        DELETE FROM @tree; \
+        INSERT INTO @tree EXEC master.sys.xp_dirtree @dir, 1, 1; \
+        INSERT INTO @files SELECT @dir + name, NULL FROM @tree WHERE is_file = 1; \
+      END; \
+      DELETE FROM @dirs WHERE dir = @dir OR dir + N'\\' = @dir; \
+      SET @dir = NULL; \
+      SELECT TOP 1 @dir = dir FROM @dirs; \
+    END; \
+    SELECT \
+      cast(f.path as NVARCHAR(512)) as path, \
+      CASE WHEN f.size_bytes IS NULL THEN '-' \
+        ELSE cast(f.size_bytes / 1048576 as NVARCHAR(20)) END as size_mb \
+    FROM @files f \
+    WHERE (f.path LIKE N'%.mdf' OR f.path LIKE N'%.ndf' OR f.path LIKE N'%.ldf') \
+      AND NOT EXISTS (SELECT 1 FROM sys.master_files m WHERE LOWER(m.physical_name) = LOWER(f.path)) \
+    ORDER BY f.path";
+
+    /// Last outcome of backup, integrity check and index maintenance per database
+    /// run by Ola Hallengren's solution(`master.dbo.CommandLog`) or by maintenance plans
+    /// Database of a maintenance plan task is the first bracketed name of its command
+    pub const MAINTENANCE: &str = "SET NOCOUNT ON; \
+    DECLARE @result TABLE (framework NVARCHAR(30), database_name NVARCHAR(256), kind NVARCHAR(20), \
+      start_time DATETIME NULL, end_time DATETIME NULL, succeeded INT NULL, message NVARCHAR(1024)); \
+    IF OBJECT_ID('master.dbo.CommandLog') IS NOT NULL \
+      INSERT INTO @result EXEC sp_executesql N' \
+        SELECT ''ola'', DatabaseName, kind, StartTime, EndTime, succeeded, ErrorMessage FROM ( \
+          SELECT cast(c.DatabaseName as NVARCHAR(256)) as DatabaseName, k.kind, c.StartTime, c.EndTime, \
+            CASE WHEN c.EndTime IS NULL THEN NULL WHEN c.ErrorNumber = 0 THEN 1 ELSE 0 END as succeeded, \
+            cast(isnull(c.ErrorMessage, '''') as NVARCHAR(1024)) as ErrorMessage, \
+            ROW_NUMBER() OVER (PARTITION BY c.DatabaseName, k.kind ORDER BY c.StartTime DESC) as rn \
+          FROM master.dbo.CommandLog c \
+          CROSS APPLY (SELECT CASE \
+            WHEN c.CommandType LIKE ''BACKUP%'' THEN ''backup'' \
+            WHEN c.CommandType LIKE ''DBCC_CHECK%'' THEN ''integrity'' \
+            ELSE ''index'' END as kind) k \
+          WHERE c.DatabaseName IS NOT NULL \
+            AND (c.CommandType LIKE ''BACKUP%'' OR c.CommandType LIKE ''DBCC_CHECK%'' \
+              OR c.CommandType IN (''ALTER_INDEX'', ''UPDATE_STATISTICS'')) \
+        ) last WHERE rn = 1'; \
+    INSERT INTO @result \
+    SELECT 'maintenance_plan', database_name, kind, start_time, end_time, succeeded, error_message FROM ( \
+      SELECT d.database_name, k.kind, ld.start_time, ld.end_time, \
+        cast(ld.succeeded as INT) as succeeded, \
+        cast(isnull(ld.error_message, '') as NVARCHAR(1024)) as error_message, \
+        ROW_NUMBER() OVER (PARTITION BY d.database_name, k.kind ORDER BY ld.start_time DESC) as rn \
+      FROM msdb.dbo.sysmaintplan_logdetail ld \
+      CROSS APPLY (SELECT cast(ld.command as NVARCHAR(MAX)) as command) c \
+      CROSS APPLY (SELECT CASE \
+        WHEN c.command LIKE '%BACKUP %' THEN 'backup' \
+        WHEN c.command LIKE '%DBCC CHECK%' THEN 'integrity' \
+        WHEN c.command LIKE '%ALTER INDEX%' OR c.command LIKE '%UPDATE STATISTICS%' THEN 'index' \
+        END as kind) k \
+      CROSS APPLY (SELECT CASE WHEN CHARINDEX('[', c.command) > 0 \
+          AND CHARINDEX(']', c.command) > CHARINDEX('[', c.command) \
+        THEN cast(SUBSTRING(c.command, CHARINDEX('[', c.command) + 1, \
+          CHARINDEX(']', c.command) - CHARINDEX('[', c.command) - 1) as NVARCHAR(256)) \
+        ELSE '-' END as database_name) d \
+      WHERE k.kind IS NOT NULL \
+    ) last WHERE rn = 1; \
+    SELECT \
+      framework, \
+      database_name, \
+      kind, \
+      isnull(convert(NVARCHAR(19), start_time, 120), '-') as last_start, \
+      isnull(convert(NVARCHAR(19), end_time, 120), '-') as last_end, \
+      CASE WHEN succeeded IS NULL THEN 'running' WHEN succeeded = 1 THEN 'success' ELSE 'failed' END as status, \
+      replace(replace(replace(message, char(9), ' '), char(13), ' '), char(10), ' ') as message \
+    FROM @result \
+    ORDER BY framework, database_name, kind";
+
+    /// Server audits and extended events sessions with their state and target
+    /// An enabled audit or a configured session not running is stopped, built-in sessions are skipped
+    /// `target_size` is the approximate size of the current audit file in bytes, empty for sessions
+    pub const AUDITS: &str = "SELECT 'audit' as kind, \
+           cast(a.name as NVARCHAR(128)) as name, \
+           cast(a.is_state_enabled as NVARCHAR(1)) as enabled, \
+           cast(ISNULL(s.status_desc, 'STOPPED') as NVARCHAR(60)) as state, \
+           cast(ISNULL(s.audit_file_path, a.type_desc) as NVARCHAR(260)) as target, \
+           cast(s.audit_file_size as NVARCHAR(20)) as target_size \
+    FROM sys.server_audits a \
+    LEFT JOIN sys.dm_server_audit_status s ON s.audit_id = a.audit_id \
+    UNION ALL \
+    SELECT 'xevent_session' as kind, \
+           cast(es.name as NVARCHAR(128)) as name, \
+           cast(es.startup_state as NVARCHAR(1)) as enabled, \
+           CASE WHEN xs.address IS NULL THEN 'STOPPED' ELSE 'STARTED' END as state, \
+           cast(ISNULL(STUFF((SELECT ',' + t.name FROM sys.server_event_session_targets t \
+                  WHERE t.event_session_id = es.event_session_id FOR XML PATH('')), 1, 1, ''), '') \
+                as NVARCHAR(260)) as target, \
+           '' as target_size \
+    FROM sys.server_event_sessions es \
+    LEFT JOIN sys.dm_xe_sessions xs ON xs.name = es.name \
+    WHERE es.name NOT IN ('AlwaysOn_health', 'telemetry_xevents', 'sp_server_diagnostics session', \
+                          'hkenginexesession', 'system_health')";
+
+    /// Resource utilization of Azure SQL: the latest row of `sys.dm_db_resource_stats`(database)
+    /// or `sys.server_resource_stats`(managed instance), empty on other editions
+    /// Data IO and log write percents are not provided for managed instances
+    pub const AZURE_RESOURCES: &str = "DECLARE @stats TABLE (scope NVARCHAR(20), end_time NVARCHAR(30), \
+        avg_cpu_percent NVARCHAR(20), avg_data_io_percent NVARCHAR(20), avg_log_write_percent NVARCHAR(20), \
+        storage_used_mb NVARCHAR(20), storage_limit_mb NVARCHAR(20), dtu_limit NVARCHAR(20), vcores NVARCHAR(20)); \
+    IF CAST(SERVERPROPERTY('EngineEdition') AS int) = 5 \
+    INSERT INTO @stats EXEC('SELECT TOP 1 ''database'', CONVERT(NVARCHAR(30), end_time, 120), \
+           cast(avg_cpu_percent as NVARCHAR(20)), \
+           cast(avg_data_io_percent as NVARCHAR(20)), \
+           cast(avg_log_write_percent as NVARCHAR(20)), \
+           (SELECT cast(SUM(CAST(FILEPROPERTY(name, ''SpaceUsed'') AS bigint)) * 8 / 1024 as NVARCHAR(20)) \
+            FROM sys.database_files WHERE type = 0), \
+           cast(CAST(DATABASEPROPERTYEX(DB_NAME(), ''MaxSizeInBytes'') AS bigint) / 1048576 as NVARCHAR(20)), \
+           ISNULL(cast(dtu_limit as NVARCHAR(20)), ''''), \
+           ISNULL(cast(cpu_limit as NVARCHAR(20)), '''') \
+        FROM sys.dm_db_resource_stats ORDER BY end_time DESC') \
+    ELSE IF CAST(SERVERPROPERTY('EngineEdition') AS int) = 8 \
+    INSERT INTO @stats EXEC('SELECT TOP 1 ''instance'', CONVERT(NVARCHAR(30), end_time, 120), \
+           cast(avg_cpu_percent as NVARCHAR(20)), '''', '''', \
+           cast(storage_space_used_mb as NVARCHAR(20)), \
+           cast(reserved_storage_mb as NVARCHAR(20)), '''', \
+           cast(virtual_core_count as NVARCHAR(20)) \
+        FROM master.sys.server_resource_stats ORDER BY end_time DESC'); \
+    SELECT scope, end_time, avg_cpu_percent, avg_data_io_percent, avg_log_write_percent, \
+           storage_used_mb, storage_limit_mb, dtu_limit, vcores FROM @stats";
+
+    /// `{AG_NAME}` is to be replaced with the escaped name of the availability group
+    pub const AG_PRIMARY_REPLICA: &str = "SELECT \
+      cast(s.primary_replica as NVARCHAR(256)) as primary_replica \
+    FROM sys.dm_hadr_availability_group_states s \
+    JOIN sys.availability_groups ag ON ag.group_id = s.group_id \
+    WHERE ag.name = N'{AG_NAME}'";
+
     /// used only for testing: it is difficult to get blocked tasks in reality
     pub const WAITING_TASKS: &str = "SELECT cast(session_id as varchar) as session_id, \
             cast(wait_duration_ms as bigint) as wait_duration_ms, \
@@ -148,6 +1038,21 @@ SELECT InstanceNames, InstanceIds, EditionNames, VersionNames, ClusterNames,Port
             cast(blocking_session_id as varchar) as blocking_session_id \
     FROM sys.dm_os_waiting_tasks";
 
+    /// blocked sessions with the statement of the blocked and of the blocking session
+    pub const BLOCKED_SESSIONS_WITH_SQL_TEXT: &str = "SELECT \
+            cast(w.session_id as varchar) as session_id, \
+            cast(w.wait_duration_ms as bigint) as wait_duration_ms, \
+            w.wait_type, \
+            cast(w.blocking_session_id as varchar) as blocking_session_id, \
+            cast(ISNULL(blocked.text, '') as NVARCHAR(4000)) as blocked_sql_text, \
+            cast(ISNULL(blocker.text, '') as NVARCHAR(4000)) as blocking_sql_text \
+    FROM sys.dm_os_waiting_tasks w \
+    LEFT JOIN sys.dm_exec_requests r ON r.session_id = w.session_id \
+    OUTER APPLY sys.dm_exec_sql_text(r.sql_handle) blocked \
+    LEFT JOIN sys.dm_exec_connections c ON c.session_id = w.blocking_session_id \
+    OUTER APPLY sys.dm_exec_sql_text(c.most_recent_sql_handle) blocker \
+    WHERE w.blocking_session_id <> 0";
+
     pub const DATABASE_NAMES: &str = "SELECT name FROM sys.databases";
 
     /// Executes `sp_spaceused` for each database parsing output as resuult set
@@ -388,8 +1293,45 @@ lazy_static::lazy_static! {
         (Id::BadQuery, query::BAD_QUERY),
         (Id::WaitingTasks, query::WAITING_TASKS), // used only in tests now
         (Id::BlockedSessions, BLOCKING_SESSIONS.as_str()),
+        (Id::BlockedSessionsWithSqlText, query::BLOCKED_SESSIONS_WITH_SQL_TEXT),
         (Id::Counters, COUNTERS.as_str()),
         (Id::Clusters, CLUSTERS.as_str()),
+        (Id::PerfKeyMetrics, query::PERF_KEY_METRICS),
+        (Id::WaitsDelta, query::WAITS_DELTA),
+        (Id::DbConnections, query::DB_CONNECTIONS),
+        (Id::TempdbContention, query::TEMPDB_CONTENTION),
+        (Id::DbOptionDrift, query::DB_OPTION_DRIFT),
+        (Id::Labels, query::LABELS),
+        (Id::AzureArc, query::AZURE_ARC),
+        (Id::TraceFlags, query::TRACE_FLAGS),
+        (Id::Encryption, query::ENCRYPTION),
+        (Id::Schedulers, query::SCHEDULERS),
+        (Id::Sessions, query::SESSIONS),
+        (Id::CheckDb, query::CHECKDB),
+        (Id::BackupUrl, query::BACKUP_URL),
+        (Id::Filestream, query::FILESTREAM),
+        (Id::Uptime, query::UPTIME),
+        (Id::VersionStore, query::VERSION_STORE),
+        (Id::MemoryFeatures, query::MEMORY_FEATURES),
+        (Id::DistributedTransactions, query::DISTRIBUTED_TRANSACTIONS),
+        (Id::Collation, query::COLLATION),
+        (Id::AgentHealth, query::AGENT_HEALTH),
+        (Id::Certs, query::CERTS),
+        (Id::QueryStore, query::QUERY_STORE),
+        (Id::Logins, query::LOGINS),
+        (Id::AgConfig, query::AG_CONFIG),
+        (Id::HostSettings, query::HOST_SETTINGS),
+        (Id::Volumes, query::VOLUMES),
+        (Id::DefaultTrace, query::DEFAULT_TRACE),
+        (Id::OrphanedFiles, query::ORPHANED_FILES),
+        (Id::Maintenance, query::MAINTENANCE),
+        (Id::Audits, query::AUDITS),
+        (Id::AzureResources, query::AZURE_RESOURCES),
+        (Id::ServerClock, query::SERVER_CLOCK),
+        (Id::Permissions, query::PERMISSIONS),
+        (Id::Filegroups, query::FILEGROUPS),
+        (Id::Constraints, query::CONSTRAINTS),
+        (Id::TopTables, query::TOP_TABLES),
     ]);
 }
 
@@ -402,3 +1344,54 @@ pub fn find_known_query<T: Borrow<Id>>(query_id: T) -> Result<&'static str> {
             query_id.borrow()
         ))
 }
+
+const WRITE_STATEMENTS: [&str; 11] = [
+    "INSERT", "UPDATE", "DELETE", "MERGE", "TRUNCATE", "CREATE", "ALTER", "DROP", "GRANT", "EXEC",
+    "EXECUTE",
+];
+
+/// Returns first data or schema modifying, granting or executing keyword of the script
+/// Comments, string literals and quoted identifiers are ignored
+pub fn find_write_statement(sql: &str) -> Option<&'static str> {
+    let mut code = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                chars.by_ref().find(|&c| c == '\n');
+                code.push(' ');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                chars.by_ref().find(|&c| {
+                    let end = prev == '*' && c == '/';
+                    prev = c;
+                    end
+                });
+                code.push(' ');
+            }
+            '\'' => {
+                while let Some(c) = chars.next() {
+                    if c == '\'' && chars.next_if_eq(&'\'').is_none() {
+                        break;
+                    }
+                }
+                code.push(' ');
+            }
+            '[' | '"' => {
+                let closing = if c == '[' { ']' } else { '"' };
+                chars.by_ref().find(|&c| c == closing);
+                code.push(' ');
+            }
+            c => code.push(c),
+        }
+    }
+    code.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '@' || c == '#'))
+        .find_map(|word| {
+            WRITE_STATEMENTS
+                .iter()
+                .find(|s| s.eq_ignore_ascii_case(word))
+                .copied()
+        })
+}
@@ -130,6 +130,381 @@ mod tests {
     }
 }
 
+/// SQL Server performance counters of Windows, used if the DMV is not accessible
+pub mod perf_counters {
+    use super::Block;
+    use crate::types::InstanceName;
+    use anyhow::Result;
+    use std::time::Duration;
+
+    /// `{PREFIX}` is to be replaced with the object prefix of the instance, see [`object_prefix`]
+    #[cfg(windows)]
+    const READ_COUNTERS: &str = "$ErrorActionPreference = 'SilentlyContinue'; \
+        [System.Diagnostics.PerformanceCounterCategory]::GetCategories() | \
+        Where-Object { $_.CategoryName -like '{PREFIX}:*' -and $_.CategoryName -notlike '*Deprecated*' } | \
+        ForEach-Object { $category = $_; $instances = $category.GetInstanceNames(); \
+          if ($instances.Count -eq 0) { $instances = @('') }; \
+          foreach ($instance in $instances) { \
+            $counters = if ($instance) { $category.GetCounters($instance) } else { $category.GetCounters() }; \
+            foreach ($counter in $counters) { \
+              \"{0}`t{1}`t{2}`t{3}\" -f $counter.CounterName, $category.CategoryName, $instance, $counter.RawValue } } }";
+
+    /// `SQLServer` for the default instance, `MSSQL$<name>` for named ones
+    pub fn object_prefix(instance: &InstanceName) -> String {
+        if instance.is_same(&InstanceName::from("MSSQLSERVER")) {
+            "SQLServer".to_string()
+        } else {
+            format!("MSSQL${}", instance.to_string().trim().to_uppercase())
+        }
+    }
+
+    /// Counters as a block with the columns of `sys.dm_os_performance_counters`
+    /// PowerShell is killed if it doesn't finish within `timeout`
+    #[cfg(windows)]
+    pub async fn read(instance: &InstanceName, timeout: Duration) -> Result<Block> {
+        let script = READ_COUNTERS.replace("{PREFIX}", &object_prefix(instance));
+        let output = tokio::process::Command::new("powershell")
+            .args(["-NoProfile", "-Command", &script])
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(timeout, output)
+            .await
+            .map_err(|e| anyhow::anyhow!("Timeout: {e} when reading performance counters"))??;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to read performance counters: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        to_block(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    #[cfg(unix)]
+    pub async fn read(_instance: &InstanceName, _timeout: Duration) -> Result<Block> {
+        anyhow::bail!("Performance counters of Windows are not available")
+    }
+
+    /// every line is `counter<TAB>object<TAB>instance<TAB>value`
+    pub fn to_block(output: &str) -> Result<Block> {
+        let rows = output
+            .lines()
+            .filter_map(|l| {
+                let values = l.trim_end_matches('\r').split('\t').collect::<Vec<_>>();
+                (values.len() == 4).then(|| values.iter().map(|v| v.to_string()).collect())
+            })
+            .collect::<Vec<Vec<String>>>();
+        if rows.is_empty() {
+            anyhow::bail!("No performance counters found");
+        }
+        Ok(Block {
+            headline: ["counter_name", "object_name", "instance_name", "cntr_value"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            rows,
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{object_prefix, to_block};
+        use crate::types::InstanceName;
+
+        #[test]
+        fn test_object_prefix() {
+            assert_eq!(
+                object_prefix(&"mssqlserver".to_string().into()),
+                "SQLServer"
+            );
+            assert_eq!(
+                object_prefix(&InstanceName::from("sqlExpress")),
+                "MSSQL$SQLEXPRESS"
+            );
+        }
+
+        #[test]
+        fn test_to_block() {
+            let block = to_block(
+                "Page life expectancy\tMSSQL$SQLEXPRESS:Buffer Manager\t\t300\r\n\
+                 bad line\n\
+                 Transactions/sec\tMSSQL$SQLEXPRESS:Databases\tmaster\t1234\n",
+            )
+            .unwrap();
+            assert_eq!(block.rows.len(), 2);
+            assert_eq!(
+                block.rows[0],
+                vec![
+                    "Page life expectancy",
+                    "MSSQL$SQLEXPRESS:Buffer Manager",
+                    "",
+                    "300"
+                ]
+            );
+            assert_eq!(
+                block.get_value_by_name(&block.rows[1], "instance_name"),
+                "master"
+            );
+            assert!(to_block("").is_err());
+        }
+    }
+}
+
+pub mod cluster {
+    use anyhow::Result;
+    use std::collections::HashMap;
+
+    #[cfg(windows)]
+    const READ_OWNERS: &str = "$ErrorActionPreference = 'SilentlyContinue'; \
+        Get-ClusterResource | Where-Object { $_.ResourceType.Name -eq 'SQL Server' } | \
+        ForEach-Object { \
+          $name = (Get-ClusterParameter -InputObject $_ -Name InstanceName).Value; \
+          \"{0}`t{1}`t{2}\" -f $name, $_.OwnerNode.Name, $_.State }";
+
+    /// Owner node of the SQL Server resource of every clustered instance on the node
+    #[cfg(windows)]
+    pub fn read_owners() -> Result<HashMap<String, String>> {
+        let output = std::process::Command::new("powershell")
+            .args(["-NoProfile", "-Command", READ_OWNERS])
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to read cluster resources: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(to_owners(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    #[cfg(unix)]
+    pub fn read_owners() -> Result<HashMap<String, String>> {
+        anyhow::bail!("Failover cluster resources are not available")
+    }
+
+    /// every line is `instance<TAB>owner node<TAB>state`, instance is upper case in result
+    pub fn to_owners(output: &str) -> HashMap<String, String> {
+        output
+            .lines()
+            .filter_map(|l| {
+                let values = l.trim_end_matches('\r').split('\t').collect::<Vec<_>>();
+                match values.as_slice() {
+                    [instance, owner, _state] if !instance.is_empty() && !owner.is_empty() => {
+                        Some((instance.trim().to_uppercase(), owner.trim().to_string()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::to_owners;
+
+        #[test]
+        fn test_to_owners() {
+            let owners = to_owners(
+                "MSSQLSERVER\tNODE1\tOnline\r\n\
+                 bad line\n\
+                 \tNODE2\tOnline\n\
+                 sqlExpress\tNODE2\tOffline\n",
+            );
+            assert_eq!(owners.len(), 2);
+            assert_eq!(owners["MSSQLSERVER"], "NODE1");
+            assert_eq!(owners["SQLEXPRESS"], "NODE2");
+        }
+    }
+}
+
+pub mod host {
+    use anyhow::Result;
+
+    /// Name of the active power plan, e.g. `High performance`
+    #[cfg(windows)]
+    pub fn read_power_plan() -> Result<String> {
+        let output = std::process::Command::new("powercfg")
+            .arg("/getactivescheme")
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to read power plan: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        to_power_plan(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| anyhow::anyhow!("Unexpected output of powercfg"))
+    }
+
+    #[cfg(unix)]
+    pub fn read_power_plan() -> Result<String> {
+        anyhow::bail!("Power plan is available only on Windows")
+    }
+
+    /// the name is in parentheses: `Power Scheme GUID: 381b4222-...  (Balanced)`
+    pub fn to_power_plan(output: &str) -> Option<String> {
+        let line = output.lines().find(|l| l.contains(':'))?.trim();
+        let name = line.strip_suffix(')')?.rsplit_once('(')?.1.trim();
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::to_power_plan;
+
+        #[test]
+        fn test_to_power_plan() {
+            assert_eq!(
+                to_power_plan(
+                    "\r\nPower Scheme GUID: 8c5e7fda-e8bf-4a96-9a85-a6e23a8c635c  (High performance)\r\n"
+                )
+                .unwrap(),
+                "High performance"
+            );
+            assert!(to_power_plan("Power Scheme GUID: 8c5e7fda ()").is_none());
+            assert!(to_power_plan("").is_none());
+        }
+    }
+}
+
+pub mod services {
+    use anyhow::Result;
+
+    #[cfg(windows)]
+    const READ_SERVICES: &str = "$ErrorActionPreference = 'SilentlyContinue'; \
+        $sql = 'HKLM:\\SOFTWARE\\Microsoft\\Microsoft SQL Server'; \
+        function Get-SetupVersion($kind, $instance) { \
+          $id = (Get-ItemProperty \"$sql\\Instance Names\\$kind\").$instance; \
+          if ($id) { (Get-ItemProperty \"$sql\\$id\\Setup\").Version } }; \
+        Get-CimInstance Win32_Service | ForEach-Object { \
+          $n = $_.Name; $i = ($n -split '\\$', 2)[1]; if (-not $i) { $i = 'MSSQLSERVER' }; \
+          if ($n -match '^MsDtsServer(\\d+)$') { $v = (Get-ItemProperty \"$sql\\$($Matches[1])\\DTS\\Setup\").Version } \
+          elseif ($n -match '^(MSSQLServerOLAPService|MSOLAP\\$)') { $v = Get-SetupVersion 'OLAP' $i } \
+          elseif ($n -match '^ReportServer') { $v = Get-SetupVersion 'RS' $i } \
+          elseif ($n -eq 'SQLServerReportingServices') { $v = Get-SetupVersion 'RS' 'SSRS' } \
+          elseif ($n -eq 'PowerBIReportServer') { $v = Get-SetupVersion 'RS' 'PBIRS' } \
+          else { return }; \
+          \"{0}`t{1}`t{2}`t{3}\" -f $n, $_.State, $_.StartMode, $v }";
+
+    /// Reporting, Integration or Analysis service of SQL Server
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct Service {
+        pub kind: &'static str,
+        pub name: String,
+        pub instance: String,
+        pub state: String,
+        pub start_mode: String,
+        pub version: Option<String>,
+    }
+
+    #[cfg(windows)]
+    pub fn read() -> Result<Vec<Service>> {
+        let output = std::process::Command::new("powershell")
+            .args(["-NoProfile", "-Command", READ_SERVICES])
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to read services: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(to_services(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    #[cfg(unix)]
+    pub fn read() -> Result<Vec<Service>> {
+        anyhow::bail!("Services are available only on Windows")
+    }
+
+    /// `ssrs`, `ssis` or `ssas` by the name of the Windows service
+    pub fn to_kind(name: &str) -> Option<&'static str> {
+        let name = name.to_lowercase();
+        if name.starts_with("reportserver")
+            || name == "sqlserverreportingservices"
+            || name == "powerbireportserver"
+        {
+            Some("ssrs")
+        } else if name.starts_with("msdtsserver") {
+            Some("ssis")
+        } else if name == "mssqlserverolapservice" || name.starts_with("msolap$") {
+            Some("ssas")
+        } else {
+            None
+        }
+    }
+
+    /// every line is `name<TAB>state<TAB>start mode<TAB>version`
+    pub fn to_services(output: &str) -> Vec<Service> {
+        output
+            .lines()
+            .filter_map(|l| {
+                let values = l.trim_end_matches('\r').split('\t').collect::<Vec<_>>();
+                let [name, state, start_mode, version] = values.as_slice() else {
+                    return None;
+                };
+                let name = name.trim();
+                Some(Service {
+                    kind: to_kind(name)?,
+                    name: name.to_string(),
+                    instance: name
+                        .split_once('$')
+                        .map(|(_, i)| i.to_uppercase())
+                        .unwrap_or_else(|| "MSSQLSERVER".to_string()),
+                    state: state.trim().to_lowercase(),
+                    start_mode: start_mode.trim().to_lowercase(),
+                    version: Some(version.trim())
+                        .filter(|v| !v.is_empty())
+                        .map(str::to_string),
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{to_kind, to_services, Service};
+
+        #[test]
+        fn test_to_kind() {
+            assert_eq!(to_kind("ReportServer$SSRS1"), Some("ssrs"));
+            assert_eq!(to_kind("SQLServerReportingServices"), Some("ssrs"));
+            assert_eq!(to_kind("MsDtsServer150"), Some("ssis"));
+            assert_eq!(to_kind("MSSQLServerOLAPService"), Some("ssas"));
+            assert_eq!(to_kind("MSOLAP$TABULAR"), Some("ssas"));
+            assert_eq!(to_kind("MSSQLSERVER"), None);
+        }
+
+        #[test]
+        fn test_to_services() {
+            let services = to_services(
+                "MsDtsServer150\tRunning\tAuto\t15.0.2000.5\r\n\
+                 MSOLAP$tabular\tStopped\tManual\t\n\
+                 MSSQLSERVER\tRunning\tAuto\t\n\
+                 bad line\n",
+            );
+            assert_eq!(
+                services,
+                vec![
+                    Service {
+                        kind: "ssis",
+                        name: "MsDtsServer150".to_string(),
+                        instance: "MSSQLSERVER".to_string(),
+                        state: "running".to_string(),
+                        start_mode: "auto".to_string(),
+                        version: Some("15.0.2000.5".to_string()),
+                    },
+                    Service {
+                        kind: "ssas",
+                        name: "MSOLAP$tabular".to_string(),
+                        instance: "TABULAR".to_string(),
+                        state: "stopped".to_string(),
+                        start_mode: "manual".to_string(),
+                        version: None,
+                    },
+                ]
+            );
+        }
+    }
+}
+
 #[cfg(windows)]
 pub mod odbc {
     use super::Block;
@@ -2,15 +2,20 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
-use crate::args::Args;
+use crate::args::{Args, Command};
+use crate::capabilities;
+use crate::config::ms_sql::Emit;
 use crate::config::system::{Logging, SystemConfig};
 use crate::config::CheckConfig;
 use crate::constants;
+use crate::ms_sql::client::PasswordChoices;
+use crate::ms_sql::fault::FaultSpec;
 use anyhow::Result;
 use clap::Parser;
 use flexi_logger::{self, Cleanup, Criterion, DeferredNow, FileSpec, LogSpecification, Record};
 use std::env::ArgsOs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Default, Clone, Debug)]
 pub struct Env {
@@ -25,6 +30,18 @@ pub struct Env {
 
     ///
     disable_caching: bool,
+
+    /// only signaling blocks and instance entries are generated
+    skeleton: bool,
+
+    /// simulated failures, testing only
+    faults: FaultSpec,
+
+    /// naming of the output, differs between configs
+    emit: Emit,
+
+    /// working candidate passwords, shared by all logons of the config run
+    password_choices: Arc<PasswordChoices>,
 }
 
 impl Env {
@@ -40,9 +57,29 @@ impl Env {
             log_dir,
             state_dir,
             disable_caching: args.no_spool,
+            skeleton: args.skeleton,
+            faults: FaultSpec::default(),
+            emit: Emit::default(),
+            password_choices: Arc::default(),
         }
     }
 
+    pub fn with_faults(mut self, faults: FaultSpec) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// output of the config is named as configured in `emit`
+    pub fn with_emit(mut self, emit: &Emit) -> Self {
+        self.emit = emit.clone();
+        self
+    }
+
+    pub fn with_password_choices(mut self, choices: Arc<PasswordChoices>) -> Self {
+        self.password_choices = choices;
+        self
+    }
+
     /// guaranteed to return temp dir or None
     pub fn temp_dir(&self) -> Option<&Path> {
         self.temp_dir.as_deref()
@@ -62,6 +99,22 @@ impl Env {
         self.disable_caching
     }
 
+    pub fn skeleton(&self) -> bool {
+        self.skeleton
+    }
+
+    pub fn emit(&self) -> &Emit {
+        &self.emit
+    }
+
+    pub fn password_choices(&self) -> &PasswordChoices {
+        &self.password_choices
+    }
+
+    pub fn faults(&self) -> &FaultSpec {
+        &self.faults
+    }
+
     /// guaranteed to return cache dir or None
     pub fn base_cache_dir(&self) -> Option<PathBuf> {
         self.state_dir()
@@ -110,7 +163,15 @@ pub enum SendTo {
     Stdout,
 }
 
-pub fn init(args: ArgsOs) -> Result<(CheckConfig, Env)> {
+/// What the binary has to do after the setup
+pub enum Action {
+    /// check with the loaded config
+    Check(CheckConfig, Env),
+    /// only print the output, non successful reports end with non zero exit code
+    Print { output: String, success: bool },
+}
+
+pub fn init(args: ArgsOs) -> Result<Action> {
     let args = Args::parse_from(args);
     let config_file = get_config_file(&args);
 
@@ -119,10 +180,34 @@ pub fn init(args: ArgsOs) -> Result<(CheckConfig, Env)> {
         .unwrap_or(None);
     let environment = Env::new(&args);
     init_logging(&args, &environment, logging_config)?;
+    let environment = match args.fault_injection.as_deref() {
+        Some(spec) => {
+            log::warn!("Fault injection is active: `{spec}`");
+            environment.with_faults(spec.parse()?)
+        }
+        None => environment,
+    };
+    if let Some(action) = find_print_action(&args, &config_file)? {
+        return Ok(action);
+    }
+    Ok(Action::Check(get_check_config(&config_file)?, environment))
+}
+
+/// commands and options printing something instead of the check
+fn find_print_action(args: &Args, config_file: &Path) -> Result<Option<Action>> {
+    if args.command == Some(Command::Capabilities) {
+        return Ok(Some(Action::Print {
+            output: capabilities::make_capabilities() + "\n",
+            success: true,
+        }));
+    }
     if !config_file.exists() {
         anyhow::bail!("The config file {:?} doesn't exist", config_file);
     }
-    Ok((get_check_config(&config_file)?, environment))
+    if args.validate_config {
+        return Ok(Some(validate_config(config_file)));
+    }
+    Ok(None)
 }
 
 fn init_logging(args: &Args, environment: &Env, logging: Option<Logging>) -> Result<()> {
@@ -171,6 +256,19 @@ fn get_check_config(file: &Path) -> Result<CheckConfig> {
     CheckConfig::load_file(file)
 }
 
+fn validate_config(file: &Path) -> Action {
+    match CheckConfig::validate_file(file) {
+        Ok(report) => Action::Print {
+            output: report.to_string(),
+            success: true,
+        },
+        Err(report) => Action::Print {
+            output: report.to_string(),
+            success: false,
+        },
+    }
+}
+
 fn get_system_config(file: &Path) -> Result<SystemConfig> {
     SystemConfig::load_file(file)
 }
@@ -273,6 +371,7 @@ mod tests {
             ..Default::default()
         };
         let e = Env::new(&args);
+        assert!(!e.skeleton());
         assert_eq!(e.log_dir(), Some(Path::new(".")));
         assert_eq!(e.temp_dir(), Some(Path::new(".")));
         assert_eq!(
@@ -285,6 +384,27 @@ mod tests {
         );
     }
     #[test]
+    fn test_env_skeleton() {
+        let e = Env::new(&Args::parse_from(["mk-sql", "--skeleton"]));
+        assert!(e.skeleton());
+        assert!(!e.disable_caching());
+    }
+    #[test]
+    fn test_capabilities_command() {
+        let args = Args::parse_from(["mk-sql", "capabilities"]);
+        assert_eq!(args.command, Some(Command::Capabilities));
+        assert_eq!(Args::parse_from(["mk-sql"]).command, None);
+        let args = Args::parse_from(["mk-sql", "capabilities"]);
+        match find_print_action(&args, Path::new("absent.yml")) {
+            Ok(Some(Action::Print { output, success })) => {
+                assert!(success);
+                assert!(output.starts_with("{\"version\":") && output.ends_with("}\n"));
+            }
+            _ => panic!("capabilities must be printed"),
+        }
+        assert!(find_print_action(&Args::parse_from(["mk-sql"]), Path::new("absent.yml")).is_err());
+    }
+    #[test]
     fn test_env_dir_absent() {
         let args = Args {
             log_dir: Some(PathBuf::from("weird-dir")),
@@ -0,0 +1,356 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+//! Minimal OpenTelemetry tracing of a run
+//! Spans are collected in memory and exported at the end of the run to the OTLP collector
+//! using OTLP/HTTP with JSON encoding. Without active trace spans cost nothing.
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const SERVICE_NAME: &str = "mk-sql";
+const TRACES_PATH: &str = "/v1/traces";
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref COLLECTOR: Mutex<Option<Collector>> = Mutex::new(None);
+}
+
+#[derive(Debug)]
+struct Collector {
+    trace_id: String,
+    root_id: String,
+    spans: Vec<SpanData>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(v: usize) -> Self {
+        Value::Int(v as i64)
+    }
+}
+
+impl From<u128> for Value {
+    fn from(v: u128) -> Self {
+        Value::Int(v as i64)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SpanData {
+    id: String,
+    parent_id: Option<String>,
+    name: String,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: Vec<(String, Value)>,
+}
+
+/// Span is recorded when dropped, inactive span(no trace started) is a no-op
+#[derive(Debug)]
+pub struct Span {
+    data: Option<SpanData>,
+}
+
+impl Span {
+    fn new(name: &str, parent_id: Option<String>) -> Self {
+        Self {
+            data: Some(SpanData {
+                id: make_id(8),
+                parent_id,
+                name: name.to_string(),
+                start: SystemTime::now(),
+                end: SystemTime::now(),
+                attributes: vec![],
+            }),
+        }
+    }
+
+    fn inactive() -> Self {
+        Self { data: None }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.data.is_some()
+    }
+
+    pub fn set_attribute<V: Into<Value>>(&mut self, key: &str, value: V) {
+        if let Some(data) = self.data.as_mut() {
+            data.attributes.push((key.to_string(), value.into()));
+        }
+    }
+
+    pub fn end(self) {}
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(mut data) = self.data.take() {
+            data.end = SystemTime::now();
+            if let Some(collector) = COLLECTOR.lock().unwrap().as_mut() {
+                collector.spans.push(data);
+            }
+        }
+    }
+}
+
+/// Starts collecting spans, the returned span is the root of the trace
+pub fn start_trace(name: &str) -> Span {
+    let root = Span::new(name, None);
+    let root_id = root.data.as_ref().map(|d| d.id.clone()).unwrap_or_default();
+    *COLLECTOR.lock().unwrap() = Some(Collector {
+        trace_id: make_id(16),
+        root_id,
+        spans: vec![],
+    });
+    root
+}
+
+/// Child of the `parent` or of the root if `parent` is None
+pub fn span(name: &str, parent: Option<&Span>) -> Span {
+    let root_id = match COLLECTOR.lock().unwrap().as_ref() {
+        Some(collector) => collector.root_id.clone(),
+        None => return Span::inactive(),
+    };
+    let parent_id = parent
+        .and_then(|p| p.data.as_ref())
+        .map(|d| d.id.clone())
+        .unwrap_or(root_id);
+    Span::new(name, Some(parent_id))
+}
+
+/// Ends the trace and sends collected spans to the `endpoint`
+pub async fn finish_trace(root: Span, endpoint: &str) -> Result<()> {
+    drop(root);
+    let collector = COLLECTOR.lock().unwrap().take();
+    match collector {
+        Some(collector) => {
+            log::info!("Exporting {} spans to {endpoint}", collector.spans.len());
+            export(endpoint, &to_otlp_json(&collector)).await
+        }
+        None => Ok(()),
+    }
+}
+
+fn make_id(bytes: usize) -> String {
+    let mut id = String::new();
+    while id.len() < bytes * 2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        id += &format!("{:016x}", hasher.finish());
+    }
+    id.truncate(bytes * 2);
+    id
+}
+
+fn to_nanos(time: &SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+pub(crate) fn to_json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn to_json_attribute(key: &str, value: &Value) -> String {
+    let value = match value {
+        Value::Str(s) => format!("{{\"stringValue\":{}}}", to_json_string(s)),
+        // OTLP JSON encodes 64 bit integers as strings
+        Value::Int(i) => format!("{{\"intValue\":\"{i}\"}}"),
+    };
+    format!("{{\"key\":{},\"value\":{value}}}", to_json_string(key))
+}
+
+fn to_json_span(trace_id: &str, span: &SpanData) -> String {
+    let parent = span
+        .parent_id
+        .as_ref()
+        .map(|p| format!("\"parentSpanId\":\"{p}\","))
+        .unwrap_or_default();
+    let attributes = span
+        .attributes
+        .iter()
+        .map(|(k, v)| to_json_attribute(k, v))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        "{{\"traceId\":\"{trace_id}\",\"spanId\":\"{}\",{parent}\"name\":{},\"kind\":1,\
+\"startTimeUnixNano\":\"{}\",\"endTimeUnixNano\":\"{}\",\"attributes\":[{attributes}]}}",
+        span.id,
+        to_json_string(&span.name),
+        to_nanos(&span.start),
+        to_nanos(&span.end),
+    )
+}
+
+fn to_otlp_json(collector: &Collector) -> String {
+    let spans = collector
+        .spans
+        .iter()
+        .map(|s| to_json_span(&collector.trace_id, s))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        "{{\"resourceSpans\":[{{\"resource\":{{\"attributes\":[{}]}},\
+\"scopeSpans\":[{{\"scope\":{{\"name\":\"{SERVICE_NAME}\"}},\"spans\":[{spans}]}}]}}]}}",
+        to_json_attribute("service.name", &Value::from(SERVICE_NAME))
+    )
+}
+
+/// `http://host:port/path` -> (host, port, path), path defaults to OTLP traces path
+fn parse_endpoint(endpoint: &str) -> Result<(String, u16, String)> {
+    let rest = match endpoint.split_once("://") {
+        Some(("http", rest)) => rest,
+        Some((scheme, _)) => anyhow::bail!("Scheme `{scheme}` is not supported, use http"),
+        None => endpoint,
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(pos) if rest[pos..].len() > 1 => (&rest[..pos], rest[pos..].to_string()),
+        Some(pos) => (&rest[..pos], TRACES_PATH.to_string()),
+        None => (rest, TRACES_PATH.to_string()),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse::<u16>()
+                .map_err(|e| anyhow::anyhow!("Bad port in `{endpoint}`: {e}"))?,
+        ),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        anyhow::bail!("No host in `{endpoint}`");
+    }
+    Ok((host.to_string(), port, path))
+}
+
+async fn export(endpoint: &str, body: &str) -> Result<()> {
+    let (host, port, path) = parse_endpoint(endpoint)?;
+    let request = format!(
+        "POST {path} HTTP/1.1\r\nHost: {host}:{port}\r\nContent-Type: application/json\r\n\
+Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let send = async {
+        let mut stream = TcpStream::connect((host.as_str(), port)).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        anyhow::Ok(response)
+    };
+    let response = tokio::time::timeout(EXPORT_TIMEOUT, send)
+        .await
+        .map_err(|_| anyhow::anyhow!("Timeout exporting spans to `{endpoint}`"))??;
+    let status_line = String::from_utf8_lossy(&response)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => anyhow::bail!("OTLP collector `{endpoint}` answered `{status_line}`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(
+            parse_endpoint("http://collector:4318").unwrap(),
+            ("collector".to_string(), 4318, TRACES_PATH.to_string())
+        );
+        assert_eq!(
+            parse_endpoint("collector:4318/").unwrap(),
+            ("collector".to_string(), 4318, TRACES_PATH.to_string())
+        );
+        assert_eq!(
+            parse_endpoint("http://collector/otlp/traces").unwrap(),
+            ("collector".to_string(), 80, "/otlp/traces".to_string())
+        );
+        assert!(parse_endpoint("https://collector:4318").is_err());
+        assert!(parse_endpoint("http://collector:x").is_err());
+        assert!(parse_endpoint("http://:4318").is_err());
+    }
+
+    #[test]
+    fn test_to_otlp_json() {
+        let start = UNIX_EPOCH + Duration::from_nanos(1_000);
+        let collector = Collector {
+            trace_id: "t".repeat(32),
+            root_id: "r".repeat(16),
+            spans: vec![SpanData {
+                id: "s".repeat(16),
+                parent_id: Some("r".repeat(16)),
+                name: "section \"jobs\"".to_string(),
+                start,
+                end: start + Duration::from_nanos(500),
+                attributes: vec![("mssql.rows".to_string(), Value::from(3usize))],
+            }],
+        };
+        let json = to_otlp_json(&collector);
+        assert!(json.starts_with("{\"resourceSpans\":[{\"resource\":{\"attributes\":[{\"key\":\"service.name\",\"value\":{\"stringValue\":\"mk-sql\"}}]}"));
+        assert!(json.contains(&format!(
+            "\"traceId\":\"{}\",\"spanId\":\"{}\",\"parentSpanId\":\"{}\",\"name\":\"section \\\"jobs\\\"\"",
+            "t".repeat(32),
+            "s".repeat(16),
+            "r".repeat(16)
+        )));
+        assert!(json.contains("\"startTimeUnixNano\":\"1000\",\"endTimeUnixNano\":\"1500\""));
+        assert!(json.contains("{\"key\":\"mssql.rows\",\"value\":{\"intValue\":\"3\"}}"));
+    }
+
+    #[test]
+    fn test_make_id() {
+        assert_eq!(make_id(8).len(), 16);
+        assert_eq!(make_id(16).len(), 32);
+        assert!(make_id(16).chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(make_id(8), make_id(8));
+    }
+}
@@ -34,6 +34,45 @@ impl From<&str> for InstanceName {
     }
 }
 
+impl InstanceName {
+    /// Name in the form suitable for comparison: trimmed and case folded
+    /// Turkish dotted/dotless `i` are folded to the plain `i`
+    pub fn normalized(&self) -> String {
+        self.0
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter(|&c| c != '\u{307}') // combining dot above, result of `İ` lowercasing
+            .map(|c| if c == 'ı' { 'i' } else { c })
+            .collect()
+    }
+
+    pub fn is_same(&self, other: &InstanceName) -> bool {
+        self.normalized() == other.normalized()
+    }
+
+    /// Names which are not the same, but probably meant to be the same: one typo
+    pub fn is_near_miss(&self, other: &InstanceName) -> bool {
+        let a: Vec<char> = self.normalized().chars().collect();
+        let b: Vec<char> = other.normalized().chars().collect();
+        a != b && edit_distance(&a, &b) <= 1
+    }
+}
+
+/// Levenshtein distance
+pub fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
 #[derive(PartialEq, From, Clone, Debug, Display, Default)]
 pub struct InstanceId(String);
 
@@ -43,6 +82,64 @@ pub struct InstanceEdition(String);
 #[derive(PartialEq, From, Clone, Debug, Display, Default, Into)]
 pub struct InstanceVersion(String);
 
+/// Parsed SQL Server version `major.minor.build.revision`, e.g. `15.0.4153.1`
+/// Missing or malformed parts are zeros
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub build: u32,
+    pub revision: u32,
+}
+
+impl From<&str> for Version {
+    fn from(s: &str) -> Self {
+        let mut parts = s
+            .trim()
+            .split('.')
+            .map(|p| p.trim().parse::<u32>().unwrap_or(0));
+        Self {
+            major: parts.next().unwrap_or(0),
+            minor: parts.next().unwrap_or(0),
+            build: parts.next().unwrap_or(0),
+            revision: parts.next().unwrap_or(0),
+        }
+    }
+}
+
+impl From<&InstanceVersion> for Version {
+    fn from(version: &InstanceVersion) -> Self {
+        Version::from(version.0.as_str())
+    }
+}
+
+/// major version only
+impl From<u32> for Version {
+    fn from(major: u32) -> Self {
+        Self {
+            major,
+            ..Default::default()
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.build, self.revision
+        )
+    }
+}
+
+impl Version {
+    /// `required` is a version string, possibly short: "15" or "15.0.4153"
+    pub fn is_at_least(&self, required: &str) -> bool {
+        *self >= Version::from(required)
+    }
+}
+
 #[derive(PartialEq, From, Clone, Debug, Display, Default, Into)]
 pub struct InstanceCluster(String);
 
@@ -78,3 +175,50 @@ pub struct HostName(String);
 /// this is a string as defined by Tiberius API
 #[derive(PartialEq, From, Clone, Debug, Display, Default, Into)]
 pub struct CertPath(String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instance_name_normalized() {
+        assert_eq!(InstanceName::from(" Sql1 ").normalized(), "sql1");
+        assert_eq!(InstanceName::from("İNST").normalized(), "inst");
+        assert_eq!(InstanceName::from("ınst").normalized(), "inst");
+        assert!(InstanceName::from("MSSQLSERVER ").is_same(&InstanceName::from("mssqlserver")));
+        assert!(!InstanceName::from("SQL1").is_same(&InstanceName::from("SQL2")));
+    }
+
+    #[test]
+    fn test_version() {
+        let v = Version::from("15.0.4153.1");
+        assert_eq!(
+            v,
+            Version {
+                major: 15,
+                minor: 0,
+                build: 4153,
+                revision: 1
+            }
+        );
+        assert_eq!(v.to_string(), "15.0.4153.1");
+        assert_eq!(Version::from("16"), Version::from(16));
+        assert_eq!(Version::from("x.1"), Version::from("0.1.0.0"));
+        assert_eq!(Version::from(""), Version::default());
+        assert!(v.is_at_least("15"));
+        assert!(v.is_at_least("15.0.4153"));
+        assert!(!v.is_at_least("15.0.4153.2"));
+        assert!(!v.is_at_least("16"));
+        assert!(Version::from("15.0.4153") > Version::from("15.0.2000.5"));
+    }
+
+    #[test]
+    fn test_instance_name_near_miss() {
+        let name = InstanceName::from("SQLEXPRESS");
+        assert!(name.is_near_miss(&InstanceName::from("SQLEXPRES")));
+        assert!(name.is_near_miss(&InstanceName::from("SQLEXPRESS1")));
+        assert!(name.is_near_miss(&InstanceName::from("SQLEXPRASS")));
+        assert!(!name.is_near_miss(&InstanceName::from("sqlexpress")));
+        assert!(!name.is_near_miss(&InstanceName::from("MSSQLSERVER")));
+    }
+}
@@ -3,6 +3,7 @@
 // conditions defined in the file COPYING, which is part of this source code package.
 
 use anyhow::Result;
+use std::fmt::Write;
 /// Platform independent file and time routines
 use std::fs;
 use std::fs::File;
@@ -29,7 +30,7 @@ pub fn touch_dir<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
     Ok(path.as_ref().to_path_buf())
 }
 
-fn get_modified_utc_time<P: AsRef<Path>>(path: P) -> Result<u64> {
+pub fn get_modified_utc_time<P: AsRef<Path>>(path: P) -> Result<u64> {
     Ok(fs::metadata(path)?
         .modified()?
         .duration_since(UNIX_EPOCH)?
@@ -42,6 +43,49 @@ pub fn get_utc_now() -> Result<u64> {
         .as_secs())
 }
 
+/// `YYYY-MM-DD HH:MM:SS` as GETUTCDATE() with style 20 in SQL Server
+pub fn format_utc(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let secs = seconds % 86_400;
+    // civil from days, proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Name of the host the agent is running on, NetBIOS name on Windows
+pub fn get_local_computer_name() -> Option<String> {
+    #[cfg(windows)]
+    let name = std::env::var("COMPUTERNAME").ok();
+    #[cfg(unix)]
+    let name = fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok());
+    name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty())
+}
+
+/// lower case hex
+pub fn sha256_hex(data: &[u8]) -> String {
+    openssl::sha::sha256(data)
+        .iter()
+        .fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        })
+}
+
 pub fn get_modified_age<P: AsRef<Path>>(path: P) -> Result<u64> {
     let modified = get_modified_utc_time(path)?;
     let now = get_utc_now()?;
@@ -50,11 +94,26 @@ pub fn get_modified_age<P: AsRef<Path>>(path: P) -> Result<u64> {
 
 #[cfg(test)]
 mod tests {
-    use super::get_modified_utc_time;
+    use super::{format_utc, get_modified_utc_time, sha256_hex};
 
     #[test]
     fn test_get_utc_modified_time() {
         let e = get_modified_utc_time(".").unwrap();
         assert!(e > 1700000000);
     }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_utc(1_704_931_199), "2024-01-10 23:59:59");
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
 }
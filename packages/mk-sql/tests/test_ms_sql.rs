@@ -7,13 +7,13 @@ use mk_sql::config::ms_sql::Discovery;
 use mk_sql::platform;
 #[cfg(windows)]
 use mk_sql::platform::odbc;
-use mk_sql::types::InstanceName;
+use mk_sql::types::{InstanceName, Version};
 
 use std::path::PathBuf;
 use std::{collections::HashSet, fs::create_dir_all};
 
 use mk_sql::ms_sql::{
-    client::{self, UniClient},
+    client::{self, PasswordChoices, UniClient},
     instance::{self, SqlInstance, SqlInstanceBuilder},
     query,
     section::Section,
@@ -69,7 +69,7 @@ fn test_section_select_query() {
     ] {
         assert_eq!(
             make_section(name)
-                .select_query(Some(custom_sql_path.to_owned()), 0)
+                .select_query(Some(custom_sql_path.to_owned()), &Version::default())
                 .unwrap(),
             "Bu!"
         );
@@ -128,9 +128,14 @@ mssql:
 #[tokio::test(flavor = "multi_thread")]
 async fn test_obtain_all_instances_from_registry_local() {
     let endpoint = make_default_endpoint();
-    let builders = instance::obtain_instance_builders(&endpoint, &[], &Discovery::default())
-        .await
-        .unwrap();
+    let builders = instance::obtain_instance_builders(
+        &endpoint,
+        &[],
+        &Discovery::default(),
+        &PasswordChoices::default(),
+    )
+    .await
+    .unwrap();
     let all: Vec<SqlInstance> = to_instances(builders)
         .into_iter()
         .filter(|i| expected_instances().contains(&i.name))
@@ -155,9 +160,10 @@ async fn test_obtain_all_instances_from_registry_local_include() {
         .unwrap()
         .unwrap();
     assert_eq!(discovery.include().len(), 1usize, "Discovery is wrong");
-    let builders = instance::obtain_instance_builders(&endpoint, &[], &discovery)
-        .await
-        .unwrap();
+    let builders =
+        instance::obtain_instance_builders(&endpoint, &[], &discovery, &PasswordChoices::default())
+            .await
+            .unwrap();
     let all: Vec<SqlInstance> = to_instances(builders);
     let names: Vec<InstanceName> = all.into_iter().map(|i| i.name).collect();
     assert_eq!(
@@ -181,9 +187,10 @@ async fn test_obtain_all_instances_from_registry_local_exclude() {
         .unwrap()
         .unwrap();
     assert_eq!(discovery.exclude().len(), 4usize, "Discovery is wrong");
-    let builders = instance::obtain_instance_builders(&endpoint, &[], &discovery)
-        .await
-        .unwrap();
+    let builders =
+        instance::obtain_instance_builders(&endpoint, &[], &discovery, &PasswordChoices::default())
+            .await
+            .unwrap();
     let all: Vec<SqlInstance> = to_instances(builders);
     let names: Vec<InstanceName> = all.into_iter().map(|i| i.name).collect();
     assert_eq!(
@@ -204,12 +211,17 @@ async fn test_validate_all_instances_local() {
     let l = tools::LogMe::new("test_validate_all_instances_local").start(log::Level::Debug);
     log::info!("{:#?}", l.dir());
     let endpoint = make_default_endpoint();
-    let builders = instance::obtain_instance_builders(&endpoint, &[], &Discovery::default())
-        .await
-        .unwrap()
-        .into_iter()
-        .filter(|i| expected_instances().contains(&i.get_name()))
-        .collect::<Vec<_>>();
+    let builders = instance::obtain_instance_builders(
+        &endpoint,
+        &[],
+        &Discovery::default(),
+        &PasswordChoices::default(),
+    )
+    .await
+    .unwrap()
+    .into_iter()
+    .filter(|i| expected_instances().contains(&i.get_name()))
+    .collect::<Vec<_>>();
     let names: Vec<InstanceName> = builders.into_iter().map(|i| i.get_name()).collect();
 
     for name in names {
@@ -245,9 +257,10 @@ async fn test_validate_all_instances_local() {
 #[tokio::test(flavor = "multi_thread")]
 async fn test_remote_connection() {
     if let Some(endpoint) = tools::get_remote_sql_from_env_var() {
-        let mut client = client::connect_main_endpoint(&endpoint.make_ep())
-            .await
-            .unwrap();
+        let mut client =
+            client::connect_main_endpoint(&endpoint.make_ep(), &PasswordChoices::default())
+                .await
+                .unwrap();
         let properties = instance::SqlInstanceProperties::obtain_by_query(&mut client)
             .await
             .unwrap();
@@ -270,10 +283,14 @@ fn to_instances(builders: Vec<SqlInstanceBuilder>) -> Vec<SqlInstance> {
 #[tokio::test(flavor = "multi_thread")]
 async fn test_find_all_instances_remote() {
     if let Some(endpoint) = tools::get_remote_sql_from_env_var() {
-        let builders =
-            instance::obtain_instance_builders(&endpoint.make_ep(), &[], &Discovery::default())
-                .await
-                .unwrap();
+        let builders = instance::obtain_instance_builders(
+            &endpoint.make_ep(),
+            &[],
+            &Discovery::default(),
+            &PasswordChoices::default(),
+        )
+        .await
+        .unwrap();
         let all = to_instances(builders);
         assert!(all.iter().all(is_instance_good));
         assert_eq!(all.len(), expected_instances().len());
@@ -297,10 +314,14 @@ async fn test_find_all_instances_remote() {
 #[tokio::test(flavor = "multi_thread")]
 async fn test_validate_all_instances_remote() {
     if let Some(endpoint) = tools::get_remote_sql_from_env_var() {
-        let builders =
-            instance::obtain_instance_builders(&endpoint.make_ep(), &[], &Discovery::default())
-                .await
-                .unwrap();
+        let builders = instance::obtain_instance_builders(
+            &endpoint.make_ep(),
+            &[],
+            &Discovery::default(),
+            &PasswordChoices::default(),
+        )
+        .await
+        .unwrap();
         let is = to_instances(builders);
 
         let cfg = Config::from_string(&create_remote_config(endpoint))
@@ -372,7 +393,12 @@ async fn validate_database_names(instance: &SqlInstance, client: &mut UniClient)
 
 async fn validate_counters(instance: &SqlInstance, client: &mut UniClient) {
     let counters = instance
-        .generate_counters_section(client, find_known_query(sqls::Id::Counters).unwrap(), '|')
+        .generate_counters_section(
+            client,
+            find_known_query(sqls::Id::Counters).unwrap(),
+            '|',
+            None,
+        )
         .await;
     let result = counters.split('\n').collect::<Vec<&str>>();
     assert!(result[0].starts_with("None|utc_time|None|"));
@@ -457,8 +483,14 @@ async fn validate_table_spaces(
 async fn validate_backup(instance: &SqlInstance, client: &mut UniClient) {
     let mut to_be_found: HashSet<&str> = ["master", "model", "msdb"].iter().cloned().collect();
 
+    let databases = instance.generate_databases(client).await;
     let result = instance
-        .generate_backup_section(client, find_known_query(sqls::Id::Backup).unwrap(), '|')
+        .generate_backup_section(
+            client,
+            &databases,
+            find_known_query(sqls::Id::Backup).unwrap(),
+            '|',
+        )
         .await;
     let lines: Vec<&str> = result.split('\n').collect();
     assert!(lines.len() >= (to_be_found.len() + 1), "{:?}", lines);
@@ -740,10 +772,14 @@ async fn validate_availability_groups_section(instance: &SqlInstance, endpoint:
 #[tokio::test(flavor = "multi_thread")]
 async fn test_validate_all_instances_remote_extra() {
     if let Some(endpoint) = tools::get_remote_sql_from_env_var() {
-        let builders =
-            instance::obtain_instance_builders(&endpoint.make_ep(), &[], &Discovery::default())
-                .await
-                .unwrap();
+        let builders = instance::obtain_instance_builders(
+            &endpoint.make_ep(),
+            &[],
+            &Discovery::default(),
+            &PasswordChoices::default(),
+        )
+        .await
+        .unwrap();
         let is = to_instances(builders);
         let ms_sql = Config::from_string(
             r"---
@@ -786,9 +822,10 @@ mssql:
 #[tokio::test(flavor = "multi_thread")]
 async fn test_get_computer_name() {
     if let Some(endpoint) = tools::get_remote_sql_from_env_var() {
-        let mut client = client::connect_main_endpoint(&endpoint.make_ep())
-            .await
-            .unwrap();
+        let mut client =
+            client::connect_main_endpoint(&endpoint.make_ep(), &PasswordChoices::default())
+                .await
+                .unwrap();
         let name = query::obtain_computer_name(&mut client).await.unwrap();
         assert!(name
             .unwrap()
@@ -803,9 +840,10 @@ async fn test_get_computer_name() {
 #[tokio::test(flavor = "multi_thread")]
 async fn test_get_user_name() {
     if let Some(endpoint) = tools::get_remote_sql_from_env_var() {
-        let mut client = client::connect_main_endpoint(&endpoint.make_ep())
-            .await
-            .unwrap();
+        let mut client =
+            client::connect_main_endpoint(&endpoint.make_ep(), &PasswordChoices::default())
+                .await
+                .unwrap();
         let name = query::obtain_system_user(&mut client).await.unwrap();
         assert_eq!(name.unwrap().to_lowercase(), endpoint.user.to_lowercase());
     } else {
@@ -936,7 +974,9 @@ async fn test_find_no_detect_local() {
     let mssql = mk_sql::config::ms_sql::Config::from_string(&make_local_config_string("", false))
         .unwrap()
         .unwrap();
-    let instances = instance::find_all_instance_builders(&mssql).await.unwrap();
+    let instances = instance::find_all_instance_builders(&mssql, &PasswordChoices::default())
+        .await
+        .unwrap();
     assert_eq!(instances.len(), 0);
 }
 
@@ -951,7 +991,11 @@ async fn test_find_no_detect_two_custom_instances_local() {
     ))
     .unwrap()
     .unwrap();
-    let instances = to_instances(instance::find_all_instance_builders(&mssql).await.unwrap());
+    let instances = to_instances(
+        instance::find_all_instance_builders(&mssql, &PasswordChoices::default())
+            .await
+            .unwrap(),
+    );
     assert_eq!(instances.len(), 1);
     assert_eq!(instances[0].name, main_instance_name());
     assert!(instances[0].edition.to_string().contains(" Edition"));
@@ -978,7 +1022,9 @@ async fn test_find_no_detect_remote() {
         ))
         .unwrap()
         .unwrap();
-        let instances = instance::find_all_instance_builders(&mssql).await.unwrap();
+        let instances = instance::find_all_instance_builders(&mssql, &PasswordChoices::default())
+            .await
+            .unwrap();
         assert_eq!(instances.len(), 0);
     } else {
         tools::skip_on_lack_of_ms_sql_endpoint();
@@ -1000,7 +1046,11 @@ async fn test_find_no_detect_two_custom_instances_remote() {
         ))
         .unwrap()
         .unwrap();
-        let instances = to_instances(instance::find_all_instance_builders(&mssql).await.unwrap());
+        let instances = to_instances(
+            instance::find_all_instance_builders(&mssql, &PasswordChoices::default())
+                .await
+                .unwrap(),
+        );
         assert_eq!(instances.len(), 1);
         assert_eq!(instances[0].name, main_instance_name());
         assert!(instances[0].edition.to_string().contains(" Edition"));
@@ -1448,13 +1498,15 @@ fn test_find_provided_query() {
 
     // Phase 1. None for Nothing
     assert!(s_a
-        .find_provided_query(Some(PathBuf::from("aswcededcececece")), 0)
+        .find_provided_query(Some(PathBuf::from("aswcededcececece")), &Version::default())
         .is_none());
     let dir = tools::create_temp_process_dir();
     let dir_to_check = || Some(dir.path().to_owned());
 
     // Phase 2. None for Empty Dir
-    assert!(s_a.find_provided_query(dir_to_check(), 0).is_none());
+    assert!(s_a
+        .find_provided_query(dir_to_check(), &Version::default())
+        .is_none());
 
     let _ = tools::create_file_with_content(dir.path(), "a.sql", "a.sql");
     let _ = tools::create_file_with_content(dir.path(), "a@20.sql", "a@20.sql");
@@ -1462,22 +1514,47 @@ fn test_find_provided_query() {
     let _ = tools::create_file_with_content(dir.path(), "jobs@100.sql", "jobs@100.sql");
 
     // Phase 3. section a
-    assert!(s_a2.find_provided_query(dir_to_check(), 0).is_none());
-    assert_eq!(s_a.find_provided_query(dir_to_check(), 0).unwrap(), "a.sql");
+    assert!(s_a2
+        .find_provided_query(dir_to_check(), &Version::default())
+        .is_none());
     assert_eq!(
-        s_a.find_provided_query(dir_to_check(), 13).unwrap(),
+        s_a.find_provided_query(dir_to_check(), &Version::default())
+            .unwrap(),
+        "a.sql"
+    );
+    assert_eq!(
+        s_a.find_provided_query(dir_to_check(), &Version::from(13))
+            .unwrap(),
         "a@4.sql"
     );
     assert_eq!(
-        s_a.find_provided_query(dir_to_check(), 30).unwrap(),
+        s_a.find_provided_query(dir_to_check(), &Version::from(30))
+            .unwrap(),
         "a@20.sql"
     );
 
-    assert!(s_jobs.find_provided_query(dir_to_check(), 30).is_none());
+    assert!(s_jobs
+        .find_provided_query(dir_to_check(), &Version::from(30))
+        .is_none());
     assert_eq!(
-        s_jobs.find_provided_query(dir_to_check(), 100).unwrap(),
+        s_jobs
+            .find_provided_query(dir_to_check(), &Version::from(100))
+            .unwrap(),
         "jobs@100.sql"
     );
+
+    // Phase 4. full build number
+    let _ = tools::create_file_with_content(dir.path(), "a@20.0.4153.sql", "a@20.0.4153.sql");
+    assert_eq!(
+        s_a.find_provided_query(dir_to_check(), &Version::from("20.0.2000.5"))
+            .unwrap(),
+        "a@20.sql"
+    );
+    assert_eq!(
+        s_a.find_provided_query(dir_to_check(), &Version::from("20.0.4153.1"))
+            .unwrap(),
+        "a@20.0.4153.sql"
+    );
 }
 
 #[test]
@@ -1552,7 +1629,7 @@ async fn test_odbc_high_level() {
 
     async fn get(name: &str) -> Option<SqlInstanceProperties> {
         let instance_name = InstanceName::from(name);
-        let mut client = create_odbc_client(&instance_name, None).unwrap();
+        let mut client = create_odbc_client(&instance_name, None, &Default::default()).unwrap();
         obtain_properties(&mut client, &instance_name).await
     }
 